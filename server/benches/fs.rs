@@ -0,0 +1,59 @@
+//! Compares the ways `fs::dir` can read the files it serves.
+//!
+//! Run with `cargo bench --bench fs`.
+
+use std::time::{Duration, Instant};
+
+use hyper::body::HttpBody;
+use nextshell::Filter;
+
+const FILE_SIZE: usize = 32 * 1024 * 1024;
+const ITERATIONS: u32 = 20;
+
+#[tokio::main]
+async fn main() {
+    let tmp = std::env::temp_dir().join("nextshell-bench-fs");
+    std::fs::create_dir_all(&tmp).unwrap();
+    std::fs::write(tmp.join("large.bin"), vec![0x5a; FILE_SIZE]).unwrap();
+
+    let routes = nextshell::path("default")
+        .and(nextshell::fs::dir(tmp.clone()))
+        .or(nextshell::path("buffered")
+            .and(nextshell::fs::dir(tmp.clone()).read_buffer_size(256 * 1024)));
+    let (addr, server) = nextshell::serve(routes).bind_ephemeral(([127, 0, 0, 1], 0));
+    tokio::spawn(server);
+
+    let client = hyper::Client::new();
+    for name in ["default", "buffered"] {
+        let uri: hyper::Uri = format!("http://{}/{}/large.bin", addr, name)
+            .parse()
+            .unwrap();
+        // Warm up the page cache and the connection.
+        download(&client, uri.clone()).await;
+
+        let mut elapsed = Duration::ZERO;
+        for _ in 0..ITERATIONS {
+            let start = Instant::now();
+            download(&client, uri.clone()).await;
+            elapsed += start.elapsed();
+        }
+        let mib = (FILE_SIZE as f64 * ITERATIONS as f64) / (1024.0 * 1024.0);
+        println!(
+            "{:>8}: {:>8.1} MiB/s ({:?} per request)",
+            name,
+            mib / elapsed.as_secs_f64(),
+            elapsed / ITERATIONS,
+        );
+    }
+
+    let _ = std::fs::remove_dir_all(&tmp);
+}
+
+async fn download(client: &hyper::Client<hyper::client::HttpConnector>, uri: hyper::Uri) {
+    let mut body = client.get(uri).await.unwrap().into_body();
+    let mut len = 0;
+    while let Some(chunk) = body.data().await {
+        len += chunk.unwrap().len();
+    }
+    assert_eq!(len, FILE_SIZE);
+}
@@ -1,30 +1,30 @@
-use futures_util::StreamExt;
-use nextshell::{sse::Event, Filter};
-use std::convert::Infallible;
+use nextshell::{
+    sse::{Broadcaster, Event},
+    Filter,
+};
 use std::time::Duration;
 use tokio::time::interval;
-use tokio_stream::wrappers::IntervalStream;
-
-// create server-sent event
-fn sse_counter(counter: u64) -> Result<Event, Infallible> {
-    Ok(nextshell::sse::Event::default().data(counter.to_string()))
-}
 
 #[tokio::main]
 async fn main() {
     pretty_env_logger::init();
 
-    let routes = nextshell::path("ticks").and(nextshell::get()).map(|| {
+    // One event source, shared by every connected client.
+    let broadcaster = Broadcaster::new(16);
+    let ticks = broadcaster.clone();
+    tokio::spawn(async move {
+        let mut interval = interval(Duration::from_secs(1));
         let mut counter: u64 = 0;
-        // create server event source
-        let interval = interval(Duration::from_secs(1));
-        let stream = IntervalStream::new(interval);
-        let event_stream = stream.map(move |_| {
+        loop {
+            interval.tick().await;
             counter += 1;
-            sse_counter(counter)
-        });
+            ticks.send(Event::default().data(counter.to_string()));
+        }
+    });
+
+    let routes = nextshell::path("ticks").and(nextshell::get()).map(move || {
         // reply using server-sent events
-        nextshell::sse::reply(event_stream)
+        nextshell::sse::reply(broadcaster.subscribe())
     });
 
     nextshell::serve(routes).run(([127, 0, 0, 1], 3030)).await;
@@ -3,15 +3,11 @@
 #[cfg(unix)]
 #[tokio::main]
 async fn main() {
-    use tokio::net::UnixListener;
-    use tokio_stream::wrappers::UnixListenerStream;
-
     pretty_env_logger::init();
 
-    let listener = UnixListener::bind("/tmp/nextshell.sock").unwrap();
-    let incoming = UnixListenerStream::new(listener);
+    let socket = nextshell::UnixSocket::new("/tmp/nextshell.sock").mode(0o660);
     nextshell::serve(nextshell::fs::dir("examples/dir"))
-        .run_incoming(incoming)
+        .run_unix(socket)
         .await;
 }
 
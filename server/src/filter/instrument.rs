@@ -0,0 +1,101 @@
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+use futures_util::{ready, TryFuture};
+use pin_project::pin_project;
+use tokio::time::Instant;
+use tracing::Span;
+
+use super::{Filter, FilterBase, Internal};
+use crate::route;
+
+#[derive(Clone, Copy, Debug)]
+pub struct Instrument<T> {
+    pub(super) filter: T,
+    pub(super) name: &'static str,
+}
+
+impl<T> FilterBase for Instrument<T>
+where
+    T: Filter,
+{
+    type Extract = T::Extract;
+    type Error = T::Error;
+    type Future = InstrumentFuture<T>;
+    #[inline]
+    fn filter(&self, _: Internal) -> Self::Future {
+        let span = tracing::debug_span!("filter", name = self.name);
+        let extract = span.in_scope(|| self.filter.filter(Internal));
+        InstrumentFuture {
+            extract,
+            name: self.name,
+            span,
+            started: Instant::now(),
+        }
+    }
+}
+
+#[allow(missing_debug_implementations)]
+#[pin_project]
+pub struct InstrumentFuture<T: Filter> {
+    #[pin]
+    extract: T::Future,
+    name: &'static str,
+    span: Span,
+    started: Instant,
+}
+
+impl<T> Future for InstrumentFuture<T>
+where
+    T: Filter,
+{
+    type Output = Result<T::Extract, T::Error>;
+
+    #[inline]
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let pin = self.project();
+        let _entered = pin.span.enter();
+        let result = ready!(pin.extract.try_poll(cx));
+
+        let name = *pin.name;
+        let elapsed = pin.started.elapsed();
+        let outcome = if result.is_ok() { "ok" } else { "rejected" };
+        tracing::debug!(
+            target: "nextshell::filter::instrument",
+            elapsed_ms = elapsed.as_secs_f64() * 1000.0,
+            outcome,
+            "filter {:?} completed",
+            name,
+        );
+        route::with(|route| {
+            let timing = Timing {
+                name,
+                outcome,
+                elapsed,
+            };
+            let extensions = route.extensions_mut();
+            match extensions.get_mut::<Timings>() {
+                Some(timings) => timings.0.push(timing),
+                None => {
+                    extensions.insert(Timings(vec![timing]));
+                }
+            }
+        });
+
+        Poll::Ready(result)
+    }
+}
+
+/// The timings recorded by instrumented filters during a request, picked up
+/// by the metrics wrapper.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct Timings(pub(crate) Vec<Timing>);
+
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct Timing {
+    pub(crate) name: &'static str,
+    pub(crate) outcome: &'static str,
+    pub(crate) elapsed: Duration,
+}
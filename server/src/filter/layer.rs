@@ -0,0 +1,242 @@
+use std::convert::Infallible;
+use std::error::Error as StdError;
+use std::fmt;
+use std::future::Future;
+use std::mem;
+use std::net::SocketAddr;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+
+use futures_util::future::{self, BoxFuture};
+use futures_util::{ready, TryFuture};
+use hyper::service::Service;
+
+use super::{Filter, FilterBase, Internal, WrapSealed};
+use crate::reject::{self, IsReject, Rejection};
+use crate::reply::{Reply, Response};
+use crate::route::{self, Route};
+use crate::Request;
+
+/// Wraps filters with a [Tower][tower] middleware.
+///
+/// The function receives the wrapped filter as an [`InnerService`], and
+/// returns the service to run instead, usually by applying a
+/// `tower::Layer` to it. Requests are then handed to that service, and the
+/// filter runs when it calls the inner service.
+///
+/// Rejections of the wrapped filter are passed through the middleware as
+/// their error responses, and then rejected again, so they can still be
+/// recovered from or combined with [`Filter::or`]. Errors of the middleware
+/// itself reject with a `LayerError`, answered with
+/// `503 Service Unavailable`.
+///
+/// # Example
+///
+/// ```
+/// use nextshell::Filter;
+/// use nextshell::service::InnerService;
+///
+/// // A `tower::Layer` would be applied like so:
+/// // nextshell::wrap_layer(move |svc| layer.layer(svc))
+/// let route = nextshell::any()
+///     .map(|| "hello")
+///     .with(nextshell::wrap_layer(|svc: InnerService| svc));
+/// ```
+///
+/// [tower]: https://docs.rs/tower
+pub fn wrap_layer<L, S>(layer: L) -> WrapLayer<L>
+where
+    L: Fn(InnerService) -> S,
+    S: Service<Request, Response = Response> + Clone + Send + Sync + 'static,
+    S::Error: Into<Box<dyn StdError + Send + Sync>>,
+    S::Future: Send,
+{
+    WrapLayer { layer }
+}
+
+/// A wrapper applying a Tower middleware, created by
+/// [`wrap_layer`](crate::wrap_layer).
+#[derive(Clone, Copy, Debug)]
+pub struct WrapLayer<L> {
+    layer: L,
+}
+
+impl<F, L, S> WrapSealed<F> for WrapLayer<L>
+where
+    F: Filter + Clone + Send + Sync + 'static,
+    F::Extract: Reply,
+    F::Error: Into<Rejection>,
+    L: Fn(InnerService) -> S,
+    S: Service<Request, Response = Response> + Clone + Send + Sync + 'static,
+    S::Error: Into<Box<dyn StdError + Send + Sync>>,
+    S::Future: Send,
+{
+    type Wrapped = WithLayer<S>;
+
+    fn wrap(&self, filter: F) -> Self::Wrapped {
+        WithLayer {
+            service: (self.layer)(InnerService::new(filter)),
+        }
+    }
+}
+
+/// A filter wrapped as a Tower `Service`, to be passed to middleware by
+/// [`wrap_layer`](crate::wrap_layer).
+///
+/// It is meant to be called with the requests the outer filter hands to the
+/// middleware; the route state, such as the matched path, is carried along
+/// with them.
+#[derive(Clone)]
+pub struct InnerService {
+    call: Arc<dyn Fn(Request) -> BoxFuture<'static, Result<Response, Infallible>> + Send + Sync>,
+}
+
+// Where the outer route was, to resume from there.
+struct Head {
+    remote_addr: Option<SocketAddr>,
+    matched: usize,
+}
+
+// A rejection of the wrapped filter, carried through the middleware in the
+// extensions of its error response.
+struct Rejected {
+    rejection: Rejection,
+    extensions: http::Extensions,
+    body: Option<hyper::Body>,
+}
+
+impl InnerService {
+    fn new<F>(filter: F) -> Self
+    where
+        F: Filter + Clone + Send + Sync + 'static,
+        F::Extract: Reply,
+        F::Error: Into<Rejection>,
+    {
+        InnerService {
+            call: Arc::new(move |mut req: Request| {
+                let filter = filter.clone();
+                let head = req.extensions_mut().remove::<Head>();
+                Box::pin(async move {
+                    let route = Route::new(req, head.as_ref().and_then(|head| head.remote_addr));
+                    if let Some(head) = head {
+                        route.borrow_mut().skip_matched_path(head.matched);
+                    }
+
+                    let mut fut = Box::pin(route::set(&route, || filter.filter(Internal)));
+                    let mut route = Some(route);
+                    let (result, route) = future::poll_fn(move |cx| {
+                        let current = route.as_ref().expect("polled after ready");
+                        let result = ready!(route::set(current, || fut.as_mut().try_poll(cx)));
+                        Poll::Ready((result, route.take().expect("polled after ready")))
+                    })
+                    .await;
+
+                    Ok(match result {
+                        Ok(reply) => reply.into_response(),
+                        Err(rejection) => {
+                            let rejection = rejection.into();
+                            let mut res = rejection.into_response();
+                            let mut route = route.into_inner();
+                            let rejected = Rejected {
+                                extensions: mem::take(route.extensions_mut()),
+                                body: route.take_body(),
+                                rejection,
+                            };
+                            res.extensions_mut().insert(rejected);
+                            res
+                        }
+                    })
+                })
+            }),
+        }
+    }
+}
+
+impl Service<Request> for InnerService {
+    type Response = Response;
+    type Error = Infallible;
+    type Future = BoxFuture<'static, Result<Response, Infallible>>;
+
+    fn poll_ready(&mut self, _: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, req: Request) -> Self::Future {
+        (self.call)(req)
+    }
+}
+
+impl fmt::Debug for InnerService {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("InnerService").finish()
+    }
+}
+
+#[allow(missing_debug_implementations)]
+#[derive(Clone)]
+pub struct WithLayer<S> {
+    service: S,
+}
+
+impl<S> FilterBase for WithLayer<S>
+where
+    S: Service<Request, Response = Response> + Clone + Send + Sync + 'static,
+    S::Error: Into<Box<dyn StdError + Send + Sync>>,
+    S::Future: Send,
+{
+    type Extract = (Response,);
+    type Error = Rejection;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Extract, Self::Error>> + Send>>;
+
+    fn filter(&self, _: Internal) -> Self::Future {
+        let req = route::with(|route| {
+            let mut req = Request::new(route.take_body().unwrap_or_else(hyper::Body::empty));
+            *req.method_mut() = route.method().clone();
+            *req.uri_mut() = route.uri().clone();
+            *req.version_mut() = route.version();
+            *req.headers_mut() = route.headers().clone();
+            *req.extensions_mut() = mem::take(route.extensions_mut());
+            req.extensions_mut().insert(Head {
+                remote_addr: route.remote_addr(),
+                matched: route.matched_path_index(),
+            });
+            req
+        });
+        let mut service = self.service.clone();
+
+        Box::pin(async move {
+            future::poll_fn(|cx| service.poll_ready(cx))
+                .await
+                .map_err(layer_error)?;
+            let mut res = service.call(req).await.map_err(layer_error)?;
+
+            if let Some(rejected) = res.extensions_mut().remove::<Rejected>() {
+                let Rejected {
+                    rejection,
+                    extensions,
+                    body,
+                } = rejected;
+                route::with(|route| {
+                    *route.extensions_mut() = extensions;
+                    if let Some(body) = body {
+                        route.replace_body(body);
+                    }
+                });
+                return Err(rejection);
+            }
+            Ok((res,))
+        })
+    }
+}
+
+fn layer_error<E: Into<Box<dyn StdError + Send + Sync>>>(err: E) -> Rejection {
+    tracing::debug!("wrapped service error: {}", err.into());
+    reject::known(LayerError { _p: () })
+}
+
+unit_error! {
+    /// An error used to reject if the middleware applied by `wrap_layer`
+    /// fails.
+    pub LayerError: "Service unavailable"
+}
@@ -0,0 +1,77 @@
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use futures_util::{ready, TryFuture};
+use pin_project::pin_project;
+
+use super::{Filter, FilterBase, Internal};
+use crate::reply::{Reply, Response};
+
+#[derive(Clone, Copy, Debug)]
+pub struct MapResponse<T, F> {
+    pub(super) filter: T,
+    pub(super) callback: F,
+}
+
+impl<T, F, U> FilterBase for MapResponse<T, F>
+where
+    T: Filter,
+    T::Extract: Reply,
+    F: Fn(Response) -> U + Clone + Send,
+    U: Future + Send,
+    U::Output: Reply,
+{
+    type Extract = (U::Output,);
+    type Error = T::Error;
+    type Future = MapResponseFuture<T, F, U>;
+    #[inline]
+    fn filter(&self, _: Internal) -> Self::Future {
+        MapResponseFuture {
+            state: State::First(self.filter.filter(Internal), self.callback.clone()),
+        }
+    }
+}
+
+#[allow(missing_debug_implementations)]
+#[pin_project]
+pub struct MapResponseFuture<T: Filter, F, U> {
+    #[pin]
+    state: State<T::Future, F, U>,
+}
+
+#[pin_project(project = StateProj)]
+enum State<T, F, U> {
+    First(#[pin] T, F),
+    Second(#[pin] U),
+    Done,
+}
+
+impl<T, F, U> Future for MapResponseFuture<T, F, U>
+where
+    T: Filter,
+    T::Extract: Reply,
+    F: Fn(Response) -> U,
+    U: Future,
+{
+    type Output = Result<(U::Output,), T::Error>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let mut state = self.project().state;
+        loop {
+            match state.as_mut().project() {
+                StateProj::First(first, callback) => {
+                    let reply = ready!(first.try_poll(cx))?;
+                    let second = callback(reply.into_response());
+                    state.set(State::Second(second));
+                }
+                StateProj::Second(second) => {
+                    let mapped = (ready!(second.poll(cx)),);
+                    state.set(State::Done);
+                    return Poll::Ready(Ok(mapped));
+                }
+                StateProj::Done => panic!("polled after complete"),
+            }
+        }
+    }
+}
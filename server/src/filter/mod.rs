@@ -1,8 +1,11 @@
 mod and;
 mod and_then;
 mod boxed;
+mod instrument;
+pub(crate) mod layer;
 mod map;
 mod map_err;
+mod map_response;
 mod or;
 mod or_else;
 mod recover;
@@ -18,16 +21,22 @@ use futures_util::{future, TryFuture, TryFutureExt};
 
 pub(crate) use crate::generic::{one, Combine, Either, Func, One, Tuple};
 use crate::reject::{CombineRejection, IsReject, Rejection};
+use crate::reply::{Reply, Response};
 use crate::route::{self, Route};
 
 pub(crate) use self::and::And;
 use self::and_then::AndThen;
 pub use self::boxed::BoxedFilter;
+use self::instrument::Instrument;
+pub(crate) use self::instrument::Timings;
+pub use self::layer::wrap_layer;
 pub(crate) use self::map::Map;
 pub(crate) use self::map_err::MapErr;
+use self::map_response::MapResponse;
 pub(crate) use self::or::Or;
 use self::or_else::OrElse;
-use self::recover::Recover;
+pub(crate) use self::recover::Recover;
+use self::service::FilteredService;
 use self::then::Then;
 use self::unify::Unify;
 use self::untuple_one::UntupleOne;
@@ -265,6 +274,53 @@ pub trait Filter: FilterBase {
         }
     }
 
+    /// Composes this `Filter` with an async function receiving the final
+    /// `Response` of its reply.
+    ///
+    /// Unlike [`Filter::then`], the function gets the whole response, once
+    /// the reply has been converted, so it can inspect and change its status,
+    /// headers and body. Rejections are left as they are.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use nextshell::Filter;
+    /// use nextshell::http::header::CONTENT_TYPE;
+    /// use nextshell::hyper::body;
+    ///
+    /// // Inject a script in the HTML pages.
+    /// let route = nextshell::any()
+    ///     .map(|| nextshell::reply::html("<body>hello</body>"))
+    ///     .map_response(|res: nextshell::reply::Response| async move {
+    ///         let is_html = res
+    ///             .headers()
+    ///             .get(CONTENT_TYPE)
+    ///             .map_or(false, |v| v.as_bytes().starts_with(b"text/html"));
+    ///         if !is_html {
+    ///             return res;
+    ///         }
+    ///         let (mut parts, body) = res.into_parts();
+    ///         let html = body::to_bytes(body).await.unwrap_or_default();
+    ///         let html = String::from_utf8_lossy(&html)
+    ///             .replace("</body>", "<script src=\"/reload.js\"></script></body>");
+    ///         parts.headers.remove("content-length");
+    ///         nextshell::reply::Response::from_parts(parts, html.into())
+    ///     });
+    /// ```
+    fn map_response<F, U>(self, fun: F) -> MapResponse<Self, F>
+    where
+        Self: Sized,
+        Self::Extract: Reply,
+        F: Fn(Response) -> U + Clone,
+        U: Future + Send,
+        U::Output: Reply,
+    {
+        MapResponse {
+            filter: self,
+            callback: fun,
+        }
+    }
+
     /// Compose this `Filter` with a function receiving an error.
     ///
     /// The function should return some `TryFuture` type yielding the
@@ -375,6 +431,38 @@ pub trait Filter: FilterBase {
         UntupleOne { filter: self }
     }
 
+    /// Records how long this filter takes to complete, and whether it
+    /// extracted or rejected, under `name`.
+    ///
+    /// The filter runs in a `filter` tracing span, and a `debug` event is
+    /// emitted once it completes. When the route is wrapped by
+    /// [`metrics`](crate::metrics()), the timings are also recorded in the
+    /// `nextshell_filter_duration_seconds` histogram, labeled by `name` and
+    /// outcome.
+    ///
+    /// This helps finding out which part of a route is slow, like
+    /// deserializing a huge JSON body.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use std::collections::HashMap;
+    /// use nextshell::Filter;
+    ///
+    /// let metrics = nextshell::metrics();
+    ///
+    /// let route = nextshell::post()
+    ///     .and(nextshell::body::json().instrument("body_json"))
+    ///     .map(|body: HashMap<String, String>| format!("{} keys", body.len()))
+    ///     .with(metrics);
+    /// ```
+    fn instrument(self, name: &'static str) -> Instrument<Self>
+    where
+        Self: Sized,
+    {
+        Instrument { filter: self, name }
+    }
+
     /// Wraps the current filter with some wrapper.
     ///
     /// The wrapper may do some preparation work before starting this filter,
@@ -399,6 +487,30 @@ pub trait Filter: FilterBase {
         wrapper.wrap(self)
     }
 
+    /// Converts this filter into a Tower `Service`.
+    ///
+    /// This is the same as [`nextshell::service`](crate::service()), so the
+    /// filter can be served by Tower-based servers or wrapped with Tower
+    /// middleware.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use nextshell::Filter;
+    ///
+    /// let svc = nextshell::any()
+    ///     .map(|| "hello")
+    ///     .into_service();
+    /// ```
+    fn into_service(self) -> FilteredService<Self>
+    where
+        Self: Sized,
+        Self::Extract: Reply,
+        Self::Error: IsReject,
+    {
+        crate::service(self)
+    }
+
     /// Boxes this filter into a trait object, making it easier to name the type.
     ///
     /// # Example
@@ -58,6 +58,7 @@ where
     FilteredService { filter }
 }
 
+/// A `Service` running a `Filter`, created by [`service`].
 #[derive(Copy, Clone, Debug)]
 pub struct FilteredService<F> {
     filter: F,
@@ -74,10 +75,16 @@ where
         &self,
         req: Request,
         remote_addr: Option<SocketAddr>,
+    ) -> FilteredFuture<F::Future> {
+        self.call_route(Route::new(req, remote_addr))
+    }
+
+    pub(crate) fn call_route(
+        &self,
+        route: ::std::cell::RefCell<Route>,
     ) -> FilteredFuture<F::Future> {
         debug_assert!(!route::is_set(), "nested route::set calls");
 
-        let route = Route::new(req, remote_addr);
         let fut = route::set(&route, || self.filter.filter(super::Internal));
         FilteredFuture { future: fut, route }
     }
@@ -125,13 +132,15 @@ where
 
         let pin = self.project();
         let fut = pin.future;
-        match route::set(pin.route, || fut.try_poll(cx)) {
+        // Replies are converted while the route is still set, so they may
+        // look at the request, like `reply::json_with_etag` does.
+        route::set(pin.route, || match fut.try_poll(cx) {
             Poll::Ready(Ok(ok)) => Poll::Ready(Ok(ok.into_response())),
             Poll::Pending => Poll::Pending,
             Poll::Ready(Err(err)) => {
                 tracing::debug!("rejected: {:?}", err);
                 Poll::Ready(Ok(err.into_response()))
             }
-        }
+        })
     }
 }
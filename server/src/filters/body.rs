@@ -2,17 +2,54 @@
 //!
 //! Filters that extract a body for a route.
 
+#[cfg(feature = "compression-brotli")]
+use async_compression::tokio::bufread::BrotliDecoder;
+#[cfg(feature = "compression-zstd")]
+use async_compression::tokio::bufread::ZstdDecoder;
+#[cfg(feature = "compression-gzip")]
+use async_compression::tokio::bufread::{GzipDecoder, ZlibDecoder};
+
 use std::error::Error as StdError;
 use std::fmt;
+use std::future::Future;
+use std::io;
 use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
 use std::task::{Context, Poll};
+use std::time::Duration;
 
 use bytes::{Buf, Bytes};
 use futures_util::{future, ready, Stream, TryFutureExt};
+#[cfg(any(
+    feature = "compression-brotli",
+    feature = "compression-gzip",
+    feature = "compression-zstd"
+))]
+use futures_util::{StreamExt, TryStreamExt};
 use headers::ContentLength;
-use http::header::CONTENT_TYPE;
+use http::header::{HeaderMap, CONTENT_TYPE};
+#[cfg(any(
+    feature = "compression-brotli",
+    feature = "compression-gzip",
+    feature = "compression-zstd"
+))]
+use http::header::{HeaderValue, CONTENT_ENCODING, CONTENT_LENGTH};
 use hyper::Body;
 use serde::de::DeserializeOwned;
+#[cfg(any(
+    feature = "compression-brotli",
+    feature = "compression-gzip",
+    feature = "compression-zstd"
+))]
+use tokio::io::AsyncRead;
+use tokio::time::Sleep;
+#[cfg(any(
+    feature = "compression-brotli",
+    feature = "compression-gzip",
+    feature = "compression-zstd"
+))]
+use tokio_util::io::{ReaderStream, StreamReader};
 
 use crate::filter::{filter_fn, filter_fn_one, Filter, FilterBase};
 use crate::reject::{self, Rejection};
@@ -79,6 +116,36 @@ pub fn stream(
     body().map(|body: Body| BodyStream { body })
 }
 
+/// Create a `Filter` that extracts the request body as a [`TrailingStream`],
+/// a stream of its chunks that also gives its trailers once read.
+///
+/// Trailers are received on HTTP/2 connections, HTTP/1.1 requests don't
+/// carry them. If other filters have already extracted the body, this filter
+/// will reject with a `500 Internal Server Error`.
+///
+/// # Example
+///
+/// ```
+/// use futures_util::TryStreamExt;
+/// use nextshell::Filter;
+///
+/// let upload = nextshell::body::stream_with_trailers()
+///     .then(|mut body: nextshell::body::TrailingStream| async move {
+///         let mut len = 0;
+///         while let Some(chunk) = body.try_next().await? {
+///             len += chunk.len();
+///         }
+///         let trailers = body.trailers().await?;
+///         let checksum = trailers.and_then(|t| t.get("x-checksum").cloned());
+///         Ok::<_, nextshell::Error>(format!("{} bytes, checksum {:?}", len, checksum))
+///     })
+///     .map(|result: Result<String, nextshell::Error>| result.unwrap_or_default());
+/// ```
+pub fn stream_with_trailers() -> impl Filter<Extract = (TrailingStream,), Error = Rejection> + Copy
+{
+    body().map(|body: Body| TrailingStream { body })
+}
+
 /// Returns a `Filter` that matches any request and extracts a `Future` of a
 /// concatenated body.
 ///
@@ -106,7 +173,7 @@ pub fn bytes() -> impl Filter<Extract = (Bytes,), Error = Rejection> + Copy {
     body().and_then(|body: hyper::Body| {
         hyper::body::to_bytes(body).map_err(|err| {
             tracing::debug!("to_bytes error: {}", err);
-            reject::known(BodyReadError(err))
+            read_error(err)
         })
     })
 }
@@ -144,11 +211,82 @@ pub fn aggregate() -> impl Filter<Extract = (impl Buf,), Error = Rejection> + Co
     body().and_then(|body: ::hyper::Body| {
         hyper::body::aggregate(body).map_err(|err| {
             tracing::debug!("aggregate error: {}", err);
-            reject::known(BodyReadError(err))
+            read_error(err)
         })
     })
 }
 
+/// Create a `Filter` that decompresses the request body for the body filters
+/// that follow, according to its `content-encoding`.
+///
+/// `gzip`, `deflate`, `br` and `zstd` bodies are supported, depending on the enabled
+/// compression features, and other encodings are rejected with a
+/// `415 Unsupported Media Type`. Requests without a `content-encoding` are
+/// left as they are.
+///
+/// Bodies decompressing to more than 10MB are rejected with a
+/// `413 Payload Too Large` by the body filters, see [`decompress_limit`] to
+/// change it. As the `content-length` of the decompressed body isn't known,
+/// [`content_length_limit`] has to come before this filter.
+///
+/// # Example
+///
+/// ```
+/// use std::collections::HashMap;
+/// use nextshell::Filter;
+///
+/// let route = nextshell::body::content_length_limit(1024 * 32)
+///     .and(nextshell::body::decompress())
+///     .and(nextshell::body::json())
+///     .map(|simple_map: HashMap<String, String>| {
+///         "Got a JSON body!"
+///     });
+/// ```
+#[cfg(any(
+    feature = "compression-brotli",
+    feature = "compression-gzip",
+    feature = "compression-zstd"
+))]
+pub fn decompress() -> impl Filter<Extract = (), Error = Rejection> + Copy {
+    decompress_limit(10 * 1024 * 1024)
+}
+
+/// Like [`decompress`], rejecting bodies decompressing to more than `limit`
+/// bytes.
+#[cfg(any(
+    feature = "compression-brotli",
+    feature = "compression-gzip",
+    feature = "compression-zstd"
+))]
+pub fn decompress_limit(limit: u64) -> impl Filter<Extract = (), Error = Rejection> + Copy {
+    filter_fn(move |route| {
+        let encoding = match route.headers().get(CONTENT_ENCODING) {
+            Some(encoding) => encoding.clone(),
+            None => return future::ok(()),
+        };
+        let encoding = match Encoding::from_header(&encoding) {
+            Some(encoding) => encoding,
+            None => {
+                tracing::debug!("unsupported content-encoding: {:?}", encoding);
+                return future::err(reject::unsupported_media_type());
+            }
+        };
+        if encoding != Encoding::Identity {
+            let body = match route.take_body() {
+                Some(body) => body,
+                None => {
+                    tracing::error!("request body already taken in previous filter");
+                    return future::err(reject::known(BodyConsumedMultipleTimes { _p: () }));
+                }
+            };
+            route.replace_body(encoding.decode(body, limit));
+            route.headers_mut().remove(CONTENT_LENGTH);
+        }
+        route.headers_mut().remove(CONTENT_ENCODING);
+        future::ok(())
+    })
+}
+
 /// Returns a `Filter` that matches any request and extracts a `Future` of a
 /// JSON-decoded body.
 ///
@@ -215,6 +353,90 @@ pub fn form<T: DeserializeOwned + Send>() -> impl Filter<Extract = (T,), Error =
         })
 }
 
+// ===== Content Encodings =====
+
+#[cfg(any(
+    feature = "compression-brotli",
+    feature = "compression-gzip",
+    feature = "compression-zstd"
+))]
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Encoding {
+    Identity,
+    #[cfg(feature = "compression-brotli")]
+    Br,
+    #[cfg(feature = "compression-gzip")]
+    Deflate,
+    #[cfg(feature = "compression-gzip")]
+    Gzip,
+    #[cfg(feature = "compression-zstd")]
+    Zstd,
+}
+
+#[cfg(any(
+    feature = "compression-brotli",
+    feature = "compression-gzip",
+    feature = "compression-zstd"
+))]
+impl Encoding {
+    fn from_header(value: &HeaderValue) -> Option<Encoding> {
+        let value = value.to_str().ok()?.trim();
+        if value.eq_ignore_ascii_case("identity") {
+            return Some(Encoding::Identity);
+        }
+        #[cfg(feature = "compression-brotli")]
+        {
+            if value.eq_ignore_ascii_case("br") {
+                return Some(Encoding::Br);
+            }
+        }
+        #[cfg(feature = "compression-gzip")]
+        {
+            if value.eq_ignore_ascii_case("deflate") {
+                return Some(Encoding::Deflate);
+            }
+            if value.eq_ignore_ascii_case("gzip") || value.eq_ignore_ascii_case("x-gzip") {
+                return Some(Encoding::Gzip);
+            }
+        }
+        #[cfg(feature = "compression-zstd")]
+        {
+            if value.eq_ignore_ascii_case("zstd") {
+                return Some(Encoding::Zstd);
+            }
+        }
+        None
+    }
+
+    fn decode(self, body: Body, limit: u64) -> Body {
+        let reader = StreamReader::new(body.map_err(io::Error::other));
+        let decoder: Pin<Box<dyn AsyncRead + Send>> = match self {
+            Encoding::Identity => Box::pin(reader),
+            #[cfg(feature = "compression-brotli")]
+            Encoding::Br => Box::pin(BrotliDecoder::new(reader)),
+            // HTTP's `deflate` is the zlib format, not raw deflate.
+            #[cfg(feature = "compression-gzip")]
+            Encoding::Deflate => Box::pin(ZlibDecoder::new(reader)),
+            #[cfg(feature = "compression-gzip")]
+            Encoding::Gzip => Box::pin(GzipDecoder::new(reader)),
+            #[cfg(feature = "compression-zstd")]
+            Encoding::Zstd => Box::pin(ZstdDecoder::new(reader)),
+        };
+
+        let mut len = 0;
+        let decoded = ReaderStream::new(decoder).map(move |chunk| {
+            let chunk = chunk?;
+            len += chunk.len() as u64;
+            if len > limit {
+                tracing::debug!("decompressed body is over limit {}", limit);
+                return Err(io::Error::other(DecompressedTooLarge { _p: () }));
+            }
+            Ok(chunk)
+        });
+        Body::wrap_stream(decoded)
+    }
+}
+
 // ===== Decoders =====
 
 trait Decode {
@@ -308,6 +530,82 @@ impl Stream for BodyStream {
     }
 }
 
+/// A request body stream, followed by trailers.
+///
+/// Extracted by [`stream_with_trailers`].
+#[derive(Debug)]
+pub struct TrailingStream {
+    body: Body,
+}
+
+impl TrailingStream {
+    /// Receive the trailers of the body, or `None` if it has none.
+    ///
+    /// This should be called once all the chunks have been read.
+    pub async fn trailers(&mut self) -> Result<Option<HeaderMap>, crate::Error> {
+        hyper::body::HttpBody::trailers(&mut self.body)
+            .await
+            .map_err(crate::Error::new)
+    }
+}
+
+impl Stream for TrailingStream {
+    type Item = Result<Bytes, crate::Error>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let opt_item = ready!(Pin::new(&mut self.get_mut().body).poll_next(cx));
+        Poll::Ready(opt_item.map(|item| item.map_err(crate::Error::new)))
+    }
+}
+
+// ===== TimedBody =====
+
+// Limits the time the body of an HTTP/1 `req` may stall between chunks.
+//
+// Returns a flag set once it has timed out, if the body is to be read at all.
+pub(crate) fn read_timeout(req: &mut crate::Request, timeout: Duration) -> Option<Arc<AtomicBool>> {
+    if req.version() >= http::Version::HTTP_2 || hyper::body::HttpBody::is_end_stream(req.body()) {
+        return None;
+    }
+    let timed_out = Arc::new(AtomicBool::new(false));
+    let body = std::mem::take(req.body_mut());
+    *req.body_mut() = Body::wrap_stream(TimedBody {
+        body,
+        timeout,
+        timer: None,
+        timed_out: timed_out.clone(),
+    });
+    Some(timed_out)
+}
+
+struct TimedBody {
+    body: Body,
+    timeout: Duration,
+    timer: Option<Pin<Box<Sleep>>>,
+    timed_out: Arc<AtomicBool>,
+}
+
+impl Stream for TimedBody {
+    type Item = Result<Bytes, BoxError>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        if let Poll::Ready(item) = Pin::new(&mut this.body).poll_next(cx) {
+            this.timer = None;
+            return Poll::Ready(item.map(|item| item.map_err(BoxError::from)));
+        }
+
+        let timeout = this.timeout;
+        let timer = this
+            .timer
+            .get_or_insert_with(|| Box::pin(tokio::time::sleep(timeout)));
+        ready!(timer.as_mut().poll(cx));
+        tracing::debug!("request body stalled for {:?}", timeout);
+        this.timed_out.store(true, Ordering::SeqCst);
+        Poll::Ready(Some(Err(BoxError::from(BodyReadTimeout { _p: () }))))
+    }
+}
+
 // ===== Rejections =====
 
 /// An error used in rejections when deserializing a request body fails.
@@ -331,6 +629,31 @@ impl StdError for BodyDeserializeError {
 #[derive(Debug)]
 pub(crate) struct BodyReadError(::hyper::Error);
 
+fn read_error(err: hyper::Error) -> Rejection {
+    // A body over the limit of `decompress`, or stalled past the server's
+    // `body_read_timeout`.
+    let mut source = StdError::source(&err);
+    while let Some(cause) = source {
+        let inner = match cause.downcast_ref::<io::Error>() {
+            Some(io) => io.get_ref().map(|inner| inner as &(dyn StdError + 'static)),
+            None => Some(cause),
+        };
+        #[cfg(any(
+            feature = "compression-brotli",
+            feature = "compression-gzip",
+            feature = "compression-zstd"
+        ))]
+        if inner.is_some_and(|inner| inner.is::<DecompressedTooLarge>()) {
+            return reject::payload_too_large();
+        }
+        if inner.is_some_and(|inner| inner.is::<BodyReadTimeout>()) {
+            return reject::request_timeout();
+        }
+        source = cause.source();
+    }
+    reject::known(BodyReadError(err))
+}
+
 impl fmt::Display for BodyReadError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(f, "Request body read error: {}", self.0)
@@ -339,6 +662,19 @@ impl fmt::Display for BodyReadError {
 
 impl StdError for BodyReadError {}
 
+unit_error! {
+    pub(crate) BodyReadTimeout: "Request body read timed out"
+}
+
 unit_error! {
     pub(crate) BodyConsumedMultipleTimes: "Request body consumed multiple times"
 }
+
+#[cfg(any(
+    feature = "compression-brotli",
+    feature = "compression-gzip",
+    feature = "compression-zstd"
+))]
+unit_error! {
+    pub(crate) DecompressedTooLarge: "Decompressed request body is too large"
+}
@@ -0,0 +1,557 @@
+//! Response Cache Filters
+//!
+//! [`cache()`](crate::cache()) creates a [`Cache`] that, used as a wrapping
+//! filter, keeps the responses of the wrapped filter in memory, and answers
+//! the following identical requests with them until they expire.
+//!
+//! Only `GET` and `HEAD` requests are cached, keyed by method, path, query
+//! and the headers given to [`Cache::vary`]. Only `200 OK` responses are
+//! stored, unless they set cookies, their `cache-control` forbids it, or
+//! their `vary` names a header that isn't keyed. Requests with an
+//! `authorization` or `cookie` header aren't cached, unless it's one of the
+//! keyed headers.
+//!
+//! # Example
+//!
+//! ```
+//! use std::time::Duration;
+//! use nextshell::Filter;
+//!
+//! let cache = nextshell::cache(Duration::from_secs(60))
+//!     .vary("accept-language")
+//!     .stale_while_revalidate(Duration::from_secs(30));
+//!
+//! let report = nextshell::path!("report")
+//!     .map(|| "an expensive report")
+//!     .with(cache.clone());
+//!
+//! // Later, when the report changes:
+//! cache.invalidate("/report");
+//! ```
+
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use bytes::{Bytes, BytesMut};
+use futures_util::{stream, StreamExt};
+use http::header::{
+    HeaderMap, HeaderName, HeaderValue, AGE, AUTHORIZATION, CACHE_CONTROL, COOKIE, SET_COOKIE, VARY,
+};
+use http::{Method, StatusCode};
+use hyper::Body;
+use linked_hash_map::LinkedHashMap;
+
+use crate::filter::{Filter, WrapSealed};
+use crate::reject::IsReject;
+use crate::reply::{Reply, Response};
+use crate::route::Route;
+
+use self::internal::WithCache;
+
+/// Create a new [`Cache`], keeping responses for `ttl`.
+///
+/// See the [module docs](crate::cache) for an example.
+pub fn cache(ttl: Duration) -> Cache {
+    Cache {
+        store: Arc::new(Mutex::new(Store {
+            entries: LinkedHashMap::new(),
+            bytes: 0,
+        })),
+        ttl,
+        stale: Duration::from_secs(0),
+        vary: Vec::new(),
+        max_entries: 1024,
+        max_bytes: 64 * 1024 * 1024,
+        max_entry_bytes: 1024 * 1024,
+    }
+}
+
+/// An in-memory response cache, also a [`Filter`] wrapper using it.
+///
+/// Cloning a `Cache` shares the same stored responses, so a clone can be
+/// kept to invalidate them.
+#[derive(Clone, Debug)]
+pub struct Cache {
+    store: Arc<Mutex<Store>>,
+    ttl: Duration,
+    stale: Duration,
+    vary: Vec<HeaderName>,
+    max_entries: usize,
+    max_bytes: usize,
+    max_entry_bytes: usize,
+}
+
+#[derive(Debug)]
+struct Store {
+    // From the least to the most recently used.
+    entries: LinkedHashMap<Key, Entry>,
+    // The sum of the sizes of the entries.
+    bytes: usize,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+struct Key {
+    method: Method,
+    path: String,
+    query: Option<String>,
+    vary: Vec<Option<HeaderValue>>,
+}
+
+#[derive(Debug)]
+struct Entry {
+    status: StatusCode,
+    headers: HeaderMap,
+    body: Bytes,
+    stored: Instant,
+    revalidating: bool,
+}
+
+// What a request gets from the cache.
+enum Lookup {
+    Fresh(Response),
+    // Whether this request should revalidate the entry.
+    Stale(Response, bool),
+    Miss,
+}
+
+impl Cache {
+    /// Key the cached responses by the value of this request header too.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `name` isn't a valid header name.
+    pub fn vary(mut self, name: &str) -> Self {
+        let name = HeaderName::from_bytes(name.as_bytes())
+            .unwrap_or_else(|_| panic!("invalid cache vary header: {:?}", name));
+        self.vary.push(name);
+        self
+    }
+
+    /// Keep serving expired responses for `stale` more, while they're
+    /// refreshed in the background.
+    ///
+    /// The first request getting an expired response runs the wrapped filter
+    /// again in a new task, on a copy of its head, and stores the new
+    /// response. Nothing is served stale by default.
+    pub fn stale_while_revalidate(mut self, stale: Duration) -> Self {
+        self.stale = stale;
+        self
+    }
+
+    /// Keep at most `max` responses, evicting the least recently used ones
+    /// first.
+    ///
+    /// Defaults to 1024.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `max` is 0.
+    pub fn max_entries(mut self, max: usize) -> Self {
+        assert!(max > 0, "cache max_entries must be at least 1");
+        self.max_entries = max;
+        self
+    }
+
+    /// Keep at most `max` bytes of responses, evicting the least recently
+    /// used ones first.
+    ///
+    /// Defaults to 64MB.
+    pub fn max_bytes(mut self, max: usize) -> Self {
+        self.max_bytes = max;
+        self
+    }
+
+    /// Don't store the responses bigger than `max` bytes.
+    ///
+    /// Defaults to 1MB.
+    pub fn max_entry_bytes(mut self, max: usize) -> Self {
+        self.max_entry_bytes = max;
+        self
+    }
+
+    /// Remove the stored responses of `path`, whatever their query.
+    pub fn invalidate(&self, path: &str) {
+        let mut store = self.store.lock().unwrap();
+        let keys = store
+            .entries
+            .keys()
+            .filter(|key| key.path == path)
+            .cloned()
+            .collect::<Vec<_>>();
+        for key in keys {
+            store.remove(&key);
+        }
+    }
+
+    /// Remove all the stored responses.
+    pub fn clear(&self) {
+        let mut store = self.store.lock().unwrap();
+        store.entries.clear();
+        store.bytes = 0;
+    }
+
+    fn key(&self, route: &Route) -> Option<Key> {
+        let method = route.method();
+        if method != Method::GET && method != Method::HEAD {
+            return None;
+        }
+        let headers = route.headers();
+        // Responses to credentialed requests are likely personal.
+        for credentials in [AUTHORIZATION, COOKIE] {
+            if headers.contains_key(&credentials) && !self.vary.contains(&credentials) {
+                return None;
+            }
+        }
+        Some(Key {
+            method: method.clone(),
+            path: route.full_path().to_owned(),
+            query: route.query().map(ToOwned::to_owned),
+            vary: self
+                .vary
+                .iter()
+                .map(|name| headers.get(name).cloned())
+                .collect(),
+        })
+    }
+
+    fn lookup(&self, key: &Key) -> Lookup {
+        let now = tokio::time::Instant::now().into_std();
+        let mut store = self.store.lock().unwrap();
+        let entry = match store.entries.get_refresh(key) {
+            Some(entry) => entry,
+            None => return Lookup::Miss,
+        };
+
+        let age = now.saturating_duration_since(entry.stored);
+        if age < self.ttl {
+            Lookup::Fresh(entry.response(age))
+        } else if age < self.ttl + self.stale {
+            let revalidate = !entry.revalidating;
+            entry.revalidating = true;
+            Lookup::Stale(entry.response(age), revalidate)
+        } else {
+            store.remove(key);
+            Lookup::Miss
+        }
+    }
+
+    /// Stores `res` if it can be, and returns it.
+    async fn store(self, key: Key, res: Response) -> Response {
+        if !is_cacheable(&res, &self.vary) {
+            self.done_revalidating(&key);
+            return res;
+        }
+
+        let (parts, body) = res.into_parts();
+        let body = match buffer(body, self.max_entry_bytes).await {
+            Ok(body) => body,
+            Err(body) => {
+                tracing::debug!("response too big to be cached");
+                self.done_revalidating(&key);
+                return Response::from_parts(parts, body);
+            }
+        };
+
+        let entry = Entry {
+            status: parts.status,
+            headers: parts.headers.clone(),
+            body: body.clone(),
+            stored: tokio::time::Instant::now().into_std(),
+            revalidating: false,
+        };
+        self.store
+            .lock()
+            .unwrap()
+            .insert(key, entry, self.max_entries, self.max_bytes);
+        Response::from_parts(parts, Body::from(body))
+    }
+
+    fn done_revalidating(&self, key: &Key) {
+        if let Some(entry) = self.store.lock().unwrap().entries.get_mut(key) {
+            entry.revalidating = false;
+        }
+    }
+
+    /// Runs `filter` again in a new task, to refresh a stale entry.
+    fn revalidate<F>(&self, filter: F, key: Key, head: Head)
+    where
+        F: Filter + Send + Sync + 'static,
+        F::Extract: Reply,
+        F::Error: IsReject,
+    {
+        let cache = self.clone();
+        tokio::spawn(async move {
+            let route = Route::new(head.req, head.remote_addr);
+            route.borrow_mut().skip_matched_path(head.matched);
+            let res = match crate::service(filter).call_route(route).await {
+                Ok(res) => res,
+                Err(never) => match never {},
+            };
+            cache.store(key, res).await;
+        });
+    }
+}
+
+/// A copy of the head of a request, to revalidate its stale entry.
+struct Head {
+    req: crate::Request,
+    remote_addr: Option<SocketAddr>,
+    matched: usize,
+}
+
+impl Head {
+    fn new(route: &Route) -> Head {
+        let mut req = crate::Request::new(Body::empty());
+        *req.method_mut() = route.method().clone();
+        *req.uri_mut() = route.uri().clone();
+        *req.version_mut() = route.version();
+        *req.headers_mut() = route.headers().clone();
+        Head {
+            req,
+            remote_addr: route.remote_addr(),
+            matched: route.matched_path_index(),
+        }
+    }
+}
+
+impl Store {
+    fn insert(&mut self, key: Key, entry: Entry, max_entries: usize, max_bytes: usize) {
+        self.remove(&key);
+        self.bytes += entry.size();
+        self.entries.insert(key, entry);
+
+        while self.entries.len() > max_entries || self.bytes > max_bytes {
+            let (_, lru) = self
+                .entries
+                .pop_front()
+                .expect("entries can't be empty while over the limits");
+            self.bytes -= lru.size();
+        }
+    }
+
+    fn remove(&mut self, key: &Key) {
+        if let Some(entry) = self.entries.remove(key) {
+            self.bytes -= entry.size();
+        }
+    }
+}
+
+impl Entry {
+    fn size(&self) -> usize {
+        self.body.len()
+            + self
+                .headers
+                .iter()
+                .map(|(name, value)| name.as_str().len() + value.len())
+                .sum::<usize>()
+    }
+
+    fn response(&self, age: Duration) -> Response {
+        let mut res = Response::new(Body::from(self.body.clone()));
+        *res.status_mut() = self.status;
+        *res.headers_mut() = self.headers.clone();
+        res.headers_mut()
+            .insert(AGE, HeaderValue::from(age.as_secs()));
+        res
+    }
+}
+
+fn is_cacheable(res: &Response, vary: &[HeaderName]) -> bool {
+    if res.status() != StatusCode::OK || res.headers().contains_key(SET_COOKIE) {
+        return false;
+    }
+    // The response may differ for requests with the same key otherwise.
+    let unkeyed = res
+        .headers()
+        .get_all(VARY)
+        .iter()
+        .flat_map(|value| value.to_str().unwrap_or("*").split(','))
+        .map(str::trim)
+        .filter(|name| !name.is_empty())
+        .any(|name| match HeaderName::from_bytes(name.as_bytes()) {
+            Ok(name) => !vary.contains(&name),
+            // Including `*`.
+            Err(_) => true,
+        });
+    if unkeyed {
+        return false;
+    }
+    !res.headers()
+        .get_all(CACHE_CONTROL)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .any(|directive| {
+            let directive = directive.trim();
+            directive.eq_ignore_ascii_case("no-store")
+                || directive.eq_ignore_ascii_case("no-cache")
+                || directive.eq_ignore_ascii_case("private")
+        })
+}
+
+/// Reads `body` in memory if it's at most `max` bytes, or gives it back.
+async fn buffer(mut body: Body, max: usize) -> Result<Bytes, Body> {
+    let mut buf = BytesMut::new();
+    while let Some(chunk) = body.next().await {
+        let chunk = match chunk {
+            Ok(chunk) => chunk,
+            Err(err) => {
+                let read = stream::once(async move { Ok(buf.freeze()) });
+                let failed = stream::once(async move { Err(err) });
+                return Err(Body::wrap_stream(read.chain(failed)));
+            }
+        };
+        if buf.len() + chunk.len() > max {
+            let read = stream::iter(vec![Ok::<_, hyper::Error>(buf.freeze()), Ok(chunk)]);
+            return Err(Body::wrap_stream(read.chain(body)));
+        }
+        buf.extend_from_slice(&chunk);
+    }
+    Ok(buf.freeze())
+}
+
+impl<F> WrapSealed<F> for Cache
+where
+    F: Filter + Clone + Send + Sync + 'static,
+    F::Extract: Reply,
+    F::Error: IsReject,
+{
+    type Wrapped = WithCache<F>;
+
+    fn wrap(&self, filter: F) -> Self::Wrapped {
+        WithCache {
+            filter,
+            cache: self.clone(),
+        }
+    }
+}
+
+mod internal {
+    use std::future::Future;
+    use std::pin::Pin;
+    use std::task::{Context, Poll};
+
+    use futures_util::{ready, TryFuture};
+    use pin_project::pin_project;
+
+    use super::{Cache, Head, Key, Lookup};
+    use crate::filter::{Filter, FilterBase, Internal};
+    use crate::reject::IsReject;
+    use crate::reply::{Reply, Response};
+    use crate::route;
+
+    #[allow(missing_debug_implementations)]
+    pub struct Cached(Response);
+
+    impl Reply for Cached {
+        #[inline]
+        fn into_response(self) -> Response {
+            self.0
+        }
+    }
+
+    #[allow(missing_debug_implementations)]
+    #[derive(Clone)]
+    pub struct WithCache<F> {
+        pub(super) filter: F,
+        pub(super) cache: Cache,
+    }
+
+    impl<F> FilterBase for WithCache<F>
+    where
+        F: Filter + Clone + Send + Sync + 'static,
+        F::Extract: Reply,
+        F::Error: IsReject,
+    {
+        type Extract = (Cached,);
+        type Error = F::Error;
+        type Future = WithCacheFuture<F::Future>;
+
+        fn filter(&self, _: Internal) -> Self::Future {
+            let cache = &self.cache;
+            let (key, lookup, head) = route::with(|route| {
+                let key = match cache.key(route) {
+                    Some(key) => key,
+                    None => return (None, Lookup::Miss, None),
+                };
+                let lookup = cache.lookup(&key);
+                let head = match lookup {
+                    Lookup::Stale(_, true) => Some(Head::new(route)),
+                    _ => None,
+                };
+                (Some(key), lookup, head)
+            });
+            if let (Some(key), Some(head)) = (&key, head) {
+                cache.revalidate(self.filter.clone(), key.clone(), head);
+            }
+
+            let state = match (key, lookup) {
+                (_, Lookup::Fresh(res)) | (_, Lookup::Stale(res, _)) => State::Hit(Some(res)),
+                (key, Lookup::Miss) => State::Filter {
+                    key,
+                    future: self.filter.filter(Internal),
+                },
+            };
+            WithCacheFuture {
+                cache: self.cache.clone(),
+                state,
+            }
+        }
+    }
+
+    #[allow(missing_debug_implementations)]
+    #[pin_project]
+    pub struct WithCacheFuture<F> {
+        cache: Cache,
+        #[pin]
+        state: State<F>,
+    }
+
+    #[pin_project(project = StateProj)]
+    enum State<F> {
+        Hit(Option<Response>),
+        Filter {
+            key: Option<Key>,
+            #[pin]
+            future: F,
+        },
+        Store(Pin<Box<dyn Future<Output = Response> + Send>>),
+    }
+
+    impl<F> Future for WithCacheFuture<F>
+    where
+        F: TryFuture,
+        F::Ok: Reply,
+        F::Error: IsReject,
+    {
+        type Output = Result<(Cached,), F::Error>;
+
+        fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+            let mut pin = self.project();
+            loop {
+                let store = match pin.state.as_mut().project() {
+                    StateProj::Hit(res) => {
+                        let res = res.take().expect("polled after complete");
+                        return Poll::Ready(Ok((Cached(res),)));
+                    }
+                    StateProj::Filter { key, future } => {
+                        let res = match ready!(future.try_poll(cx)) {
+                            Ok(reply) => reply.into_response(),
+                            Err(reject) => return Poll::Ready(Err(reject)),
+                        };
+                        match key.take() {
+                            Some(key) => Box::pin(pin.cache.clone().store(key, res)),
+                            None => return Poll::Ready(Ok((Cached(res),))),
+                        }
+                    }
+                    StateProj::Store(future) => {
+                        let res = ready!(future.as_mut().poll(cx));
+                        return Poll::Ready(Ok((Cached(res),)));
+                    }
+                };
+                pin.state.set(State::Store(store));
+            }
+        }
+    }
+}
@@ -8,11 +8,15 @@ use async_compression::tokio::bufread::BrotliEncoder;
 #[cfg(feature = "compression-gzip")]
 use async_compression::tokio::bufread::{DeflateEncoder, GzipEncoder};
 
-use http::header::HeaderValue;
+use std::sync::Arc;
+
+use http::header::{HeaderValue, CONTENT_TYPE};
 use hyper::{
+    body::HttpBody,
     header::{CONTENT_ENCODING, CONTENT_LENGTH},
     Body,
 };
+use mime::Mime;
 use tokio_util::io::{ReaderStream, StreamReader};
 
 use crate::filter::{Filter, WrapSealed};
@@ -21,6 +25,7 @@ use crate::reply::{Reply, Response};
 
 use self::internal::{CompressionProps, WithCompression};
 
+#[derive(Clone, Copy, Debug, PartialEq)]
 enum CompressionAlgo {
     #[cfg(feature = "compression-brotli")]
     BR,
@@ -33,25 +38,328 @@ enum CompressionAlgo {
 impl From<CompressionAlgo> for HeaderValue {
     #[inline]
     fn from(algo: CompressionAlgo) -> Self {
-        HeaderValue::from_static(match algo {
+        HeaderValue::from_static(algo.name())
+    }
+}
+
+impl CompressionAlgo {
+    /// Supported algorithms, in the order preferred by the server when the
+    /// client accepts several of them equally.
+    const PREFERRED: &'static [CompressionAlgo] = &[
+        #[cfg(feature = "compression-brotli")]
+        CompressionAlgo::BR,
+        #[cfg(feature = "compression-gzip")]
+        CompressionAlgo::GZIP,
+        #[cfg(feature = "compression-gzip")]
+        CompressionAlgo::DEFLATE,
+    ];
+
+    fn name(self) -> &'static str {
+        match self {
             #[cfg(feature = "compression-brotli")]
             CompressionAlgo::BR => "br",
             #[cfg(feature = "compression-gzip")]
             CompressionAlgo::DEFLATE => "deflate",
             #[cfg(feature = "compression-gzip")]
             CompressionAlgo::GZIP => "gzip",
+        }
+    }
+
+    fn encode(self, mut props: CompressionProps) -> Response {
+        let reader = StreamReader::new(props.body);
+        let body = match self {
+            #[cfg(feature = "compression-brotli")]
+            CompressionAlgo::BR => Body::wrap_stream(ReaderStream::new(BrotliEncoder::new(reader))),
+            #[cfg(feature = "compression-gzip")]
+            CompressionAlgo::DEFLATE => {
+                Body::wrap_stream(ReaderStream::new(DeflateEncoder::new(reader)))
+            }
+            #[cfg(feature = "compression-gzip")]
+            CompressionAlgo::GZIP => Body::wrap_stream(ReaderStream::new(GzipEncoder::new(reader))),
+        };
+        props.head.headers.append(CONTENT_ENCODING, self.into());
+        props.head.headers.remove(CONTENT_LENGTH);
+        Response::from_parts(props.head, body)
+    }
+}
+
+/// Picks the best supported algorithm from an `accept-encoding` header value.
+///
+/// Returns `None` if the client doesn't accept any supported algorithm, or
+/// explicitly prefers `identity` over all of them.
+fn negotiate(accept_encoding: &HeaderValue) -> Option<CompressionAlgo> {
+    let accept_encoding = accept_encoding.to_str().ok()?;
+
+    let mut wildcard = None;
+    let mut identity = None;
+    let mut explicit = Vec::new();
+    for item in accept_encoding.split(',') {
+        let mut parts = item.split(';');
+        let coding = parts.next().unwrap_or("").trim();
+        if coding.is_empty() {
+            continue;
+        }
+        let quality = parts
+            .filter_map(|param| {
+                let (key, value) = param.split_once('=')?;
+                if key.trim().eq_ignore_ascii_case("q") {
+                    value.trim().parse::<f32>().ok()
+                } else {
+                    None
+                }
+            })
+            .next()
+            .unwrap_or(1.0);
+
+        if coding == "*" {
+            wildcard = Some(quality);
+        } else if coding.eq_ignore_ascii_case("identity") {
+            identity = Some(quality);
+        } else {
+            explicit.push((coding, quality));
+        }
+    }
+
+    let (algo, quality) = CompressionAlgo::PREFERRED
+        .iter()
+        .filter_map(|&algo| {
+            let quality = explicit
+                .iter()
+                .find(|(coding, _)| coding.eq_ignore_ascii_case(algo.name()))
+                .map(|&(_, quality)| quality)
+                .or(wildcard)?;
+            Some((algo, quality))
         })
+        .filter(|&(_, quality)| quality > 0.0)
+        // `max_by` returns the last maximum, so iterate in reverse to keep
+        // the server's preference among equal qualities.
+        .rev()
+        .max_by(|a, b| a.1.total_cmp(&b.1))?;
+
+    // `identity` is always acceptable unless excluded, but it only competes
+    // with the compressed encodings when the client lists it explicitly.
+    match identity {
+        Some(identity) if identity > quality => None,
+        _ => Some(algo),
     }
 }
 
 /// Compression
-#[derive(Clone, Copy, Debug)]
+///
+/// Besides wrapping a filter, this can be configured to only compress
+/// responses of a minimum size or with certain content types.
+///
+/// # Example
+///
+/// ```
+/// use nextshell::Filter;
+///
+/// let compression = nextshell::compression::gzip()
+///     .min_size(1024)
+///     .deny_content_types(["image/*", "application/zip"]);
+///
+/// let route = nextshell::path("static")
+///     .and(nextshell::fs::dir("./static"))
+///     .with(compression);
+/// ```
+#[derive(Clone, Debug)]
 pub struct Compression<F> {
     func: F,
+    options: Arc<Options>,
+    negotiate: bool,
+}
+
+#[derive(Clone, Debug, Default)]
+struct Options {
+    min_size: u64,
+    allowed_types: ContentTypes,
+    denied_types: ContentTypes,
+}
+
+/// A list of content type patterns, as type and subtype.
+#[derive(Clone, Debug, Default)]
+struct ContentTypes {
+    patterns: Vec<(&'static str, &'static str)>,
+}
+
+impl ContentTypes {
+    fn push(&mut self, content_type: &'static str) {
+        if content_type.parse::<Mime>().is_err() {
+            panic!("illegal content type");
+        }
+        let (type_, subtype) = content_type
+            .split(';')
+            .next()
+            .and_then(|essence| essence.trim().split_once('/'))
+            .expect("parsed content type has a subtype");
+        self.patterns.push((type_, subtype));
+    }
+
+    fn is_empty(&self) -> bool {
+        self.patterns.is_empty()
+    }
+
+    fn matches(&self, content_type: &Mime) -> bool {
+        self.patterns.iter().any(|&(type_, subtype)| {
+            (type_ == "*" || content_type.type_().as_str().eq_ignore_ascii_case(type_))
+                && (subtype == "*"
+                    || content_type
+                        .subtype()
+                        .as_str()
+                        .eq_ignore_ascii_case(subtype))
+        })
+    }
+}
+
+impl<F> Compression<F> {
+    fn new(func: F) -> Self {
+        Compression {
+            func,
+            options: Arc::default(),
+            negotiate: false,
+        }
+    }
+
+    fn configure(mut self, f: impl FnOnce(&mut Options)) -> Self {
+        f(Arc::make_mut(&mut self.options));
+        self
+    }
+
+    /// Sets the minimum body size, in bytes, for a response to be compressed.
+    ///
+    /// The size is taken from the `content-length` header, or from the body
+    /// itself when its length is known. Streaming bodies of unknown length
+    /// are always compressed.
+    pub fn min_size(self, bytes: u64) -> Self {
+        self.configure(|options| options.min_size = bytes)
+    }
+
+    /// Adds a content type to the list of compressed content types.
+    ///
+    /// Once any content type is allowed, responses with other content types,
+    /// or without a `content-type` header, are not compressed. A wildcard
+    /// subtype, such as `text/*`, matches any subtype.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the provided argument is not a valid content type.
+    pub fn allow_content_type(self, content_type: &'static str) -> Self {
+        self.configure(|options| options.allowed_types.push(content_type))
+    }
+
+    /// Adds multiple content types to the list of compressed content types.
+    ///
+    /// # Panics
+    ///
+    /// Panics if any of the provided arguments is not a valid content type.
+    pub fn allow_content_types<I>(self, content_types: I) -> Self
+    where
+        I: IntoIterator<Item = &'static str>,
+    {
+        content_types.into_iter().fold(self, |this, content_type| {
+            this.allow_content_type(content_type)
+        })
+    }
+
+    /// Adds a content type which is never compressed, such as already
+    /// compressed images or archives.
+    ///
+    /// A wildcard subtype, such as `image/*`, matches any subtype. Denied
+    /// content types take precedence over allowed ones.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the provided argument is not a valid content type.
+    pub fn deny_content_type(self, content_type: &'static str) -> Self {
+        self.configure(|options| options.denied_types.push(content_type))
+    }
+
+    /// Adds multiple content types which are never compressed.
+    ///
+    /// # Panics
+    ///
+    /// Panics if any of the provided arguments is not a valid content type.
+    pub fn deny_content_types<I>(self, content_types: I) -> Self
+    where
+        I: IntoIterator<Item = &'static str>,
+    {
+        content_types.into_iter().fold(self, |this, content_type| {
+            this.deny_content_type(content_type)
+        })
+    }
 }
 
-// TODO: The implementation of `gzip()`, `deflate()`, and `brotli()` could be replaced with
-// generics or a macro
+impl Options {
+    fn should_compress(&self, res: &Response) -> bool {
+        let len = res
+            .headers()
+            .get(CONTENT_LENGTH)
+            .and_then(|value| value.to_str().ok()?.parse::<u64>().ok())
+            .or_else(|| res.body().size_hint().exact());
+        if matches!(len, Some(len) if len < self.min_size) {
+            return false;
+        }
+
+        if self.allowed_types.is_empty() && self.denied_types.is_empty() {
+            return true;
+        }
+
+        let content_type = res
+            .headers()
+            .get(CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok()?.parse::<Mime>().ok());
+        match content_type {
+            Some(content_type) => {
+                !self.denied_types.matches(&content_type)
+                    && (self.allowed_types.is_empty() || self.allowed_types.matches(&content_type))
+            }
+            None => self.allowed_types.is_empty(),
+        }
+    }
+}
+
+/// Create a wrapping filter that compresses the Body of a [`Response`](crate::reply::Response)
+/// using the best algorithm accepted by the client.
+///
+/// The algorithm is negotiated from the request's `accept-encoding` header,
+/// honoring quality values (`gzip;q=0.8`), the `*` wildcard, and an explicit
+/// `identity` preference. When several algorithms are equally acceptable,
+/// brotli is preferred over gzip, and gzip over deflate, limited to those
+/// enabled by the crate features.
+///
+/// If the client doesn't accept any supported algorithm, or the response
+/// already has a `content-encoding`, the response is left untouched. A
+/// `vary: accept-encoding` header is always added, even to responses skipped
+/// for their size or content type, so caches never serve a variant picked
+/// for another client.
+///
+/// # Example
+///
+/// ```
+/// use nextshell::Filter;
+///
+/// let route = nextshell::get()
+///     .and(nextshell::path::end())
+///     .and(nextshell::fs::file("./README.md"))
+///     .with(nextshell::compression::auto());
+/// ```
+pub fn auto() -> Compression<impl Fn(CompressionProps) -> Response + Copy> {
+    let func = move |props: CompressionProps| {
+        let algo = props
+            .accept_encoding
+            .as_ref()
+            .and_then(negotiate)
+            .filter(|_| !props.head.headers.contains_key(CONTENT_ENCODING));
+        match algo {
+            Some(algo) => algo.encode(props),
+            None => Response::from_parts(props.head, props.body.into_inner()),
+        }
+    };
+    Compression {
+        negotiate: true,
+        ..Compression::new(func)
+    }
+}
 
 /// Create a wrapping filter that compresses the Body of a [`Response`](crate::reply::Response)
 /// using gzip, adding `content-encoding: gzip` to the Response's [`HeaderMap`](hyper::HeaderMap)
@@ -68,18 +376,8 @@ pub struct Compression<F> {
 /// ```
 #[cfg(feature = "compression-gzip")]
 pub fn gzip() -> Compression<impl Fn(CompressionProps) -> Response + Copy> {
-    let func = move |mut props: CompressionProps| {
-        let body = Body::wrap_stream(ReaderStream::new(GzipEncoder::new(StreamReader::new(
-            props.body,
-        ))));
-        props
-            .head
-            .headers
-            .append(CONTENT_ENCODING, CompressionAlgo::GZIP.into());
-        props.head.headers.remove(CONTENT_LENGTH);
-        Response::from_parts(props.head, body)
-    };
-    Compression { func }
+    let func = move |props: CompressionProps| CompressionAlgo::GZIP.encode(props);
+    Compression::new(func)
 }
 
 /// Create a wrapping filter that compresses the Body of a [`Response`](crate::reply::Response)
@@ -97,18 +395,8 @@ pub fn gzip() -> Compression<impl Fn(CompressionProps) -> Response + Copy> {
 /// ```
 #[cfg(feature = "compression-gzip")]
 pub fn deflate() -> Compression<impl Fn(CompressionProps) -> Response + Copy> {
-    let func = move |mut props: CompressionProps| {
-        let body = Body::wrap_stream(ReaderStream::new(DeflateEncoder::new(StreamReader::new(
-            props.body,
-        ))));
-        props
-            .head
-            .headers
-            .append(CONTENT_ENCODING, CompressionAlgo::DEFLATE.into());
-        props.head.headers.remove(CONTENT_LENGTH);
-        Response::from_parts(props.head, body)
-    };
-    Compression { func }
+    let func = move |props: CompressionProps| CompressionAlgo::DEFLATE.encode(props);
+    Compression::new(func)
 }
 
 /// Create a wrapping filter that compresses the Body of a [`Response`](crate::reply::Response)
@@ -126,18 +414,8 @@ pub fn deflate() -> Compression<impl Fn(CompressionProps) -> Response + Copy> {
 /// ```
 #[cfg(feature = "compression-brotli")]
 pub fn brotli() -> Compression<impl Fn(CompressionProps) -> Response + Copy> {
-    let func = move |mut props: CompressionProps| {
-        let body = Body::wrap_stream(ReaderStream::new(BrotliEncoder::new(StreamReader::new(
-            props.body,
-        ))));
-        props
-            .head
-            .headers
-            .append(CONTENT_ENCODING, CompressionAlgo::BR.into());
-        props.head.headers.remove(CONTENT_LENGTH);
-        Response::from_parts(props.head, body)
-    };
-    Compression { func }
+    let func = move |props: CompressionProps| CompressionAlgo::BR.encode(props);
+    Compression::new(func)
 }
 
 impl<FN, F> WrapSealed<F> for Compression<FN>
@@ -164,12 +442,14 @@ mod internal {
 
     use bytes::Bytes;
     use futures_util::{ready, Stream, TryFuture};
+    use http::header::{HeaderValue, ACCEPT_ENCODING, VARY};
     use hyper::Body;
     use pin_project::pin_project;
 
     use crate::filter::{Filter, FilterBase, Internal};
     use crate::reject::IsReject;
     use crate::reply::{Reply, Response};
+    use crate::route;
 
     use super::Compression;
 
@@ -207,11 +487,18 @@ mod internal {
         }
     }
 
+    impl CompressableBody<Body, hyper::Error> {
+        pub(super) fn into_inner(self) -> Body {
+            self.body
+        }
+    }
+
     /// Compression Props
     #[derive(Debug)]
     pub struct CompressionProps {
         pub(super) body: CompressableBody<Body, hyper::Error>,
         pub(super) head: http::response::Parts,
+        pub(super) accept_encoding: Option<HeaderValue>,
     }
 
     impl From<http::Response<Body>> for CompressionProps {
@@ -220,6 +507,7 @@ mod internal {
             CompressionProps {
                 body: body.into(),
                 head,
+                accept_encoding: None,
             }
         }
     }
@@ -235,7 +523,7 @@ mod internal {
     }
 
     #[allow(missing_debug_implementations)]
-    #[derive(Clone, Copy)]
+    #[derive(Clone)]
     pub struct WithCompression<FN, F> {
         pub(super) compress: Compression<FN>,
         pub(super) filter: F,
@@ -253,8 +541,11 @@ mod internal {
         type Future = WithCompressionFuture<FN, F::Future>;
 
         fn filter(&self, _: Internal) -> Self::Future {
+            let accept_encoding =
+                route::with(|route| route.headers().get(ACCEPT_ENCODING).cloned());
             WithCompressionFuture {
                 compress: self.compress.clone(),
+                accept_encoding,
                 future: self.filter.filter(Internal),
             }
         }
@@ -264,6 +555,7 @@ mod internal {
     #[pin_project]
     pub struct WithCompressionFuture<FN, F> {
         compress: Compression<FN>,
+        accept_encoding: Option<HeaderValue>,
         #[pin]
         future: F,
     }
@@ -282,7 +574,17 @@ mod internal {
             let result = ready!(pin.future.try_poll(cx));
             match result {
                 Ok(reply) => {
-                    let resp = (self.compress.func)(reply.into_response().into());
+                    let mut resp = reply.into_response();
+                    if pin.compress.negotiate {
+                        resp.headers_mut()
+                            .append(VARY, HeaderValue::from_static("accept-encoding"));
+                    }
+                    if !pin.compress.options.should_compress(&resp) {
+                        return Poll::Ready(Ok((Compressed(resp),)));
+                    }
+                    let mut props = CompressionProps::from(resp);
+                    props.accept_encoding = pin.accept_encoding.take();
+                    let resp = (pin.compress.func)(props);
                     Poll::Ready(Ok((Compressed(resp),)))
                 }
                 Err(reject) => Poll::Ready(Err(reject)),
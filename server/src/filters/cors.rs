@@ -4,12 +4,16 @@ use std::collections::HashSet;
 use std::convert::TryFrom;
 use std::error::Error as StdError;
 use std::fmt;
+use std::future::Future;
+use std::pin::Pin;
 use std::sync::Arc;
 
 use headers::{
     AccessControlAllowHeaders, AccessControlAllowMethods, AccessControlExposeHeaders, HeaderMapExt,
 };
 use http::header::{self, HeaderName, HeaderValue};
+#[cfg(feature = "cors-regex")]
+use regex::Regex;
 
 use crate::filter::{Filter, WrapSealed};
 use crate::reject::{CombineRejection, Rejection};
@@ -50,6 +54,10 @@ pub fn cors() -> Builder {
         max_age: None,
         methods: HashSet::new(),
         origins: None,
+        origin_patterns: Vec::new(),
+        origin_fn: None,
+        private_network: false,
+        any_header: false,
     }
 }
 
@@ -68,6 +76,58 @@ pub struct Builder {
     max_age: Option<u64>,
     methods: HashSet<http::Method>,
     origins: Option<HashSet<HeaderValue>>,
+    origin_patterns: Vec<OriginPattern>,
+    origin_fn: Option<OriginFn>,
+    private_network: bool,
+    any_header: bool,
+}
+
+/// An allowed origin matched by a pattern, rather than listed as is.
+#[derive(Clone, Debug)]
+enum OriginPattern {
+    /// `scheme://*.host`, see `Builder::allow_origin_pattern`.
+    Subdomains { scheme: String, host: String },
+    #[cfg(feature = "cors-regex")]
+    Regex(Regex),
+}
+
+impl OriginPattern {
+    fn is_match(&self, origin: &str) -> bool {
+        match self {
+            OriginPattern::Subdomains { scheme, host } => {
+                let subdomains = origin
+                    .split_once("://")
+                    .filter(|(origin_scheme, _)| origin_scheme.eq_ignore_ascii_case(scheme))
+                    .and_then(|(_, rest)| {
+                        let split = rest.len().checked_sub(host.len())?;
+                        let (subdomains, origin_host) = (rest.get(..split)?, rest.get(split..)?);
+                        origin_host.eq_ignore_ascii_case(host).then_some(subdomains)
+                    })
+                    .and_then(|subdomains| subdomains.strip_suffix('.'));
+                subdomains.is_some_and(|subdomains| {
+                    subdomains.split('.').all(|label| {
+                        !label.is_empty()
+                            && label
+                                .bytes()
+                                .all(|b| b.is_ascii_alphanumeric() || b == b'-')
+                    })
+                })
+            }
+            #[cfg(feature = "cors-regex")]
+            OriginPattern::Regex(regex) => regex.is_match(origin),
+        }
+    }
+}
+
+type OriginCheck = Pin<Box<dyn Future<Output = bool> + Send>>;
+
+#[derive(Clone)]
+struct OriginFn(Arc<dyn Fn(String) -> OriginCheck + Send + Sync>);
+
+impl fmt::Debug for OriginFn {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("OriginFn").finish()
+    }
 }
 
 impl Builder {
@@ -151,6 +211,16 @@ impl Builder {
         self
     }
 
+    /// Sets that *any* request header is allowed.
+    ///
+    /// Preflights then answer with the headers from their
+    /// `Access-Control-Request-Headers`, which is needed for clients sending
+    /// headers that aren't known in advance.
+    pub fn allow_any_header(mut self) -> Self {
+        self.any_header = true;
+        self
+    }
+
     /// Adds a header to the list of exposed headers.
     ///
     /// # Panics
@@ -192,8 +262,12 @@ impl Builder {
     ///
     /// This can allow websites you didn't intend to access this resource,
     /// it is usually better to set an explicit list.
+    ///
+    /// This also forgets any origin pattern or predicate set before.
     pub fn allow_any_origin(mut self) -> Self {
         self.origins = None;
+        self.origin_patterns.clear();
+        self.origin_fn = None;
         self
     }
 
@@ -231,6 +305,93 @@ impl Builder {
         self
     }
 
+    /// Allows the `Origin`s matching a wildcard subdomain `pattern`, such as
+    /// `https://*.example.com`.
+    ///
+    /// The `*` matches one or more subdomain labels, so the pattern above
+    /// allows `https://a.example.com` and `https://a.b.example.com`, but not
+    /// `https://example.com` itself.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the pattern isn't a scheme followed by `://*.` and a host.
+    pub fn allow_origin_pattern(mut self, pattern: &str) -> Self {
+        let (scheme, host) = pattern
+            .split_once("://*.")
+            .expect("origin pattern must look like `scheme://*.host`");
+        assert!(
+            !scheme.is_empty() && !host.is_empty() && !host.contains('*'),
+            "origin pattern must look like `scheme://*.host`"
+        );
+        self.origin_patterns.push(OriginPattern::Subdomains {
+            scheme: scheme.to_owned(),
+            host: host.to_owned(),
+        });
+        self.origins.get_or_insert_with(HashSet::new);
+        self
+    }
+
+    /// Allows the `Origin`s fully matching the regular expression `regex`.
+    ///
+    /// *This method requires the `"cors-regex"` feature.*
+    ///
+    /// # Panics
+    ///
+    /// Panics if `regex` isn't a valid regular expression.
+    #[cfg(feature = "cors-regex")]
+    pub fn allow_origin_regex(mut self, regex: &str) -> Self {
+        let regex = Regex::new(&format!("^(?:{})$", regex)).expect("invalid origin regex");
+        self.origin_patterns.push(OriginPattern::Regex(regex));
+        self.origins.get_or_insert_with(HashSet::new);
+        self
+    }
+
+    /// Allows the `Origin`s for which `predicate` resolves to `true`.
+    ///
+    /// The predicate is only asked about origins that the other `allow_origin`
+    /// settings don't already allow, and replaces any previous predicate.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use nextshell::Filter;
+    ///
+    /// let cors = nextshell::cors()
+    ///     .allow_origin("https://hyper.rs")
+    ///     .allow_origin_fn(|origin| async move {
+    ///         // Look up the tenants' origins...
+    ///         origin.ends_with(".tenants.example")
+    ///     });
+    ///
+    /// let route = nextshell::any()
+    ///     .map(nextshell::reply)
+    ///     .with(cors);
+    /// ```
+    pub fn allow_origin_fn<F, Fut>(mut self, predicate: F) -> Self
+    where
+        F: Fn(String) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = bool> + Send + 'static,
+    {
+        self.origin_fn = Some(OriginFn(Arc::new(move |origin| {
+            Box::pin(predicate(origin)) as OriginCheck
+        })));
+        self.origins.get_or_insert_with(HashSet::new);
+        self
+    }
+
+    /// Sets whether to answer [Private Network Access][pna] preflights,
+    /// which ask with `Access-Control-Request-Private-Network: true`, with
+    /// `Access-Control-Allow-Private-Network: true`.
+    ///
+    /// Browsers send these before public websites may access servers on a
+    /// private network, such as `localhost`.
+    ///
+    /// [pna]: https://wicg.github.io/private-network-access/
+    pub fn allow_private_network(mut self, allow: bool) -> Self {
+        self.private_network = allow;
+        self
+    }
+
     /// Sets the `Access-Control-Max-Age` header.
     ///
     /// # Example
@@ -337,6 +498,11 @@ impl fmt::Display for CorsForbidden {
 
 impl StdError for CorsForbidden {}
 
+const ACCESS_CONTROL_REQUEST_PRIVATE_NETWORK: HeaderName =
+    HeaderName::from_static("access-control-request-private-network");
+const ACCESS_CONTROL_ALLOW_PRIVATE_NETWORK: HeaderName =
+    HeaderName::from_static("access-control-allow-private-network");
+
 #[derive(Clone, Debug)]
 struct Configured {
     cors: Builder,
@@ -345,23 +511,33 @@ struct Configured {
     methods_header: AccessControlAllowMethods,
 }
 
+// What a valid preflight asked for, to answer it.
+#[derive(Debug)]
+struct Requested {
+    private_network: bool,
+    // The `Access-Control-Request-Headers` to reflect, with `allow_any_header`.
+    headers: Option<HeaderValue>,
+}
+
 enum Validated {
-    Preflight(HeaderValue),
+    Preflight(HeaderValue, Requested),
     Simple(HeaderValue),
     NotCors,
 }
 
 impl Configured {
+    // With `origin_checked`, the origin was already allowed by `origin_fn`.
     fn check_request(
         &self,
         method: &http::Method,
         headers: &http::HeaderMap,
+        origin_checked: bool,
     ) -> Result<Validated, Forbidden> {
         match (headers.get(header::ORIGIN), method) {
             (Some(origin), &http::Method::OPTIONS) => {
                 // OPTIONS requests are preflight CORS requests...
 
-                if !self.is_origin_allowed(origin) {
+                if !origin_checked && !self.is_origin_allowed(origin) {
                     return Err(Forbidden::OriginNotAllowed);
                 }
 
@@ -376,7 +552,8 @@ impl Configured {
                     return Err(Forbidden::MethodNotAllowed);
                 }
 
-                if let Some(req_headers) = headers.get(header::ACCESS_CONTROL_REQUEST_HEADERS) {
+                let req_headers = headers.get(header::ACCESS_CONTROL_REQUEST_HEADERS);
+                if let Some(req_headers) = req_headers.filter(|_| !self.cors.any_header) {
                     let headers = req_headers
                         .to_str()
                         .map_err(|_| Forbidden::HeaderNotAllowed)?;
@@ -387,13 +564,22 @@ impl Configured {
                     }
                 }
 
-                Ok(Validated::Preflight(origin.clone()))
+                let private_network = self.cors.private_network
+                    && headers
+                        .get(ACCESS_CONTROL_REQUEST_PRIVATE_NETWORK)
+                        .is_some_and(|value| value == "true");
+
+                let requested = Requested {
+                    private_network,
+                    headers: req_headers.filter(|_| self.cors.any_header).cloned(),
+                };
+                Ok(Validated::Preflight(origin.clone(), requested))
             }
             (Some(origin), _) => {
                 // Any other method, simply check for a valid origin...
 
                 tracing::trace!("origin header: {:?}", origin);
-                if self.is_origin_allowed(origin) {
+                if origin_checked || self.is_origin_allowed(origin) {
                     Ok(Validated::Simple(origin.clone()))
                 } else {
                     Err(Forbidden::OriginNotAllowed)
@@ -421,20 +607,54 @@ impl Configured {
     fn is_origin_allowed(&self, origin: &HeaderValue) -> bool {
         if let Some(ref allowed) = self.cors.origins {
             allowed.contains(origin)
+                || origin.to_str().is_ok_and(|origin| {
+                    self.cors
+                        .origin_patterns
+                        .iter()
+                        .any(|pattern| pattern.is_match(origin))
+                })
         } else {
             true
         }
     }
 
-    fn append_preflight_headers(&self, headers: &mut http::HeaderMap) {
+    // Asks `origin_fn` about an origin that isn't otherwise allowed.
+    fn check_origin_fn(&self, headers: &http::HeaderMap) -> Option<OriginCheck> {
+        let origin_fn = self.cors.origin_fn.as_ref()?;
+        let origin = headers.get(header::ORIGIN)?.to_str().ok()?;
+        Some((origin_fn.0)(origin.to_owned()))
+    }
+
+    fn append_preflight_headers(&self, headers: &mut http::HeaderMap, requested: Requested) {
         self.append_common_headers(headers);
 
-        headers.typed_insert(self.allowed_headers_header.clone());
+        match requested.headers {
+            Some(req_headers) => {
+                headers.insert(header::ACCESS_CONTROL_ALLOW_HEADERS, req_headers);
+            }
+            None => headers.typed_insert(self.allowed_headers_header.clone()),
+        }
         headers.typed_insert(self.methods_header.clone());
 
         if let Some(max_age) = self.cors.max_age {
             headers.insert(header::ACCESS_CONTROL_MAX_AGE, max_age.into());
         }
+
+        if requested.private_network {
+            headers.insert(
+                ACCESS_CONTROL_ALLOW_PRIVATE_NETWORK,
+                HeaderValue::from_static("true"),
+            );
+        }
+
+        // Preflights are answered from all the request's CORS headers.
+        let vary = if self.cors.private_network {
+            "origin, access-control-request-method, access-control-request-headers, \
+             access-control-request-private-network"
+        } else {
+            "origin, access-control-request-method, access-control-request-headers"
+        };
+        headers.append(header::VARY, HeaderValue::from_static(vary));
     }
 
     fn append_common_headers(&self, headers: &mut http::HeaderMap) {
@@ -456,12 +676,12 @@ mod internal {
     use std::sync::Arc;
     use std::task::{Context, Poll};
 
-    use futures_util::{future, ready, TryFuture};
+    use futures_util::{ready, TryFuture};
     use headers::Origin;
     use http::header;
     use pin_project::pin_project;
 
-    use super::{Configured, CorsForbidden, Validated};
+    use super::{Configured, CorsForbidden, Forbidden, OriginCheck, Requested, Validated};
     use crate::filter::{Filter, FilterBase, Internal, One};
     use crate::generic::Either;
     use crate::reject::{CombineRejection, Rejection};
@@ -473,59 +693,146 @@ mod internal {
         pub(super) inner: F,
     }
 
+    type Extract<R> = One<Either<One<Preflight>, One<Wrapped<R>>>>;
+    type Rejected<E> = <E as CombineRejection<Rejection>>::One;
+    type Outcome<R, E> = Result<Extract<R>, Rejected<E>>;
+
     impl<F> FilterBase for CorsFilter<F>
     where
-        F: Filter,
+        F: Filter + Clone + Send,
         F::Extract: Send,
         F::Future: Future,
         F::Error: CombineRejection<Rejection>,
     {
-        type Extract =
-            One<Either<One<Preflight>, One<Either<One<Wrapped<F::Extract>>, F::Extract>>>>;
-        type Error = <F::Error as CombineRejection<Rejection>>::One;
-        type Future = future::Either<
-            future::Ready<Result<Self::Extract, Self::Error>>,
-            WrappedFuture<F::Future>,
-        >;
+        type Extract = Extract<F::Extract>;
+        type Error = Rejected<F::Error>;
+        type Future = CorsFuture<F>;
 
         fn filter(&self, _: Internal) -> Self::Future {
-            let validated =
-                route::with(|route| self.config.check_request(route.method(), route.headers()));
+            let validated = route::with(|route| {
+                match self
+                    .config
+                    .check_request(route.method(), route.headers(), false)
+                {
+                    Err(Forbidden::OriginNotAllowed) => {
+                        match self.config.check_origin_fn(route.headers()) {
+                            Some(check) => Err(check),
+                            None => Ok(Err(Forbidden::OriginNotAllowed)),
+                        }
+                    }
+                    validated => Ok(validated),
+                }
+            });
+
+            match validated {
+                Ok(validated) => CorsFuture::start(&self.config, &self.inner, validated),
+                Err(check) => CorsFuture::Checking(Checking {
+                    check,
+                    config: self.config.clone(),
+                    inner: self.inner.clone(),
+                }),
+            }
+        }
+    }
+
+    #[allow(missing_debug_implementations)]
+    #[pin_project(project = CorsFutureProj)]
+    pub enum CorsFuture<F>
+    where
+        F: Filter,
+        F::Error: CombineRejection<Rejection>,
+    {
+        Done(Option<Outcome<F::Extract, F::Error>>),
+        Checking(Checking<F>),
+        Wrapped(#[pin] WrappedFuture<F::Future>),
+    }
+
+    // Waiting on `allow_origin_fn` before going on with the request.
+    #[allow(missing_debug_implementations)]
+    pub struct Checking<F> {
+        check: OriginCheck,
+        config: Arc<Configured>,
+        inner: F,
+    }
 
+    impl<F> CorsFuture<F>
+    where
+        F: Filter,
+        F::Error: CombineRejection<Rejection>,
+    {
+        fn start(
+            config: &Arc<Configured>,
+            inner: &F,
+            validated: Result<Validated, Forbidden>,
+        ) -> Self {
             match validated {
-                Ok(Validated::Preflight(origin)) => {
+                Ok(Validated::Preflight(origin, requested)) => {
                     let preflight = Preflight {
-                        config: self.config.clone(),
+                        config: config.clone(),
                         origin,
+                        requested,
                     };
-                    future::Either::Left(future::ok((Either::A((preflight,)),)))
+                    CorsFuture::Done(Some(Ok((Either::A((preflight,)),))))
                 }
-                Ok(Validated::Simple(origin)) => future::Either::Right(WrappedFuture {
-                    inner: self.inner.filter(Internal),
-                    wrapped: Some((self.config.clone(), origin)),
+                Ok(Validated::Simple(origin)) => CorsFuture::Wrapped(WrappedFuture {
+                    inner: inner.filter(Internal),
+                    wrapped: Some((config.clone(), Some(origin))),
                 }),
-                Ok(Validated::NotCors) => future::Either::Right(WrappedFuture {
-                    inner: self.inner.filter(Internal),
-                    wrapped: None,
+                Ok(Validated::NotCors) => CorsFuture::Wrapped(WrappedFuture {
+                    inner: inner.filter(Internal),
+                    wrapped: Some((config.clone(), None)),
                 }),
                 Err(err) => {
                     let rejection = crate::reject::known(CorsForbidden { kind: err });
-                    future::Either::Left(future::err(rejection.into()))
+                    CorsFuture::Done(Some(Err(rejection.into())))
                 }
             }
         }
     }
 
+    impl<F> Future for CorsFuture<F>
+    where
+        F: Filter,
+        F::Error: CombineRejection<Rejection>,
+    {
+        type Output = Outcome<F::Extract, F::Error>;
+
+        fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+            loop {
+                let next = match self.as_mut().project() {
+                    CorsFutureProj::Done(result) => {
+                        return Poll::Ready(result.take().expect("polled after complete"))
+                    }
+                    CorsFutureProj::Wrapped(wrapped) => return wrapped.poll(cx),
+                    CorsFutureProj::Checking(checking) => {
+                        let validated = if ready!(checking.check.as_mut().poll(cx)) {
+                            route::with(|route| {
+                                let config = &checking.config;
+                                config.check_request(route.method(), route.headers(), true)
+                            })
+                        } else {
+                            Err(Forbidden::OriginNotAllowed)
+                        };
+                        CorsFuture::start(&checking.config, &checking.inner, validated)
+                    }
+                };
+                self.set(next);
+            }
+        }
+    }
+
     #[derive(Debug)]
     pub struct Preflight {
         config: Arc<Configured>,
         origin: header::HeaderValue,
+        requested: Requested,
     }
 
     impl crate::reply::Reply for Preflight {
         fn into_response(self) -> crate::reply::Response {
             let mut res = crate::reply::Response::default();
-            self.config.append_preflight_headers(res.headers_mut());
+            self.config
+                .append_preflight_headers(res.headers_mut(), self.requested);
             res.headers_mut()
                 .insert(header::ACCESS_CONTROL_ALLOW_ORIGIN, self.origin);
             res
@@ -536,7 +843,8 @@ mod internal {
     pub struct Wrapped<R> {
         config: Arc<Configured>,
         inner: R,
-        origin: header::HeaderValue,
+        // `None` if the request wasn't a CORS one.
+        origin: Option<header::HeaderValue>,
     }
 
     impl<R> crate::reply::Reply for Wrapped<R>
@@ -545,9 +853,15 @@ mod internal {
     {
         fn into_response(self) -> crate::reply::Response {
             let mut res = self.inner.into_response();
-            self.config.append_common_headers(res.headers_mut());
+            if let Some(origin) = self.origin {
+                self.config.append_common_headers(res.headers_mut());
+                res.headers_mut()
+                    .insert(header::ACCESS_CONTROL_ALLOW_ORIGIN, origin);
+            }
+            // Even without an `Origin`, a cached reply mustn't be reused
+            // for requests with one.
             res.headers_mut()
-                .insert(header::ACCESS_CONTROL_ALLOW_ORIGIN, self.origin);
+                .append(header::VARY, header::HeaderValue::from_static("origin"));
             res
         }
     }
@@ -557,7 +871,7 @@ mod internal {
     pub struct WrappedFuture<F> {
         #[pin]
         inner: F,
-        wrapped: Option<(Arc<Configured>, header::HeaderValue)>,
+        wrapped: Option<(Arc<Configured>, Option<header::HeaderValue>)>,
     }
 
     impl<F> Future for WrappedFuture<F>
@@ -565,25 +879,18 @@ mod internal {
         F: TryFuture,
         F::Error: CombineRejection<Rejection>,
     {
-        type Output = Result<
-            One<Either<One<Preflight>, One<Either<One<Wrapped<F::Ok>>, F::Ok>>>>,
-            <F::Error as CombineRejection<Rejection>>::One,
-        >;
+        type Output = Outcome<F::Ok, F::Error>;
 
         fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
             let pin = self.project();
             match ready!(pin.inner.try_poll(cx)) {
                 Ok(inner) => {
-                    let item = if let Some((config, origin)) = pin.wrapped.take() {
-                        (Either::A((Wrapped {
-                            config,
-                            inner,
-                            origin,
-                        },)),)
-                    } else {
-                        (Either::B(inner),)
-                    };
-                    let item = (Either::B(item),);
+                    let (config, origin) = pin.wrapped.take().expect("polled after complete");
+                    let item = (Either::B((Wrapped {
+                        config,
+                        inner,
+                        origin,
+                    },)),);
                     Poll::Ready(Ok(item))
                 }
                 Err(err) => Poll::Ready(Err(err.into())),
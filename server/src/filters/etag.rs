@@ -0,0 +1,227 @@
+//! ETag Filters
+//!
+//! Filters that add an `etag` header to replies, and answer matching
+//! conditional `GET` requests with `304 Not Modified`.
+
+use std::fmt::Write;
+
+use headers::{HeaderMapExt, IfNoneMatch};
+use http::header::{CONTENT_LENGTH, ETAG};
+use http::StatusCode;
+use hyper::Body;
+use sha1::{Digest, Sha1};
+
+use crate::filter::{Filter, WrapSealed};
+use crate::reject::IsReject;
+use crate::reply::{Reply, Response};
+
+use self::internal::WithETag;
+
+/// Create a wrapping [`Filter`](crate::Filter) that handles `etag` and
+/// `if-none-match` headers.
+///
+/// Successful replies to `GET` and `HEAD` requests are tagged with a strong
+/// `etag`. If the reply already has an `etag` header, for instance one
+/// computed by the handler from a version column, it is used as is.
+/// Otherwise, the reply body is buffered in memory and hashed.
+///
+/// When the request's `if-none-match` header matches the tag, the body is
+/// dropped and `304 Not Modified` is returned instead.
+///
+/// # Example
+///
+/// ```
+/// use nextshell::Filter;
+///
+/// let route = nextshell::path("todos")
+///     .map(|| nextshell::reply::json(&vec!["write docs"]))
+///     .with(nextshell::etag());
+/// ```
+pub fn etag() -> ETag {
+    ETag { _p: () }
+}
+
+/// Decorates a [`Filter`] to add `etag` headers and handle `if-none-match`.
+#[derive(Clone, Copy, Debug)]
+pub struct ETag {
+    _p: (),
+}
+
+impl<F> WrapSealed<F> for ETag
+where
+    F: Filter + Clone + Send,
+    F::Extract: Reply,
+    F::Error: IsReject,
+{
+    type Wrapped = WithETag<F>;
+
+    fn wrap(&self, filter: F) -> Self::Wrapped {
+        WithETag { filter }
+    }
+}
+
+/// Computes a strong entity tag from the bytes of a body.
+///
+/// The tag is a SHA-1 digest of the bytes, so replicas and restarted
+/// servers agree on it for identical bodies.
+pub(crate) fn strong_etag(bytes: &[u8]) -> headers::ETag {
+    let mut etag = format!("\"{:x}-", bytes.len());
+    for byte in Sha1::digest(bytes) {
+        let _ = write!(etag, "{:02x}", byte);
+    }
+    etag.push('"');
+    etag.parse().expect("hex digits are a valid etag")
+}
+
+/// Answers with `304 Not Modified` if `if_none_match` matches the `etag`
+/// header of the response.
+pub(crate) fn not_modified(res: Response, if_none_match: Option<&IfNoneMatch>) -> Response {
+    let etag = match res.headers().typed_get::<headers::ETag>() {
+        Some(etag) => etag,
+        None => return res,
+    };
+    match if_none_match {
+        Some(if_none_match) if !if_none_match.precondition_passes(&etag) => {
+            let (mut parts, _) = res.into_parts();
+            parts.status = StatusCode::NOT_MODIFIED;
+            parts.headers.remove(CONTENT_LENGTH);
+            Response::from_parts(parts, Body::empty())
+        }
+        _ => res,
+    }
+}
+
+async fn tag(res: Response, if_none_match: Option<IfNoneMatch>) -> Response {
+    let (mut parts, body) = res.into_parts();
+    let bytes = match hyper::body::to_bytes(body).await {
+        Ok(bytes) => bytes,
+        Err(err) => {
+            tracing::error!("etag body error: {}", err);
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+    };
+    parts.headers.typed_insert(strong_etag(&bytes));
+    not_modified(
+        Response::from_parts(parts, Body::from(bytes)),
+        if_none_match.as_ref(),
+    )
+}
+
+mod internal {
+    use std::future::Future;
+    use std::pin::Pin;
+    use std::task::{Context, Poll};
+
+    use futures_util::{ready, TryFuture};
+    use headers::{HeaderMapExt, IfNoneMatch};
+    use http::{Method, StatusCode};
+    use pin_project::pin_project;
+
+    use super::ETAG;
+    use crate::filter::{Filter, FilterBase, Internal};
+    use crate::reject::IsReject;
+    use crate::reply::{Reply, Response};
+    use crate::route;
+
+    #[allow(missing_debug_implementations)]
+    pub struct Tagged(pub(super) Response);
+
+    impl Reply for Tagged {
+        #[inline]
+        fn into_response(self) -> Response {
+            self.0
+        }
+    }
+
+    #[allow(missing_debug_implementations)]
+    #[derive(Clone, Copy)]
+    pub struct WithETag<F> {
+        pub(super) filter: F,
+    }
+
+    impl<F> FilterBase for WithETag<F>
+    where
+        F: Filter + Clone + Send,
+        F::Extract: Reply,
+        F::Error: IsReject,
+    {
+        type Extract = (Tagged,);
+        type Error = F::Error;
+        type Future = WithETagFuture<F::Future>;
+
+        fn filter(&self, _: Internal) -> Self::Future {
+            let conditional = route::with(|route| {
+                let method = route.method();
+                if method == Method::GET || method == Method::HEAD {
+                    Some(route.headers().typed_get::<IfNoneMatch>())
+                } else {
+                    None
+                }
+            });
+            WithETagFuture {
+                state: State::Filter {
+                    conditional,
+                    future: self.filter.filter(Internal),
+                },
+            }
+        }
+    }
+
+    #[allow(missing_debug_implementations)]
+    #[pin_project]
+    pub struct WithETagFuture<F> {
+        #[pin]
+        state: State<F>,
+    }
+
+    #[pin_project(project = StateProj)]
+    enum State<F> {
+        Filter {
+            // `None` if the request method isn't cacheable.
+            conditional: Option<Option<IfNoneMatch>>,
+            #[pin]
+            future: F,
+        },
+        Buffer(Pin<Box<dyn Future<Output = Response> + Send>>),
+    }
+
+    impl<F> Future for WithETagFuture<F>
+    where
+        F: TryFuture,
+        F::Ok: Reply,
+        F::Error: IsReject,
+    {
+        type Output = Result<(Tagged,), F::Error>;
+
+        fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+            let mut pin = self.project();
+            loop {
+                let buffer = match pin.state.as_mut().project() {
+                    StateProj::Filter {
+                        conditional,
+                        future,
+                    } => {
+                        let res = match ready!(future.try_poll(cx)) {
+                            Ok(reply) => reply.into_response(),
+                            Err(reject) => return Poll::Ready(Err(reject)),
+                        };
+                        let if_none_match = match conditional.take() {
+                            Some(if_none_match) if res.status() == StatusCode::OK => if_none_match,
+                            _ => return Poll::Ready(Ok((Tagged(res),))),
+                        };
+                        if res.headers().contains_key(ETAG) {
+                            let res = super::not_modified(res, if_none_match.as_ref());
+                            return Poll::Ready(Ok((Tagged(res),)));
+                        }
+                        Box::pin(super::tag(res, if_none_match))
+                    }
+                    StateProj::Buffer(future) => {
+                        let res = ready!(future.as_mut().poll(cx));
+                        return Poll::Ready(Ok((Tagged(res),)));
+                    }
+                };
+                pin.state.set(State::Buffer(buffer));
+            }
+        }
+    }
+}
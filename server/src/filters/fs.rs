@@ -1,30 +1,36 @@
 //! File System Filters
 
 use std::cmp;
+use std::collections::HashMap;
 use std::convert::Infallible;
+use std::fmt;
 use std::fs::Metadata;
 use std::future::Future;
 use std::io;
 use std::path::{Path, PathBuf};
 use std::pin::Pin;
-use std::sync::Arc;
+use std::sync::{Arc, Mutex, MutexGuard};
 use std::task::Poll;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
 use bytes::{Bytes, BytesMut};
 use futures_util::future::Either;
 use futures_util::{future, ready, stream, FutureExt, Stream, StreamExt, TryFutureExt};
 use headers::{
-    AcceptRanges, ContentLength, ContentRange, ContentType, HeaderMapExt, IfModifiedSince, IfRange,
-    IfUnmodifiedSince, LastModified, Range,
+    ETag, HeaderMapExt, IfMatch, IfModifiedSince, IfNoneMatch, IfRange, IfUnmodifiedSince,
+    LastModified, Range,
 };
-use http::StatusCode;
+use http::header::{ACCEPT, CONTENT_TYPE};
+use http::{HeaderValue, StatusCode};
 use hyper::Body;
-use percent_encoding::percent_decode_str;
+use linked_hash_map::LinkedHashMap;
+use percent_encoding::{percent_decode_str, utf8_percent_encode, AsciiSet, CONTROLS};
 use tokio::fs::File as TkFile;
 use tokio::io::AsyncSeekExt;
 use tokio_util::io::poll_read_buf;
 
-use crate::filter::{Filter, FilterClone, One};
+use super::range;
+use crate::filter::{BoxedFilter, Filter, FilterBase, FilterClone, Internal, One};
 use crate::reject::{self, Rejection};
 use crate::reply::{Reply, Response};
 
@@ -52,7 +58,7 @@ pub fn file(path: impl Into<PathBuf>) -> impl FilterClone<Extract = One<File>, E
             ArcPath(path.clone())
         })
         .and(conditionals())
-        .and_then(file_reply)
+        .and_then(|path, conditionals| file_reply(path, conditionals, None))
 }
 
 /// Creates a `Filter` that serves a directory at the base `path` joined
@@ -62,6 +68,9 @@ pub fn file(path: impl Into<PathBuf>) -> impl FilterClone<Extract = One<File>, E
 /// common pattern of serving static files is for `GET` requests, so this
 /// filter automatically includes a `GET` check.
 ///
+/// The returned [`Dir`] can be further configured, for instance to render
+/// directory listings.
+///
 /// # Example
 ///
 /// ```
@@ -77,34 +86,411 @@ pub fn file(path: impl Into<PathBuf>) -> impl FilterClone<Extract = One<File>, E
 /// // - `GET /static/app.js` would serve the file `/www/static/app.js`
 /// // - `GET /static/css/app.css` would serve the file `/www/static/css/app.css`
 /// ```
-pub fn dir(path: impl Into<PathBuf>) -> impl FilterClone<Extract = One<File>, Error = Rejection> {
-    let base = Arc::new(path.into());
+pub fn dir(
+    path: impl Into<PathBuf>,
+) -> Dir<impl FilterClone<Extract = One<File>, Error = Rejection>> {
+    Dir::new(
+        DirConfig {
+            base: Arc::new(path.into()),
+            listing: false,
+            listing_hidden: false,
+            fallback: None,
+            symlinks: Symlinks::Always,
+            dotfiles: true,
+            extensions: None,
+            cache: None,
+            read_buffer_size: None,
+        },
+        dir_filter,
+    )
+}
+
+/// A `Filter` serving the files of a directory, created by [`dir`].
+///
+/// `F` is the filter serving the current configuration, rebuilt by each
+/// configuration method.
+#[derive(Clone)]
+pub struct Dir<F> {
+    config: Arc<DirConfig>,
+    build: fn(Arc<DirConfig>) -> F,
+    filter: F,
+}
+
+#[derive(Clone, Debug)]
+struct DirConfig {
+    base: Arc<PathBuf>,
+    listing: bool,
+    listing_hidden: bool,
+    fallback: Option<PathBuf>,
+    symlinks: Symlinks,
+    dotfiles: bool,
+    extensions: Option<Vec<String>>,
+    cache: Option<FileCache>,
+    read_buffer_size: Option<usize>,
+}
+
+impl<F> Dir<F> {
+    /// Renders a listing of the directory contents when a directory without
+    /// an `index.html` is requested, instead of rejecting with a `404`.
+    ///
+    /// The listing is an HTML page, or a JSON array of entries if the
+    /// request's `accept` header asks for `application/json`. Hidden files,
+    /// whose names start with a `.`, are left out unless
+    /// [`listing_hidden`](Dir::listing_hidden) is set.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use nextshell::Filter;
+    ///
+    /// // Serve the current directory, like `python -m http.server`.
+    /// let route = nextshell::fs::dir(".").listing();
+    /// ```
+    pub fn listing(self) -> Self {
+        self.configure(|config| config.listing = true)
+    }
+
+    /// Sets whether hidden files are included in directory listings.
+    ///
+    /// Defaults to `false`. This only affects listings, hidden files can
+    /// still be requested by name.
+    pub fn listing_hidden(self, show: bool) -> Self {
+        self.configure(|config| config.listing_hidden = show)
+    }
+
+    /// Serves the file at `path`, relative to the directory, for request
+    /// paths that don't match any file.
+    ///
+    /// This is what client-side routed frontends, or single page apps,
+    /// need: every unknown path gets the app's entry point with a `200 OK`,
+    /// and the app picks the view from the URL.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use nextshell::Filter;
+    ///
+    /// // `GET /app/settings` serves `/www/app/index.html`.
+    /// let route = nextshell::path("app")
+    ///     .and(nextshell::fs::dir("/www/app").fallback("index.html"));
+    /// ```
+    pub fn fallback(self, path: impl Into<PathBuf>) -> Self {
+        self.configure(|config| config.fallback = Some(path.into()))
+    }
+
+    /// Sets whether symbolic links inside the directory are followed.
+    ///
+    /// Defaults to [`Symlinks::Always`]. Paths going through a link that
+    /// isn't allowed are rejected with a [`SymlinkForbidden`].
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use nextshell::fs::Symlinks;
+    ///
+    /// let route = nextshell::fs::dir("/www/static").symlinks(Symlinks::WithinRoot);
+    /// ```
+    pub fn symlinks(self, symlinks: Symlinks) -> Self {
+        self.configure(|config| config.symlinks = symlinks)
+    }
+
+    /// Sets whether hidden files and directories, whose names start with a
+    /// `.`, can be requested.
+    ///
+    /// Defaults to `true`. When disabled, such requests are rejected with a
+    /// [`DotfileForbidden`], and listings leave hidden files out.
+    pub fn dotfiles(self, allow: bool) -> Self {
+        self.configure(|config| config.dotfiles = allow)
+    }
+
+    /// Only serves files with one of the given extensions.
+    ///
+    /// Extensions are given without the leading `.`, and compared ignoring
+    /// case. Other files are rejected with an [`ExtensionForbidden`].
+    /// Directory listings aren't affected.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// let route = nextshell::fs::dir("/www/static")
+    ///     .allow_extensions(["html", "css", "js"]);
+    /// ```
+    pub fn allow_extensions<I>(self, extensions: I) -> Self
+    where
+        I: IntoIterator,
+        I::Item: AsRef<str>,
+    {
+        self.configure(|config| {
+            config.extensions.get_or_insert_with(Vec::new).extend(
+                extensions
+                    .into_iter()
+                    .map(|ext| ext.as_ref().trim_start_matches('.').to_ascii_lowercase()),
+            )
+        })
+    }
+
+    /// Keeps small, frequently requested files in memory, using the given
+    /// [`FileCache`].
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use std::time::Duration;
+    /// use nextshell::fs::FileCache;
+    ///
+    /// // Up to 16MiB of files, each at most 256KiB, checked every 5 seconds.
+    /// let cache = FileCache::new(16 * 1024 * 1024)
+    ///     .max_file_size(256 * 1024)
+    ///     .ttl(Duration::from_secs(5));
+    /// let route = nextshell::fs::dir("/www/static").cache(cache);
+    /// ```
+    pub fn cache(self, cache: FileCache) -> Self {
+        self.configure(|config| config.cache = Some(cache))
+    }
+
+    /// Sets the size of the chunks files are read and sent in.
+    ///
+    /// By default, this is the block size of the file system, with a
+    /// minimum of 8KiB. Larger chunks mean fewer reads and writes for large
+    /// files, at the cost of memory per response.
+    ///
+    /// Note that file contents are always copied through these buffers:
+    /// hyper writes response bodies from memory, so zero-copy mechanisms
+    /// such as `sendfile` can't be used. See the `fs` benchmark for the
+    /// difference larger buffers make.
+    ///
+    /// # Panics
+    ///
+    /// If `size` is `0`.
+    pub fn read_buffer_size(self, size: usize) -> Self {
+        assert!(size > 0, "read buffer size must be greater than 0");
+        self.configure(|config| config.read_buffer_size = Some(size))
+    }
+
+    fn new(config: DirConfig, build: fn(Arc<DirConfig>) -> F) -> Self {
+        let config = Arc::new(config);
+        Dir {
+            filter: build(config.clone()),
+            build,
+            config,
+        }
+    }
+
+    /// Updates the configuration, and rebuilds the filter serving it.
+    fn configure(mut self, f: impl FnOnce(&mut DirConfig)) -> Self {
+        f(Arc::make_mut(&mut self.config));
+        self.filter = (self.build)(self.config.clone());
+        self
+    }
+}
+
+impl<F> fmt::Debug for Dir<F> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Dir").field("config", &self.config).finish()
+    }
+}
+
+impl<F> FilterBase for Dir<F>
+where
+    F: FilterBase<Extract = One<File>, Error = Rejection>,
+{
+    type Extract = One<File>;
+    type Error = Rejection;
+    type Future = F::Future;
+
+    fn filter(&self, _: Internal) -> Self::Future {
+        self.filter.filter(Internal)
+    }
+}
+
+fn dir_filter(config: Arc<DirConfig>) -> impl FilterClone<Extract = One<File>, Error = Rejection> {
     crate::get()
         .or(crate::head())
         .unify()
-        .and(path_from_tail(base))
+        .and(path_from_tail(config.clone()))
         .and(conditionals())
-        .and_then(file_reply)
+        .and(listing_format())
+        .and_then(
+            move |resolved: Resolved, conditionals: Conditionals, format: ListingFormat| {
+                match resolved {
+                    Resolved::File(path) => {
+                        let read_buf = config.read_buffer_size;
+                        match config.cache {
+                            Some(ref cache) => Either::Left(Either::Left(cached_file_reply(
+                                cache.clone(),
+                                path,
+                                conditionals,
+                                read_buf,
+                            ))),
+                            None => Either::Left(Either::Right(file_reply(
+                                path,
+                                conditionals,
+                                read_buf,
+                            ))),
+                        }
+                    }
+                    Resolved::Dir { path, href } => {
+                        Either::Right(listing_reply(config.clone(), path, href, format))
+                    }
+                }
+            },
+        )
+}
+
+/// What a request path in a served directory resolved to.
+enum Resolved {
+    File(ArcPath),
+    /// A directory without an `index.html`, to be listed.
+    Dir {
+        path: ArcPath,
+        /// The request path of the directory, ending with a `/`.
+        href: String,
+    },
 }
 
 fn path_from_tail(
-    base: Arc<PathBuf>,
-) -> impl FilterClone<Extract = One<ArcPath>, Error = Rejection> {
-    crate::path::tail().and_then(move |tail: crate::path::Tail| {
-        future::ready(sanitize_path(base.as_ref(), tail.as_str())).and_then(|mut buf| async {
-            let is_dir = tokio::fs::metadata(buf.clone())
-                .await
-                .map(|m| m.is_dir())
-                .unwrap_or(false);
+    config: Arc<DirConfig>,
+) -> impl FilterClone<Extract = One<Resolved>, Error = Rejection> {
+    crate::path::tail().and(crate::path::full()).and_then(
+        move |tail: crate::path::Tail, full: crate::path::FullPath| {
+            let config = config.clone();
+            future::ready(sanitize_path(config.base.as_ref(), tail.as_str())).and_then(
+                move |buf| async move {
+                    if !config.dotfiles && has_dotfile(tail.as_str()) {
+                        tracing::debug!("dir: rejecting hidden path segment");
+                        return Err(reject::known(DotfileForbidden { _p: () }));
+                    }
+                    let resolved = resolve(&config, buf, full).await;
+                    config.check(&resolved).await?;
+                    Ok(resolved)
+                },
+            )
+        },
+    )
+}
 
-            if is_dir {
-                tracing::debug!("dir: appending index.html to directory path");
-                buf.push("index.html");
+async fn resolve(config: &DirConfig, mut buf: PathBuf, full: crate::path::FullPath) -> Resolved {
+    let is_dir = tokio::fs::metadata(buf.clone())
+        .await
+        .map(|m| m.is_dir())
+        .unwrap_or(false);
+
+    if is_dir {
+        let index = buf.join("index.html");
+        if config.listing && !tokio::fs::try_exists(&index).await.unwrap_or(false) {
+            tracing::debug!("dir: listing directory without index.html");
+            let mut href = full.as_str().to_owned();
+            if !href.ends_with('/') {
+                href.push('/');
             }
-            tracing::trace!("dir: {:?}", buf);
-            Ok(ArcPath(Arc::new(buf)))
-        })
-    })
+            return Resolved::Dir {
+                path: ArcPath(Arc::new(buf)),
+                href,
+            };
+        }
+        tracing::debug!("dir: appending index.html to directory path");
+        buf = index;
+    }
+    if let Some(ref fallback) = config.fallback {
+        if !tokio::fs::try_exists(&buf).await.unwrap_or(false) {
+            tracing::debug!("dir: {:?} not found, serving fallback", buf);
+            buf = config.base.join(fallback);
+        }
+    }
+    tracing::trace!("dir: {:?}", buf);
+    Resolved::File(ArcPath(Arc::new(buf)))
+}
+
+fn has_dotfile(tail: &str) -> bool {
+    percent_decode_str(tail)
+        .decode_utf8_lossy()
+        .split('/')
+        .any(|seg| seg.starts_with('.'))
+}
+
+impl DirConfig {
+    /// Applies the symlink and extension policies to a resolved path.
+    async fn check(&self, resolved: &Resolved) -> Result<(), Rejection> {
+        let path = match resolved {
+            Resolved::File(path) => {
+                if let Some(ref allowed) = self.extensions {
+                    let ext = path
+                        .as_ref()
+                        .extension()
+                        .and_then(|ext| ext.to_str())
+                        .map(|ext| ext.to_ascii_lowercase());
+                    let allowed = ext.is_some_and(|ext| allowed.contains(&ext));
+                    if !allowed {
+                        tracing::debug!("dir: rejecting extension of {:?}", path.as_ref());
+                        return Err(reject::known(ExtensionForbidden { _p: () }));
+                    }
+                }
+                path
+            }
+            Resolved::Dir { path, .. } => path,
+        };
+
+        if self
+            .symlinks
+            .allows(self.base.as_ref(), path.as_ref())
+            .await
+        {
+            Ok(())
+        } else {
+            tracing::debug!("dir: rejecting symlink in {:?}", path.as_ref());
+            Err(reject::known(SymlinkForbidden { _p: () }))
+        }
+    }
+}
+
+/// Whether symbolic links are followed when serving a directory.
+///
+/// See [`Dir::symlinks`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Symlinks {
+    /// Reject any path going through a symbolic link below the directory.
+    Never,
+    /// Follow symbolic links only if their target is inside the directory.
+    WithinRoot,
+    /// Follow all symbolic links. This is the default.
+    Always,
+}
+
+impl Symlinks {
+    async fn allows(self, base: &Path, path: &Path) -> bool {
+        match self {
+            Symlinks::Always => true,
+            Symlinks::Never => {
+                let rel = match path.strip_prefix(base) {
+                    Ok(rel) => rel,
+                    Err(_) => return false,
+                };
+                let mut cur = base.to_path_buf();
+                for component in rel.components() {
+                    cur.push(component);
+                    match tokio::fs::symlink_metadata(&cur).await {
+                        Ok(meta) if meta.file_type().is_symlink() => return false,
+                        Ok(_) => (),
+                        // Missing files are left to be rejected when opened.
+                        Err(_) => return true,
+                    }
+                }
+                true
+            }
+            Symlinks::WithinRoot => {
+                let (base, path) = match (
+                    tokio::fs::canonicalize(base).await,
+                    tokio::fs::canonicalize(path).await,
+                ) {
+                    (Ok(base), Ok(path)) => (base, path),
+                    // Missing files are left to be rejected when opened.
+                    (_, Err(_)) => return true,
+                    (Err(_), _) => return false,
+                };
+                path.starts_with(base)
+            }
+        }
+    }
 }
 
 fn sanitize_path(base: impl AsRef<Path>, tail: &str) -> Result<PathBuf, Rejection> {
@@ -136,6 +522,8 @@ fn sanitize_path(base: impl AsRef<Path>, tail: &str) -> Result<PathBuf, Rejectio
 
 #[derive(Debug)]
 struct Conditionals {
+    if_match: Option<IfMatch>,
+    if_none_match: Option<IfNoneMatch>,
     if_modified_since: Option<IfModifiedSince>,
     if_unmodified_since: Option<IfUnmodifiedSince>,
     if_range: Option<IfRange>,
@@ -148,8 +536,21 @@ enum Cond {
 }
 
 impl Conditionals {
-    fn check(self, last_modified: Option<LastModified>) -> Cond {
-        if let Some(since) = self.if_unmodified_since {
+    fn check(self, last_modified: Option<LastModified>, etag: Option<&ETag>) -> Cond {
+        // `if-match` takes precedence over `if-unmodified-since`, and
+        // `if-none-match` over `if-modified-since`, see RFC 7232 section 6.
+        if let Some(if_match) = self.if_match {
+            let precondition = etag
+                .map(|etag| if_match.precondition_passes(etag))
+                .unwrap_or(false);
+
+            tracing::trace!("if-match? {:?} vs {:?} = {}", if_match, etag, precondition);
+            if !precondition {
+                let mut res = Response::new(Body::empty());
+                *res.status_mut() = StatusCode::PRECONDITION_FAILED;
+                return Cond::NoBody(res);
+            }
+        } else if let Some(since) = self.if_unmodified_since {
             let precondition = last_modified
                 .map(|time| since.precondition_passes(time.into()))
                 .unwrap_or(false);
@@ -167,7 +568,21 @@ impl Conditionals {
             }
         }
 
-        if let Some(since) = self.if_modified_since {
+        if let Some(if_none_match) = self.if_none_match {
+            let unmodified = etag
+                .map(|etag| !if_none_match.precondition_passes(etag))
+                .unwrap_or(false);
+
+            tracing::trace!(
+                "if-none-match? {:?} vs {:?} = {}",
+                if_none_match,
+                etag,
+                unmodified
+            );
+            if unmodified {
+                return Cond::NoBody(not_modified(last_modified, etag));
+            }
+        } else if let Some(since) = self.if_modified_since {
             tracing::trace!(
                 "if-modified-since? header = {:?}, file = {:?}",
                 since,
@@ -178,36 +593,57 @@ impl Conditionals {
                 // no last_modified means its always modified
                 .unwrap_or(false);
             if unmodified {
-                let mut res = Response::new(Body::empty());
-                *res.status_mut() = StatusCode::NOT_MODIFIED;
-                return Cond::NoBody(res);
+                return Cond::NoBody(not_modified(last_modified, etag));
             }
         }
 
-        if let Some(if_range) = self.if_range {
-            tracing::trace!("if-range? {:?} vs {:?}", if_range, last_modified);
-            let can_range = !if_range.is_modified(None, last_modified.as_ref());
-
-            if !can_range {
-                return Cond::WithBody(None);
-            }
+        if !range::if_range_passes(self.if_range.as_ref(), etag, last_modified.as_ref()) {
+            return Cond::WithBody(None);
         }
 
         Cond::WithBody(self.range)
     }
 }
 
+/// A `304 Not Modified` response, keeping the validators of the file.
+fn not_modified(last_modified: Option<LastModified>, etag: Option<&ETag>) -> Response {
+    let mut res = Response::new(Body::empty());
+    *res.status_mut() = StatusCode::NOT_MODIFIED;
+    if let Some(etag) = etag {
+        res.headers_mut().typed_insert(etag.clone());
+    }
+    if let Some(last_modified) = last_modified {
+        res.headers_mut().typed_insert(last_modified);
+    }
+    res
+}
+
+/// Computes a strong entity tag from the modification time and length of a
+/// file, the same way most static file servers do.
+fn file_etag(modified: Option<SystemTime>, len: u64) -> Option<ETag> {
+    let modified = modified?.duration_since(UNIX_EPOCH).ok()?;
+    format!("\"{:x}-{:x}\"", modified.as_secs(), len)
+        .parse()
+        .ok()
+}
+
 fn conditionals() -> impl Filter<Extract = One<Conditionals>, Error = Infallible> + Copy {
     crate::header::optional2()
+        .and(crate::header::optional2())
+        .and(crate::header::optional2())
         .and(crate::header::optional2())
         .and(crate::header::optional2())
         .and(crate::header::optional2())
         .map(
-            |if_modified_since, if_unmodified_since, if_range, range| Conditionals {
-                if_modified_since,
-                if_unmodified_since,
-                if_range,
-                range,
+            |if_match, if_none_match, if_modified_since, if_unmodified_since, if_range, range| {
+                Conditionals {
+                    if_match,
+                    if_none_match,
+                    if_modified_since,
+                    if_unmodified_since,
+                    if_range,
+                    range,
+                }
             },
         )
 }
@@ -263,9 +699,10 @@ impl Reply for File {
 fn file_reply(
     path: ArcPath,
     conditionals: Conditionals,
+    read_buf: Option<usize>,
 ) -> impl Future<Output = Result<File, Rejection>> + Send {
     TkFile::open(path.clone()).then(move |res| match res {
-        Ok(f) => Either::Left(file_conditional(f, path, conditionals)),
+        Ok(f) => Either::Left(file_conditional(f, path, conditionals, read_buf)),
         Err(err) => {
             let rej = match err.kind() {
                 io::ErrorKind::NotFound => {
@@ -304,102 +741,286 @@ fn file_conditional(
     f: TkFile,
     path: ArcPath,
     conditionals: Conditionals,
+    read_buf: Option<usize>,
 ) -> impl Future<Output = Result<File, Rejection>> + Send {
     file_metadata(f).map_ok(move |(file, meta)| {
-        let mut len = meta.len();
-        let modified = meta.modified().ok().map(LastModified::from);
-
-        let resp = match conditionals.check(modified) {
-            Cond::NoBody(resp) => resp,
-            Cond::WithBody(range) => {
-                bytes_range(range, len)
-                    .map(|(start, end)| {
-                        let sub_len = end - start;
-                        let buf_size = optimal_buf_size(&meta);
-                        let stream = file_stream(file, buf_size, (start, end));
-                        let body = Body::wrap_stream(stream);
-
-                        let mut resp = Response::new(body);
-
-                        if sub_len != len {
-                            *resp.status_mut() = StatusCode::PARTIAL_CONTENT;
-                            resp.headers_mut().typed_insert(
-                                ContentRange::bytes(start..end, len).expect("valid ContentRange"),
-                            );
-
-                            len = sub_len;
-                        }
-
-                        let mime = mime_guess::from_path(path.as_ref()).first_or_octet_stream();
+        let buf_size = match read_buf {
+            Some(size) => cmp::min(size as u64, meta.len()) as usize,
+            None => optimal_buf_size(&meta),
+        };
+        let mtime = meta.modified().ok();
+        let resp = conditional_response(
+            path.as_ref(),
+            meta.len(),
+            mtime,
+            file_etag(mtime, meta.len()),
+            conditionals,
+            |ranges| {
+                let mut source = FileSource::new(file, ranges);
+                move |start, end| source.stream(buf_size, (start, end))
+            },
+        );
+        File { resp, path }
+    })
+}
 
-                        resp.headers_mut().typed_insert(ContentLength(len));
-                        resp.headers_mut().typed_insert(ContentType::from(mime));
-                        resp.headers_mut().typed_insert(AcceptRanges::bytes());
+/// Checks the `conditionals` against a file of `len` bytes, and its
+/// validators, and builds the response.
+///
+/// `body` gets the resolved ranges, and returns the factory of their body
+/// streams passed to [`range::ranged_response`].
+fn conditional_response<F, B, S>(
+    path: &Path,
+    len: u64,
+    mtime: Option<SystemTime>,
+    etag: Option<ETag>,
+    conditionals: Conditionals,
+    body: F,
+) -> Response
+where
+    F: FnOnce(&Result<Vec<(u64, u64)>, range::BadRange>) -> B,
+    B: FnMut(u64, u64) -> S,
+    S: Stream<Item = Result<Bytes, io::Error>> + Send + 'static,
+{
+    let modified = mtime.map(LastModified::from);
+
+    match conditionals.check(modified, etag.as_ref()) {
+        Cond::NoBody(resp) => resp,
+        Cond::WithBody(range) => {
+            let ranges = range::satisfiable(range.as_ref(), len);
+            let mime = mime_guess::from_path(path).first_or_octet_stream();
+            let content_type = HeaderValue::from_str(mime.as_ref()).ok();
+
+            let body = body(&ranges);
+            let mut resp = range::ranged_response(ranges, len, content_type, body);
+
+            if resp.status() != StatusCode::RANGE_NOT_SATISFIABLE {
+                if let Some(last_modified) = modified {
+                    resp.headers_mut().typed_insert(last_modified);
+                }
+                if let Some(etag) = etag {
+                    resp.headers_mut().typed_insert(etag);
+                }
+            }
 
-                        if let Some(last_modified) = modified {
-                            resp.headers_mut().typed_insert(last_modified);
-                        }
+            resp
+        }
+    }
+}
 
-                        resp
-                    })
-                    .unwrap_or_else(|BadRange| {
-                        // bad byte range
-                        let mut resp = Response::new(Body::empty());
-                        *resp.status_mut() = StatusCode::RANGE_NOT_SATISFIABLE;
-                        resp.headers_mut()
-                            .typed_insert(ContentRange::unsatisfied_bytes(len));
-                        resp
-                    })
-            }
-        };
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum ListingFormat {
+    Html,
+    Json,
+}
 
-        File { resp, path }
+fn listing_format() -> impl Filter<Extract = One<ListingFormat>, Error = Infallible> + Copy {
+    crate::filter::filter_fn_one(|route| {
+        let json = route
+            .headers()
+            .get(ACCEPT)
+            .and_then(|accept| accept.to_str().ok())
+            .map(|accept| accept.contains("application/json"))
+            .unwrap_or(false);
+        future::ok(if json {
+            ListingFormat::Json
+        } else {
+            ListingFormat::Html
+        })
     })
 }
 
-struct BadRange;
+#[derive(Debug)]
+struct ListingEntry {
+    name: String,
+    is_dir: bool,
+    size: u64,
+    modified: Option<u64>,
+}
+
+async fn read_listing(path: &Path, hidden: bool) -> io::Result<Vec<ListingEntry>> {
+    let mut entries = Vec::new();
+    let mut read_dir = tokio::fs::read_dir(path).await?;
+    while let Some(entry) = read_dir.next_entry().await? {
+        let name = match entry.file_name().into_string() {
+            Ok(name) => name,
+            // Names that aren't valid UTF-8 couldn't be requested anyway.
+            Err(_) => continue,
+        };
+        if !hidden && name.starts_with('.') {
+            continue;
+        }
+        let meta = match entry.metadata().await {
+            Ok(meta) => meta,
+            Err(err) => {
+                tracing::debug!("dir listing: metadata error for {:?}: {}", name, err);
+                continue;
+            }
+        };
+        entries.push(ListingEntry {
+            name,
+            is_dir: meta.is_dir(),
+            size: if meta.is_dir() { 0 } else { meta.len() },
+            modified: meta
+                .modified()
+                .ok()
+                .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+                .map(|time| time.as_secs()),
+        });
+    }
+    entries.sort_by(|a, b| b.is_dir.cmp(&a.is_dir).then_with(|| a.name.cmp(&b.name)));
+    Ok(entries)
+}
 
-fn bytes_range(range: Option<Range>, max_len: u64) -> Result<(u64, u64), BadRange> {
-    use std::ops::Bound;
+async fn listing_reply(
+    config: Arc<DirConfig>,
+    path: ArcPath,
+    href: String,
+    format: ListingFormat,
+) -> Result<File, Rejection> {
+    let hidden = config.listing_hidden && config.dotfiles;
+    let entries = match read_listing(path.as_ref(), hidden).await {
+        Ok(entries) => entries,
+        Err(err) => {
+            tracing::debug!("dir listing error: {}", err);
+            return Err(reject::not_found());
+        }
+    };
 
-    let range = if let Some(range) = range {
-        range
-    } else {
-        return Ok((0, max_len));
+    let resp = match format {
+        ListingFormat::Json => {
+            let entries = entries
+                .iter()
+                .map(|entry| {
+                    serde_json::json!({
+                        "name": entry.name,
+                        "is_dir": entry.is_dir,
+                        "size": entry.size,
+                        "modified": entry.modified,
+                    })
+                })
+                .collect::<Vec<_>>();
+            crate::reply::json(&entries).into_response()
+        }
+        ListingFormat::Html => {
+            let mut resp = Response::new(render_listing(&href, &entries).into());
+            resp.headers_mut().insert(
+                CONTENT_TYPE,
+                HeaderValue::from_static("text/html; charset=utf-8"),
+            );
+            resp
+        }
     };
+    Ok(File { resp, path })
+}
 
-    let ret = range
-        .iter()
-        .map(|(start, end)| {
-            let start = match start {
-                Bound::Unbounded => 0,
-                Bound::Included(s) => s,
-                Bound::Excluded(s) => s + 1,
-            };
+fn render_listing(href: &str, entries: &[ListingEntry]) -> String {
+    use std::fmt::Write;
+
+    let title = escape_html(&percent_decode_str(href).decode_utf8_lossy());
+    // The request path is written into the links as well, so it is encoded
+    // again segment by segment, the same way as the entry names.
+    let href = href
+        .split('/')
+        .map(|seg| {
+            utf8_percent_encode(&percent_decode_str(seg).decode_utf8_lossy(), PATH_SEGMENT)
+                .to_string()
+        })
+        .collect::<Vec<_>>()
+        .join("/");
+    let mut html = format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n\
+         <title>Index of {title}</title>\n</head>\n<body>\n<h1>Index of {title}</h1>\n<ul>\n",
+        title = title
+    );
+    if href != "/" {
+        html.push_str("<li><a href=\"../\">../</a></li>\n");
+    }
+    for entry in entries {
+        let slash = if entry.is_dir { "/" } else { "" };
+        let _ = writeln!(
+            html,
+            "<li><a href=\"{}{}{}\">{}{}</a></li>",
+            href,
+            utf8_percent_encode(&entry.name, PATH_SEGMENT),
+            slash,
+            escape_html(&entry.name),
+            slash,
+        );
+    }
+    html.push_str("</ul>\n</body>\n</html>\n");
+    html
+}
 
-            let end = match end {
-                Bound::Unbounded => max_len,
-                Bound::Included(s) => {
-                    // For the special case where s == the file size
-                    if s == max_len {
-                        s
-                    } else {
-                        s + 1
-                    }
-                }
-                Bound::Excluded(s) => s,
-            };
+fn escape_html(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Characters percent-encoded in the links of a directory listing.
+const PATH_SEGMENT: &AsciiSet = &CONTROLS
+    .add(b' ')
+    .add(b'"')
+    .add(b'#')
+    .add(b'%')
+    .add(b'/')
+    .add(b'<')
+    .add(b'>')
+    .add(b'?')
+    .add(b'`')
+    .add(b'{')
+    .add(b'}');
+
+/// Opens the file streams for each requested range.
+///
+/// With multiple ranges, the file is duplicated for each of them. The streams
+/// are read one after the other, and each one seeks to its own start.
+enum FileSource {
+    Single(Option<TkFile>),
+    Multi(io::Result<std::fs::File>),
+}
+
+impl FileSource {
+    fn new(file: TkFile, ranges: &Result<Vec<(u64, u64)>, range::BadRange>) -> Self {
+        match ranges {
+            Ok(ranges) if ranges.len() > 1 => FileSource::Multi(
+                file.try_into_std()
+                    .map_err(|_| io::Error::other("file busy")),
+            ),
+            _ => FileSource::Single(Some(file)),
+        }
+    }
 
-            if start < end && end <= max_len {
-                Ok((start, end))
-            } else {
-                tracing::trace!("unsatisfiable byte range: {}-{}/{}", start, end, max_len);
-                Err(BadRange)
+    fn stream(
+        &mut self,
+        buf_size: usize,
+        range: (u64, u64),
+    ) -> impl Stream<Item = Result<Bytes, io::Error>> + Send {
+        let file = match self {
+            FileSource::Single(file) => file
+                .take()
+                .ok_or_else(|| io::Error::other("file already streamed")),
+            FileSource::Multi(Ok(file)) => file.try_clone().map(TkFile::from_std),
+            FileSource::Multi(Err(err)) => Err(io::Error::new(err.kind(), err.to_string())),
+        };
+        match file {
+            Ok(file) => Either::Left(file_stream(file, buf_size, range)),
+            Err(err) => {
+                tracing::debug!("file stream error: {}", err);
+                Either::Right(stream::once(future::err(err)))
             }
-        })
-        .next()
-        .unwrap_or(Ok((0, max_len)));
-    ret
+        }
+    }
 }
 
 fn file_stream(
@@ -489,6 +1110,416 @@ fn get_block_size(_metadata: &Metadata) -> usize {
     DEFAULT_READ_BUF_SIZE
 }
 
+// ===== Cache =====
+
+/// An in-memory cache of small files, for [`Dir::cache`].
+///
+/// Files are kept until the cache grows past its maximum size, evicting the
+/// least recently used ones first. A cached file is served without touching
+/// the file system for the cache's time to live, after which its metadata
+/// is checked again, and the file reloaded if it changed.
+///
+/// Clones of a `FileCache` share the same entries.
+#[derive(Clone)]
+pub struct FileCache {
+    max_size: u64,
+    max_file_size: u64,
+    ttl: Duration,
+    inner: Arc<Mutex<CacheInner>>,
+}
+
+struct CacheInner {
+    /// The entries, from the least to the most recently used.
+    entries: LinkedHashMap<PathBuf, CacheEntry>,
+    size: u64,
+}
+
+#[derive(Clone)]
+struct CacheEntry {
+    bytes: Bytes,
+    mtime: Option<SystemTime>,
+    checked: Instant,
+}
+
+impl FileCache {
+    /// Creates a cache holding at most `max_size` bytes of file contents.
+    ///
+    /// Files larger than 1MiB are not cached by default, see
+    /// [`max_file_size`](FileCache::max_file_size), and cached files are
+    /// checked again after a second, see [`ttl`](FileCache::ttl).
+    pub fn new(max_size: u64) -> Self {
+        FileCache {
+            max_size,
+            max_file_size: cmp::min(max_size, 1024 * 1024),
+            ttl: Duration::from_secs(1),
+            inner: Arc::new(Mutex::new(CacheInner {
+                entries: LinkedHashMap::new(),
+                size: 0,
+            })),
+        }
+    }
+
+    /// Sets the size of the largest file kept in the cache.
+    pub fn max_file_size(mut self, size: u64) -> Self {
+        self.max_file_size = cmp::min(size, self.max_size);
+        self
+    }
+
+    /// Sets how long a cached file is served before checking if it changed.
+    pub fn ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = ttl;
+        self
+    }
+
+    fn lock(&self) -> MutexGuard<'_, CacheInner> {
+        self.inner
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// Gets an entry checked less than `ttl` ago.
+    fn get_fresh(&self, path: &Path) -> Option<CacheEntry> {
+        let mut inner = self.lock();
+        let entry = inner.entries.get_refresh(path)?;
+        if entry.checked.elapsed() >= self.ttl {
+            return None;
+        }
+        Some(entry.clone())
+    }
+
+    /// Gets an entry if it still matches the file `meta`, marking it as
+    /// freshly checked.
+    fn revalidate(&self, path: &Path, meta: &Metadata) -> Option<CacheEntry> {
+        let mut inner = self.lock();
+        let entry = inner.entries.get_mut(path)?;
+        if entry.mtime != meta.modified().ok() || entry.bytes.len() as u64 != meta.len() {
+            tracing::trace!("file cache: {:?} changed", path);
+            return None;
+        }
+        entry.checked = Instant::now();
+        Some(entry.clone())
+    }
+
+    fn insert(&self, path: PathBuf, bytes: Bytes, mtime: Option<SystemTime>) -> CacheEntry {
+        let mut inner = self.lock();
+        let entry = CacheEntry {
+            bytes,
+            mtime,
+            checked: Instant::now(),
+        };
+        let len = entry.bytes.len() as u64;
+
+        if let Some(old) = inner.entries.remove(&path) {
+            inner.size -= old.bytes.len() as u64;
+        }
+        while inner.size + len > self.max_size {
+            let (lru, old) = match inner.entries.pop_front() {
+                Some(lru) => lru,
+                None => break,
+            };
+            tracing::trace!("file cache: evicting {:?}", lru);
+            inner.size -= old.bytes.len() as u64;
+        }
+        inner.size += len;
+        inner.entries.insert(path, entry.clone());
+        entry
+    }
+}
+
+impl fmt::Debug for FileCache {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let inner = self.lock();
+        f.debug_struct("FileCache")
+            .field("max_size", &self.max_size)
+            .field("max_file_size", &self.max_file_size)
+            .field("ttl", &self.ttl)
+            .field("entries", &inner.entries.len())
+            .field("size", &inner.size)
+            .finish()
+    }
+}
+
+async fn cached_file_reply(
+    cache: FileCache,
+    path: ArcPath,
+    conditionals: Conditionals,
+    read_buf: Option<usize>,
+) -> Result<File, Rejection> {
+    if let Some(entry) = cache.get_fresh(path.as_ref()) {
+        tracing::trace!("file cache: hit {:?}", path.as_ref());
+        return Ok(cached_conditional(entry, path, conditionals));
+    }
+
+    let meta = match tokio::fs::metadata(path.as_ref()).await {
+        Ok(meta) if meta.is_file() => meta,
+        // Let the uncached path report the error.
+        _ => return file_reply(path, conditionals, read_buf).await,
+    };
+    if let Some(entry) = cache.revalidate(path.as_ref(), &meta) {
+        tracing::trace!("file cache: revalidated {:?}", path.as_ref());
+        return Ok(cached_conditional(entry, path, conditionals));
+    }
+    if meta.len() > cache.max_file_size {
+        return file_reply(path, conditionals, read_buf).await;
+    }
+
+    match tokio::fs::read(path.as_ref()).await {
+        // The file could have changed in between.
+        Ok(bytes) if bytes.len() as u64 == meta.len() => {
+            tracing::trace!("file cache: loaded {:?}", path.as_ref());
+            let entry = cache.insert(
+                path.as_ref().to_owned(),
+                Bytes::from(bytes),
+                meta.modified().ok(),
+            );
+            Ok(cached_conditional(entry, path, conditionals))
+        }
+        _ => file_reply(path, conditionals, read_buf).await,
+    }
+}
+
+fn cached_conditional(entry: CacheEntry, path: ArcPath, conditionals: Conditionals) -> File {
+    let bytes = entry.bytes;
+    let len = bytes.len() as u64;
+    let resp = conditional_response(
+        path.as_ref(),
+        len,
+        entry.mtime,
+        file_etag(entry.mtime, len),
+        conditionals,
+        |_| move |start, end| bytes_stream(&bytes, start, end),
+    );
+    File { resp, path }
+}
+
+fn bytes_stream(
+    bytes: &Bytes,
+    start: u64,
+    end: u64,
+) -> impl Stream<Item = Result<Bytes, io::Error>> + Send {
+    let chunk = bytes.slice(start as usize..end as usize);
+    stream::once(future::ok(chunk))
+}
+
+// ===== Embedded =====
+
+/// Creates a `Filter` that serves files compiled into the binary.
+///
+/// `files` pairs each file's path, relative to the served directory and
+/// without a leading `/`, with its contents. It is usually built by the
+/// [`embed!`] macro. As with [`dir`], the request path selects the file,
+/// directories are served their `index.html`, and only `GET` and `HEAD`
+/// requests are matched.
+///
+/// Responses have a strong `etag`, computed once from the contents, and
+/// support conditional and range requests. Wrap the filter with
+/// `compression::auto()` to compress them.
+///
+/// # Example
+///
+/// ```
+/// use nextshell::Filter;
+///
+/// static FILES: &[(&str, &[u8])] = &[
+///     ("index.html", b"<h1>Hello</h1>"),
+///     ("app.js", b"console.log('hello')"),
+/// ];
+///
+/// let route = nextshell::path("static").and(nextshell::fs::embedded(FILES));
+/// ```
+pub fn embedded(files: &'static [(&'static str, &'static [u8])]) -> Embedded {
+    let assets = files
+        .iter()
+        .map(|&(path, bytes)| (path.trim_start_matches('/'), Asset::new(bytes)))
+        .collect();
+    Embedded::new(assets)
+}
+
+/// Embeds the files of a directory into the binary, and serves them.
+///
+/// The whole directory is walked at compile time, and every file in it,
+/// including those in subdirectories, is served. This is the same as
+/// listing each of them with [`embedded`], see it for details on how the
+/// files are served.
+///
+/// The path is relative to the compiler's working directory, so it should
+/// start with `$CARGO_MANIFEST_DIR`, which is expanded to the directory of
+/// the crate's `Cargo.toml`. Changes to embedded files trigger a rebuild,
+/// but new files are only picked up once the crate is rebuilt for another
+/// reason.
+///
+/// Files can also be listed one by one, relative to the directory.
+///
+/// *This macro requires the `"embed"` feature.*
+///
+/// # Example
+///
+/// ```
+/// use nextshell::Filter;
+///
+/// // Embeds everything in `examples/dir`.
+/// let route = nextshell::path("static")
+///     .and(nextshell::fs::embed!("$CARGO_MANIFEST_DIR/examples/dir"));
+///
+/// // Embeds only `examples/dir/index.html` and `examples/todos.rs`.
+/// let route = nextshell::path("static").and(nextshell::fs::embed!(
+///     "examples",
+///     ["dir/index.html", "todos.rs"]
+/// ));
+/// ```
+#[cfg(feature = "embed")]
+#[doc(inline)]
+pub use crate::__fs_embed as embed;
+
+#[cfg(feature = "embed")]
+#[doc(hidden)]
+#[macro_export]
+// not public API, see `fs::embed!`
+macro_rules! __fs_embed {
+    ($dir:literal, [$($file:literal),* $(,)?]) => {
+        $crate::fs::embedded(&[$(
+            (
+                $file,
+                include_bytes!(concat!(env!("CARGO_MANIFEST_DIR"), "/", $dir, "/", $file))
+                    as &'static [u8],
+            ),
+        )*])
+    };
+    // `include_dir!` only accepts a plain literal, not a `$dir:literal` fragment.
+    ($dir:tt) => {{
+        use $crate::fs::__private::include_dir;
+        static DIR: include_dir::Dir<'static> = include_dir::include_dir!($dir);
+        $crate::fs::__private::embedded_dir(&DIR)
+    }};
+}
+
+#[cfg(feature = "embed")]
+#[doc(hidden)]
+// not public API, used by `fs::embed!`
+pub mod __private {
+    pub use include_dir;
+
+    use super::{Asset, Embedded};
+    use std::collections::HashMap;
+
+    pub fn embedded_dir(dir: &'static include_dir::Dir<'static>) -> Embedded {
+        fn walk(
+            dir: &'static include_dir::Dir<'static>,
+            assets: &mut HashMap<&'static str, Asset>,
+        ) {
+            for file in dir.files() {
+                match file.path().to_str() {
+                    Some(path) => {
+                        assets.insert(path, Asset::new(file.contents()));
+                    }
+                    None => tracing::warn!("embed: skipping non UTF-8 path {:?}", file.path()),
+                }
+            }
+            for dir in dir.dirs() {
+                walk(dir, assets);
+            }
+        }
+
+        let mut assets = HashMap::new();
+        walk(dir, &mut assets);
+        Embedded::new(assets)
+    }
+}
+
+/// A `Filter` serving embedded files, created by [`embedded`].
+#[derive(Clone)]
+pub struct Embedded {
+    assets: Arc<HashMap<&'static str, Asset>>,
+    filter: BoxedFilter<One<File>>,
+}
+
+struct Asset {
+    bytes: &'static [u8],
+    etag: ETag,
+}
+
+impl Asset {
+    fn new(bytes: &'static [u8]) -> Self {
+        Asset {
+            bytes,
+            etag: crate::filters::etag::strong_etag(bytes),
+        }
+    }
+}
+
+impl Embedded {
+    fn new(assets: HashMap<&'static str, Asset>) -> Self {
+        let assets = Arc::new(assets);
+        let filter = {
+            let assets = assets.clone();
+            crate::get()
+                .or(crate::head())
+                .unify()
+                .and(crate::path::tail())
+                .and(conditionals())
+                .and_then(move |tail: crate::path::Tail, conditionals| {
+                    future::ready(embedded_reply(&assets, tail.as_str(), conditionals))
+                })
+                .boxed()
+        };
+        Embedded { assets, filter }
+    }
+}
+
+impl fmt::Debug for Embedded {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_set().entries(self.assets.keys()).finish()
+    }
+}
+
+impl FilterBase for Embedded {
+    type Extract = One<File>;
+    type Error = Rejection;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Extract, Self::Error>> + Send>>;
+
+    fn filter(&self, _: Internal) -> Self::Future {
+        self.filter.filter(Internal)
+    }
+}
+
+fn embedded_reply(
+    assets: &HashMap<&'static str, Asset>,
+    tail: &str,
+    conditionals: Conditionals,
+) -> Result<File, Rejection> {
+    let path = match percent_decode_str(tail).decode_utf8() {
+        Ok(path) => path,
+        Err(_) => return Err(reject::not_found()),
+    };
+    let (path, asset) = if path.is_empty() || path.ends_with('/') {
+        let index = format!("{}index.html", path);
+        let asset = assets.get(index.as_str());
+        (index, asset)
+    } else {
+        let asset = assets.get(&*path);
+        (path.into_owned(), asset)
+    };
+    let asset = match asset {
+        Some(asset) => asset,
+        None => {
+            tracing::debug!("embedded file not found: {:?}", path);
+            return Err(reject::not_found());
+        }
+    };
+
+    let bytes = Bytes::from_static(asset.bytes);
+    let path = ArcPath(Arc::new(PathBuf::from(path)));
+    let resp = conditional_response(
+        path.as_ref(),
+        bytes.len() as u64,
+        None,
+        Some(asset.etag.clone()),
+        conditionals,
+        |_| move |start, end| bytes_stream(&bytes, start, end),
+    );
+    Ok(File { resp, path })
+}
+
 // ===== Rejections =====
 
 unit_error! {
@@ -499,6 +1530,21 @@ unit_error! {
     pub(crate) FilePermissionError: "file perimission error"
 }
 
+unit_error! {
+    /// A path went through a symbolic link not allowed by [`Dir::symlinks`].
+    pub SymlinkForbidden: "symbolic link not allowed"
+}
+
+unit_error! {
+    /// A hidden file was requested, see [`Dir::dotfiles`].
+    pub DotfileForbidden: "hidden file not allowed"
+}
+
+unit_error! {
+    /// A file extension not allowed by [`Dir::allow_extensions`] was requested.
+    pub ExtensionForbidden: "file extension not allowed"
+}
+
 #[cfg(test)]
 mod tests {
     use super::sanitize_path;
@@ -535,4 +1581,22 @@ mod tests {
         assert_eq!(buf.len(), 0);
         assert_eq!(buf.capacity(), cap);
     }
+
+    #[test]
+    fn test_file_cache_evicts_lru() {
+        let cache = super::FileCache::new(10);
+        let insert = |name: &str, len: usize| {
+            cache.insert(name.into(), vec![0; len].into(), None);
+        };
+
+        insert("a", 4);
+        insert("b", 4);
+        assert!(cache.get_fresh("a".as_ref()).is_some());
+        insert("c", 4);
+
+        assert!(cache.get_fresh("a".as_ref()).is_some());
+        assert!(cache.get_fresh("b".as_ref()).is_none());
+        assert!(cache.get_fresh("c".as_ref()).is_some());
+        assert_eq!(cache.lock().size, 8);
+    }
 }
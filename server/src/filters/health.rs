@@ -0,0 +1,233 @@
+//! Health Check Filters
+//!
+//! [`health()`](crate::health()) creates a [`Health`] registry of checks,
+//! serving Kubernetes-style probes:
+//!
+//! - `GET /livez` answers as long as the server is able to, without running
+//!   any check,
+//! - `GET /readyz` runs every registered check concurrently, and answers
+//!   `200 OK` if they all pass, or `503 Service Unavailable` otherwise.
+//!
+//! Both answer with a JSON report, such as:
+//!
+//! ```json
+//! {
+//!   "status": "fail",
+//!   "checks": {
+//!     "db": { "status": "ok", "duration_ms": 3 },
+//!     "queue": { "status": "fail", "duration_ms": 1000, "error": "timed out" }
+//!   }
+//! }
+//! ```
+//!
+//! # Example
+//!
+//! ```
+//! use std::time::Duration;
+//! use nextshell::Filter;
+//!
+//! # async fn ping_db() -> Result<(), std::io::Error> { Ok(()) }
+//! let health = nextshell::health()
+//!     .check("db", || ping_db())
+//!     .check_with_timeout("queue", Duration::from_millis(200), || async {
+//!         Ok::<_, String>(())
+//!     });
+//!
+//! let hello = nextshell::path("hello").map(|| "Hello, World!");
+//!
+//! let routes = health.endpoints().or(hello);
+//! ```
+
+use std::fmt;
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use futures_util::future::{self, BoxFuture, FutureExt};
+use http::header::{HeaderValue, CONTENT_TYPE};
+use http::StatusCode;
+use serde_json::{json, Map, Value};
+
+use crate::filter::Filter;
+use crate::reject::Rejection;
+use crate::reply::Response;
+
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Create a new, empty [`Health`] registry, whose checks time out after 5
+/// seconds.
+///
+/// See the [module docs](crate::health) for an example.
+pub fn health() -> Health {
+    Health {
+        inner: Arc::new(Registry {
+            timeout: Mutex::new(DEFAULT_TIMEOUT),
+            checks: Mutex::new(Vec::new()),
+        }),
+    }
+}
+
+/// A registry of health checks, serving the `/livez` and `/readyz` probes.
+///
+/// Cloning a `Health` shares the same registry, so checks can still be
+/// registered once the filters are built.
+#[derive(Clone, Debug)]
+pub struct Health {
+    inner: Arc<Registry>,
+}
+
+#[derive(Debug)]
+struct Registry {
+    timeout: Mutex<Duration>,
+    checks: Mutex<Vec<Check>>,
+}
+
+type CheckFn = dyn Fn() -> BoxFuture<'static, Result<(), String>> + Send + Sync;
+
+#[derive(Clone)]
+struct Check {
+    name: &'static str,
+    timeout: Option<Duration>,
+    run: Arc<CheckFn>,
+}
+
+impl fmt::Debug for Check {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Check")
+            .field("name", &self.name)
+            .field("timeout", &self.timeout)
+            .finish()
+    }
+}
+
+impl Health {
+    /// Register a readiness check named `name`.
+    ///
+    /// `check` is called for every `/readyz` request, and fails the probe if
+    /// its future resolves to an error, or takes longer than the registry's
+    /// [`timeout`](Health::timeout).
+    ///
+    /// Registering a second check with the same name replaces the first.
+    pub fn check<F, Fut, E>(self, name: &'static str, check: F) -> Self
+    where
+        F: Fn() -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<(), E>> + Send + 'static,
+        E: fmt::Display,
+    {
+        self.register(name, None, check)
+    }
+
+    /// Register a readiness check named `name`, with its own `timeout`.
+    ///
+    /// See [`check`](Health::check).
+    pub fn check_with_timeout<F, Fut, E>(
+        self,
+        name: &'static str,
+        timeout: Duration,
+        check: F,
+    ) -> Self
+    where
+        F: Fn() -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<(), E>> + Send + 'static,
+        E: fmt::Display,
+    {
+        self.register(name, Some(timeout), check)
+    }
+
+    /// Set how long checks registered without their own timeout may take.
+    ///
+    /// Defaults to 5 seconds.
+    pub fn timeout(self, timeout: Duration) -> Self {
+        *self.inner.timeout.lock().unwrap() = timeout;
+        self
+    }
+
+    fn register<F, Fut, E>(self, name: &'static str, timeout: Option<Duration>, check: F) -> Self
+    where
+        F: Fn() -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<(), E>> + Send + 'static,
+        E: fmt::Display,
+    {
+        let run = Arc::new(move || {
+            check()
+                .map(|result| result.map_err(|err| err.to_string()))
+                .boxed()
+        });
+        let mut checks = self.inner.checks.lock().unwrap();
+        checks.retain(|check| check.name != name);
+        checks.push(Check { name, timeout, run });
+        drop(checks);
+        self
+    }
+
+    /// A filter answering `GET /livez`, always with `200 OK`.
+    pub fn livez(&self) -> impl Filter<Extract = (Response,), Error = Rejection> + Clone {
+        crate::path("livez")
+            .and(crate::path::end())
+            .and(crate::get())
+            .map(|| report(true, Map::new()))
+    }
+
+    /// A filter answering `GET /readyz` with the results of every check.
+    pub fn readyz(&self) -> impl Filter<Extract = (Response,), Error = Rejection> + Clone {
+        let health = self.clone();
+        crate::path("readyz")
+            .and(crate::path::end())
+            .and(crate::get())
+            .then(move || {
+                let health = health.clone();
+                async move { health.ready().await }
+            })
+    }
+
+    /// Both [`livez`](Health::livez) and [`readyz`](Health::readyz).
+    pub fn endpoints(&self) -> impl Filter<Extract = (Response,), Error = Rejection> + Clone {
+        self.livez().or(self.readyz()).unify()
+    }
+
+    async fn ready(&self) -> Response {
+        let default = *self.inner.timeout.lock().unwrap();
+        let checks = self.inner.checks.lock().unwrap().clone();
+
+        let results = future::join_all(checks.into_iter().map(|check| async move {
+            let start = Instant::now();
+            let timeout = check.timeout.unwrap_or(default);
+            let result = match tokio::time::timeout(timeout, (check.run)()).await {
+                Ok(result) => result,
+                Err(_) => Err("timed out".to_owned()),
+            };
+            (check.name, start.elapsed(), result)
+        }))
+        .await;
+
+        let mut ok = true;
+        let mut checks = Map::new();
+        for (name, elapsed, result) in results {
+            let mut entry = json!({
+                "status": if result.is_ok() { "ok" } else { "fail" },
+                "duration_ms": elapsed.as_millis() as u64,
+            });
+            if let Err(err) = result {
+                tracing::debug!("health check {:?} failed: {}", name, err);
+                entry["error"] = err.into();
+                ok = false;
+            }
+            checks.insert(name.to_owned(), entry);
+        }
+        report(ok, checks)
+    }
+}
+
+fn report(ok: bool, checks: Map<String, Value>) -> Response {
+    let body = json!({
+        "status": if ok { "ok" } else { "fail" },
+        "checks": checks,
+    });
+    let mut res = Response::new(body.to_string().into());
+    if !ok {
+        *res.status_mut() = StatusCode::SERVICE_UNAVAILABLE;
+    }
+    res.headers_mut()
+        .insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+    res
+}
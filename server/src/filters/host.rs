@@ -3,7 +3,9 @@
 use crate::filter::{filter_fn_one, Filter, One};
 use crate::reject::{self, Rejection};
 use futures_util::future;
-pub use http::uri::Authority;
+use http::header::{HeaderMap, FORWARDED};
+pub use http::uri::{Authority, Scheme};
+use std::convert::Infallible;
 use std::str::FromStr;
 
 /// Creates a `Filter` that requires a specific authority (target server's
@@ -94,3 +96,57 @@ pub fn optional() -> impl Filter<Extract = One<Option<Authority>>, Error = Rejec
         })
     })
 }
+
+/// Creates a `Filter` that extracts the scheme the client used to reach the
+/// server.
+///
+/// Proxies terminating TLS report the original scheme in the
+/// `x-forwarded-proto` or `forwarded` headers, which are checked first.
+/// Otherwise, the scheme of the target URI is used, defaulting to `http`.
+///
+/// Since clients can set these headers too, only rely on them when the
+/// server is only reachable through such a proxy.
+///
+/// # Example
+///
+/// ```
+/// use nextshell::{Filter, host::Scheme};
+///
+/// let route = nextshell::host::scheme()
+///     .map(|scheme: Scheme| format!("reached over {}", scheme));
+/// ```
+pub fn scheme() -> impl Filter<Extract = One<Scheme>, Error = Infallible> + Copy {
+    filter_fn_one(|route| {
+        let scheme = forwarded_proto(route.headers())
+            .or_else(|| route.uri().scheme().cloned())
+            .unwrap_or(Scheme::HTTP);
+        future::ok(scheme)
+    })
+}
+
+fn forwarded_proto(headers: &HeaderMap) -> Option<Scheme> {
+    let x_forwarded = headers
+        .get("x-forwarded-proto")
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.split(',').next());
+    // Only the first, closest to the client, element of `forwarded` is used.
+    let forwarded = || {
+        headers
+            .get(FORWARDED)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.split(',').next())
+            .and_then(|element| {
+                element.split(';').find_map(|pair| {
+                    let (key, value) = pair.split_once('=')?;
+                    if key.trim().eq_ignore_ascii_case("proto") {
+                        Some(value.trim().trim_matches('"'))
+                    } else {
+                        None
+                    }
+                })
+            })
+    };
+    x_forwarded
+        .or_else(forwarded)
+        .and_then(|proto| Scheme::from_str(proto.trim()).ok())
+}
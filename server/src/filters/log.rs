@@ -1,8 +1,13 @@
 //! Logger Filters
 
 use std::fmt;
+use std::fs::OpenOptions;
+use std::io::{self, Write};
 use std::net::SocketAddr;
-use std::time::{Duration, Instant};
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
 use http::{header, StatusCode};
 
@@ -11,7 +16,7 @@ use crate::reject::IsReject;
 use crate::reply::Reply;
 use crate::route::Route;
 
-use self::internal::WithLog;
+use self::internal::{LogFunc, WithLog};
 
 /// Create a wrapping [`Filter`](crate::Filter) with the specified `name` as the `target`.
 ///
@@ -77,6 +82,292 @@ where
     Log { func }
 }
 
+/// Create a wrapping [`Filter`](crate::Filter) logging requests in `format`
+/// through the `log` facade, with the specified `name` as the `target`.
+///
+/// # Example
+///
+/// ```
+/// use nextshell::Filter;
+/// use nextshell::log::Format;
+///
+/// let log = nextshell::log::format("example::access", Format::combined());
+/// let route = nextshell::any()
+///     .map(nextshell::reply)
+///     .with(log);
+/// ```
+pub fn format(name: &'static str, format: Format) -> Log<impl Fn(Info<'_>) + Clone + Send> {
+    let func = move |info: Info<'_>| {
+        log::info!(target: name, "{}", format.line(&info));
+    };
+    Log { func }
+}
+
+/// Create a wrapping [`Filter`](crate::Filter) writing a line in `format`
+/// for each request to `writer`, such as `std::io::stdout()`.
+///
+/// Lines are written synchronously and write errors are ignored; wrap slow
+/// writers in a `std::io::BufWriter` if flushing on each line isn't needed.
+///
+/// # Example
+///
+/// ```
+/// use nextshell::Filter;
+/// use nextshell::log::Format;
+///
+/// let log = nextshell::log::writer(Format::common(), std::io::stdout());
+/// let route = nextshell::any()
+///     .map(nextshell::reply)
+///     .with(log);
+/// ```
+pub fn writer<W>(format: Format, writer: W) -> Log<impl Fn(Info<'_>) + Clone + Send>
+where
+    W: Write + Send + 'static,
+{
+    let writer = Arc::new(Mutex::new(writer));
+    let func = move |info: Info<'_>| {
+        let mut line = format.line(&info);
+        line.push('\n');
+        let mut writer = writer
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        let _ = writer.write_all(line.as_bytes());
+    };
+    Log { func }
+}
+
+/// Create a wrapping [`Filter`](crate::Filter) appending a line in `format`
+/// for each request to the file at `path`, creating it if needed.
+///
+/// See [`writer`] for details.
+pub fn file(format: Format, path: &Path) -> io::Result<Log<impl Fn(Info<'_>) + Clone + Send>> {
+    let file = OpenOptions::new().create(true).append(true).open(path)?;
+    Ok(writer(format, file))
+}
+
+/// A format of access log lines.
+#[derive(Clone)]
+pub struct Format(FormatKind);
+
+#[derive(Clone)]
+enum FormatKind {
+    Common,
+    Combined,
+    Custom(Arc<dyn Fn(&Info<'_>) -> String + Send + Sync>),
+}
+
+impl Format {
+    /// The Common Log Format:
+    ///
+    /// `127.0.0.1 - - [10/Oct/2000:13:55:36 +0000] "GET /index.html HTTP/1.1" 200 2326`
+    pub fn common() -> Format {
+        Format(FormatKind::Common)
+    }
+
+    /// The Combined Log Format, which is the Common Log Format followed by
+    /// the quoted referer and user agent.
+    pub fn combined() -> Format {
+        Format(FormatKind::Combined)
+    }
+
+    /// A custom format, building each line from the [`Info`] of a request.
+    pub fn custom<F>(func: F) -> Format
+    where
+        F: Fn(&Info<'_>) -> String + Send + Sync + 'static,
+    {
+        Format(FormatKind::Custom(Arc::new(func)))
+    }
+
+    fn line(&self, info: &Info<'_>) -> String {
+        match self.0 {
+            FormatKind::Common => info.common().to_string(),
+            FormatKind::Combined => format!(
+                "{} \"{}\" \"{}\"",
+                info.common(),
+                OptFmt(info.referer()),
+                OptFmt(info.user_agent())
+            ),
+            FormatKind::Custom(ref func) => func(info),
+        }
+    }
+}
+
+impl fmt::Debug for Format {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self.0 {
+            FormatKind::Common => "Common",
+            FormatKind::Combined => "Combined",
+            FormatKind::Custom(_) => "Custom",
+        };
+        f.debug_tuple("Format").field(&name).finish()
+    }
+}
+
+/// Create a wrapping [`Filter`](crate::Filter) logging one JSON object per
+/// request, sampled by status class.
+///
+/// Each object has the `method`, `path`, `route` (see
+/// [`metrics::route`](crate::metrics::route)), `status`, `latency_ms`,
+/// `request_bytes`, `response_bytes`, `request_id` (from the
+/// `x-request-id` header of the request or else the response) and
+/// `remote_addr` of the request, with `null` for anything unknown.
+///
+/// By default every request is logged through the `log` facade, with the
+/// `nextshell::log::structured` target.
+///
+/// # Example
+///
+/// ```
+/// use nextshell::Filter;
+///
+/// // Log 1% of successes and every error.
+/// let log = nextshell::log::structured()
+///     .sample(2, 0.01)
+///     .sample(3, 0.01);
+/// let route = nextshell::any()
+///     .map(nextshell::reply)
+///     .with(log);
+/// ```
+pub fn structured() -> Structured {
+    Structured {
+        target: "nextshell::log::structured",
+        sink: None,
+        sampling: Arc::new(Sampling {
+            rates: [1.0; 5],
+            seen: Default::default(),
+        }),
+    }
+}
+
+/// Decorates a [`Filter`] to log requests as JSON, see [`structured`].
+#[derive(Clone)]
+pub struct Structured {
+    target: &'static str,
+    sink: Option<Arc<Mutex<dyn Write + Send>>>,
+    sampling: Arc<Sampling>,
+}
+
+struct Sampling {
+    // Indexed by status class, from 1xx to 5xx.
+    rates: [f64; 5],
+    seen: [AtomicU64; 5],
+}
+
+impl Structured {
+    /// Log this fraction, from `0.0` to `1.0`, of the requests whose status
+    /// is in `class`, such as `2` for 2xx.
+    ///
+    /// Sampling is deterministic: with a rate of `0.01`, every hundredth
+    /// request of the class is logged.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `class` isn't between 1 and 5, or `rate` between 0 and 1.
+    pub fn sample(mut self, class: u16, rate: f64) -> Self {
+        assert!(
+            (1..=5).contains(&class),
+            "status class must be between 1 and 5"
+        );
+        assert!(
+            (0.0..=1.0).contains(&rate),
+            "sampling rate must be between 0 and 1"
+        );
+        let mut rates = self.sampling.rates;
+        rates[usize::from(class) - 1] = rate;
+        self.sampling = Arc::new(Sampling {
+            rates,
+            seen: Default::default(),
+        });
+        self
+    }
+
+    /// Use `name` as the `log` target.
+    pub fn target(mut self, name: &'static str) -> Self {
+        self.target = name;
+        self
+    }
+
+    /// Write each object on its own line to `writer` instead of the `log`
+    /// facade.
+    pub fn writer<W>(mut self, writer: W) -> Self
+    where
+        W: Write + Send + 'static,
+    {
+        self.sink = Some(Arc::new(Mutex::new(writer)));
+        self
+    }
+
+    fn sampled(&self, status: StatusCode) -> bool {
+        let class = usize::from(status.as_u16() / 100).clamp(1, 5) - 1;
+        let rate = self.sampling.rates[class];
+        let n = self.sampling.seen[class].fetch_add(1, Ordering::Relaxed);
+        // Logs exactly `rate` of the requests, evenly spread.
+        ((n + 1) as f64 * rate).floor() > (n as f64 * rate).floor()
+    }
+}
+
+impl LogFunc for Structured {
+    fn log(&self, info: Info<'_>) {
+        if !self.sampled(info.status()) {
+            return;
+        }
+        let request_id = info
+            .request_headers()
+            .get("x-request-id")
+            .or_else(|| info.response_headers()?.get("x-request-id"))
+            .and_then(|id| id.to_str().ok());
+        let request_bytes = info
+            .request_headers()
+            .get(header::CONTENT_LENGTH)
+            .and_then(|len| len.to_str().ok()?.parse::<u64>().ok());
+        let line = serde_json::json!({
+            "method": info.method().as_str(),
+            "path": info.path(),
+            "route": info.route_template(),
+            "status": info.status().as_u16(),
+            "latency_ms": info.elapsed().as_secs_f64() * 1000.0,
+            "request_bytes": request_bytes,
+            "response_bytes": info.content_length(),
+            "request_id": request_id,
+            "remote_addr": info.remote_addr().map(|addr| addr.to_string()),
+        })
+        .to_string();
+
+        match self.sink {
+            Some(ref sink) => {
+                let mut sink = sink.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+                let _ = writeln!(sink, "{}", line);
+            }
+            None => log::info!(target: self.target, "{}", line),
+        }
+    }
+}
+
+impl fmt::Debug for Structured {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Structured")
+            .field("target", &self.target)
+            .field("rates", &self.sampling.rates)
+            .finish()
+    }
+}
+
+impl<F> WrapSealed<F> for Structured
+where
+    F: Filter + Clone + Send,
+    F::Extract: Reply,
+    F::Error: IsReject,
+{
+    type Wrapped = WithLog<Structured, F>;
+
+    fn wrap(&self, filter: F) -> Self::Wrapped {
+        WithLog {
+            filter,
+            log: Log { func: self.clone() },
+        }
+    }
+}
+
 /// Decorates a [`Filter`] to log requests and responses.
 #[derive(Clone, Copy, Debug)]
 pub struct Log<F> {
@@ -89,6 +380,7 @@ pub struct Info<'a> {
     route: &'a Route,
     start: Instant,
     status: StatusCode,
+    response: Option<&'a crate::reply::Response>,
 }
 
 impl<FN, F> WrapSealed<F> for Log<FN>
@@ -124,6 +416,11 @@ impl<'a> Info<'a> {
         self.route.full_path()
     }
 
+    /// View the full URI of the request, including the query string.
+    pub fn uri(&self) -> &http::Uri {
+        self.route.uri()
+    }
+
     /// View the `http::Version` of the request.
     pub fn version(&self) -> http::Version {
         self.route.version()
@@ -167,6 +464,96 @@ impl<'a> Info<'a> {
     pub fn request_headers(&self) -> &http::HeaderMap {
         self.route.headers()
     }
+
+    fn route_template(&self) -> Option<&'static str> {
+        self.route
+            .extensions()
+            .get::<crate::filters::metrics::RouteTemplate>()
+            .map(|template| template.0)
+    }
+
+    /// Access the headers of the response, unless the request was rejected.
+    pub fn response_headers(&self) -> Option<&http::HeaderMap> {
+        self.response.map(|res| res.headers())
+    }
+
+    /// View the length of the response body, if known upfront.
+    pub fn content_length(&self) -> Option<u64> {
+        use hyper::body::HttpBody;
+
+        let res = self.response?;
+        res.headers()
+            .get(header::CONTENT_LENGTH)
+            .and_then(|len| len.to_str().ok()?.parse().ok())
+            .or_else(|| res.body().size_hint().exact())
+    }
+
+    fn common(&self) -> impl fmt::Display + '_ {
+        CommonFmt(self)
+    }
+}
+
+struct CommonFmt<'a, 'b>(&'a Info<'b>);
+
+impl fmt::Display for CommonFmt<'_, '_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let info = self.0;
+        let request_line = info
+            .uri()
+            .path_and_query()
+            .map_or_else(|| info.path(), |pq| pq.as_str());
+        write!(
+            f,
+            "{} - - [{}] \"{} {} {:?}\" {} {}",
+            OptFmt(info.remote_addr().map(|addr| addr.ip())),
+            ClfTime(SystemTime::now() - info.elapsed()),
+            info.method(),
+            request_line,
+            info.version(),
+            info.status().as_u16(),
+            OptFmt(info.content_length()),
+        )
+    }
+}
+
+/// Formats a time like `10/Oct/2000:13:55:36 +0000`, always in UTC.
+struct ClfTime(SystemTime);
+
+impl fmt::Display for ClfTime {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        const MONTHS: [&str; 12] = [
+            "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+        ];
+
+        let secs = self
+            .0
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        let (days, secs) = (secs / 86_400, secs % 86_400);
+
+        // Howard Hinnant's `civil_from_days`.
+        let z = days as i64 + 719_468;
+        let era = z.div_euclid(146_097);
+        let doe = z.rem_euclid(146_097);
+        let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+        let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+        let mp = (5 * doy + 2) / 153;
+        let day = doy - (153 * mp + 2) / 5 + 1;
+        let month = if mp < 10 { mp + 3 } else { mp - 9 };
+        let year = yoe + era * 400 + i64::from(month <= 2);
+
+        write!(
+            f,
+            "{:02}/{}/{}:{:02}:{:02}:{:02} +0000",
+            day,
+            MONTHS[month as usize - 1],
+            year,
+            secs / 3600,
+            secs % 3600 / 60,
+            secs % 60,
+        )
+    }
 }
 
 struct OptFmt<T>(Option<T>);
@@ -196,6 +583,17 @@ mod internal {
     use crate::reply::{Reply, Response};
     use crate::route;
 
+    // Lets `Structured` be wrapped like logging closures.
+    pub trait LogFunc {
+        fn log(&self, info: Info<'_>);
+    }
+
+    impl<F: Fn(Info<'_>)> LogFunc for F {
+        fn log(&self, info: Info<'_>) {
+            self(info)
+        }
+    }
+
     #[allow(missing_debug_implementations)]
     pub struct Logged(pub(super) Response);
 
@@ -215,7 +613,7 @@ mod internal {
 
     impl<FN, F> FilterBase for WithLog<FN, F>
     where
-        FN: Fn(Info<'_>) + Clone + Send,
+        FN: LogFunc + Clone + Send,
         F: Filter + Clone + Send,
         F::Extract: Reply,
         F::Error: IsReject,
@@ -245,7 +643,7 @@ mod internal {
 
     impl<FN, F> Future for WithLogFuture<FN, F>
     where
-        FN: Fn(Info<'_>),
+        FN: LogFunc,
         F: TryFuture,
         F::Ok: Reply,
         F::Error: IsReject,
@@ -258,23 +656,38 @@ mod internal {
                 Ok(reply) => {
                     let resp = reply.into_response();
                     let status = resp.status();
-                    (Poll::Ready(Ok((Logged(resp),))), status)
+                    (Ok((Logged(resp),)), status)
                 }
                 Err(reject) => {
                     let status = reject.status();
-                    (Poll::Ready(Err(reject)), status)
+                    (Err(reject), status)
                 }
             };
 
             route::with(|route| {
-                (self.log.func)(Info {
+                self.log.func.log(Info {
                     route,
                     start: self.started,
                     status,
+                    response: result.as_ref().ok().map(|(logged,)| &logged.0),
                 });
             });
 
-            result
+            Poll::Ready(result)
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::ClfTime;
+    use std::time::{Duration, UNIX_EPOCH};
+
+    #[test]
+    fn clf_time() {
+        let at = |secs| ClfTime(UNIX_EPOCH + Duration::from_secs(secs)).to_string();
+        assert_eq!(at(0), "01/Jan/1970:00:00:00 +0000");
+        assert_eq!(at(971_185_536), "10/Oct/2000:13:45:36 +0000");
+        assert_eq!(at(951_782_400), "29/Feb/2000:00:00:00 +0000");
+    }
+}
@@ -6,10 +6,15 @@
 //!
 //! There is also [`nextshell::method()`](method), which never rejects
 //! a request, and just extracts the method to be used in your filter chains.
+//!
+//! Wrapping routes with [`auto_head()`] also answers `HEAD` requests with
+//! their `GET` routes, and [`auto_options()`] answers `OPTIONS` requests with
+//! the methods allowed on their path.
 use futures_util::future;
 use http::Method;
 
-use crate::filter::{filter_fn, filter_fn_one, Filter, One};
+use self::internal::{WithAutoHead, WithAutoOptions};
+use crate::filter::{filter_fn, filter_fn_one, Filter, One, WrapSealed};
 use crate::reject::Rejection;
 use std::convert::Infallible;
 
@@ -122,6 +127,113 @@ pub fn method() -> impl Filter<Extract = One<Method>, Error = Infallible> + Copy
     filter_fn_one(|route| future::ok::<_, Infallible>(route.method().clone()))
 }
 
+/// Create a wrapping filter answering `HEAD` requests with the `GET` routes
+/// it wraps.
+///
+/// A `HEAD` request is first given to the wrapped filter as is, so explicit
+/// [`head()`] routes still win. If it's rejected, it's tried again as a
+/// `GET`, and the reply's body is dropped, keeping its headers and its
+/// `content-length`.
+///
+/// # Example
+///
+/// ```
+/// use nextshell::Filter;
+///
+/// // `HEAD /health` answers like `GET /health`, without the body.
+/// let health = nextshell::get()
+///     .and(nextshell::path("health"))
+///     .map(|| "ok")
+///     .with(nextshell::auto_head());
+/// ```
+pub fn auto_head() -> AutoHead {
+    AutoHead { _p: () }
+}
+
+/// Decorates a [`Filter`] to answer `HEAD` requests, see [`auto_head()`].
+#[derive(Clone, Copy, Debug)]
+pub struct AutoHead {
+    _p: (),
+}
+
+impl<F> WrapSealed<F> for AutoHead
+where
+    F: Filter<Error = Rejection> + Clone + Send,
+    F::Extract: crate::reply::Reply,
+{
+    type Wrapped = WithAutoHead<F>;
+
+    fn wrap(&self, filter: F) -> Self::Wrapped {
+        WithAutoHead { filter }
+    }
+}
+
+/// Create a wrapping filter answering bare `OPTIONS` requests with a
+/// `204 No Content` and an `Allow` header, instead of a `405`.
+///
+/// CORS preflights, carrying both `origin` and
+/// `access-control-request-method` headers, are left to the wrapped filter,
+/// and so are requests matched by an explicit [`options()`] route.
+///
+/// The `Allow` header lists the methods of the routes that matched the whole
+/// path, so the method filters must come after the path ones to be seen. If
+/// none did, the rejection is returned unchanged. `HEAD` is listed for `GET`
+/// routes wrapped by [`auto_head()`], inside or outside of `auto_options`.
+///
+/// # Example
+///
+/// ```
+/// use nextshell::Filter;
+///
+/// // `OPTIONS /items` answers with `Allow: GET, HEAD, POST, OPTIONS`.
+/// let list = nextshell::path!("items").and(nextshell::get()).map(|| "[]");
+/// let create = nextshell::path!("items").and(nextshell::post()).map(|| "created");
+/// let routes = list
+///     .or(create)
+///     .with(nextshell::auto_head())
+///     .with(nextshell::auto_options());
+/// ```
+pub fn auto_options() -> AutoOptions {
+    AutoOptions { _p: () }
+}
+
+/// Decorates a [`Filter`] to answer `OPTIONS` requests, see [`auto_options()`].
+#[derive(Clone, Copy, Debug)]
+pub struct AutoOptions {
+    _p: (),
+}
+
+impl<F> WrapSealed<F> for AutoOptions
+where
+    F: Filter<Error = Rejection> + Clone + Send,
+    F::Extract: crate::reply::Reply,
+{
+    type Wrapped = WithAutoOptions<F>;
+
+    fn wrap(&self, filter: F) -> Self::Wrapped {
+        WithAutoOptions { filter }
+    }
+}
+
+// The methods of the routes matching the whole path of an `OPTIONS` request,
+// recorded by `method_is` while `auto_options` looks for them.
+struct AllowedMethods(Vec<Method>);
+
+impl AllowedMethods {
+    // `HEAD` is answered like `GET`, right after it in the list.
+    fn allow_head(&mut self) {
+        if let Some(get) = self.0.iter().position(|m| m == Method::GET) {
+            if !self.0.contains(&Method::HEAD) {
+                self.0.insert(get + 1, Method::HEAD);
+            }
+        }
+    }
+}
+
+// Marks an `OPTIONS` request passing through `auto_head` before reaching
+// `auto_options`, so all the `GET` routes it finds also answer `HEAD`.
+struct HeadAllowed;
+
 // NOTE: This takes a static function instead of `&'static Method` directly
 // so that the `impl Filter` can be zero-sized. Moving it around should be
 // cheaper than holding a single static pointer (which would make it 1 word).
@@ -135,11 +247,274 @@ where
         if route.method() == method {
             future::ok(())
         } else {
+            let matched = route.path().is_empty();
+            if let Some(allowed) = route.extensions_mut().get_mut::<AllowedMethods>() {
+                if matched && !allowed.0.contains(method) {
+                    allowed.0.push(method.clone());
+                }
+            }
             future::err(crate::reject::method_not_allowed())
         }
     })
 }
 
+mod internal {
+    use std::future::Future;
+    use std::pin::Pin;
+    use std::task::{Context, Poll};
+
+    use futures_util::{ready, TryFuture};
+    use http::header::{HeaderValue, ACCESS_CONTROL_REQUEST_METHOD, ALLOW, CONTENT_LENGTH, ORIGIN};
+    use http::{Method, StatusCode};
+    use hyper::body::HttpBody;
+    use hyper::Body;
+    use pin_project::pin_project;
+
+    use super::{AllowedMethods, HeadAllowed};
+    use crate::filter::{Filter, FilterBase, Internal};
+    use crate::reject::{CombineRejection, Rejection};
+    use crate::reply::{Reply, Response};
+    use crate::route;
+
+    #[allow(missing_debug_implementations)]
+    pub struct Headed(Response);
+
+    impl Reply for Headed {
+        #[inline]
+        fn into_response(self) -> Response {
+            self.0
+        }
+    }
+
+    #[allow(missing_debug_implementations)]
+    #[derive(Clone, Copy)]
+    pub struct WithAutoHead<F> {
+        pub(super) filter: F,
+    }
+
+    impl<F> FilterBase for WithAutoHead<F>
+    where
+        F: Filter<Error = Rejection> + Clone + Send,
+        F::Extract: Reply,
+    {
+        type Extract = (Headed,);
+        type Error = Rejection;
+        type Future = WithAutoHeadFuture<F>;
+
+        fn filter(&self, _: Internal) -> Self::Future {
+            let (is_head, idx, probed_get) = route::with(|route| {
+                let probed_get = if route.method() == Method::OPTIONS {
+                    match route.extensions().get::<AllowedMethods>() {
+                        Some(allowed) => Some(allowed.0.contains(&Method::GET)),
+                        None => {
+                            route.extensions_mut().insert(HeadAllowed);
+                            None
+                        }
+                    }
+                } else {
+                    None
+                };
+                (
+                    route.method() == Method::HEAD,
+                    route.matched_path_index(),
+                    probed_get,
+                )
+            });
+            WithAutoHeadFuture {
+                state: State::First(self.filter.filter(Internal)),
+                retry: if is_head {
+                    Some((self.filter.clone(), idx))
+                } else {
+                    None
+                },
+                probed_get,
+            }
+        }
+    }
+
+    #[allow(missing_debug_implementations)]
+    #[pin_project]
+    pub struct WithAutoHeadFuture<F: Filter> {
+        #[pin]
+        state: State<F::Future>,
+        // The filter and path index to retry a `HEAD` request as a `GET`.
+        retry: Option<(F, usize)>,
+        // Whether `auto_options` had already found a `GET` route, when it
+        // probes the wrapped filter with an `OPTIONS` request.
+        probed_get: Option<bool>,
+    }
+
+    #[pin_project(project = StateProj)]
+    enum State<T> {
+        First(#[pin] T),
+        Second(#[pin] T, Option<Rejection>),
+    }
+
+    impl<F> Future for WithAutoHeadFuture<F>
+    where
+        F: Filter<Error = Rejection>,
+        F::Extract: Reply,
+    {
+        type Output = Result<(Headed,), Rejection>;
+
+        fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+            let mut pin = self.project();
+            loop {
+                let (result, first_err) = match pin.state.as_mut().project() {
+                    StateProj::First(first) => match ready!(first.try_poll(cx)) {
+                        Ok(ex) => return Poll::Ready(Ok((Headed(ex.into_response()),))),
+                        Err(err) if *pin.probed_get == Some(false) => {
+                            // The `GET` routes found in the wrapped filter
+                            // answer `HEAD` too.
+                            route::with(|route| {
+                                if let Some(allowed) =
+                                    route.extensions_mut().get_mut::<AllowedMethods>()
+                                {
+                                    allowed.allow_head();
+                                }
+                            });
+                            return Poll::Ready(Err(err));
+                        }
+                        Err(err) => match pin.retry.take() {
+                            Some((filter, idx)) => {
+                                route::with(|route| {
+                                    route.reset_matched_path_index(idx);
+                                    route.set_method(Method::GET);
+                                });
+                                let second = filter.filter(Internal);
+                                pin.state.set(State::Second(second, Some(err)));
+                                continue;
+                            }
+                            None => return Poll::Ready(Err(err)),
+                        },
+                    },
+                    StateProj::Second(second, first_err) => {
+                        (ready!(second.try_poll(cx)), first_err.take())
+                    }
+                };
+
+                // Outer filters, like logs, still see the `HEAD`.
+                route::with(|route| route.set_method(Method::HEAD));
+                return Poll::Ready(match result {
+                    Ok(ex) => Ok((Headed(without_body(ex.into_response())),)),
+                    Err(err) => Err(first_err.expect("first rejection").combine(err)),
+                });
+            }
+        }
+    }
+
+    fn without_body(res: Response) -> Response {
+        let (mut parts, body) = res.into_parts();
+        if !parts.headers.contains_key(CONTENT_LENGTH) {
+            if let Some(len) = body.size_hint().exact() {
+                parts.headers.insert(CONTENT_LENGTH, HeaderValue::from(len));
+            }
+        }
+        Response::from_parts(parts, Body::empty())
+    }
+
+    #[allow(missing_debug_implementations)]
+    pub struct Optioned(Response);
+
+    impl Reply for Optioned {
+        #[inline]
+        fn into_response(self) -> Response {
+            self.0
+        }
+    }
+
+    #[allow(missing_debug_implementations)]
+    #[derive(Clone, Copy)]
+    pub struct WithAutoOptions<F> {
+        pub(super) filter: F,
+    }
+
+    impl<F> FilterBase for WithAutoOptions<F>
+    where
+        F: Filter<Error = Rejection> + Clone + Send,
+        F::Extract: Reply,
+    {
+        type Extract = (Optioned,);
+        type Error = Rejection;
+        type Future = WithAutoOptionsFuture<F::Future>;
+
+        fn filter(&self, _: Internal) -> Self::Future {
+            let probing = route::with(|route| {
+                let preflight = route.headers().contains_key(ORIGIN)
+                    && route.headers().contains_key(ACCESS_CONTROL_REQUEST_METHOD);
+                let probing = route.method() == Method::OPTIONS && !preflight;
+                if probing {
+                    route.extensions_mut().insert(AllowedMethods(Vec::new()));
+                }
+                probing
+            });
+            WithAutoOptionsFuture {
+                future: self.filter.filter(Internal),
+                probing,
+            }
+        }
+    }
+
+    #[allow(missing_debug_implementations)]
+    #[pin_project]
+    pub struct WithAutoOptionsFuture<F> {
+        #[pin]
+        future: F,
+        probing: bool,
+    }
+
+    impl<F> Future for WithAutoOptionsFuture<F>
+    where
+        F: TryFuture<Error = Rejection>,
+        F::Ok: Reply,
+    {
+        type Output = Result<(Optioned,), Rejection>;
+
+        fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+            let pin = self.project();
+            let result = ready!(pin.future.try_poll(cx));
+            let allowed = if *pin.probing {
+                route::with(|route| {
+                    let head = route.extensions_mut().remove::<HeadAllowed>().is_some();
+                    let mut allowed = route.extensions_mut().remove::<AllowedMethods>();
+                    if let (Some(allowed), true) = (&mut allowed, head) {
+                        allowed.allow_head();
+                    }
+                    allowed
+                })
+            } else {
+                None
+            };
+            Poll::Ready(match (result, allowed) {
+                (Ok(ex), _) => Ok((Optioned(ex.into_response()),)),
+                (Err(_), Some(AllowedMethods(methods))) if !methods.is_empty() => {
+                    Ok((Optioned(allow(methods)),))
+                }
+                (Err(err), _) => Err(err),
+            })
+        }
+    }
+
+    fn allow(mut methods: Vec<Method>) -> Response {
+        if !methods.contains(&Method::OPTIONS) {
+            methods.push(Method::OPTIONS);
+        }
+        let allow = methods
+            .iter()
+            .map(Method::as_str)
+            .collect::<Vec<_>>()
+            .join(", ");
+
+        let mut res = Response::new(Body::empty());
+        *res.status_mut() = StatusCode::NO_CONTENT;
+        res.headers_mut().insert(
+            ALLOW,
+            HeaderValue::from_str(&allow).expect("methods are valid header values"),
+        );
+        res
+    }
+}
+
 #[cfg(test)]
 mod tests {
     #[test]
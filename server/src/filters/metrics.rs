@@ -0,0 +1,462 @@
+//! Prometheus Metrics Filters
+//!
+//! [`metrics()`](crate::metrics()) creates a [`Metrics`] registry that, used
+//! as a wrapping filter, records for every request:
+//!
+//! - `nextshell_http_requests_total`, a counter,
+//! - `nextshell_http_requests_in_flight`, a gauge,
+//! - `nextshell_http_request_duration_seconds`, a histogram,
+//!
+//! labeled by method, route template and status. Routes are labeled with
+//! the template given to [`route`], and `unmatched` otherwise, and methods
+//! other than the standard ones with `other`, so that arbitrary requests
+//! can't blow up the number of series.
+//!
+//! Filters marked with [`Filter::instrument`] are also timed, in the
+//! `nextshell_filter_duration_seconds` histogram, labeled by filter name and
+//! outcome.
+//!
+//! # Example
+//!
+//! ```
+//! use nextshell::Filter;
+//!
+//! let metrics = nextshell::metrics();
+//!
+//! let hello = nextshell::path!("hello" / String)
+//!     .and(nextshell::metrics::route("/hello/{name}"))
+//!     .map(|name| format!("Hello, {}!", name));
+//!
+//! let routes = metrics
+//!     .endpoint()
+//!     .or(hello)
+//!     .with(metrics);
+//! ```
+
+use std::collections::btree_map::Entry;
+use std::collections::BTreeMap;
+use std::convert::Infallible;
+use std::fmt::{self, Write};
+use std::sync::atomic::{AtomicI64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use futures_util::future;
+use http::header::{HeaderValue, CONTENT_TYPE};
+
+use crate::filter::{filter_fn, Filter, Timings, WrapSealed};
+use crate::reject::{IsReject, Rejection};
+use crate::reply::{Reply, Response};
+
+use self::internal::WithMetrics;
+
+const DEFAULT_BUCKETS: &[f64] = &[
+    0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0,
+];
+
+/// Create a new [`Metrics`] registry, with Prometheus' default latency
+/// buckets.
+///
+/// See the [module docs](crate::metrics) for an example.
+pub fn metrics() -> Metrics {
+    Metrics::with_buckets(DEFAULT_BUCKETS.to_vec())
+}
+
+/// Label the requests matched by the filter it's combined with as
+/// `template` in the metrics.
+///
+/// When several filters set a template, the last one to run wins.
+pub fn route(template: &'static str) -> impl Filter<Extract = (), Error = Infallible> + Copy {
+    filter_fn(move |route| {
+        route.extensions_mut().insert(RouteTemplate(template));
+        future::ok(())
+    })
+}
+
+/// A registry of HTTP metrics, also a [`Filter`] wrapper recording them.
+///
+/// Cloning a `Metrics` shares the same registry.
+#[derive(Clone, Debug)]
+pub struct Metrics {
+    inner: Arc<Registry>,
+}
+
+#[derive(Debug)]
+struct Registry {
+    buckets: Vec<f64>,
+    in_flight: AtomicI64,
+    series: Mutex<BTreeMap<Labels, Series>>,
+    filters: Mutex<BTreeMap<FilterLabels, Series>>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+struct Labels {
+    method: &'static str,
+    route: &'static str,
+    status: u16,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+struct FilterLabels {
+    filter: &'static str,
+    outcome: &'static str,
+}
+
+#[derive(Debug)]
+struct Series {
+    count: u64,
+    sum: f64,
+    // Not cumulative, one per bucket and a last one for `+Inf`.
+    buckets: Vec<u64>,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct RouteTemplate(pub(crate) &'static str);
+
+impl Metrics {
+    fn with_buckets(buckets: Vec<f64>) -> Self {
+        Metrics {
+            inner: Arc::new(Registry {
+                buckets,
+                in_flight: AtomicI64::new(0),
+                series: Mutex::new(BTreeMap::new()),
+                filters: Mutex::new(BTreeMap::new()),
+            }),
+        }
+    }
+
+    /// Use these upper bounds, in seconds, for the latency histogram
+    /// buckets instead of the default ones.
+    ///
+    /// This starts a new, empty registry.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `buckets` is empty, or isn't strictly increasing.
+    pub fn buckets(self, buckets: impl Into<Vec<f64>>) -> Self {
+        let buckets = buckets.into();
+        assert!(!buckets.is_empty(), "metrics buckets can't be empty");
+        assert!(
+            buckets.windows(2).all(|w| w[0] < w[1]),
+            "metrics buckets must be strictly increasing"
+        );
+        Metrics::with_buckets(buckets)
+    }
+
+    /// A filter answering `GET /metrics` with the recorded metrics, in the
+    /// Prometheus text exposition format.
+    pub fn endpoint(&self) -> impl Filter<Extract = (impl Reply,), Error = Rejection> + Clone {
+        let metrics = self.clone();
+        crate::path("metrics")
+            .and(crate::path::end())
+            .and(crate::get())
+            .map(move || {
+                let mut res = Response::new(metrics.render().into());
+                res.headers_mut().insert(
+                    CONTENT_TYPE,
+                    HeaderValue::from_static("text/plain; version=0.0.4"),
+                );
+                res
+            })
+    }
+
+    /// Render the recorded metrics in the Prometheus text exposition format.
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+        self.write(&mut out).expect("writing to a String");
+        out
+    }
+
+    fn write(&self, out: &mut String) -> fmt::Result {
+        let series = self.inner.series.lock().unwrap();
+
+        out.push_str("# HELP nextshell_http_requests_total Total number of HTTP requests.\n");
+        out.push_str("# TYPE nextshell_http_requests_total counter\n");
+        for (labels, s) in series.iter() {
+            writeln!(
+                out,
+                "nextshell_http_requests_total{{{}}} {}",
+                labels, s.count
+            )?;
+        }
+
+        out.push_str(
+            "# HELP nextshell_http_requests_in_flight Number of HTTP requests being handled.\n",
+        );
+        out.push_str("# TYPE nextshell_http_requests_in_flight gauge\n");
+        writeln!(
+            out,
+            "nextshell_http_requests_in_flight {}",
+            self.inner.in_flight.load(Ordering::SeqCst)
+        )?;
+
+        out.push_str(
+            "# HELP nextshell_http_request_duration_seconds HTTP request latency in seconds.\n",
+        );
+        out.push_str("# TYPE nextshell_http_request_duration_seconds histogram\n");
+        for (labels, s) in series.iter() {
+            self.write_histogram(out, "nextshell_http_request_duration_seconds", labels, s)?;
+        }
+        drop(series);
+
+        let filters = self.inner.filters.lock().unwrap();
+        if !filters.is_empty() {
+            out.push_str(
+                "# HELP nextshell_filter_duration_seconds Instrumented filter latency in seconds.\n",
+            );
+            out.push_str("# TYPE nextshell_filter_duration_seconds histogram\n");
+            for (labels, s) in filters.iter() {
+                self.write_histogram(out, "nextshell_filter_duration_seconds", labels, s)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn write_histogram(
+        &self,
+        out: &mut String,
+        name: &str,
+        labels: &dyn fmt::Display,
+        s: &Series,
+    ) -> fmt::Result {
+        let mut cumulative = 0;
+        for (le, count) in self.inner.buckets.iter().zip(&s.buckets) {
+            cumulative += count;
+            writeln!(
+                out,
+                "{}_bucket{{{},le=\"{}\"}} {}",
+                name, labels, le, cumulative
+            )?;
+        }
+        writeln!(out, "{}_bucket{{{},le=\"+Inf\"}} {}", name, labels, s.count)?;
+        writeln!(out, "{}_sum{{{}}} {}", name, labels, s.sum)?;
+        writeln!(out, "{}_count{{{}}} {}", name, labels, s.count)
+    }
+
+    fn observe(&self, labels: Labels, elapsed: Duration) {
+        let mut series = self.inner.series.lock().unwrap();
+        self.new_series(series.entry(labels))
+            .observe(&self.inner.buckets, elapsed);
+    }
+
+    fn observe_filters(&self, timings: Timings) {
+        let mut filters = self.inner.filters.lock().unwrap();
+        for timing in timings.0 {
+            let labels = FilterLabels {
+                filter: timing.name,
+                outcome: timing.outcome,
+            };
+            self.new_series(filters.entry(labels))
+                .observe(&self.inner.buckets, timing.elapsed);
+        }
+    }
+
+    fn new_series<'a, L: Ord>(&self, entry: Entry<'a, L, Series>) -> &'a mut Series {
+        let len = self.inner.buckets.len();
+        entry.or_insert_with(|| Series {
+            count: 0,
+            sum: 0.0,
+            buckets: vec![0; len + 1],
+        })
+    }
+}
+
+impl Series {
+    fn observe(&mut self, buckets: &[f64], elapsed: Duration) {
+        let secs = elapsed.as_secs_f64();
+        self.count += 1;
+        self.sum += secs;
+        let idx = buckets
+            .iter()
+            .position(|le| secs <= *le)
+            .unwrap_or(buckets.len());
+        self.buckets[idx] += 1;
+    }
+}
+
+impl fmt::Display for Labels {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "method=\"{}\",route=\"{}\",status=\"{}\"",
+            Escaped(self.method),
+            Escaped(self.route),
+            self.status
+        )
+    }
+}
+
+impl fmt::Display for FilterLabels {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "filter=\"{}\",outcome=\"{}\"",
+            Escaped(self.filter),
+            self.outcome
+        )
+    }
+}
+
+/// The label of a request method, bucketing extension methods together.
+fn method_label(method: &http::Method) -> &'static str {
+    match *method {
+        http::Method::GET => "GET",
+        http::Method::HEAD => "HEAD",
+        http::Method::POST => "POST",
+        http::Method::PUT => "PUT",
+        http::Method::DELETE => "DELETE",
+        http::Method::CONNECT => "CONNECT",
+        http::Method::OPTIONS => "OPTIONS",
+        http::Method::TRACE => "TRACE",
+        http::Method::PATCH => "PATCH",
+        _ => "other",
+    }
+}
+
+struct Escaped<'a>(&'a str);
+
+impl fmt::Display for Escaped<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for c in self.0.chars() {
+            match c {
+                '\\' => f.write_str("\\\\")?,
+                '"' => f.write_str("\\\"")?,
+                '\n' => f.write_str("\\n")?,
+                c => f.write_char(c)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<F> WrapSealed<F> for Metrics
+where
+    F: Filter + Clone + Send,
+    F::Extract: Reply,
+    F::Error: IsReject,
+{
+    type Wrapped = WithMetrics<F>;
+
+    fn wrap(&self, filter: F) -> Self::Wrapped {
+        WithMetrics {
+            filter,
+            metrics: self.clone(),
+        }
+    }
+}
+
+mod internal {
+    use std::future::Future;
+    use std::pin::Pin;
+    use std::sync::atomic::Ordering;
+    use std::task::{Context, Poll};
+    use std::time::Instant;
+
+    use futures_util::{ready, TryFuture};
+    use pin_project::pin_project;
+
+    use super::{method_label, Labels, Metrics, RouteTemplate};
+    use crate::filter::Timings;
+    use crate::filter::{Filter, FilterBase, Internal};
+    use crate::reject::IsReject;
+    use crate::reply::{Reply, Response};
+    use crate::route;
+
+    #[allow(missing_debug_implementations)]
+    pub struct Metered(pub(super) Response);
+
+    impl Reply for Metered {
+        #[inline]
+        fn into_response(self) -> Response {
+            self.0
+        }
+    }
+
+    #[allow(missing_debug_implementations)]
+    #[derive(Clone)]
+    pub struct WithMetrics<F> {
+        pub(super) filter: F,
+        pub(super) metrics: Metrics,
+    }
+
+    impl<F> FilterBase for WithMetrics<F>
+    where
+        F: Filter + Clone + Send,
+        F::Extract: Reply,
+        F::Error: IsReject,
+    {
+        type Extract = (Metered,);
+        type Error = F::Error;
+        type Future = WithMetricsFuture<F::Future>;
+
+        fn filter(&self, _: Internal) -> Self::Future {
+            self.metrics.inner.in_flight.fetch_add(1, Ordering::SeqCst);
+            WithMetricsFuture {
+                in_flight: InFlight(self.metrics.clone()),
+                future: self.filter.filter(Internal),
+                started: tokio::time::Instant::now().into_std(),
+            }
+        }
+    }
+
+    // Decrements the gauge even if the request is dropped midway.
+    struct InFlight(Metrics);
+
+    impl Drop for InFlight {
+        fn drop(&mut self) {
+            self.0.inner.in_flight.fetch_sub(1, Ordering::SeqCst);
+        }
+    }
+
+    #[allow(missing_debug_implementations)]
+    #[pin_project]
+    pub struct WithMetricsFuture<F> {
+        in_flight: InFlight,
+        #[pin]
+        future: F,
+        started: Instant,
+    }
+
+    impl<F> Future for WithMetricsFuture<F>
+    where
+        F: TryFuture,
+        F::Ok: Reply,
+        F::Error: IsReject,
+    {
+        type Output = Result<(Metered,), F::Error>;
+
+        fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+            let pin = self.project();
+            let (result, status) = match ready!(pin.future.try_poll(cx)) {
+                Ok(reply) => {
+                    let resp = reply.into_response();
+                    let status = resp.status();
+                    (Ok((Metered(resp),)), status)
+                }
+                Err(reject) => {
+                    let status = reject.status();
+                    (Err(reject), status)
+                }
+            };
+
+            let (labels, timings) = route::with(|route| {
+                let labels = Labels {
+                    method: method_label(route.method()),
+                    route: route
+                        .extensions()
+                        .get::<RouteTemplate>()
+                        .map_or("unmatched", |template| template.0),
+                    status: status.as_u16(),
+                };
+                (labels, route.extensions_mut().remove::<Timings>())
+            });
+            let elapsed = tokio::time::Instant::now().into_std() - *pin.started;
+            pin.in_flight.0.observe(labels, elapsed);
+            if let Some(timings) = timings {
+                pin.in_flight.0.observe_filters(timings);
+            }
+
+            Poll::Ready(result)
+        }
+    }
+}
@@ -6,22 +6,30 @@
 pub mod addr;
 pub mod any;
 pub mod body;
+pub mod cache;
 #[cfg(any(feature = "compression-brotli", feature = "compression-gzip"))]
 pub mod compression;
 pub mod cookie;
 pub mod cors;
+pub mod etag;
 pub mod ext;
 pub mod fs;
 pub mod header;
+pub mod health;
 pub mod host;
 pub mod log;
 pub mod method;
+pub mod metrics;
 #[cfg(feature = "multipart")]
 pub mod multipart;
 pub mod path;
+pub mod precondition;
+pub mod proxy;
 pub mod query;
+pub mod range;
 pub mod reply;
 pub mod sse;
+pub mod state;
 pub mod trace;
 #[cfg(feature = "websocket")]
 pub mod ws;
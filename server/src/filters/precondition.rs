@@ -0,0 +1,170 @@
+//! Precondition Filters
+//!
+//! Filters extracting the `if-match` and `if-unmodified-since` request
+//! headers, used for optimistic concurrency control: a client sends the
+//! `etag` (or `last-modified` date) of the version it based its changes on,
+//! and the server refuses to apply them with `412 Precondition Failed` if
+//! the resource changed since.
+//!
+//! # Example
+//!
+//! ```
+//! use std::time::SystemTime;
+//! use nextshell::Filter;
+//! use nextshell::precondition::Preconditions;
+//!
+//! # fn current_version(id: u32) -> (String, SystemTime) {
+//! #     ("\"v1\"".into(), SystemTime::UNIX_EPOCH)
+//! # }
+//! let update = nextshell::put()
+//!     .and(nextshell::path!("todos" / u32))
+//!     .and(nextshell::precondition())
+//!     .and_then(|id: u32, pre: Preconditions| async move {
+//!         let (etag, modified) = current_version(id);
+//!         pre.check(Some(&etag), Some(modified))?;
+//!         // The client has seen the latest version, apply the update...
+//!         Ok::<_, nextshell::Rejection>(nextshell::reply())
+//!     });
+//! ```
+
+use std::time::SystemTime;
+
+use futures_util::future;
+use headers::{ETag, HeaderMapExt};
+
+use crate::filter::{filter_fn_one, Filter, One};
+use crate::reject::{self, Rejection};
+
+/// Create a `Filter` extracting both the `if-match` and `if-unmodified-since`
+/// headers of the request.
+///
+/// Rejects the request if either header is malformed.
+///
+/// See the [module docs](crate::precondition) for an example.
+pub fn precondition() -> impl Filter<Extract = One<Preconditions>, Error = Rejection> + Copy {
+    if_match()
+        .and(if_unmodified_since())
+        .map(|if_match, if_unmodified_since| Preconditions {
+            if_match,
+            if_unmodified_since,
+        })
+}
+
+/// Create a `Filter` extracting the `if-match` header, if any.
+///
+/// Rejects the request if the header is malformed.
+pub fn if_match() -> impl Filter<Extract = One<Option<IfMatch>>, Error = Rejection> + Copy {
+    filter_fn_one(|route| {
+        let if_match = route
+            .headers()
+            .typed_try_get::<headers::IfMatch>()
+            .map(|if_match| if_match.map(IfMatch))
+            .map_err(|_| reject::invalid_header("if-match"));
+        future::ready(if_match)
+    })
+}
+
+/// Create a `Filter` extracting the `if-unmodified-since` header, if any.
+///
+/// Rejects the request if the header is malformed.
+pub fn if_unmodified_since(
+) -> impl Filter<Extract = One<Option<SystemTime>>, Error = Rejection> + Copy {
+    filter_fn_one(|route| {
+        let since = route
+            .headers()
+            .typed_try_get::<headers::IfUnmodifiedSince>()
+            .map(|since| since.map(SystemTime::from))
+            .map_err(|_| reject::invalid_header("if-unmodified-since"));
+        future::ready(since)
+    })
+}
+
+/// A parsed `if-match` header.
+#[derive(Clone, Debug, PartialEq)]
+pub struct IfMatch(headers::IfMatch);
+
+impl IfMatch {
+    /// Whether this is `if-match: *`, matching any current representation.
+    pub fn is_any(&self) -> bool {
+        self.0 == headers::IfMatch::any()
+    }
+
+    /// Whether `etag` is one of the listed entity tags.
+    ///
+    /// `etag` must be a quoted entity tag, such as `"v3"`. Matching uses the
+    /// strong comparison, so weak tags (`W/"v3"`) never match.
+    pub fn matches(&self, etag: &str) -> bool {
+        match etag.parse::<ETag>() {
+            Ok(etag) => self.0.precondition_passes(&etag),
+            Err(_) => {
+                tracing::debug!("if-match compared against an invalid etag: {:?}", etag);
+                false
+            }
+        }
+    }
+}
+
+/// The `if-match` and `if-unmodified-since` preconditions of a request.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Preconditions {
+    if_match: Option<IfMatch>,
+    if_unmodified_since: Option<SystemTime>,
+}
+
+impl Preconditions {
+    /// The `if-match` header, if any.
+    pub fn if_match(&self) -> Option<&IfMatch> {
+        self.if_match.as_ref()
+    }
+
+    /// The `if-unmodified-since` date, if any.
+    pub fn if_unmodified_since(&self) -> Option<SystemTime> {
+        self.if_unmodified_since
+    }
+
+    /// Whether the request has no precondition at all.
+    pub fn is_empty(&self) -> bool {
+        self.if_match.is_none() && self.if_unmodified_since.is_none()
+    }
+
+    /// Evaluate the preconditions against the current `etag` and
+    /// `last_modified` date of the resource.
+    ///
+    /// As specified by RFC 7232, `if-unmodified-since` is ignored when
+    /// `if-match` is present. Pass `None` for both if the resource doesn't
+    /// exist, which fails any precondition, even `if-match: *`.
+    ///
+    /// Returns a rejection answered with `412 Precondition Failed` if the
+    /// preconditions don't hold.
+    pub fn check(
+        &self,
+        etag: Option<&str>,
+        last_modified: Option<SystemTime>,
+    ) -> Result<(), Rejection> {
+        let passes = if let Some(ref if_match) = self.if_match {
+            match etag {
+                Some(etag) => if_match.matches(etag),
+                None => if_match.is_any() && last_modified.is_some(),
+            }
+        } else if let Some(since) = self.if_unmodified_since {
+            last_modified
+                .map(|time| headers::IfUnmodifiedSince::from(since).precondition_passes(time))
+                .unwrap_or(false)
+        } else {
+            true
+        };
+
+        tracing::trace!(
+            "preconditions {:?} vs etag = {:?}, last-modified = {:?}: {}",
+            self,
+            etag,
+            last_modified,
+            passes
+        );
+        if passes {
+            Ok(())
+        } else {
+            Err(reject::precondition_failed())
+        }
+    }
+}
@@ -0,0 +1,521 @@
+//! Proxy Filters
+//!
+//! Filters forwarding requests to an upstream server, and streaming its
+//! responses back, either a single one with [`to()`], or one of a
+//! [`ProxyPool`] made with [`pool()`].
+
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicU32, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use http::header::{self, HeaderMap, HeaderName, HeaderValue};
+use http::uri::{Authority, Scheme, Uri};
+use http::{Request, StatusCode};
+use hyper::client::HttpConnector;
+use hyper::{Body, Client};
+
+use crate::filter::{filter_fn, Filter, One};
+use crate::reject::Rejection;
+use crate::reply::Response;
+use crate::route::Route;
+
+/// Creates a `Filter` forwarding requests to the `upstream` server.
+///
+/// The request method, headers and body are forwarded as they are, except
+/// for the hop-by-hop headers, and for `host`, which is set to the
+/// upstream's, the original one being sent as `x-forwarded-host`. The remote
+/// address is appended to `x-forwarded-for`. The path not matched yet by
+/// other filters is appended to the path of `upstream`, along with the query.
+///
+/// Connections to the upstream server are pooled, and both bodies are
+/// streamed. If the upstream server can't be reached, the reply is a
+/// `502 Bad Gateway`.
+///
+/// Protocol upgrades, like websockets, aren't forwarded.
+///
+/// # Panics
+///
+/// Panics if `upstream` isn't a valid `http://` URI, or has a query.
+///
+/// # Example
+///
+/// ```
+/// use nextshell::Filter;
+///
+/// // `GET /api/users?page=2` is forwarded as `GET /v1/users?page=2`.
+/// let api = nextshell::path("api").and(nextshell::proxy::to("http://backend:8080/v1"));
+/// ```
+pub fn to(upstream: &str) -> impl Filter<Extract = One<Response>, Error = Rejection> + Clone {
+    let upstream = Upstream::new(upstream);
+    let client = Client::new();
+    filter_fn(move |route| {
+        let req = upstream.request(route);
+        let client = client.clone();
+        async move {
+            let res = forward(&client, req).await.unwrap_or_else(|err| {
+                tracing::warn!("proxy upstream error: {}", err);
+                bad_gateway()
+            });
+            Ok::<_, Rejection>((res,))
+        }
+    })
+}
+
+/// Creates an empty [`ProxyPool`], balancing requests between upstream
+/// servers.
+///
+/// # Example
+///
+/// ```
+/// use std::time::Duration;
+/// use nextshell::Filter;
+/// use nextshell::proxy::{self, Strategy};
+///
+/// let pool = proxy::pool()
+///     .upstream("http://10.0.0.1:8080")
+///     .upstream(proxy::backend("http://10.0.0.2:8080").weight(3))
+///     .strategy(Strategy::Weighted)
+///     .timeout(Duration::from_secs(10));
+///
+/// let api = nextshell::path("api").and(pool.forward());
+/// ```
+pub fn pool() -> ProxyPool {
+    ProxyPool {
+        backends: Vec::new(),
+        strategy: Strategy::RoundRobin,
+        timeout: None,
+        max_failures: 3,
+        fail_timeout: Duration::from_secs(10),
+    }
+}
+
+/// Creates a [`Backend`] of a [`ProxyPool`], forwarding to `upstream`.
+///
+/// # Panics
+///
+/// Panics if `upstream` isn't a valid `http://` URI, or has a query.
+pub fn backend(upstream: &str) -> Backend {
+    Backend {
+        upstream: Upstream::new(upstream),
+        weight: 1,
+        timeout: None,
+    }
+}
+
+/// A pool of upstream servers, see [`pool()`].
+///
+/// Requests are forwarded like [`to()`] does, to one of the backends picked
+/// by the [`Strategy`]. Backends failing `max_failures` times in a row, by
+/// not being reachable or by timing out, are considered down and skipped for
+/// `fail_timeout`, after which they're given requests again.
+#[derive(Clone, Debug)]
+pub struct ProxyPool {
+    backends: Vec<Backend>,
+    strategy: Strategy,
+    timeout: Option<Duration>,
+    max_failures: u32,
+    fail_timeout: Duration,
+}
+
+/// An upstream server of a [`ProxyPool`], see [`backend()`].
+#[derive(Clone, Debug)]
+pub struct Backend {
+    upstream: Upstream,
+    weight: u32,
+    timeout: Option<Duration>,
+}
+
+/// How a [`ProxyPool`] picks the backend of each request.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Strategy {
+    /// Each backend in turn.
+    RoundRobin,
+    /// The backend with the fewest requests waiting for their response.
+    LeastConnections,
+    /// Each backend in turn, as many times in a row as its weight.
+    Weighted,
+}
+
+impl ProxyPool {
+    /// Add a backend to the pool, either an upstream URI, or a [`Backend`].
+    ///
+    /// # Panics
+    ///
+    /// Panics if `backend` is an invalid URI, see [`backend()`].
+    pub fn upstream(mut self, backend: impl Into<Backend>) -> Self {
+        self.backends.push(backend.into());
+        self
+    }
+
+    /// Set how the backend of each request is picked.
+    ///
+    /// Defaults to [`Strategy::RoundRobin`].
+    pub fn strategy(mut self, strategy: Strategy) -> Self {
+        self.strategy = strategy;
+        self
+    }
+
+    /// Time out the backends without their own [timeout](Backend::timeout)
+    /// after `timeout`.
+    ///
+    /// Backends not sending the response head in time are replied to with a
+    /// `504 Gateway Timeout`. There is no timeout by default.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Consider a backend down after `max_failures` failed requests in a
+    /// row.
+    ///
+    /// Defaults to 3.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `max_failures` is 0.
+    pub fn max_failures(mut self, max_failures: u32) -> Self {
+        assert!(max_failures > 0, "proxy max_failures must be at least 1");
+        self.max_failures = max_failures;
+        self
+    }
+
+    /// Skip the backends considered down for `fail_timeout`.
+    ///
+    /// Defaults to 10 seconds.
+    pub fn fail_timeout(mut self, fail_timeout: Duration) -> Self {
+        self.fail_timeout = fail_timeout;
+        self
+    }
+
+    /// A `Filter` forwarding requests to the backends of this pool.
+    ///
+    /// The returned filter, and its clones, share the connections and the
+    /// health of the backends. If every backend is down, the reply is a
+    /// `502 Bad Gateway`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the pool has no backend.
+    pub fn forward(&self) -> impl Filter<Extract = One<Response>, Error = Rejection> + Clone {
+        assert!(!self.backends.is_empty(), "proxy pool has no upstream");
+        let balancer = Arc::new(Balancer {
+            members: self
+                .backends
+                .iter()
+                .map(|backend| Member {
+                    upstream: backend.upstream.clone(),
+                    weight: backend.weight,
+                    timeout: backend.timeout.or(self.timeout),
+                    in_flight: AtomicUsize::new(0),
+                    failures: AtomicU32::new(0),
+                    down_until: Mutex::new(None),
+                })
+                .collect(),
+            next: AtomicUsize::new(0),
+            strategy: self.strategy,
+            max_failures: self.max_failures,
+            fail_timeout: self.fail_timeout,
+            client: Client::new(),
+        });
+        filter_fn(move |route| {
+            let balancer = balancer.clone();
+            let picked = balancer
+                .pick()
+                .map(|idx| (idx, balancer.members[idx].upstream.request(route)));
+            async move {
+                let res = match picked {
+                    Some((idx, req)) => balancer.send(idx, req).await,
+                    None => {
+                        tracing::warn!("proxy pool has no live upstream");
+                        bad_gateway()
+                    }
+                };
+                Ok::<_, Rejection>((res,))
+            }
+        })
+    }
+}
+
+impl Backend {
+    /// Set the weight of this backend, used by [`Strategy::Weighted`].
+    ///
+    /// Defaults to 1.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `weight` is 0.
+    pub fn weight(mut self, weight: u32) -> Self {
+        assert!(weight > 0, "proxy backend weight must be at least 1");
+        self.weight = weight;
+        self
+    }
+
+    /// Time out this backend after `timeout`, instead of using the pool's
+    /// [timeout](ProxyPool::timeout).
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+}
+
+impl<'a> From<&'a str> for Backend {
+    fn from(upstream: &'a str) -> Backend {
+        backend(upstream)
+    }
+}
+
+impl From<String> for Backend {
+    fn from(upstream: String) -> Backend {
+        backend(&upstream)
+    }
+}
+
+struct Balancer {
+    members: Vec<Member>,
+    next: AtomicUsize,
+    strategy: Strategy,
+    max_failures: u32,
+    fail_timeout: Duration,
+    client: Client<HttpConnector>,
+}
+
+struct Member {
+    upstream: Upstream,
+    weight: u32,
+    timeout: Option<Duration>,
+    in_flight: AtomicUsize,
+    failures: AtomicU32,
+    down_until: Mutex<Option<Instant>>,
+}
+
+impl Balancer {
+    /// Picks the index of the member to forward a request to, among the live
+    /// ones.
+    fn pick(&self) -> Option<usize> {
+        let now = tokio::time::Instant::now().into_std();
+        let live = (0..self.members.len())
+            .filter(|&idx| self.members[idx].is_live(now))
+            .collect::<Vec<_>>();
+        if live.is_empty() {
+            return None;
+        }
+
+        let next = self.next.fetch_add(1, Ordering::Relaxed);
+        let idx = match self.strategy {
+            Strategy::RoundRobin => live[next % live.len()],
+            // Ties are broken in turn, so that idle backends all get requests.
+            Strategy::LeastConnections => (0..live.len())
+                .map(|i| live[(next + i) % live.len()])
+                .min_by_key(|&idx| self.members[idx].in_flight.load(Ordering::SeqCst))
+                .expect("live isn't empty"),
+            Strategy::Weighted => {
+                let total = live
+                    .iter()
+                    .map(|&idx| u64::from(self.members[idx].weight))
+                    .sum::<u64>();
+                let mut n = next as u64 % total;
+                *live
+                    .iter()
+                    .find(|&&idx| {
+                        let weight = u64::from(self.members[idx].weight);
+                        if n < weight {
+                            true
+                        } else {
+                            n -= weight;
+                            false
+                        }
+                    })
+                    .expect("n is less than the total weight")
+            }
+        };
+        Some(idx)
+    }
+
+    async fn send(&self, idx: usize, req: Request<Body>) -> Response {
+        let member = &self.members[idx];
+        member.in_flight.fetch_add(1, Ordering::SeqCst);
+        let _in_flight = InFlight(member);
+
+        let forwarded = forward(&self.client, req);
+        let result = match member.timeout {
+            Some(timeout) => match tokio::time::timeout(timeout, forwarded).await {
+                Ok(result) => result.map_err(Failure::Upstream),
+                Err(_) => Err(Failure::Timeout),
+            },
+            None => forwarded.await.map_err(Failure::Upstream),
+        };
+
+        match result {
+            Ok(res) => {
+                member.failures.store(0, Ordering::SeqCst);
+                *member.down_until.lock().unwrap() = None;
+                res
+            }
+            Err(failure) => {
+                let failures = member.failures.fetch_add(1, Ordering::SeqCst) + 1;
+                if failures >= self.max_failures {
+                    tracing::warn!("proxy upstream {} is down", member.upstream.authority);
+                    let until = tokio::time::Instant::now().into_std() + self.fail_timeout;
+                    *member.down_until.lock().unwrap() = Some(until);
+                }
+                match failure {
+                    Failure::Upstream(err) => {
+                        tracing::warn!("proxy upstream error: {}", err);
+                        bad_gateway()
+                    }
+                    Failure::Timeout => {
+                        tracing::warn!("proxy upstream {} timed out", member.upstream.authority);
+                        let mut res = Response::new(Body::empty());
+                        *res.status_mut() = StatusCode::GATEWAY_TIMEOUT;
+                        res
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl Member {
+    fn is_live(&self, now: Instant) -> bool {
+        match *self.down_until.lock().unwrap() {
+            Some(until) => until <= now,
+            None => true,
+        }
+    }
+}
+
+enum Failure {
+    Upstream(hyper::Error),
+    Timeout,
+}
+
+// Decrements the count even if the request is dropped midway.
+struct InFlight<'a>(&'a Member);
+
+impl Drop for InFlight<'_> {
+    fn drop(&mut self) {
+        self.0.in_flight.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// An upstream server, where requests are forwarded.
+#[derive(Clone, Debug)]
+pub(crate) struct Upstream {
+    scheme: Scheme,
+    authority: Authority,
+    // Without the trailing slash.
+    base: String,
+}
+
+impl Upstream {
+    pub(crate) fn new(upstream: &str) -> Self {
+        let uri = upstream
+            .parse::<Uri>()
+            .expect("proxy upstream must be a valid URI");
+        assert_eq!(
+            uri.scheme(),
+            Some(&Scheme::HTTP),
+            "proxy upstream must be an http:// URI"
+        );
+        assert!(uri.query().is_none(), "proxy upstream can't have a query");
+        Upstream {
+            scheme: Scheme::HTTP,
+            authority: uri
+                .authority()
+                .cloned()
+                .expect("proxy upstream must have a host"),
+            base: uri.path().trim_end_matches('/').to_owned(),
+        }
+    }
+
+    /// Builds the request to forward to this upstream, taking the body of
+    /// `route`.
+    pub(crate) fn request(&self, route: &mut Route) -> Request<Body> {
+        let mut path_and_query = format!("{}/{}", self.base, route.path());
+        if let Some(query) = route.query() {
+            path_and_query.push('?');
+            path_and_query.push_str(query);
+        }
+        let uri = Uri::builder()
+            .scheme(self.scheme.clone())
+            .authority(self.authority.clone())
+            .path_and_query(path_and_query)
+            .build()
+            .expect("request path is a valid URI path");
+
+        let mut headers = route.headers().clone();
+        strip_hop_by_hop(&mut headers);
+        if let Some(host) = headers.remove(header::HOST) {
+            headers.insert(X_FORWARDED_HOST, host);
+        }
+        if let Some(addr) = route.remote_addr() {
+            forwarded_for(&mut headers, addr);
+        }
+
+        let mut req = Request::new(route.take_body().unwrap_or_else(Body::empty));
+        *req.method_mut() = route.method().clone();
+        *req.uri_mut() = uri;
+        *req.headers_mut() = headers;
+        req
+    }
+}
+
+const X_FORWARDED_FOR: HeaderName = HeaderName::from_static("x-forwarded-for");
+const X_FORWARDED_HOST: HeaderName = HeaderName::from_static("x-forwarded-host");
+
+/// Sends `req` upstream, returning its response without the hop-by-hop
+/// headers.
+pub(crate) async fn forward(
+    client: &Client<HttpConnector>,
+    req: Request<Body>,
+) -> Result<Response, hyper::Error> {
+    let mut res = client.request(req).await?;
+    strip_hop_by_hop(res.headers_mut());
+    Ok(res)
+}
+
+pub(crate) fn bad_gateway() -> Response {
+    let mut res = Response::new(Body::empty());
+    *res.status_mut() = StatusCode::BAD_GATEWAY;
+    res
+}
+
+fn forwarded_for(headers: &mut HeaderMap, addr: SocketAddr) {
+    let ip = addr.ip().to_string();
+    let value = match headers.get(&X_FORWARDED_FOR).and_then(|v| v.to_str().ok()) {
+        Some(prev) => format!("{}, {}", prev, ip),
+        None => ip,
+    };
+    if let Ok(value) = HeaderValue::from_str(&value) {
+        headers.insert(X_FORWARDED_FOR, value);
+    }
+}
+
+/// Removes the headers only meant for a single connection, including those
+/// listed in `connection`.
+fn strip_hop_by_hop(headers: &mut HeaderMap) {
+    let listed = headers
+        .get_all(header::CONNECTION)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .filter_map(|name| HeaderName::from_bytes(name.trim().as_bytes()).ok())
+        .collect::<Vec<_>>();
+    for name in listed {
+        headers.remove(name);
+    }
+
+    for name in &[
+        header::CONNECTION,
+        header::PROXY_AUTHENTICATE,
+        header::PROXY_AUTHORIZATION,
+        header::TE,
+        header::TRAILER,
+        header::TRANSFER_ENCODING,
+        header::UPGRADE,
+    ] {
+        headers.remove(name);
+    }
+    headers.remove("keep-alive");
+}
@@ -0,0 +1,441 @@
+//! Range Filters
+//!
+//! Filters that answer `Range` requests, as done by the [`fs`](super::fs)
+//! filters, for any other reply.
+//!
+//! Large dynamic bodies can be returned with
+//! [`reply::ranged`](crate::reply::ranged), so that only the requested ranges
+//! are streamed instead of buffering the whole body.
+
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::io;
+use std::ops::Bound;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+use bytes::Bytes;
+use futures_util::{stream, Stream, StreamExt};
+use headers::{
+    AcceptRanges, ContentLength, ContentRange, ETag, HeaderMapExt, IfRange, LastModified, Range,
+};
+use http::header::{HeaderValue, CONTENT_RANGE, CONTENT_TYPE};
+use http::StatusCode;
+use hyper::Body;
+
+use crate::filter::{Filter, WrapSealed};
+use crate::reject::IsReject;
+use crate::reply::{Part, Reply, Response};
+
+use self::internal::WithRange;
+
+/// Create a wrapping [`Filter`](crate::Filter) that answers `range` requests
+/// for the wrapped replies.
+///
+/// Successful `200 OK` replies advertise `accept-ranges: bytes`. When the
+/// request has a `range` header, the reply body is buffered in memory and
+/// only the requested bytes are sent back, in a `206 Partial Content`
+/// reply. Replies made with [`reply::ranged`](crate::reply::ranged) aren't
+/// buffered, only the streams of the requested bytes are opened. Single,
+/// suffix (`bytes=-500`) and multiple ranges are supported, the latter using
+/// a `multipart/byteranges` body.
+///
+/// An `if-range` header is checked against the `etag` or `last-modified`
+/// header of the reply, if any.
+///
+/// # Example
+///
+/// ```
+/// use nextshell::Filter;
+///
+/// let route = nextshell::path("report.csv")
+///     .map(|| "id,name\n1,nextshell\n")
+///     .with(nextshell::range());
+/// ```
+pub fn range() -> Ranges {
+    Ranges { _p: () }
+}
+
+/// Decorates a [`Filter`] to answer `range` requests.
+#[derive(Clone, Copy, Debug)]
+pub struct Ranges {
+    _p: (),
+}
+
+impl<F> WrapSealed<F> for Ranges
+where
+    F: Filter + Clone + Send,
+    F::Extract: Reply,
+    F::Error: IsReject,
+{
+    type Wrapped = WithRange<F>;
+
+    fn wrap(&self, filter: F) -> Self::Wrapped {
+        WithRange { filter }
+    }
+}
+
+/// The requested ranges can't be satisfied.
+pub(crate) struct BadRange;
+
+type RangeStream = Pin<Box<dyn Stream<Item = Result<Bytes, io::Error>> + Send>>;
+
+/// The body of a [`reply::ranged`](crate::reply::ranged) reply.
+///
+/// It's kept in the response extensions, so that [`range()`] can open the
+/// streams of the requested ranges instead of buffering the whole body.
+#[derive(Clone)]
+pub(crate) struct RangedBody {
+    len: u64,
+    streams: Arc<dyn Fn(u64, u64) -> RangeStream + Send + Sync>,
+}
+
+impl RangedBody {
+    pub(crate) fn new<F, S>(len: u64, streams: F) -> Self
+    where
+        F: Fn(u64, u64) -> S + Send + Sync + 'static,
+        S: Stream<Item = Result<Bytes, io::Error>> + Send + 'static,
+    {
+        RangedBody {
+            len,
+            streams: Arc::new(move |start, end| Box::pin(streams(start, end))),
+        }
+    }
+
+    fn stream(&self, start: u64, end: u64) -> RangeStream {
+        (self.streams)(start, end)
+    }
+
+    /// The whole body, whose stream is only opened once it's polled.
+    pub(crate) fn into_response(self) -> Response {
+        let len = self.len;
+        let body = self.clone();
+        let whole = stream::once(async move { body.stream(0, len) }).flatten();
+        let mut resp = Response::new(Body::wrap_stream(whole));
+        resp.headers_mut().typed_insert(ContentLength(len));
+        resp.extensions_mut().insert(self);
+        resp
+    }
+}
+
+/// More ranges than this in a single request are ignored, and the full body
+/// is sent instead, since many small overlapping ranges are a common way to
+/// abuse servers.
+const MAX_RANGES: usize = 32;
+
+/// Resolves the `range` header against a body of `len` bytes, into a list of
+/// `[start, end)` byte ranges.
+///
+/// Without a `range` header, the whole body is a single range.
+pub(crate) fn satisfiable(range: Option<&Range>, len: u64) -> Result<Vec<(u64, u64)>, BadRange> {
+    let range = match range {
+        Some(range) => range,
+        None => return Ok(vec![(0, len)]),
+    };
+
+    let specs = range.iter().collect::<Vec<_>>();
+    if specs.is_empty() || specs.len() > MAX_RANGES {
+        return Ok(vec![(0, len)]);
+    }
+
+    let ranges = specs
+        .into_iter()
+        .filter_map(|(start, end)| {
+            let (start, end) = match (start, end) {
+                // A suffix range, the last `n` bytes.
+                (Bound::Unbounded, Bound::Included(n)) if n > 0 => (len.saturating_sub(n), len),
+                (Bound::Unbounded, Bound::Included(_)) => {
+                    tracing::trace!("unsatisfiable empty suffix byte range");
+                    return None;
+                }
+                (start, end) => {
+                    let start = match start {
+                        Bound::Unbounded => 0,
+                        Bound::Included(s) => s,
+                        Bound::Excluded(s) => s + 1,
+                    };
+
+                    let end = match end {
+                        Bound::Unbounded => len,
+                        Bound::Included(s) => {
+                            // For the special case where s == the file size
+                            if s == len {
+                                s
+                            } else {
+                                s + 1
+                            }
+                        }
+                        Bound::Excluded(s) => s,
+                    };
+                    (start, end)
+                }
+            };
+
+            if start < end && end <= len {
+                Some((start, end))
+            } else {
+                tracing::trace!("unsatisfiable byte range: {}-{}/{}", start, end, len);
+                None
+            }
+        })
+        .collect::<Vec<_>>();
+
+    if ranges.is_empty() {
+        Err(BadRange)
+    } else {
+        Ok(ranges)
+    }
+}
+
+/// Builds the response for the resolved `ranges` of a body of `len` bytes.
+///
+/// `body` is called with each `[start, end)` range, in order, and must
+/// return a stream of exactly those bytes. The streams are polled one after
+/// the other.
+pub(crate) fn ranged_response<F, S>(
+    ranges: Result<Vec<(u64, u64)>, BadRange>,
+    len: u64,
+    content_type: Option<HeaderValue>,
+    mut body: F,
+) -> Response
+where
+    F: FnMut(u64, u64) -> S,
+    S: Stream<Item = Result<Bytes, io::Error>> + Send + 'static,
+{
+    let ranges = match ranges {
+        Ok(ranges) => ranges,
+        Err(BadRange) => {
+            let mut resp = Response::new(Body::empty());
+            *resp.status_mut() = StatusCode::RANGE_NOT_SATISFIABLE;
+            resp.headers_mut()
+                .typed_insert(ContentRange::unsatisfied_bytes(len));
+            return resp;
+        }
+    };
+
+    let mut resp = if let [(start, end)] = ranges[..] {
+        let mut resp = Response::new(Body::wrap_stream(body(start, end)));
+        if end - start != len {
+            *resp.status_mut() = StatusCode::PARTIAL_CONTENT;
+            resp.headers_mut()
+                .typed_insert(ContentRange::bytes(start..end, len).expect("valid ContentRange"));
+        }
+        resp.headers_mut().typed_insert(ContentLength(end - start));
+        if let Some(content_type) = content_type {
+            resp.headers_mut().insert(CONTENT_TYPE, content_type);
+        }
+        resp
+    } else {
+        let mut multipart = crate::reply::multipart().subtype("byteranges");
+        for (start, end) in ranges {
+            let mut part = Part::stream(body(start, end)).with_len(end - start);
+            if let Some(content_type) = &content_type {
+                part = part.header(CONTENT_TYPE, content_type.clone());
+            }
+            let content_range = format!("bytes {}-{}/{}", start, end - 1, len);
+            multipart = multipart.part(part.header(CONTENT_RANGE, content_range));
+        }
+        let mut resp = multipart.into_response();
+        *resp.status_mut() = StatusCode::PARTIAL_CONTENT;
+        resp
+    };
+    resp.headers_mut().typed_insert(AcceptRanges::bytes());
+    resp
+}
+
+/// Whether an `if-range` precondition allows sending a partial body.
+pub(crate) fn if_range_passes(
+    if_range: Option<&IfRange>,
+    etag: Option<&ETag>,
+    last_modified: Option<&LastModified>,
+) -> bool {
+    match if_range {
+        Some(if_range) => {
+            tracing::trace!("if-range? {:?} vs {:?} {:?}", if_range, etag, last_modified);
+            !if_range.is_modified(etag, last_modified)
+        }
+        None => true,
+    }
+}
+
+/// Generates a boundary for a multipart body.
+pub(crate) fn boundary() -> String {
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+
+    let mut hasher = RandomState::new().build_hasher();
+    hasher.write_u64(COUNTER.fetch_add(1, Ordering::Relaxed));
+    format!("{:016x}", hasher.finish())
+}
+
+mod internal {
+    use std::future::Future;
+    use std::io;
+    use std::pin::Pin;
+    use std::task::{Context, Poll};
+
+    use bytes::Bytes;
+    use futures_util::{future, ready, stream, TryFuture};
+    use headers::{AcceptRanges, ETag, HeaderMapExt, IfRange, LastModified, Range};
+    use http::header::{HeaderMap, CONTENT_LENGTH, CONTENT_RANGE, CONTENT_TYPE};
+    use http::StatusCode;
+    use pin_project::pin_project;
+
+    use super::RangedBody;
+    use crate::filter::{Filter, FilterBase, Internal};
+    use crate::reject::IsReject;
+    use crate::reply::{Reply, Response};
+    use crate::route;
+
+    #[allow(missing_debug_implementations)]
+    pub struct Ranged(pub(super) Response);
+
+    impl Reply for Ranged {
+        #[inline]
+        fn into_response(self) -> Response {
+            self.0
+        }
+    }
+
+    #[allow(missing_debug_implementations)]
+    #[derive(Clone, Copy)]
+    pub struct WithRange<F> {
+        pub(super) filter: F,
+    }
+
+    impl<F> FilterBase for WithRange<F>
+    where
+        F: Filter + Clone + Send,
+        F::Extract: Reply,
+        F::Error: IsReject,
+    {
+        type Extract = (Ranged,);
+        type Error = F::Error;
+        type Future = WithRangeFuture<F::Future>;
+
+        fn filter(&self, _: Internal) -> Self::Future {
+            let request = route::with(|route| {
+                let range = route.headers().typed_get::<Range>()?;
+                Some((range, route.headers().typed_get::<IfRange>()))
+            });
+            WithRangeFuture {
+                state: State::Filter {
+                    request,
+                    future: self.filter.filter(Internal),
+                },
+            }
+        }
+    }
+
+    #[allow(missing_debug_implementations)]
+    #[pin_project]
+    pub struct WithRangeFuture<F> {
+        #[pin]
+        state: State<F>,
+    }
+
+    #[pin_project(project = StateProj)]
+    enum State<F> {
+        Filter {
+            request: Option<(Range, Option<IfRange>)>,
+            #[pin]
+            future: F,
+        },
+        Buffer(Pin<Box<dyn Future<Output = Response> + Send>>),
+    }
+
+    impl<F> Future for WithRangeFuture<F>
+    where
+        F: TryFuture,
+        F::Ok: Reply,
+        F::Error: IsReject,
+    {
+        type Output = Result<(Ranged,), F::Error>;
+
+        fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+            let mut pin = self.project();
+            loop {
+                let buffer = match pin.state.as_mut().project() {
+                    StateProj::Filter { request, future } => {
+                        let mut res = match ready!(future.try_poll(cx)) {
+                            Ok(reply) => reply.into_response(),
+                            Err(reject) => return Poll::Ready(Err(reject)),
+                        };
+                        if res.status() != StatusCode::OK
+                            || res.headers().contains_key(CONTENT_RANGE)
+                        {
+                            return Poll::Ready(Ok((Ranged(res),)));
+                        }
+                        res.headers_mut().typed_insert(AcceptRanges::bytes());
+
+                        let ranged = res.extensions_mut().remove::<RangedBody>();
+                        let range = match request.take() {
+                            Some((range, if_range))
+                                if super::if_range_passes(
+                                    if_range.as_ref(),
+                                    res.headers().typed_get::<ETag>().as_ref(),
+                                    res.headers().typed_get::<LastModified>().as_ref(),
+                                ) =>
+                            {
+                                range
+                            }
+                            _ => return Poll::Ready(Ok((Ranged(res),))),
+                        };
+                        if let Some(ranged) = ranged {
+                            return Poll::Ready(Ok((Ranged(streamed(res, ranged, range)),)));
+                        }
+                        Box::pin(buffered(res, range))
+                    }
+                    StateProj::Buffer(future) => {
+                        let res = ready!(future.as_mut().poll(cx));
+                        return Poll::Ready(Ok((Ranged(res),)));
+                    }
+                };
+                pin.state.set(State::Buffer(buffer));
+            }
+        }
+    }
+
+    async fn buffered(res: Response, range: Range) -> Response {
+        let (parts, body) = res.into_parts();
+        let bytes = match hyper::body::to_bytes(body).await {
+            Ok(bytes) => bytes,
+            Err(err) => {
+                tracing::error!("range body error: {}", err);
+                return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+            }
+        };
+
+        let len = bytes.len() as u64;
+        let ranges = super::satisfiable(Some(&range), len);
+        let content_type = parts.headers.get(CONTENT_TYPE).cloned();
+        let ranged = super::ranged_response(ranges, len, content_type, |start, end| {
+            let chunk: Bytes = bytes.slice(start as usize..end as usize);
+            stream::once(future::ok::<_, io::Error>(chunk))
+        });
+        with_headers(ranged, &parts.headers)
+    }
+
+    fn streamed(res: Response, body: RangedBody, range: Range) -> Response {
+        let ranges = super::satisfiable(Some(&range), body.len);
+        let content_type = res.headers().get(CONTENT_TYPE).cloned();
+        let ranged = super::ranged_response(ranges, body.len, content_type, |start, end| {
+            body.stream(start, end)
+        });
+        with_headers(ranged, res.headers())
+    }
+
+    // Keep the other headers of the reply, such as validators.
+    fn with_headers(ranged: Response, headers: &HeaderMap) -> Response {
+        let (mut ranged_parts, body) = ranged.into_parts();
+        for name in headers.keys() {
+            if name == CONTENT_LENGTH || ranged_parts.headers.contains_key(name) {
+                continue;
+            }
+            for value in headers.get_all(name) {
+                ranged_parts.headers.append(name, value.clone());
+            }
+        }
+        Response::from_parts(ranged_parts, body)
+    }
+}
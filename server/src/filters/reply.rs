@@ -20,14 +20,26 @@
 //! the inner filter (though the `with::header` wrapper does not).
 
 use std::convert::TryFrom;
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
+use std::time::{Duration, SystemTime};
 
-use http::header::{HeaderMap, HeaderName, HeaderValue};
+use headers::{Expires, HeaderMapExt};
+use http::header::{
+    HeaderMap, HeaderName, HeaderValue, CACHE_CONTROL, CONTENT_SECURITY_POLICY, EXPIRES, LINK,
+    REFERRER_POLICY, STRICT_TRANSPORT_SECURITY, X_CONTENT_TYPE_OPTIONS, X_FRAME_OPTIONS,
+};
 
-use self::sealed::{WithDefaultHeader_, WithHeader_, WithHeaders_};
+use self::sealed::{
+    WithContentLanguage_, WithDefaultHeader_, WithDefaultHeaders_, WithDeprecation_, WithExpires_,
+    WithHeader_, WithHeaders_,
+};
 use crate::filter::{Filter, Map, WrapSealed};
 use crate::reply::Reply;
 
+const DEPRECATION: HeaderName = HeaderName::from_static("deprecation");
+const SUNSET: HeaderName = HeaderName::from_static("sunset");
+
 /// Wrap a [`Filter`] that adds a header to the reply.
 ///
 /// # Note
@@ -177,6 +189,750 @@ where
     }
 }
 
+/// Wrap a [`Filter`] that sets the `cache-control` header of the reply.
+///
+/// The returned [`CacheControl`] is a builder for the directives, which
+/// replaces writing the header value by hand.
+///
+/// # Note
+///
+/// This **only** adds a header if the underlying filter is successful, and
+/// returns a [`Reply`] If the underlying filter was rejected, the
+/// header is not added.
+///
+/// # Example
+///
+/// ```
+/// use std::time::Duration;
+/// use nextshell::Filter;
+///
+/// // Sets `cache-control: public, max-age=31536000, immutable`
+/// let assets = nextshell::path("assets")
+///     .and(nextshell::fs::dir("./assets"))
+///     .with(
+///         nextshell::reply::with::cache_control()
+///             .public()
+///             .max_age(Duration::from_secs(60 * 60 * 24 * 365))
+///             .immutable(),
+///     );
+///
+/// // Sets `cache-control: no-store`
+/// let api = nextshell::path("api")
+///     .map(nextshell::reply)
+///     .with(nextshell::reply::with::cache_control().no_store());
+/// ```
+pub fn cache_control() -> CacheControl {
+    CacheControl::default()
+}
+
+/// Wrap a [`Filter`] that sets the `expires` header of the reply to the
+/// given duration after the reply is rendered.
+///
+/// This is mostly useful for old HTTP/1.0 caches, as `max-age` from
+/// [`cache_control`] takes precedence when both are present.
+///
+/// # Note
+///
+/// This **only** adds a header if the underlying filter is successful, and
+/// returns a [`Reply`] If the underlying filter was rejected, the
+/// header is not added.
+///
+/// # Example
+///
+/// ```
+/// use std::time::Duration;
+/// use nextshell::Filter;
+///
+/// let route = nextshell::any()
+///     .map(nextshell::reply)
+///     .with(nextshell::reply::with::expires(Duration::from_secs(3600)));
+/// ```
+pub fn expires(duration: Duration) -> WithExpires {
+    WithExpires { duration }
+}
+
+/// Wrap a `Filter` to set the `cache-control` header.
+///
+/// Constructed via `nextshell::reply::with::cache_control()`.
+#[derive(Clone, Debug, Default)]
+pub struct CacheControl {
+    max_age: Option<Duration>,
+    s_maxage: Option<Duration>,
+    public: bool,
+    private: bool,
+    immutable: bool,
+    no_cache: bool,
+    no_store: bool,
+    must_revalidate: bool,
+}
+
+impl CacheControl {
+    /// Sets the `max-age` directive, the time the reply stays fresh.
+    pub fn max_age(mut self, duration: Duration) -> Self {
+        self.max_age = Some(duration);
+        self
+    }
+
+    /// Sets the `s-maxage` directive, overriding `max-age` for shared caches.
+    pub fn s_maxage(mut self, duration: Duration) -> Self {
+        self.s_maxage = Some(duration);
+        self
+    }
+
+    /// Sets the `public` directive, allowing shared caches to store the reply.
+    pub fn public(mut self) -> Self {
+        self.public = true;
+        self.private = false;
+        self
+    }
+
+    /// Sets the `private` directive, allowing only the client to store the
+    /// reply.
+    pub fn private(mut self) -> Self {
+        self.private = true;
+        self.public = false;
+        self
+    }
+
+    /// Sets the `immutable` directive, telling clients the reply will not
+    /// change while it is fresh.
+    pub fn immutable(mut self) -> Self {
+        self.immutable = true;
+        self
+    }
+
+    /// Sets the `no-cache` directive, requiring revalidation before reuse.
+    pub fn no_cache(mut self) -> Self {
+        self.no_cache = true;
+        self
+    }
+
+    /// Sets the `no-store` directive, forbidding any cache from storing the
+    /// reply.
+    pub fn no_store(mut self) -> Self {
+        self.no_store = true;
+        self
+    }
+
+    /// Sets the `must-revalidate` directive, forbidding stale replies.
+    pub fn must_revalidate(mut self) -> Self {
+        self.must_revalidate = true;
+        self
+    }
+
+    fn header_value(&self) -> HeaderValue {
+        let mut value = String::new();
+        let mut directive = |name: &str| {
+            if !value.is_empty() {
+                value.push_str(", ");
+            }
+            value.push_str(name);
+        };
+        if self.public {
+            directive("public");
+        }
+        if self.private {
+            directive("private");
+        }
+        if self.no_cache {
+            directive("no-cache");
+        }
+        if self.no_store {
+            directive("no-store");
+        }
+        if let Some(max_age) = self.max_age {
+            directive(&format!("max-age={}", max_age.as_secs()));
+        }
+        if let Some(s_maxage) = self.s_maxage {
+            directive(&format!("s-maxage={}", s_maxage.as_secs()));
+        }
+        if self.must_revalidate {
+            directive("must-revalidate");
+        }
+        if self.immutable {
+            directive("immutable");
+        }
+        HeaderValue::try_from(value).expect("cache-control directives are a valid header value")
+    }
+}
+
+impl<F, R> WrapSealed<F> for CacheControl
+where
+    F: Filter<Extract = (R,)>,
+    R: Reply,
+{
+    type Wrapped = Map<F, WithHeader_>;
+
+    fn wrap(&self, filter: F) -> Self::Wrapped {
+        let with = WithHeader_ {
+            with: WithHeader {
+                name: CACHE_CONTROL,
+                value: self.header_value(),
+            },
+        };
+        filter.map(with)
+    }
+}
+
+/// Wrap a `Filter` to set the `expires` header.
+///
+/// Constructed via `nextshell::reply::with::expires()`.
+#[derive(Clone, Copy, Debug)]
+pub struct WithExpires {
+    duration: Duration,
+}
+
+impl<F, R> WrapSealed<F> for WithExpires
+where
+    F: Filter<Extract = (R,)>,
+    R: Reply,
+{
+    type Wrapped = Map<F, WithExpires_>;
+
+    fn wrap(&self, filter: F) -> Self::Wrapped {
+        let with = WithExpires_ { with: *self };
+        filter.map(with)
+    }
+}
+
+/// Wrap a [`Filter`] that sets common security headers on the reply.
+///
+/// By default, this sets:
+///
+/// - `strict-transport-security: max-age=31536000; includeSubDomains`
+/// - `x-content-type-options: nosniff`
+/// - `x-frame-options: DENY`
+/// - `referrer-policy: strict-origin-when-cross-origin`
+///
+/// and a `content-security-policy` once one is configured. Each of them can
+/// be changed or turned off with the returned [`SecurityHeaders`] builder.
+///
+/// # Note
+///
+/// These headers are only set if the reply doesn't already have them, so a
+/// route can still use its own. If the underlying filter was rejected, no
+/// header is added.
+///
+/// # Example
+///
+/// ```
+/// use nextshell::Filter;
+/// use nextshell::reply::with::{ContentSecurityPolicy, FrameOptions};
+///
+/// let security = nextshell::reply::with::security_headers()
+///     .frame_options(Some(FrameOptions::SameOrigin))
+///     .content_security_policy(
+///         ContentSecurityPolicy::new()
+///             .default_src(["'self'"])
+///             .img_src(["'self'", "data:"]),
+///     );
+///
+/// let route = nextshell::any()
+///     .map(nextshell::reply)
+///     .with(security);
+/// ```
+pub fn security_headers() -> SecurityHeaders {
+    SecurityHeaders {
+        hsts: Some(Duration::from_secs(60 * 60 * 24 * 365)),
+        hsts_include_subdomains: true,
+        hsts_preload: false,
+        content_type_options: true,
+        frame_options: Some(FrameOptions::Deny),
+        referrer_policy: Some("strict-origin-when-cross-origin"),
+        content_security_policy: None,
+    }
+}
+
+/// Wrap a `Filter` to set security headers.
+///
+/// Constructed via `nextshell::reply::with::security_headers()`.
+#[derive(Clone, Debug)]
+pub struct SecurityHeaders {
+    hsts: Option<Duration>,
+    hsts_include_subdomains: bool,
+    hsts_preload: bool,
+    content_type_options: bool,
+    frame_options: Option<FrameOptions>,
+    referrer_policy: Option<&'static str>,
+    content_security_policy: Option<ContentSecurityPolicy>,
+}
+
+/// The values of the `x-frame-options` header.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FrameOptions {
+    /// Forbids displaying the reply in any frame.
+    Deny,
+    /// Only allows displaying the reply in frames of the same origin.
+    SameOrigin,
+}
+
+impl SecurityHeaders {
+    /// Sets the `max-age` of `strict-transport-security`, or `None` to not
+    /// send it.
+    pub fn hsts(mut self, max_age: Option<Duration>) -> Self {
+        self.hsts = max_age;
+        self
+    }
+
+    /// Sets whether `strict-transport-security` applies to subdomains too,
+    /// which it does by default.
+    pub fn hsts_include_subdomains(mut self, enabled: bool) -> Self {
+        self.hsts_include_subdomains = enabled;
+        self
+    }
+
+    /// Sets whether `strict-transport-security` has the `preload` directive,
+    /// asking to be included in the browsers' HSTS preload lists.
+    pub fn hsts_preload(mut self, enabled: bool) -> Self {
+        self.hsts_preload = enabled;
+        self
+    }
+
+    /// Sets whether to send `x-content-type-options: nosniff`.
+    pub fn content_type_options(mut self, enabled: bool) -> Self {
+        self.content_type_options = enabled;
+        self
+    }
+
+    /// Sets the `x-frame-options` header, or `None` to not send it.
+    pub fn frame_options(mut self, options: Option<FrameOptions>) -> Self {
+        self.frame_options = options;
+        self
+    }
+
+    /// Sets the `referrer-policy` header, or `None` to not send it.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `policy` isn't a valid header value.
+    pub fn referrer_policy(mut self, policy: Option<&'static str>) -> Self {
+        if let Some(policy) = policy {
+            assert!(
+                HeaderValue::try_from(policy).is_ok(),
+                "invalid referrer-policy"
+            );
+        }
+        self.referrer_policy = policy;
+        self
+    }
+
+    /// Sets the `content-security-policy` header.
+    pub fn content_security_policy(mut self, policy: ContentSecurityPolicy) -> Self {
+        self.content_security_policy = Some(policy);
+        self
+    }
+
+    fn header_map(&self) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        if let Some(max_age) = self.hsts {
+            let mut value = format!("max-age={}", max_age.as_secs());
+            if self.hsts_include_subdomains {
+                value.push_str("; includeSubDomains");
+            }
+            if self.hsts_preload {
+                value.push_str("; preload");
+            }
+            headers.insert(
+                STRICT_TRANSPORT_SECURITY,
+                HeaderValue::try_from(value).expect("hsts is a valid header value"),
+            );
+        }
+        if self.content_type_options {
+            headers.insert(X_CONTENT_TYPE_OPTIONS, HeaderValue::from_static("nosniff"));
+        }
+        if let Some(options) = self.frame_options {
+            let value = match options {
+                FrameOptions::Deny => "DENY",
+                FrameOptions::SameOrigin => "SAMEORIGIN",
+            };
+            headers.insert(X_FRAME_OPTIONS, HeaderValue::from_static(value));
+        }
+        if let Some(policy) = self.referrer_policy {
+            headers.insert(REFERRER_POLICY, HeaderValue::from_static(policy));
+        }
+        if let Some(ref policy) = self.content_security_policy {
+            headers.insert(CONTENT_SECURITY_POLICY, policy.header_value());
+        }
+        headers
+    }
+}
+
+impl<F, R> WrapSealed<F> for SecurityHeaders
+where
+    F: Filter<Extract = (R,)>,
+    R: Reply,
+{
+    type Wrapped = Map<F, WithDefaultHeaders_>;
+
+    fn wrap(&self, filter: F) -> Self::Wrapped {
+        let with = WithDefaultHeaders_ {
+            headers: Arc::new(self.header_map()),
+        };
+        filter.map(with)
+    }
+}
+
+/// A builder for the `content-security-policy` header.
+///
+/// Directives are written in the order they're set. Sources are written
+/// as given, so keywords must keep their quotes, such as `'self'`.
+#[derive(Clone, Debug, Default)]
+pub struct ContentSecurityPolicy {
+    directives: Vec<(&'static str, Vec<String>)>,
+}
+
+impl ContentSecurityPolicy {
+    /// Create an empty policy.
+    pub fn new() -> Self {
+        ContentSecurityPolicy::default()
+    }
+
+    /// Sets the `default-src` directive.
+    pub fn default_src<I>(self, sources: I) -> Self
+    where
+        I: IntoIterator,
+        I::Item: Into<String>,
+    {
+        self.directive("default-src", sources)
+    }
+
+    /// Sets the `script-src` directive.
+    pub fn script_src<I>(self, sources: I) -> Self
+    where
+        I: IntoIterator,
+        I::Item: Into<String>,
+    {
+        self.directive("script-src", sources)
+    }
+
+    /// Sets the `style-src` directive.
+    pub fn style_src<I>(self, sources: I) -> Self
+    where
+        I: IntoIterator,
+        I::Item: Into<String>,
+    {
+        self.directive("style-src", sources)
+    }
+
+    /// Sets the `img-src` directive.
+    pub fn img_src<I>(self, sources: I) -> Self
+    where
+        I: IntoIterator,
+        I::Item: Into<String>,
+    {
+        self.directive("img-src", sources)
+    }
+
+    /// Sets the `connect-src` directive.
+    pub fn connect_src<I>(self, sources: I) -> Self
+    where
+        I: IntoIterator,
+        I::Item: Into<String>,
+    {
+        self.directive("connect-src", sources)
+    }
+
+    /// Sets the `frame-ancestors` directive.
+    pub fn frame_ancestors<I>(self, sources: I) -> Self
+    where
+        I: IntoIterator,
+        I::Item: Into<String>,
+    {
+        self.directive("frame-ancestors", sources)
+    }
+
+    /// Sets the `upgrade-insecure-requests` directive.
+    pub fn upgrade_insecure_requests(self) -> Self {
+        self.directive("upgrade-insecure-requests", Vec::<String>::new())
+    }
+
+    /// Sets any directive `name`, replacing its previous sources.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a source contains a `;` or `,`, or isn't valid in a header
+    /// value.
+    pub fn directive<I>(mut self, name: &'static str, sources: I) -> Self
+    where
+        I: IntoIterator,
+        I::Item: Into<String>,
+    {
+        let sources = sources.into_iter().map(Into::into).collect::<Vec<_>>();
+        for source in &sources {
+            assert!(
+                !source.contains([';', ',']) && HeaderValue::try_from(source.as_str()).is_ok(),
+                "invalid content-security-policy source: {:?}",
+                source
+            );
+        }
+        match self.directives.iter_mut().find(|(n, _)| *n == name) {
+            Some(directive) => directive.1 = sources,
+            None => self.directives.push((name, sources)),
+        }
+        self
+    }
+
+    fn header_value(&self) -> HeaderValue {
+        let value = self
+            .directives
+            .iter()
+            .map(|(name, sources)| {
+                let mut directive = name.to_string();
+                for source in sources {
+                    directive.push(' ');
+                    directive.push_str(source);
+                }
+                directive
+            })
+            .collect::<Vec<_>>()
+            .join("; ");
+        HeaderValue::try_from(value).expect("content-security-policy is a valid header value")
+    }
+}
+
+/// Wrap a [`Filter`] that marks its replies as deprecated since `date`.
+///
+/// This sets the `deprecation` header ([RFC 9745]), and a `link` header
+/// pointing to `link`, which should document the deprecation and how to
+/// migrate. A date the route goes away can be announced with
+/// [`sunset`](Deprecation::sunset).
+///
+/// # Note
+///
+/// This **only** adds the headers if the underlying filter is successful,
+/// and returns a [`Reply`]. If the underlying filter was rejected, the
+/// headers are not added.
+///
+/// # Panics
+///
+/// Panics if `link` isn't valid in a header value.
+///
+/// # Example
+///
+/// ```
+/// use std::time::{Duration, SystemTime};
+/// use nextshell::Filter;
+///
+/// let since = SystemTime::UNIX_EPOCH + Duration::from_secs(1_735_689_600);
+/// let deprecation = nextshell::reply::with::deprecation(since, "https://example.com/v1-migration")
+///     .sunset(since + Duration::from_secs(60 * 60 * 24 * 180))
+///     .log(true);
+///
+/// let v1 = nextshell::path("v1")
+///     .map(nextshell::reply)
+///     .with(deprecation.clone());
+///
+/// // The number of deprecated replies so far.
+/// let uses = deprecation.uses();
+/// ```
+///
+/// [RFC 9745]: https://www.rfc-editor.org/rfc/rfc9745
+pub fn deprecation(date: SystemTime, link: impl Into<String>) -> Deprecation {
+    let link = link.into();
+    assert!(
+        !link.contains('>') && HeaderValue::try_from(format!("<{}>", link)).is_ok(),
+        "invalid deprecation link: {:?}",
+        link
+    );
+    Deprecation {
+        date,
+        link,
+        sunset: None,
+        log: false,
+        uses: Arc::new(AtomicU64::new(0)),
+    }
+}
+
+/// Wrap a `Filter` to mark its replies as deprecated.
+///
+/// Constructed via `nextshell::reply::with::deprecation()`. Clones share
+/// the same usage count.
+#[derive(Clone, Debug)]
+pub struct Deprecation {
+    date: SystemTime,
+    link: String,
+    sunset: Option<SystemTime>,
+    log: bool,
+    uses: Arc<AtomicU64>,
+}
+
+impl Deprecation {
+    /// Also sets the `sunset` header ([RFC 8594]), the date after which the
+    /// route may stop answering.
+    ///
+    /// [RFC 8594]: https://www.rfc-editor.org/rfc/rfc8594
+    pub fn sunset(mut self, date: SystemTime) -> Self {
+        self.sunset = Some(date);
+        self
+    }
+
+    /// Logs a warning with the running usage count for every deprecated
+    /// reply, to find the clients still relying on it.
+    ///
+    /// Defaults to `false`.
+    pub fn log(mut self, enabled: bool) -> Self {
+        self.log = enabled;
+        self
+    }
+
+    /// The number of replies marked as deprecated so far.
+    pub fn uses(&self) -> u64 {
+        self.uses.load(Ordering::Relaxed)
+    }
+
+    fn header_map(&self) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        let since = self
+            .date
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .map_or(0, |since| since.as_secs());
+        headers.insert(
+            DEPRECATION,
+            HeaderValue::try_from(format!("@{}", since)).expect("a date is a valid header value"),
+        );
+        if let Some(sunset) = self.sunset {
+            headers.typed_insert(Expires::from(sunset));
+            let value = headers.remove(EXPIRES).expect("just inserted");
+            headers.insert(SUNSET, value);
+        }
+        headers.insert(
+            LINK,
+            HeaderValue::try_from(format!(
+                "<{}>; rel=\"deprecation\"; type=\"text/html\"",
+                self.link
+            ))
+            .expect("the link was checked"),
+        );
+        headers
+    }
+}
+
+impl<F, R> WrapSealed<F> for Deprecation
+where
+    F: Filter<Extract = (R,)>,
+    R: Reply,
+{
+    type Wrapped = Map<F, WithDeprecation_>;
+
+    fn wrap(&self, filter: F) -> Self::Wrapped {
+        let with = WithDeprecation_ {
+            headers: Arc::new(self.header_map()),
+            log: self.log,
+            uses: self.uses.clone(),
+        };
+        filter.map(with)
+    }
+}
+
+/// Wrap a [`Filter`] that sends the variant of a
+/// [`localized`](crate::reply::localized) reply that best matches the
+/// request's `accept-language` header.
+///
+/// Language ranges are matched as prefixes, so `fr` accepts `fr-CA`, and
+/// weighted by their q-values. The chosen variant has its
+/// `content-language` header set, and `vary: accept-language` is added.
+/// Other replies are left as they are.
+///
+/// # Example
+///
+/// ```
+/// use nextshell::Filter;
+///
+/// let route = nextshell::any()
+///     .map(|| {
+///         nextshell::reply::localized("en", nextshell::reply::html("<p>Not found</p>"))
+///             .variant("de", nextshell::reply::html("<p>Nicht gefunden</p>"))
+///     })
+///     .with(nextshell::reply::with::content_language());
+/// ```
+pub fn content_language() -> ContentLanguage {
+    ContentLanguage { _p: () }
+}
+
+/// Wrap a `Filter` to negotiate the language of its replies.
+///
+/// Constructed via `nextshell::reply::with::content_language()`.
+#[derive(Clone, Copy, Debug)]
+pub struct ContentLanguage {
+    _p: (),
+}
+
+impl<F, R> WrapSealed<F> for ContentLanguage
+where
+    F: Filter<Extract = (R,)>,
+    R: Reply,
+{
+    type Wrapped = Map<F, WithContentLanguage_>;
+
+    fn wrap(&self, filter: F) -> Self::Wrapped {
+        filter.map(WithContentLanguage_ { _p: () })
+    }
+}
+
+/// Picks the index of the language tag best matching an `accept-language`
+/// header value, or `None` if none is acceptable.
+fn negotiate_language(accept_language: &str, tags: &[&str]) -> Option<usize> {
+    let ranges = accept_language
+        .split(',')
+        .filter_map(|item| {
+            let mut parts = item.split(';');
+            let range = parts.next().unwrap_or("").trim();
+            if range.is_empty() {
+                return None;
+            }
+            let quality = parts
+                .filter_map(|param| {
+                    let (key, value) = param.split_once('=')?;
+                    if key.trim().eq_ignore_ascii_case("q") {
+                        value.trim().parse::<f32>().ok()
+                    } else {
+                        None
+                    }
+                })
+                .next()
+                .unwrap_or(1.0);
+            Some((range, quality))
+        })
+        .collect::<Vec<_>>();
+
+    // How closely a range matches a tag: `fr` matches `fr-CA` as well as
+    // `fr`, before `fr-CA` matches `fr` as a fallback, and `*` anything.
+    let specificity = |range: &str, tag: &str| {
+        let prefix = |long: &str, short: &str| {
+            long.len() > short.len()
+                && long.as_bytes()[short.len()] == b'-'
+                && long[..short.len()].eq_ignore_ascii_case(short)
+        };
+        if range == "*" {
+            Some((0, 0))
+        } else if range.eq_ignore_ascii_case(tag) || prefix(tag, range) {
+            Some((2, range.len()))
+        } else if prefix(range, tag) {
+            Some((1, range.len()))
+        } else {
+            None
+        }
+    };
+
+    let (index, _) = tags
+        .iter()
+        .enumerate()
+        .filter_map(|(index, tag)| {
+            // The most specific range matching the tag decides its quality.
+            let (_, quality) = ranges
+                .iter()
+                .filter_map(|&(range, quality)| Some((specificity(range, tag)?, quality)))
+                .max_by_key(|&(specificity, _)| specificity)?;
+            Some((index, quality))
+        })
+        .filter(|&(_, quality)| quality > 0.0)
+        // `max_by` returns the last maximum, so iterate in reverse to keep
+        // the server's preference among equal qualities.
+        .rev()
+        .max_by(|a, b| a.1.total_cmp(&b.1))?;
+    Some(index)
+}
+
 fn assert_name_and_value<K, V>(name: K, value: V) -> (HeaderName, HeaderValue)
 where
     HeaderName: TryFrom<K>,
@@ -196,9 +952,21 @@ where
 }
 
 mod sealed {
-    use super::{WithDefaultHeader, WithHeader, WithHeaders};
+    use std::sync::atomic::{AtomicU64, Ordering};
+    use std::time::SystemTime;
+
+    use headers::{Expires, HeaderMapExt};
+
+    use std::sync::Arc;
+
+    use http::header::HeaderMap;
+
+    use http::header::{HeaderValue, ACCEPT_LANGUAGE, CONTENT_LANGUAGE, VARY};
+
+    use super::{negotiate_language, WithDefaultHeader, WithExpires, WithHeader, WithHeaders};
     use crate::generic::{Func, One};
-    use crate::reply::{Reply, Reply_};
+    use crate::reply::{Reply, Reply_, Variants};
+    use crate::route;
 
     #[derive(Clone)]
     #[allow(missing_debug_implementations)]
@@ -254,4 +1022,109 @@ mod sealed {
             Reply_(resp)
         }
     }
+
+    #[derive(Clone)]
+    #[allow(missing_debug_implementations)]
+    pub struct WithDefaultHeaders_ {
+        pub(super) headers: Arc<HeaderMap>,
+    }
+
+    impl<R: Reply> Func<One<R>> for WithDefaultHeaders_ {
+        type Output = Reply_;
+
+        fn call(&self, args: One<R>) -> Self::Output {
+            let mut resp = args.0.into_response();
+            for (name, value) in &*self.headers {
+                resp.headers_mut()
+                    .entry(name)
+                    .or_insert_with(|| value.clone());
+            }
+            Reply_(resp)
+        }
+    }
+
+    #[derive(Clone)]
+    #[allow(missing_debug_implementations)]
+    pub struct WithExpires_ {
+        pub(super) with: WithExpires,
+    }
+
+    impl<R: Reply> Func<One<R>> for WithExpires_ {
+        type Output = Reply_;
+
+        fn call(&self, args: One<R>) -> Self::Output {
+            let mut resp = args.0.into_response();
+            let expires = SystemTime::now() + self.with.duration;
+            resp.headers_mut().typed_insert(Expires::from(expires));
+            Reply_(resp)
+        }
+    }
+
+    #[derive(Clone)]
+    #[allow(missing_debug_implementations)]
+    pub struct WithDeprecation_ {
+        pub(super) headers: Arc<HeaderMap>,
+        pub(super) log: bool,
+        pub(super) uses: Arc<AtomicU64>,
+    }
+
+    impl<R: Reply> Func<One<R>> for WithDeprecation_ {
+        type Output = Reply_;
+
+        fn call(&self, args: One<R>) -> Self::Output {
+            let mut resp = args.0.into_response();
+            let uses = self.uses.fetch_add(1, Ordering::Relaxed) + 1;
+            if self.log {
+                tracing::warn!(uses, "deprecated route used");
+            }
+            for (name, value) in &*self.headers {
+                if name == http::header::LINK {
+                    resp.headers_mut().append(name, value.clone());
+                } else {
+                    resp.headers_mut().insert(name, value.clone());
+                }
+            }
+            Reply_(resp)
+        }
+    }
+
+    #[derive(Clone)]
+    #[allow(missing_debug_implementations)]
+    pub struct WithContentLanguage_ {
+        pub(super) _p: (),
+    }
+
+    impl<R: Reply> Func<One<R>> for WithContentLanguage_ {
+        type Output = Reply_;
+
+        fn call(&self, args: One<R>) -> Self::Output {
+            let mut resp = args.0.into_response();
+            let mut others = match resp.extensions_mut().remove::<Variants>() {
+                Some(Variants(others)) => others,
+                None => return Reply_(resp),
+            };
+
+            let accept_language =
+                route::with(|route| route.headers().get(ACCEPT_LANGUAGE).cloned());
+            if let Some(accept_language) = accept_language.as_ref().and_then(|v| v.to_str().ok()) {
+                let tags = resp
+                    .headers()
+                    .get(CONTENT_LANGUAGE)
+                    .into_iter()
+                    .chain(others.iter().map(|(lang, _)| lang))
+                    .map(|lang| lang.to_str().unwrap_or(""))
+                    .collect::<Vec<_>>();
+                if let Some(index) = negotiate_language(accept_language, &tags) {
+                    if index > 0 {
+                        let (lang, chosen) = others.swap_remove(index - 1);
+                        resp = chosen;
+                        resp.headers_mut().insert(CONTENT_LANGUAGE, lang);
+                    }
+                }
+            }
+            resp.headers_mut()
+                .append(VARY, HeaderValue::from_static("accept-language"));
+            Reply_(resp)
+        }
+    }
 }
@@ -43,6 +43,7 @@
 
 use serde::Serialize;
 use std::borrow::Cow;
+use std::convert::Infallible;
 use std::error::Error as StdError;
 use std::fmt::{self, Write};
 use std::future::Future;
@@ -51,11 +52,12 @@ use std::str::FromStr;
 use std::task::{Context, Poll};
 use std::time::Duration;
 
-use futures_util::{future, Stream, TryStream, TryStreamExt};
+use futures_util::{future, Stream, StreamExt, TryStream, TryStreamExt};
 use http::header::{HeaderValue, CACHE_CONTROL, CONTENT_TYPE};
 use hyper::Body;
 use pin_project::pin_project;
 use serde_json::Error;
+use tokio::sync::broadcast;
 use tokio::time::{self, Sleep};
 
 use self::sealed::SseError;
@@ -65,14 +67,14 @@ use crate::reply::Response;
 use crate::{Filter, Rejection, Reply};
 
 // Server-sent event data type
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 enum DataType {
     Text(String),
     Json(String),
 }
 
 /// Server-sent event
-#[derive(Default, Debug)]
+#[derive(Clone, Default, Debug)]
 pub struct Event {
     id: Option<String>,
     data: Option<DataType>,
@@ -496,6 +498,352 @@ where
     }
 }
 
+/// Serializes a stream of values into JSON events.
+///
+/// Each value becomes the `data` of an event. When the values are enums,
+/// with serde's default externally tagged representation, the variant
+/// name becomes the event name and the variant content the data. A unit
+/// variant sends `null`, since clients ignore events without data.
+///
+/// # Example
+///
+/// ```
+/// use std::convert::Infallible;
+/// use futures_util::stream::iter;
+/// use nextshell::Filter;
+/// use serde_derive::Serialize;
+///
+/// #[derive(Serialize)]
+/// enum Feed {
+///     #[serde(rename = "chat")]
+///     Chat { from: u32, text: String },
+///     #[serde(rename = "leave")]
+///     Leave(u32),
+/// }
+///
+/// # async fn run() {
+/// let app = nextshell::any().map(|| {
+///     nextshell::sse::reply(nextshell::sse::json_stream(iter(vec![
+///         Ok::<_, Infallible>(Feed::Chat { from: 1, text: "hi".into() }),
+///         Ok(Feed::Leave(1)),
+///     ])))
+/// });
+///
+/// let body = nextshell::test::request().reply(&app).await.into_body();
+/// assert_eq!(
+///     body,
+///     "event:chat\ndata:{\"from\":1,\"text\":\"hi\"}\n\nevent:leave\ndata:1\n\n",
+/// );
+/// # }
+/// # tokio::runtime::Runtime::new().unwrap().block_on(run());
+/// ```
+pub fn json_stream<S, T>(
+    stream: S,
+) -> impl TryStream<Ok = Event, Error = impl StdError + Send + Sync + 'static> + Send + 'static
+where
+    S: TryStream<Ok = T> + Send + 'static,
+    S::Error: StdError + Send + Sync + 'static,
+    T: Serialize,
+{
+    stream.into_stream().map(|item| match item {
+        Ok(value) => json_event(&value).map_err(|error| {
+            log::error!("sse json serialization error: {}", error);
+            SseError
+        }),
+        Err(error) => {
+            log::error!("sse stream error: {}", error);
+            Err(SseError)
+        }
+    })
+}
+
+fn json_event<T: Serialize>(value: &T) -> Result<Event, Error> {
+    let name = value.serialize(variant::VariantName).ok();
+    let data = match (name, serde_json::to_value(value)?) {
+        (Some(name), serde_json::Value::Object(mut map)) if map.len() == 1 => {
+            map.remove(name).unwrap_or(serde_json::Value::Null)
+        }
+        (Some(_), serde_json::Value::String(_)) => serde_json::Value::Null,
+        (_, data) => data,
+    };
+    let event = Event::default().json_data(data)?;
+    Ok(match name {
+        Some(name) => event.event(name),
+        None => event,
+    })
+}
+
+/// A `Serializer` that only finds out which enum variant a value is.
+mod variant {
+    use serde::ser::{self, Impossible, Serialize, Serializer};
+    use std::fmt;
+
+    pub(super) struct VariantName;
+
+    #[derive(Debug)]
+    pub(super) struct NotAnEnum;
+
+    impl fmt::Display for NotAnEnum {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            f.write_str("not an enum")
+        }
+    }
+
+    impl std::error::Error for NotAnEnum {}
+
+    impl ser::Error for NotAnEnum {
+        fn custom<T: fmt::Display>(_: T) -> Self {
+            NotAnEnum
+        }
+    }
+
+    macro_rules! not_an_enum {
+        ($($method:ident($($arg:ty),*);)*) => {$(
+            fn $method(self, $(_: $arg),*) -> Result<Self::Ok, Self::Error> {
+                Err(NotAnEnum)
+            }
+        )*};
+    }
+
+    impl Serializer for VariantName {
+        type Ok = &'static str;
+        type Error = NotAnEnum;
+        type SerializeSeq = Impossible<Self::Ok, NotAnEnum>;
+        type SerializeTuple = Impossible<Self::Ok, NotAnEnum>;
+        type SerializeTupleStruct = Impossible<Self::Ok, NotAnEnum>;
+        type SerializeTupleVariant = Variant;
+        type SerializeMap = Impossible<Self::Ok, NotAnEnum>;
+        type SerializeStruct = Impossible<Self::Ok, NotAnEnum>;
+        type SerializeStructVariant = Variant;
+
+        not_an_enum! {
+            serialize_bool(bool);
+            serialize_i8(i8);
+            serialize_i16(i16);
+            serialize_i32(i32);
+            serialize_i64(i64);
+            serialize_u8(u8);
+            serialize_u16(u16);
+            serialize_u32(u32);
+            serialize_u64(u64);
+            serialize_f32(f32);
+            serialize_f64(f64);
+            serialize_char(char);
+            serialize_str(&str);
+            serialize_bytes(&[u8]);
+            serialize_none();
+            serialize_unit();
+            serialize_unit_struct(&'static str);
+        }
+
+        fn serialize_some<T: ?Sized + Serialize>(self, _: &T) -> Result<Self::Ok, Self::Error> {
+            Err(NotAnEnum)
+        }
+
+        fn serialize_unit_variant(
+            self,
+            _: &'static str,
+            _: u32,
+            variant: &'static str,
+        ) -> Result<Self::Ok, Self::Error> {
+            Ok(variant)
+        }
+
+        fn serialize_newtype_struct<T: ?Sized + Serialize>(
+            self,
+            _: &'static str,
+            value: &T,
+        ) -> Result<Self::Ok, Self::Error> {
+            value.serialize(self)
+        }
+
+        fn serialize_newtype_variant<T: ?Sized + Serialize>(
+            self,
+            _: &'static str,
+            _: u32,
+            variant: &'static str,
+            _: &T,
+        ) -> Result<Self::Ok, Self::Error> {
+            Ok(variant)
+        }
+
+        fn serialize_seq(self, _: Option<usize>) -> Result<Self::SerializeSeq, Self::Error> {
+            Err(NotAnEnum)
+        }
+
+        fn serialize_tuple(self, _: usize) -> Result<Self::SerializeTuple, Self::Error> {
+            Err(NotAnEnum)
+        }
+
+        fn serialize_tuple_struct(
+            self,
+            _: &'static str,
+            _: usize,
+        ) -> Result<Self::SerializeTupleStruct, Self::Error> {
+            Err(NotAnEnum)
+        }
+
+        fn serialize_tuple_variant(
+            self,
+            _: &'static str,
+            _: u32,
+            variant: &'static str,
+            _: usize,
+        ) -> Result<Self::SerializeTupleVariant, Self::Error> {
+            Ok(Variant(variant))
+        }
+
+        fn serialize_map(self, _: Option<usize>) -> Result<Self::SerializeMap, Self::Error> {
+            Err(NotAnEnum)
+        }
+
+        fn serialize_struct(
+            self,
+            _: &'static str,
+            _: usize,
+        ) -> Result<Self::SerializeStruct, Self::Error> {
+            Err(NotAnEnum)
+        }
+
+        fn serialize_struct_variant(
+            self,
+            _: &'static str,
+            _: u32,
+            variant: &'static str,
+            _: usize,
+        ) -> Result<Self::SerializeStructVariant, Self::Error> {
+            Ok(Variant(variant))
+        }
+    }
+
+    pub(super) struct Variant(&'static str);
+
+    impl ser::SerializeTupleVariant for Variant {
+        type Ok = &'static str;
+        type Error = NotAnEnum;
+
+        fn serialize_field<T: ?Sized + Serialize>(&mut self, _: &T) -> Result<(), Self::Error> {
+            Ok(())
+        }
+
+        fn end(self) -> Result<Self::Ok, Self::Error> {
+            Ok(self.0)
+        }
+    }
+
+    impl ser::SerializeStructVariant for Variant {
+        type Ok = &'static str;
+        type Error = NotAnEnum;
+
+        fn serialize_field<T: ?Sized + Serialize>(
+            &mut self,
+            _: &'static str,
+            _: &T,
+        ) -> Result<(), Self::Error> {
+            Ok(())
+        }
+
+        fn end(self) -> Result<Self::Ok, Self::Error> {
+            Ok(self.0)
+        }
+    }
+}
+
+/// Fans events out to every subscribed client.
+///
+/// Each subscriber buffers up to `capacity` events. A client that falls
+/// further behind skips the events it missed, or is disconnected with
+/// [`disconnect_lagging`](Broadcaster::disconnect_lagging) so its
+/// `EventSource` reconnects, possibly resuming from its `Last-Event-ID`.
+///
+/// # Example
+///
+/// ```
+/// use std::time::Duration;
+/// use futures_util::StreamExt;
+/// use nextshell::{Filter, sse::{Broadcaster, Event}};
+///
+/// # async fn run() {
+/// let broadcaster = Broadcaster::new(64);
+///
+/// let ticks = broadcaster.clone();
+/// tokio::spawn(async move {
+///     let mut interval = tokio::time::interval(Duration::from_secs(1));
+///     for counter in 0u64.. {
+///         interval.tick().await;
+///         ticks.send(Event::default().data(counter.to_string()));
+///     }
+/// });
+///
+/// let routes = nextshell::path("ticks").and(nextshell::get()).map(move || {
+///     let stream = nextshell::sse::keep_alive().stream(broadcaster.subscribe());
+///     nextshell::sse::reply(stream)
+/// });
+/// # }
+/// ```
+#[derive(Clone, Debug)]
+pub struct Broadcaster {
+    tx: broadcast::Sender<Event>,
+    disconnect_lagging: bool,
+}
+
+impl Broadcaster {
+    /// Creates a broadcaster buffering up to `capacity` events per client.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `capacity` is zero.
+    pub fn new(capacity: usize) -> Self {
+        assert!(capacity > 0, "sse::Broadcaster capacity must be positive");
+        let (tx, _) = broadcast::channel(capacity);
+        Broadcaster {
+            tx,
+            disconnect_lagging: false,
+        }
+    }
+
+    /// Whether clients that miss events are disconnected, instead of
+    /// skipping ahead.
+    ///
+    /// Defaults to `false`. Only affects later subscriptions.
+    pub fn disconnect_lagging(mut self, enabled: bool) -> Self {
+        self.disconnect_lagging = enabled;
+        self
+    }
+
+    /// Sends an event to every current subscriber, returning how many there
+    /// are.
+    pub fn send(&self, event: Event) -> usize {
+        self.tx.send(event).unwrap_or(0)
+    }
+
+    /// The number of currently subscribed clients.
+    pub fn subscribers(&self) -> usize {
+        self.tx.receiver_count()
+    }
+
+    /// Subscribes to events sent from now on, to be passed to [`reply`].
+    ///
+    /// The stream ends once every `Broadcaster` clone has been dropped.
+    pub fn subscribe(&self) -> impl Stream<Item = Result<Event, Infallible>> + Send + 'static {
+        let disconnect_lagging = self.disconnect_lagging;
+        futures_util::stream::unfold(self.tx.subscribe(), move |mut rx| async move {
+            loop {
+                match rx.recv().await {
+                    Ok(event) => return Some((Ok(event), rx)),
+                    Err(broadcast::error::RecvError::Lagged(missed)) => {
+                        log::debug!("sse client lagged behind by {} events", missed);
+                        if disconnect_lagging {
+                            return None;
+                        }
+                    }
+                    Err(broadcast::error::RecvError::Closed) => return None,
+                }
+            }
+        })
+    }
+}
+
 mod sealed {
     use super::*;
 
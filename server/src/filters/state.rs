@@ -0,0 +1,86 @@
+//! Shared State
+//!
+//! State registered with [`Server::with_state`](crate::Server::with_state)
+//! is available to every request, and extracted by type with
+//! [`state()`](crate::state()), instead of cloning it into each closure.
+//!
+//! # Example
+//!
+//! ```
+//! use std::sync::Arc;
+//! use nextshell::Filter;
+//!
+//! #[derive(Clone)]
+//! struct Config {
+//!     greeting: Arc<str>,
+//! }
+//!
+//! let hello = nextshell::path!("hello" / String)
+//!     .and(nextshell::state::<Config>())
+//!     .map(|name, config: Config| format!("{}, {}!", config.greeting, name));
+//!
+//! let server = nextshell::serve(hello).with_state(Config {
+//!     greeting: "Hello".into(),
+//! });
+//! ```
+
+use std::any::{Any, TypeId};
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use futures_util::future;
+
+use crate::filter::{filter_fn_one, Filter};
+use crate::reject::{self, Rejection};
+
+/// Extract the server state of type `T`.
+///
+/// If no state of that type was registered, this rejects with a
+/// `MissingState`.
+pub fn state<T: Clone + Send + Sync + 'static>(
+) -> impl Filter<Extract = (T,), Error = Rejection> + Copy {
+    filter_fn_one(|route| {
+        let state = route
+            .extensions()
+            .get::<States>()
+            .and_then(States::get::<T>)
+            .ok_or_else(|| reject::known(MissingState { _p: () }));
+        future::ready(state)
+    })
+}
+
+/// The states of a server, by type.
+///
+/// Cloning is cheap, so it can be put in each request's extensions.
+#[derive(Clone, Default)]
+pub(crate) struct States(Arc<HashMap<TypeId, Arc<dyn Any + Send + Sync>>>);
+
+impl States {
+    pub(crate) fn insert<T: Send + Sync + 'static>(&mut self, state: T) {
+        Arc::make_mut(&mut self.0).insert(TypeId::of::<T>(), Arc::new(state));
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    fn get<T: Clone + 'static>(&self) -> Option<T> {
+        self.0
+            .get(&TypeId::of::<T>())
+            .and_then(|state| state.downcast_ref::<T>())
+            .cloned()
+    }
+}
+
+impl std::fmt::Debug for States {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("States")
+            .field("len", &self.0.len())
+            .finish()
+    }
+}
+
+unit_error! {
+    /// An error used to reject if `state` cannot find the state.
+    pub MissingState: "Missing server state"
+}
@@ -10,16 +10,22 @@
 //! [`Spans`]: https://docs.rs/tracing/latest/tracing/#spans
 use tracing::Span;
 
+use std::convert::Infallible;
+use std::fmt::Write;
 use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
 
-use http::header;
+use futures_util::future;
+use http::header::{self, HeaderName, HeaderValue};
+use tokio::time::Instant;
 
-use crate::filter::{Filter, WrapSealed};
+use crate::filter::{filter_fn_one, Filter, WrapSealed};
 use crate::reject::IsReject;
-use crate::reply::Reply;
+use crate::reply::{Reply, Response};
 use crate::route::Route;
 
-use self::internal::WithTrace;
+use self::internal::{WithServerTiming, WithTrace};
 
 /// Create a wrapping filter that instruments every request with a `tracing`
 /// [`Span`] at the [`INFO`] level, containing a summary of the request.
@@ -123,6 +129,203 @@ pub fn named(name: &'static str) -> Trace<impl Fn(Info<'_>) -> Span + Copy> {
     trace(move |_| tracing::debug_span!("context", "{}", name,))
 }
 
+/// Create a wrapping filter that adds a `server-timing` header to replies,
+/// so browser developer tools can show where the time went.
+///
+/// The header lists the timings recorded with [`timings`] by the handler,
+/// those of filters marked with [`Filter::instrument`], and a `total` metric
+/// for the whole wrapped filter. Rejections are passed through untouched.
+///
+/// # Example
+///
+/// ```
+/// use std::time::Duration;
+/// use nextshell::Filter;
+/// use nextshell::trace::ServerTimings;
+///
+/// let route = nextshell::path("report")
+///     .and(nextshell::trace::timings())
+///     .map(|timings: ServerTimings| {
+///         let db = timings.start("db");
+///         // query the database...
+///         drop(db);
+///         timings.record_with_description("cache", "cache miss", Duration::from_millis(2));
+///         "report"
+///     })
+///     .with(nextshell::trace::server_timing());
+/// ```
+pub fn server_timing() -> ServerTiming {
+    ServerTiming { _p: () }
+}
+
+/// Extract the [`ServerTimings`] of the request, to record timings shown
+/// by [`server_timing`].
+///
+/// Outside of a `server_timing` wrapper, the recorded timings are dropped.
+pub fn timings() -> impl Filter<Extract = (ServerTimings,), Error = Infallible> + Copy {
+    filter_fn_one(|route| {
+        let timings = route
+            .extensions()
+            .get::<ServerTimings>()
+            .cloned()
+            .unwrap_or_default();
+        future::ok(timings)
+    })
+}
+
+/// Decorates a [`Filter`] to add a `server-timing` header to replies.
+#[derive(Clone, Copy, Debug)]
+pub struct ServerTiming {
+    _p: (),
+}
+
+impl<F> WrapSealed<F> for ServerTiming
+where
+    F: Filter + Clone + Send,
+    F::Extract: Reply,
+    F::Error: IsReject,
+{
+    type Wrapped = WithServerTiming<F>;
+
+    fn wrap(&self, filter: F) -> Self::Wrapped {
+        WithServerTiming { filter }
+    }
+}
+
+/// The timings of a request, reported in its `server-timing` header.
+///
+/// Cloning shares the same timings.
+#[derive(Clone, Debug, Default)]
+pub struct ServerTimings {
+    metrics: Arc<Mutex<Vec<Metric>>>,
+}
+
+#[derive(Debug)]
+struct Metric {
+    name: String,
+    description: Option<String>,
+    duration: Duration,
+}
+
+impl ServerTimings {
+    /// Record that `name` took `duration`.
+    ///
+    /// Characters not allowed in a metric name are replaced with `_`.
+    pub fn record(&self, name: impl Into<String>, duration: Duration) {
+        self.push(name.into(), None, duration);
+    }
+
+    /// Record that `name` took `duration`, with a human readable
+    /// `description`.
+    pub fn record_with_description(
+        &self,
+        name: impl Into<String>,
+        description: impl Into<String>,
+        duration: Duration,
+    ) {
+        self.push(name.into(), Some(description.into()), duration);
+    }
+
+    /// Start timing `name`, until the returned guard is dropped.
+    pub fn start(&self, name: impl Into<String>) -> TimingGuard {
+        TimingGuard {
+            timings: self.clone(),
+            name: Some(name.into()),
+            started: Instant::now(),
+        }
+    }
+
+    fn push(&self, name: String, description: Option<String>, duration: Duration) {
+        self.metrics.lock().unwrap().push(Metric {
+            name,
+            description,
+            duration,
+        });
+    }
+
+    fn header(&self, total: Duration, instrumented: Option<&crate::filter::Timings>) -> String {
+        let mut out = String::new();
+        for metric in self.metrics.lock().unwrap().iter() {
+            write_metric(
+                &mut out,
+                &metric.name,
+                metric.description.as_deref(),
+                metric.duration,
+            );
+        }
+        for timing in instrumented.iter().flat_map(|timings| &timings.0) {
+            let description = if timing.outcome == "ok" {
+                None
+            } else {
+                Some(timing.outcome)
+            };
+            write_metric(&mut out, timing.name, description, timing.elapsed);
+        }
+        write_metric(&mut out, "total", None, total);
+        out
+    }
+}
+
+fn write_metric(out: &mut String, name: &str, description: Option<&str>, duration: Duration) {
+    if !out.is_empty() {
+        out.push_str(", ");
+    }
+    // A metric name is a token, and a description a quoted string.
+    out.extend(name.chars().map(|c| {
+        if c.is_ascii_alphanumeric() || "!#$%&'*+-.^_`|~".contains(c) {
+            c
+        } else {
+            '_'
+        }
+    }));
+    if let Some(description) = description {
+        out.push_str(";desc=\"");
+        for c in description
+            .chars()
+            .filter(|c| *c == '\t' || (' '..='~').contains(c))
+        {
+            if c == '"' || c == '\\' {
+                out.push('\\');
+            }
+            out.push(c);
+        }
+        out.push('"');
+    }
+    let _ = write!(out, ";dur={:.1}", duration.as_secs_f64() * 1000.0);
+}
+
+fn add_server_timing(mut res: Response, timings: &ServerTimings, started: Instant) -> Response {
+    let instrumented =
+        crate::route::with(|route| route.extensions().get::<crate::filter::Timings>().cloned());
+    let header = timings.header(started.elapsed(), instrumented.as_ref());
+    match HeaderValue::from_str(&header) {
+        Ok(value) => {
+            res.headers_mut()
+                .append(HeaderName::from_static("server-timing"), value);
+        }
+        Err(err) => tracing::warn!("invalid server-timing header {:?}: {}", header, err),
+    }
+    res
+}
+
+/// Records a timing when dropped.
+///
+/// Returned by [`ServerTimings::start`].
+#[derive(Debug)]
+pub struct TimingGuard {
+    timings: ServerTimings,
+    name: Option<String>,
+    started: Instant,
+}
+
+impl Drop for TimingGuard {
+    fn drop(&mut self) {
+        if let Some(name) = self.name.take() {
+            self.timings.push(name, None, self.started.elapsed());
+        }
+    }
+}
+
 /// Decorates a [`Filter`] to create a [`tracing`] [span] for
 /// requests and responses.
 ///
@@ -208,9 +411,15 @@ impl<'a> Info<'a> {
 }
 
 mod internal {
-    use futures_util::{future::Inspect, future::MapOk, FutureExt, TryFutureExt};
+    use std::future::Future;
+    use std::pin::Pin;
+    use std::task::{Context, Poll};
+
+    use futures_util::{future::Inspect, future::MapOk, FutureExt, TryFuture, TryFutureExt};
+    use pin_project::pin_project;
+    use tokio::time::Instant;
 
-    use super::{Info, Trace};
+    use super::{Info, ServerTimings, Trace};
     use crate::filter::{Filter, FilterBase, Internal};
     use crate::reject::IsReject;
     use crate::reply::Reply;
@@ -306,4 +515,77 @@ mod internal {
                 .instrument(span.clone())
         }
     }
+
+    #[allow(missing_debug_implementations)]
+    pub struct Timed(pub(super) Response);
+
+    impl Reply for Timed {
+        #[inline]
+        fn into_response(self) -> Response {
+            self.0
+        }
+    }
+
+    #[allow(missing_debug_implementations)]
+    #[derive(Clone, Copy)]
+    pub struct WithServerTiming<F> {
+        pub(super) filter: F,
+    }
+
+    impl<F> FilterBase for WithServerTiming<F>
+    where
+        F: Filter + Clone + Send,
+        F::Extract: Reply,
+        F::Error: IsReject,
+    {
+        type Extract = (Timed,);
+        type Error = F::Error;
+        type Future = WithServerTimingFuture<F::Future>;
+
+        fn filter(&self, _: Internal) -> Self::Future {
+            let timings = route::with(|route| {
+                let extensions = route.extensions_mut();
+                match extensions.get::<ServerTimings>() {
+                    Some(timings) => timings.clone(),
+                    None => {
+                        let timings = ServerTimings::default();
+                        extensions.insert(timings.clone());
+                        timings
+                    }
+                }
+            });
+            WithServerTimingFuture {
+                future: self.filter.filter(Internal),
+                timings,
+                started: Instant::now(),
+            }
+        }
+    }
+
+    #[allow(missing_debug_implementations)]
+    #[pin_project]
+    pub struct WithServerTimingFuture<F> {
+        #[pin]
+        future: F,
+        timings: ServerTimings,
+        started: Instant,
+    }
+
+    impl<F> Future for WithServerTimingFuture<F>
+    where
+        F: TryFuture,
+        F::Ok: Reply,
+        F::Error: IsReject,
+    {
+        type Output = Result<(Timed,), F::Error>;
+
+        fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+            let pin = self.project();
+            let (timings, started) = (pin.timings, *pin.started);
+            pin.future.try_poll(cx).map_ok(|reply| {
+                let res = super::add_server_timing(reply.into_response(), timings, started);
+                (Timed(res),)
+            })
+        }
+    }
 }
@@ -5,6 +5,7 @@ use std::fmt;
 use std::future::Future;
 use std::pin::Pin;
 use std::task::{Context, Poll};
+use std::time::Duration;
 
 use super::header;
 use crate::filter::{filter_fn_one, Filter, One};
@@ -13,8 +14,12 @@ use crate::reply::{Reply, Response};
 use futures_util::{future, ready, FutureExt, Sink, Stream, TryFutureExt};
 use headers::{Connection, HeaderMapExt, SecWebsocketAccept, SecWebsocketKey, Upgrade};
 use hyper::upgrade::OnUpgrade;
+use tokio::time::{Instant, Interval, MissedTickBehavior, Sleep};
 use tokio_tungstenite::{
-    tungstenite::protocol::{self, WebSocketConfig},
+    tungstenite::{
+        self,
+        protocol::{self, frame::coding::CloseCode, CloseFrame, WebSocketConfig},
+    },
     WebSocketStream,
 };
 
@@ -61,6 +66,8 @@ pub fn ws() -> impl Filter<Extract = One<Ws>, Error = Rejection> + Copy {
         .map(
             move |key: SecWebsocketKey, on_upgrade: Option<OnUpgrade>| Ws {
                 config: None,
+                keepalive: KeepaliveConfig::default(),
+                close_timeout: DEFAULT_CLOSE_TIMEOUT,
                 key,
                 on_upgrade,
             },
@@ -70,10 +77,16 @@ pub fn ws() -> impl Filter<Extract = One<Ws>, Error = Rejection> + Copy {
 /// Extracted by the [`ws`] filter, and used to finish an upgrade.
 pub struct Ws {
     config: Option<WebSocketConfig>,
+    keepalive: KeepaliveConfig,
+    close_timeout: Duration,
     key: SecWebsocketKey,
     on_upgrade: Option<OnUpgrade>,
 }
 
+/// How long [`WebSocket::close_with`] waits for the peer's close frame by
+/// default.
+const DEFAULT_CLOSE_TIMEOUT: Duration = Duration::from_secs(5);
+
 impl Ws {
     /// Finish the upgrade, passing a function to handle the `WebSocket`.
     ///
@@ -102,6 +115,9 @@ impl Ws {
     }
 
     /// The max size of the write buffer, in bytes.
+    ///
+    /// This bounds outgoing bytes buffered while the client isn't reading:
+    /// past it, sending fails until the buffer drains. Unlimited by default.
     pub fn max_write_buffer_size(mut self, max: usize) -> Self {
         self.config
             .get_or_insert_with(WebSocketConfig::default)
@@ -110,6 +126,9 @@ impl Ws {
     }
 
     /// Set the maximum message size (defaults to 64 megabytes)
+    ///
+    /// A client sending a bigger message gets a close frame with code 1009
+    /// (message too big), and the `WebSocket` stream yields an error.
     pub fn max_message_size(mut self, max: usize) -> Self {
         self.config
             .get_or_insert_with(WebSocketConfig::default)
@@ -118,12 +137,45 @@ impl Ws {
     }
 
     /// Set the maximum frame size (defaults to 16 megabytes)
+    ///
+    /// Violations close the connection like
+    /// [`max_message_size`](Ws::max_message_size).
     pub fn max_frame_size(mut self, max: usize) -> Self {
         self.config
             .get_or_insert_with(WebSocketConfig::default)
             .max_frame_size = Some(max);
         self
     }
+
+    /// Send a ping to the client at this interval.
+    ///
+    /// If the client hasn't answered the previous ping, or sent anything
+    /// else, by the time the next one is due, the connection is considered
+    /// dead: it is closed and the `WebSocket` stream yields an error. Set
+    /// [`idle_timeout`](Ws::idle_timeout) to control that deadline instead.
+    ///
+    /// Like pongs, keepalive only runs while the `WebSocket` is being read.
+    pub fn ping_interval(mut self, interval: Duration) -> Self {
+        self.keepalive.ping_interval = Some(interval);
+        self
+    }
+
+    /// Close the connection when nothing, pongs included, has been received
+    /// from the client for this long.
+    ///
+    /// The `WebSocket` stream then yields an error, and ends.
+    pub fn idle_timeout(mut self, timeout: Duration) -> Self {
+        self.keepalive.idle_timeout = Some(timeout);
+        self
+    }
+
+    /// How long [`WebSocket::close_with`] waits for the client to answer
+    /// with its own close frame before dropping the connection (defaults to
+    /// 5 seconds).
+    pub fn close_timeout(mut self, timeout: Duration) -> Self {
+        self.close_timeout = timeout;
+        self
+    }
 }
 
 impl fmt::Debug for Ws {
@@ -147,10 +199,17 @@ where
         if let Some(on_upgrade) = self.ws.on_upgrade {
             let on_upgrade_cb = self.on_upgrade;
             let config = self.ws.config;
+            let keepalive = self.ws.keepalive;
+            let close_timeout = self.ws.close_timeout;
             let fut = on_upgrade
                 .and_then(move |upgraded| {
                     tracing::trace!("websocket upgrade complete");
-                    WebSocket::from_raw_socket(upgraded, protocol::Role::Server, config).map(Ok)
+                    WebSocket::from_raw_socket(upgraded, protocol::Role::Server, config).map(
+                        move |mut socket| {
+                            socket.close_timeout = close_timeout;
+                            Ok(socket.with_keepalive(keepalive))
+                        },
+                    )
                 })
                 .and_then(move |socket| on_upgrade_cb(socket).map(Ok))
                 .map(|result| {
@@ -192,6 +251,55 @@ fn on_upgrade() -> impl Filter<Extract = (Option<OnUpgrade>,), Error = Rejection
 
 pub struct WebSocket {
     inner: WebSocketStream<hyper::upgrade::Upgraded>,
+    keepalive: Option<Keepalive>,
+    closing: bool,
+    peer_close: Option<(u16, String)>,
+    close_timeout: Duration,
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+struct KeepaliveConfig {
+    ping_interval: Option<Duration>,
+    idle_timeout: Option<Duration>,
+}
+
+struct Keepalive {
+    ping: Option<Interval>,
+    ping_due: bool,
+    awaiting_pong: bool,
+    idle_timeout: Option<Duration>,
+    idle: Option<Pin<Box<Sleep>>>,
+}
+
+impl Keepalive {
+    fn new(config: KeepaliveConfig) -> Option<Self> {
+        if config.ping_interval.is_none() && config.idle_timeout.is_none() {
+            return None;
+        }
+        let ping = config.ping_interval.map(|period| {
+            let mut ping = tokio::time::interval_at(Instant::now() + period, period);
+            ping.set_missed_tick_behavior(MissedTickBehavior::Delay);
+            ping
+        });
+        let idle = config
+            .idle_timeout
+            .map(|timeout| Box::pin(tokio::time::sleep(timeout)));
+        Some(Keepalive {
+            ping,
+            ping_due: false,
+            awaiting_pong: false,
+            idle_timeout: config.idle_timeout,
+            idle,
+        })
+    }
+
+    // Anything from the peer proves the connection is alive.
+    fn on_receive(&mut self) {
+        self.awaiting_pong = false;
+        if let (Some(idle), Some(timeout)) = (&mut self.idle, self.idle_timeout) {
+            idle.as_mut().reset(Instant::now() + timeout);
+        }
+    }
 }
 
 impl WebSocket {
@@ -201,24 +309,182 @@ impl WebSocket {
         config: Option<protocol::WebSocketConfig>,
     ) -> Self {
         WebSocketStream::from_raw_socket(upgraded, role, config)
-            .map(|inner| WebSocket { inner })
+            .map(|inner| WebSocket {
+                inner,
+                keepalive: None,
+                closing: false,
+                peer_close: None,
+                close_timeout: DEFAULT_CLOSE_TIMEOUT,
+            })
             .await
     }
 
+    fn with_keepalive(mut self, config: KeepaliveConfig) -> Self {
+        self.keepalive = Keepalive::new(config);
+        self
+    }
+
+    /// Polls the keepalive timers, resolving with an error once the peer is
+    /// considered dead.
+    fn poll_keepalive(&mut self, cx: &mut Context<'_>) -> Poll<crate::Error> {
+        let keepalive = match &mut self.keepalive {
+            Some(keepalive) => keepalive,
+            None => return Poll::Pending,
+        };
+
+        let mut dead = None;
+        if let Some(idle) = &mut keepalive.idle {
+            if idle.as_mut().poll(cx).is_ready() {
+                dead = Some("websocket idle timeout");
+            }
+        }
+        if let Some(ping) = &mut keepalive.ping {
+            while dead.is_none() && ping.poll_tick(cx).is_ready() {
+                if keepalive.awaiting_pong && keepalive.idle.is_none() {
+                    dead = Some("websocket ping not answered");
+                }
+                keepalive.ping_due = true;
+            }
+        }
+        if let Some(reason) = dead {
+            tracing::debug!("{}, closing", reason);
+            self.closing = true;
+            self.start_close(cx, CloseCode::Away, "idle timeout");
+            return Poll::Ready(crate::Error::new(reason));
+        }
+
+        if keepalive.ping_due {
+            match Pin::new(&mut self.inner).poll_ready(cx) {
+                Poll::Ready(Ok(())) => {
+                    if let Err(err) =
+                        Pin::new(&mut self.inner).start_send(protocol::Message::Ping(Vec::new()))
+                    {
+                        return Poll::Ready(crate::Error::new(err));
+                    }
+                    keepalive.ping_due = false;
+                    keepalive.awaiting_pong = true;
+                    // Whatever isn't flushed now goes out with the next read.
+                    let _ = Pin::new(&mut self.inner).poll_flush(cx);
+                }
+                Poll::Ready(Err(err)) => return Poll::Ready(crate::Error::new(err)),
+                Poll::Pending => {}
+            }
+        }
+        Poll::Pending
+    }
+
+    /// Queues a close frame, without waiting for it to be written.
+    fn start_close(&mut self, cx: &mut Context<'_>, code: CloseCode, reason: &'static str) {
+        let mut inner = Pin::new(&mut self.inner);
+        if let Poll::Ready(Ok(())) = inner.as_mut().poll_ready(cx) {
+            let frame = CloseFrame {
+                code,
+                reason: reason.into(),
+            };
+            let _ = inner
+                .as_mut()
+                .start_send(protocol::Message::Close(Some(frame)));
+            let _ = inner.poll_flush(cx);
+        }
+    }
+
     /// Gracefully close this websocket.
     pub async fn close(mut self) -> Result<(), crate::Error> {
         future::poll_fn(|cx| Pin::new(&mut self).poll_close(cx)).await
     }
+
+    /// Close this websocket with a code and reason, and wait for the peer
+    /// to acknowledge it.
+    ///
+    /// Messages received in the meantime are discarded. Resolves with the
+    /// peer's close code and reason, as returned by
+    /// [`peer_close_frame`](WebSocket::peer_close_frame).
+    ///
+    /// A peer that doesn't complete the handshake within the
+    /// [`close_timeout`](Ws::close_timeout) is dropped, resolving with
+    /// `None`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # async fn handle(websocket: nextshell::ws::WebSocket) -> Result<(), nextshell::Error> {
+    /// if let Some((code, reason)) = websocket.close_with(1000u16, "bye").await? {
+    ///     println!("client closed with {}: {}", code, reason);
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn close_with(
+        mut self,
+        code: impl Into<u16>,
+        reason: impl Into<Cow<'static, str>>,
+    ) -> Result<Option<(u16, String)>, crate::Error> {
+        use futures_util::{SinkExt, StreamExt};
+
+        if self.peer_close.is_none() {
+            self.send(Message::close_with(code, reason)).await?;
+        }
+        // The stream ends once the close handshake completes.
+        let timeout = self.close_timeout;
+        let handshake = async {
+            while let Some(msg) = self.next().await {
+                msg?;
+            }
+            Ok::<_, crate::Error>(())
+        };
+        match tokio::time::timeout(timeout, handshake).await {
+            Ok(result) => result?,
+            Err(_) => {
+                tracing::debug!("websocket close not acknowledged within {:?}", timeout);
+                return Ok(None);
+            }
+        }
+        Ok(self.peer_close)
+    }
+
+    /// The close code and reason sent by the peer, once its close frame has
+    /// been received.
+    ///
+    /// A close frame without a code is reported as 1005 (no status
+    /// received), with an empty reason.
+    pub fn peer_close_frame(&self) -> Option<(u16, &str)> {
+        self.peer_close
+            .as_ref()
+            .map(|(code, reason)| (*code, reason.as_str()))
+    }
 }
 
 impl Stream for WebSocket {
     type Item = Result<Message, crate::Error>;
 
     fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
-        match ready!(Pin::new(&mut self.inner).poll_next(cx)) {
-            Some(Ok(item)) => Poll::Ready(Some(Ok(Message { inner: item }))),
+        if self.closing {
+            return Poll::Ready(None);
+        }
+        let item = match Pin::new(&mut self.inner).poll_next(cx) {
+            Poll::Ready(item) => item,
+            Poll::Pending => return self.poll_keepalive(cx).map(|err| Some(Err(err))),
+        };
+        match item {
+            Some(Ok(item)) => {
+                if let Some(keepalive) = &mut self.keepalive {
+                    keepalive.on_receive();
+                }
+                if let protocol::Message::Close(ref frame) = item {
+                    self.peer_close = Some(match frame {
+                        Some(frame) => (frame.code.into(), frame.reason.to_string()),
+                        None => (CloseCode::Status.into(), String::new()),
+                    });
+                }
+                Poll::Ready(Some(Ok(Message { inner: item })))
+            }
             Some(Err(e)) => {
                 tracing::debug!("websocket poll error: {}", e);
+                if let tungstenite::Error::Capacity(_) = e {
+                    // The peer broke a size limit: tell it so, and stop reading.
+                    self.closing = true;
+                    self.start_close(cx, CloseCode::Size, "message too big");
+                }
                 Poll::Ready(Some(Err(crate::Error::new(e))))
             }
             None => {
@@ -95,10 +95,10 @@ pub mod reject;
 pub mod reply;
 mod route;
 mod server;
-mod service;
+pub mod service;
 pub mod test;
 #[cfg(feature = "tls")]
-mod tls;
+pub mod tls;
 mod transport;
 
 pub use self::error::Error;
@@ -121,35 +121,57 @@ pub use self::filters::{
     // any() function
     any::any,
     body,
+    cache,
+    // cache() function
+    cache::cache,
     cookie,
     // cookie() function
     cookie::cookie,
     cors,
     // cors() function
     cors::cors,
+    etag,
+    // etag() function
+    etag::etag,
     ext,
     fs,
     header,
     // header() function
     header::header,
+    health,
+    // health() function
+    health::health,
     host,
     log,
     // log() function
     log::log,
-    method::{delete, get, head, method, options, patch, post, put},
+    method::{auto_head, auto_options, delete, get, head, method, options, patch, post, put},
+    metrics,
+    // metrics() function
+    metrics::metrics,
     path,
     // path() function and macro
     path::path,
+    precondition,
+    // precondition() function
+    precondition::precondition,
+    proxy,
     query,
     // query() function
     query::query,
+    range,
+    // range() function
+    range::range,
     sse,
+    state,
+    // state() function
+    state::state,
     trace,
     // trace() function
     trace::trace,
 };
 // ws() function
-pub use self::filter::wrap_fn;
+pub use self::filter::{wrap_fn, wrap_layer};
 #[cfg(feature = "websocket")]
 #[doc(hidden)]
 pub use self::filters::ws::ws;
@@ -162,8 +184,12 @@ pub use self::reject::{reject, Rejection};
 pub use self::reply::{reply, Reply};
 #[cfg(feature = "tls")]
 pub use self::server::TlsServer;
-pub use self::server::{serve, Server};
+#[cfg(unix)]
+pub use self::server::UnixSocket;
+pub use self::server::{serve, RequestInfo, Server, ServerHandle};
 pub use self::service::service;
+#[cfg(feature = "tls")]
+pub use self::tls::TlsVersion;
 #[doc(hidden)]
 pub use http;
 #[doc(hidden)]
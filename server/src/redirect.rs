@@ -3,10 +3,18 @@
 //! The types in this module are helpers that implement [`Reply`], and easy
 //! to use in order to setup redirects.
 
+use std::convert::Infallible;
+
+use futures_util::future;
+use http::header::HeaderValue;
+use http::uri::{PathAndQuery, Scheme, Uri};
 use http::{header, StatusCode};
 
 pub use self::sealed::AsLocation;
-use crate::reply::{self, Reply};
+use crate::filter::{filter_fn_one, Filter, FilterBase, Internal, One};
+use crate::host::Authority;
+use crate::reject::{self, Rejection};
+use crate::reply::{self, Reply, Response};
 
 /// HTTP 301 Moved Permanently
 /// Description: The requested resource has been permanently moved to a new URL.
@@ -123,6 +131,212 @@ pub fn permanent(uri: impl AsLocation) -> impl Reply {
     )
 }
 
+/// Creates a `Filter` redirecting plain HTTP requests to HTTPS.
+///
+/// Requests reaching the server over `http`, as told by
+/// [`host::scheme`](crate::host::scheme), are answered with a
+/// `301 Moved Permanently` to the same path and query over `https`, on the
+/// default port. Requests already over `https` are rejected, so other
+/// routes can handle them.
+///
+/// Rejects with `400 Bad Request` if the request has no `host`.
+///
+/// # Example
+///
+/// ```no_run
+/// // Bind port 80, and send everyone to the HTTPS server.
+/// # async fn run() {
+/// nextshell::serve(nextshell::redirect::to_https())
+///     .run(([0, 0, 0, 0], 80))
+///     .await;
+/// # }
+/// ```
+pub fn to_https() -> impl Filter<Extract = One<Response>, Error = Rejection> + Copy {
+    crate::host::scheme()
+        .and(crate::host::optional())
+        .and(filter_fn_one(|route| {
+            future::ok::<_, Rejection>(route.uri().path_and_query().cloned())
+        }))
+        .and_then(
+            |scheme: Scheme, authority: Option<Authority>, path: Option<PathAndQuery>| {
+                if scheme == Scheme::HTTPS {
+                    return future::err(reject::not_found());
+                }
+                let authority = match authority {
+                    Some(authority) => authority,
+                    None => return future::err(reject::missing_header("host")),
+                };
+                let uri = Uri::builder()
+                    .scheme(Scheme::HTTPS)
+                    .authority(authority.host())
+                    .path_and_query(path.unwrap_or_else(|| PathAndQuery::from_static("/")))
+                    .build();
+                match uri {
+                    Ok(uri) => future::ok(redirect(uri).into_response()),
+                    Err(err) => {
+                        tracing::debug!("https redirect error: {}", err);
+                        future::err(reject::invalid_header("host"))
+                    }
+                }
+            },
+        )
+}
+
+/// Creates a `Filter` redirecting to `location`, which can be relative to
+/// the request.
+///
+/// Unlike the other redirects of this module, the location doesn't have to
+/// be an absolute `Uri`: a relative path, such as `"../new"` or `"new"`, is
+/// resolved against the request path, as browsers would. The original query
+/// string can also be carried over with [`preserve_query`](To::preserve_query).
+///
+/// Redirects with `301 Moved Permanently` by default.
+///
+/// # Panics
+///
+/// If `location` isn't a valid header value.
+///
+/// # Example
+///
+/// ```
+/// use nextshell::{http::StatusCode, Filter};
+///
+/// // `/docs/old?page=2` redirects to `/docs/new?page=2`.
+/// let route = nextshell::path!("docs" / "old")
+///     .and(nextshell::redirect::to("new").preserve_query());
+///
+/// let login = nextshell::path!("admin")
+///     .and(nextshell::redirect::to("/login?next=admin").status(StatusCode::SEE_OTHER));
+/// ```
+pub fn to(location: impl Into<String>) -> To {
+    let location = location.into();
+    if HeaderValue::from_str(&location).is_err() {
+        panic!("invalid redirect location: {:?}", location);
+    }
+    To {
+        location,
+        status: StatusCode::MOVED_PERMANENTLY,
+        preserve_query: false,
+    }
+}
+
+/// A `Filter` redirecting to a location, created by [`to`].
+#[derive(Clone, Debug)]
+pub struct To {
+    location: String,
+    status: StatusCode,
+    preserve_query: bool,
+}
+
+impl To {
+    /// Sets the status code of the redirect.
+    ///
+    /// # Panics
+    ///
+    /// If `status` isn't a redirection (`3xx`) status code.
+    pub fn status(mut self, status: StatusCode) -> Self {
+        assert!(
+            status.is_redirection(),
+            "redirect status must be 3xx, got {}",
+            status
+        );
+        self.status = status;
+        self
+    }
+
+    /// Appends the query string of the request to the location.
+    ///
+    /// If the location has a query string too, both are kept, the
+    /// location's first.
+    pub fn preserve_query(mut self) -> Self {
+        self.preserve_query = true;
+        self
+    }
+
+    fn resolve(&self, base: &Uri) -> String {
+        let (target, fragment) = match self.location.find('#') {
+            Some(idx) => self.location.split_at(idx),
+            None => (self.location.as_str(), ""),
+        };
+        let (path, query) = match target.find('?') {
+            Some(idx) => (&target[..idx], Some(&target[idx + 1..])),
+            None => (target, None),
+        };
+
+        let mut location = if path.contains("://") || path.starts_with("//") {
+            path.to_owned()
+        } else if path.starts_with('/') {
+            remove_dot_segments(path)
+        } else if path.is_empty() {
+            base.path().to_owned()
+        } else {
+            let base = base.path();
+            let dir = &base[..base.rfind('/').map_or(0, |idx| idx + 1)];
+            remove_dot_segments(&format!("{}{}", dir, path))
+        };
+
+        let base_query = if self.preserve_query {
+            base.query().filter(|query| !query.is_empty())
+        } else {
+            None
+        };
+        match (query, base_query) {
+            (Some(query), Some(base_query)) if !query.is_empty() => {
+                location.push('?');
+                location.push_str(query);
+                location.push('&');
+                location.push_str(base_query);
+            }
+            (_, Some(query)) | (Some(query), None) => {
+                location.push('?');
+                location.push_str(query);
+            }
+            (None, None) => (),
+        }
+        location.push_str(fragment);
+        location
+    }
+}
+
+/// Removes the `.` and `..` segments of an absolute path, as described in
+/// RFC 3986 section 5.2.4.
+fn remove_dot_segments(path: &str) -> String {
+    let mut segments: Vec<&str> = Vec::new();
+    let mut iter = path.split('/').skip(1).peekable();
+    while let Some(segment) = iter.next() {
+        let last = iter.peek().is_none();
+        match segment {
+            "." | ".." => {
+                if segment == ".." {
+                    segments.pop();
+                }
+                // `/a/b/..` is the directory `/a/`.
+                if last {
+                    segments.push("");
+                }
+            }
+            segment => segments.push(segment),
+        }
+    }
+    format!("/{}", segments.join("/"))
+}
+
+impl FilterBase for To {
+    type Extract = One<Response>;
+    type Error = Infallible;
+    type Future = future::Ready<Result<Self::Extract, Self::Error>>;
+
+    fn filter(&self, _: Internal) -> Self::Future {
+        let location = crate::route::with(|route| self.resolve(route.uri()));
+        let mut res = Response::default();
+        *res.status_mut() = self.status;
+        let location =
+            HeaderValue::from_str(&location).expect("resolved location is a valid header value");
+        res.headers_mut().insert(header::LOCATION, location);
+        future::ok((res,))
+    }
+}
+
 mod sealed {
     use bytes::Bytes;
     use http::{header::HeaderValue, Uri};
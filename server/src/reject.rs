@@ -55,18 +55,37 @@
 //!     })
 //!     .recover(handle_rejection);
 //! ```
+//!
+//! Custom rejections can also be [`register`]ed once with a status and a
+//! message, so the default handling replies with them instead of a `500`,
+//! without a `recover` at all.
+//!
+//! When several rejections are combined by `or`, the one with the most
+//! specific status is replied with, ranking `404` and then `405` lowest.
+//! Wrap a filter tree [`with`](../trait.Filter.html#method.with) a
+//! [`priority`] to rank them differently.
+//!
+//! JSON APIs can instead [`recover`](../trait.Filter.html#method.recover)
+//! with [`problem_json`], replying to every rejection with an RFC 7807
+//! `application/problem+json` body.
 
-use std::any::Any;
+use std::any::{Any, TypeId};
+use std::collections::HashMap;
 use std::convert::Infallible;
 use std::error::Error as StdError;
 use std::fmt;
+use std::sync::{Arc, OnceLock, RwLock};
 
+use futures_util::future;
 use http::{
-    header::{HeaderValue, CONTENT_TYPE},
+    header::{HeaderValue, CONTENT_TYPE, VARY},
     StatusCode,
 };
 use hyper::Body;
 
+use crate::filter::{Filter, WrapSealed};
+
+use self::priority::WithPriority;
 pub(crate) use self::sealed::{CombineRejection, IsReject};
 
 /// Rejects a request with `404 Not Found`.
@@ -113,12 +132,29 @@ pub(crate) fn method_not_allowed() -> Rejection {
     known(MethodNotAllowed { _p: () })
 }
 
+// 408 Request Timeout
+//
+// Used by the body filters if the request body stalls for too long.
+#[inline]
+pub(crate) fn request_timeout() -> Rejection {
+    known(RequestTimeout { _p: () })
+}
+
 // 411 Length Required
 #[inline]
 pub(crate) fn length_required() -> Rejection {
     known(LengthRequired { _p: () })
 }
 
+// 412 Precondition Failed
+//
+// Used by the precondition filters if `if-match` or `if-unmodified-since`
+// doesn't hold.
+#[inline]
+pub(crate) fn precondition_failed() -> Rejection {
+    known(PreconditionFailed { _p: () })
+}
+
 // 413 Payload Too Large
 #[inline]
 pub(crate) fn payload_too_large() -> Rejection {
@@ -134,6 +170,14 @@ pub(crate) fn unsupported_media_type() -> Rejection {
     known(UnsupportedMediaType { _p: () })
 }
 
+// 431 Request Header Fields Too Large
+//
+// Used by the server if the request headers are over its limits.
+#[inline]
+pub(crate) fn header_fields_too_large() -> Rejection {
+    known(HeaderFieldsTooLarge { _p: () })
+}
+
 /// Rejects a request with a custom cause.
 ///
 /// A [`recover`][] filter should convert this `Rejection` into a `Reply`,
@@ -144,6 +188,166 @@ pub fn custom<T: Reject>(err: T) -> Rejection {
     Rejection::custom(Box::new(err))
 }
 
+/// Register how the default rejection handling replies to custom rejections
+/// of type `T`, with `status` and the body built by `message`.
+///
+/// Registered rejections are no longer treated as unhandled `500`s. When
+/// combined with others in an `or` chain, their `status` is compared like
+/// the built-in ones to pick the response. Registering a type again
+/// replaces its previous registration.
+///
+/// Registration is global to the process, and usually done once at
+/// startup. A [`recover`][] filter still sees registered rejections first.
+///
+/// # Example
+///
+/// ```
+/// use nextshell::{http::StatusCode, reject::{self, Reject}, Filter};
+///
+/// #[derive(Debug)]
+/// struct RateLimited {
+///     retry_in: u64,
+/// }
+///
+/// impl Reject for RateLimited {}
+///
+/// reject::register(StatusCode::TOO_MANY_REQUESTS, |e: &RateLimited| {
+///     format!("Rate limited, retry in {}s", e.retry_in)
+/// });
+///
+/// let route = nextshell::any().and_then(|| async {
+///     Err::<&str, _>(reject::custom(RateLimited { retry_in: 5 }))
+/// });
+/// ```
+///
+/// [`recover`]: ../trait.Filter.html#method.recover
+pub fn register<T, F>(status: StatusCode, message: F)
+where
+    T: Reject,
+    F: Fn(&T) -> String + Send + Sync + 'static,
+{
+    let registered = Registered {
+        status,
+        message: Arc::new(move |cause| {
+            message(cause.downcast_ref().expect("registered under its TypeId"))
+        }),
+    };
+    registry()
+        .write()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+        .insert(TypeId::of::<T>(), registered);
+}
+
+#[derive(Clone)]
+struct Registered {
+    status: StatusCode,
+    message: Message,
+}
+
+type Message = Arc<dyn Fn(&dyn Any) -> String + Send + Sync>;
+
+fn registry() -> &'static RwLock<HashMap<TypeId, Registered>> {
+    static REGISTRY: OnceLock<RwLock<HashMap<TypeId, Registered>>> = OnceLock::new();
+    REGISTRY.get_or_init(Default::default)
+}
+
+// Looks up the registration of a custom rejection, if any.
+//
+// The registration is cloned out of the registry, so that its message
+// builder runs without holding the lock, and may itself register types.
+fn registered(cause: &dyn Cause) -> Option<Registered> {
+    let cause = cause.as_any();
+    registry()
+        .read()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+        .get(&cause.type_id())
+        .cloned()
+}
+
+/// A [`recover`][] handler replying to any rejection with an RFC 7807
+/// `application/problem+json` body.
+///
+/// The body has the `type` (always `about:blank`), `title` and `status` of
+/// the rejection, and a `detail` for built-in and [`register`]ed ones.
+/// Unhandled custom rejections become a `500` without a `detail`, so their
+/// `Debug` output isn't leaked to clients.
+///
+/// # Example
+///
+/// ```
+/// use nextshell::Filter;
+///
+/// let route = nextshell::path("hello")
+///     .map(|| "world")
+///     .recover(nextshell::reject::problem_json());
+/// ```
+///
+/// [`recover`]: ../trait.Filter.html#method.recover
+pub fn problem_json(
+) -> impl Fn(Rejection) -> future::Ready<Result<crate::reply::Response, Infallible>> + Clone + Send + Sync
+{
+    |rejection: Rejection| future::ok(rejection.problem_json())
+}
+
+/// Create a wrapping filter ranking the rejections of the filters it wraps
+/// with `rank`, instead of the built-in precedence.
+///
+/// When rejections are combined by `or`, the one whose status has the
+/// highest rank is replied with. Ties fall back to the built-in precedence.
+///
+/// # Example
+///
+/// ```
+/// use nextshell::{http::StatusCode, Filter};
+///
+/// let hello = nextshell::get().and(nextshell::path("hello")).map(|| "hello");
+/// let bye = nextshell::post().and(nextshell::path("bye")).map(|| "bye");
+///
+/// // `GET /bye` is a `404`, not a `405`.
+/// let routes = hello
+///     .or(bye)
+///     .with(nextshell::reject::priority(|status| match status {
+///         StatusCode::NOT_FOUND => 1,
+///         _ => 0,
+///     }));
+/// ```
+pub fn priority<F>(rank: F) -> Priority
+where
+    F: Fn(StatusCode) -> i32 + Send + Sync + 'static,
+{
+    Priority {
+        rank: Arc::new(rank),
+    }
+}
+
+/// Decorates a [`Filter`] to rank its rejections, see [`priority`].
+#[derive(Clone)]
+pub struct Priority {
+    rank: Rank,
+}
+
+type Rank = Arc<dyn Fn(StatusCode) -> i32 + Send + Sync>;
+
+impl fmt::Debug for Priority {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Priority").finish()
+    }
+}
+
+impl<F> WrapSealed<F> for Priority
+where
+    F: Filter<Error = Rejection> + Clone + Send,
+{
+    type Wrapped = WithPriority<F>;
+
+    fn wrap(&self, filter: F) -> Self::Wrapped {
+        WithPriority {
+            filter,
+            rank: self.rank.clone(),
+        }
+    }
+}
+
 /// Protect against re-rejecting a rejection.
 ///
 /// ```compile_fail
@@ -213,6 +417,9 @@ enum Reason {
 enum Rejections {
     Known(Known),
     Custom(Box<dyn Cause>),
+    // A `not_found` combined with others, kept for `priority` to rank it.
+    NotFound,
+    Ranked(Box<Rejections>, Rank),
     Combined(Box<Rejections>, Box<Rejections>),
 }
 
@@ -277,16 +484,24 @@ enum_known! {
     MissingCookie(MissingCookie),
     InvalidQuery(InvalidQuery),
     LengthRequired(LengthRequired),
+    PreconditionFailed(PreconditionFailed),
     PayloadTooLarge(PayloadTooLarge),
+    RequestTimeout(RequestTimeout),
     UnsupportedMediaType(UnsupportedMediaType),
+    HeaderFieldsTooLarge(HeaderFieldsTooLarge),
     FileOpenError(crate::fs::FileOpenError),
     FilePermissionError(crate::fs::FilePermissionError),
+    SymlinkForbidden(crate::fs::SymlinkForbidden),
+    DotfileForbidden(crate::fs::DotfileForbidden),
+    ExtensionForbidden(crate::fs::ExtensionForbidden),
     BodyReadError(crate::body::BodyReadError),
     BodyDeserializeError(crate::body::BodyDeserializeError),
     CorsForbidden(crate::cors::CorsForbidden),
     #[cfg(feature = "websocket")]
     MissingConnectionUpgrade(crate::ws::MissingConnectionUpgrade),
     MissingExtension(crate::ext::MissingExtension),
+    MissingState(crate::state::MissingState),
+    LayerError(crate::service::LayerError),
     BodyConsumedMultipleTimes(crate::body::BodyConsumedMultipleTimes),
 }
 
@@ -329,6 +544,44 @@ impl Rejection {
         None
     }
 
+    fn ranked(self, rank: Rank) -> Self {
+        match self.reason {
+            Reason::NotFound => self,
+            Reason::Other(other) => Rejection {
+                reason: Reason::Other(Box::new(Rejections::Ranked(other, rank))),
+            },
+        }
+    }
+
+    fn problem_json(&self) -> crate::reply::Response {
+        let (status, detail, vary) = match self.reason {
+            Reason::NotFound => (StatusCode::NOT_FOUND, None, None),
+            Reason::Other(ref other) => {
+                let preferred = other.preferred();
+                (preferred.status(), preferred.detail(), preferred.vary())
+            }
+        };
+        let mut problem = serde_json::json!({
+            "type": "about:blank",
+            "title": status.canonical_reason().unwrap_or("Unknown"),
+            "status": status.as_u16(),
+        });
+        if let Some(detail) = detail {
+            problem["detail"] = detail.into();
+        }
+
+        let mut res = http::Response::new(Body::from(problem.to_string()));
+        *res.status_mut() = status;
+        res.headers_mut().insert(
+            CONTENT_TYPE,
+            HeaderValue::from_static("application/problem+json"),
+        );
+        if let Some(vary) = vary {
+            res.headers_mut().insert(VARY, vary);
+        }
+        res
+    }
+
     /// Returns true if this Rejection was made via `nextshell::reject::not_found`.
     ///
     /// # Example
@@ -397,16 +650,16 @@ impl fmt::Debug for Reason {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match *self {
             Reason::NotFound => f.write_str("NotFound"),
-            Reason::Other(ref other) => match **other {
-                Rejections::Known(ref e) => fmt::Debug::fmt(e, f),
-                Rejections::Custom(ref e) => fmt::Debug::fmt(e, f),
-                Rejections::Combined(ref a, ref b) => {
-                    let mut list = f.debug_list();
-                    a.debug_list(&mut list);
-                    b.debug_list(&mut list);
-                    list.finish()
+            Reason::Other(ref other) => {
+                let mut causes = Vec::new();
+                other.causes(&mut causes);
+                // The `NotFound`s aren't causes, so one cause left alone
+                // isn't shown as a list.
+                match causes[..] {
+                    [cause] => cause.fmt(f),
+                    _ => f.debug_list().entries(causes).finish(),
                 }
-            },
+            }
         }
     }
 }
@@ -427,15 +680,28 @@ impl Rejections {
                 #[cfg(feature = "websocket")]
                 Known::MissingConnectionUpgrade(_) => StatusCode::BAD_REQUEST,
                 Known::LengthRequired(_) => StatusCode::LENGTH_REQUIRED,
+                Known::PreconditionFailed(_) => StatusCode::PRECONDITION_FAILED,
                 Known::PayloadTooLarge(_) => StatusCode::PAYLOAD_TOO_LARGE,
+                Known::RequestTimeout(_) => StatusCode::REQUEST_TIMEOUT,
                 Known::UnsupportedMediaType(_) => StatusCode::UNSUPPORTED_MEDIA_TYPE,
-                Known::FilePermissionError(_) | Known::CorsForbidden(_) => StatusCode::FORBIDDEN,
+                Known::HeaderFieldsTooLarge(_) => StatusCode::REQUEST_HEADER_FIELDS_TOO_LARGE,
+                Known::LayerError(_) => StatusCode::SERVICE_UNAVAILABLE,
+                Known::FilePermissionError(_)
+                | Known::SymlinkForbidden(_)
+                | Known::DotfileForbidden(_)
+                | Known::ExtensionForbidden(_)
+                | Known::CorsForbidden(_) => StatusCode::FORBIDDEN,
                 Known::FileOpenError(_)
                 | Known::MissingExtension(_)
+                | Known::MissingState(_)
                 | Known::BodyConsumedMultipleTimes(_) => StatusCode::INTERNAL_SERVER_ERROR,
             },
-            Rejections::Custom(..) => StatusCode::INTERNAL_SERVER_ERROR,
-            Rejections::Combined(..) => self.preferred().status(),
+            Rejections::Custom(ref e) => registered(&**e)
+                .map_or(StatusCode::INTERNAL_SERVER_ERROR, |registered| {
+                    registered.status
+                }),
+            Rejections::NotFound => StatusCode::NOT_FOUND,
+            Rejections::Ranked(..) | Rejections::Combined(..) => self.preferred().status(),
         }
     }
 
@@ -448,9 +714,22 @@ impl Rejections {
                     CONTENT_TYPE,
                     HeaderValue::from_static("text/plain; charset=utf-8"),
                 );
+                if let Some(vary) = self.vary() {
+                    res.headers_mut().insert(VARY, vary);
+                }
                 res
             }
             Rejections::Custom(ref e) => {
+                if let Some(registered) = registered(&**e) {
+                    let message = (registered.message)((**e).as_any());
+                    let mut res = http::Response::new(Body::from(message));
+                    *res.status_mut() = registered.status;
+                    res.headers_mut().insert(
+                        CONTENT_TYPE,
+                        HeaderValue::from_static("text/plain; charset=utf-8"),
+                    );
+                    return res;
+                }
                 tracing::error!(
                     "unhandled custom rejection, returning 500 response: {:?}",
                     e
@@ -464,7 +743,42 @@ impl Rejections {
                 );
                 res
             }
-            Rejections::Combined(..) => self.preferred().into_response(),
+            Rejections::NotFound => {
+                let mut res = http::Response::default();
+                *res.status_mut() = StatusCode::NOT_FOUND;
+                res
+            }
+            Rejections::Ranked(..) | Rejections::Combined(..) => self.preferred().into_response(),
+        }
+    }
+
+    // The human readable explanation of a preferred rejection.
+    fn detail(&self) -> Option<String> {
+        match *self {
+            Rejections::Known(ref e) => Some(e.to_string()),
+            Rejections::Custom(ref e) => {
+                let detail =
+                    registered(&**e).map(|registered| (registered.message)((**e).as_any()));
+                if detail.is_none() {
+                    tracing::error!(
+                        "unhandled custom rejection, returning 500 response: {:?}",
+                        e
+                    );
+                }
+                detail
+            }
+            Rejections::NotFound => None,
+            Rejections::Ranked(..) | Rejections::Combined(..) => self.preferred().detail(),
+        }
+    }
+
+    // The request headers a preferred rejection depends on, so that caches
+    // don't reuse it for other requests.
+    fn vary(&self) -> Option<HeaderValue> {
+        match *self {
+            Rejections::Known(Known::CorsForbidden(_)) => Some(HeaderValue::from_static("origin")),
+            Rejections::Known(_) | Rejections::Custom(_) | Rejections::NotFound => None,
+            Rejections::Ranked(..) | Rejections::Combined(..) => self.preferred().vary(),
         }
     }
 
@@ -472,32 +786,54 @@ impl Rejections {
         match *self {
             Rejections::Known(ref e) => e.inner_as_any().downcast_ref(),
             Rejections::Custom(ref e) => e.downcast_ref(),
+            Rejections::NotFound => None,
+            Rejections::Ranked(ref r, _) => r.find(),
             Rejections::Combined(ref a, ref b) => a.find().or_else(|| b.find()),
         }
     }
 
-    fn debug_list(&self, f: &mut fmt::DebugList<'_, '_>) {
+    fn causes<'a>(&'a self, causes: &mut Vec<&'a dyn fmt::Debug>) {
         match *self {
-            Rejections::Known(ref e) => {
-                f.entry(e);
-            }
-            Rejections::Custom(ref e) => {
-                f.entry(e);
-            }
+            Rejections::Known(ref e) => causes.push(e),
+            Rejections::Custom(ref e) => causes.push(e),
+            Rejections::NotFound => (),
+            Rejections::Ranked(ref r, _) => r.causes(causes),
             Rejections::Combined(ref a, ref b) => {
-                a.debug_list(f);
-                b.debug_list(f);
+                a.causes(causes);
+                b.causes(causes);
             }
         }
     }
 
+    fn has_not_found(&self) -> bool {
+        match *self {
+            Rejections::Known(_) | Rejections::Custom(_) => false,
+            Rejections::NotFound => true,
+            Rejections::Ranked(ref r, _) => r.has_not_found(),
+            Rejections::Combined(ref a, ref b) => a.has_not_found() || b.has_not_found(),
+        }
+    }
+
     fn preferred(&self) -> &Rejections {
+        self.preferred_by(None)
+    }
+
+    fn preferred_by(&self, rank: Option<&Rank>) -> &Rejections {
         match self {
-            Rejections::Known(_) | Rejections::Custom(_) => self,
+            Rejections::Known(_) | Rejections::Custom(_) | Rejections::NotFound => self,
+            // The innermost ranking wins within its own tree.
+            Rejections::Ranked(r, rank) => r.preferred_by(Some(rank)),
             Rejections::Combined(a, b) => {
-                let a = a.preferred();
-                let b = b.preferred();
-                // Now both a and b are known or custom, so it is safe
+                let a = a.preferred_by(rank);
+                let b = b.preferred_by(rank);
+                if let Some(rank) = rank {
+                    match rank(a.status()).cmp(&rank(b.status())) {
+                        std::cmp::Ordering::Less => return b,
+                        std::cmp::Ordering::Greater => return a,
+                        std::cmp::Ordering::Equal => {}
+                    }
+                }
+                // Now both a and b are leaves, so it is safe
                 // to get status
                 // Compare status codes, with this priority:
                 // - NOT_FOUND is lowest
@@ -532,16 +868,31 @@ unit_error! {
     pub LengthRequired: "A content-length header is required"
 }
 
+unit_error! {
+    /// A precondition given in the request headers failed
+    pub PreconditionFailed: "A precondition given in the request headers failed"
+}
+
 unit_error! {
     /// The request payload is too large
     pub PayloadTooLarge: "The request payload is too large"
 }
 
+unit_error! {
+    /// The request took too long to be received
+    pub RequestTimeout: "The request took too long to be received"
+}
+
 unit_error! {
     /// The request's content-type is not supported
     pub UnsupportedMediaType: "The request's content-type is not supported"
 }
 
+unit_error! {
+    /// The request headers are too large
+    pub HeaderFieldsTooLarge: "The request headers are too large"
+}
+
 /// Missing request header
 #[derive(Debug)]
 pub struct MissingHeader {
@@ -665,8 +1016,16 @@ mod sealed {
                 }
                 (Reason::Other(other), Reason::NotFound)
                 | (Reason::NotFound, Reason::Other(other)) => {
-                    // ignore the NotFound
-                    Reason::Other(other)
+                    // The NotFound is ranked lowest unless a `priority`
+                    // says otherwise, so one per tree is enough.
+                    if other.has_not_found() {
+                        Reason::Other(other)
+                    } else {
+                        Reason::Other(Box::new(Rejections::Combined(
+                            other,
+                            Box::new(Rejections::NotFound),
+                        )))
+                    }
                 }
                 (Reason::NotFound, Reason::NotFound) => Reason::NotFound,
             };
@@ -703,6 +1062,63 @@ mod sealed {
     }
 }
 
+mod priority {
+    use std::future::Future;
+    use std::pin::Pin;
+    use std::task::{Context, Poll};
+
+    use futures_util::{ready, TryFuture};
+    use pin_project::pin_project;
+
+    use super::{Rank, Rejection};
+    use crate::filter::{Filter, FilterBase, Internal};
+
+    #[allow(missing_debug_implementations)]
+    #[derive(Clone)]
+    pub struct WithPriority<F> {
+        pub(super) filter: F,
+        pub(super) rank: Rank,
+    }
+
+    impl<F> FilterBase for WithPriority<F>
+    where
+        F: Filter<Error = Rejection> + Clone + Send,
+    {
+        type Extract = F::Extract;
+        type Error = Rejection;
+        type Future = WithPriorityFuture<F::Future>;
+
+        fn filter(&self, _: Internal) -> Self::Future {
+            WithPriorityFuture {
+                future: self.filter.filter(Internal),
+                rank: self.rank.clone(),
+            }
+        }
+    }
+
+    #[allow(missing_debug_implementations)]
+    #[pin_project]
+    pub struct WithPriorityFuture<F> {
+        #[pin]
+        future: F,
+        rank: Rank,
+    }
+
+    impl<F> Future for WithPriorityFuture<F>
+    where
+        F: TryFuture<Error = Rejection>,
+    {
+        type Output = Result<F::Ok, Rejection>;
+
+        fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+            let pin = self.project();
+            let rank = pin.rank;
+            let result = ready!(pin.future.try_poll(cx));
+            Poll::Ready(result.map_err(|rejection| rejection.ranked(rank.clone())))
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -815,6 +1231,68 @@ mod tests {
         assert!(rej.find::<MethodNotAllowed>().is_some(), "MethodNotAllowed");
     }
 
+    #[derive(Debug)]
+    struct Teapot(&'static str);
+    impl Reject for Teapot {}
+
+    #[tokio::test]
+    async fn registered_custom() {
+        register(StatusCode::IM_A_TEAPOT, |e: &Teapot| {
+            format!("short and {}", e.0)
+        });
+
+        let resp = custom(Teapot("stout")).into_response();
+        assert_eq!(resp.status(), StatusCode::IM_A_TEAPOT);
+        assert_eq!(response_body_string(resp).await, "short and stout");
+
+        // Preferred over a 405, still below unregistered customs.
+        let reject = method_not_allowed().combine(custom(Teapot("stout")));
+        assert_eq!(reject.status(), StatusCode::IM_A_TEAPOT);
+        let reject = not_found()
+            .combine(custom(Teapot("stout")))
+            .combine(custom(Right));
+        assert_eq!(reject.status(), StatusCode::INTERNAL_SERVER_ERROR);
+    }
+
+    #[derive(Debug)]
+    struct Reentrant;
+    impl Reject for Reentrant {}
+
+    #[tokio::test]
+    async fn registered_message_runs_unlocked() {
+        register(StatusCode::CONFLICT, |_: &Reentrant| {
+            // Would deadlock if the registry were still locked.
+            register(StatusCode::CONFLICT, |_: &Reentrant| "again".to_owned());
+            "first".to_owned()
+        });
+
+        let resp = custom(Reentrant).into_response();
+        assert_eq!(resp.status(), StatusCode::CONFLICT);
+        assert_eq!(response_body_string(resp).await, "first");
+        let resp = custom(Reentrant).into_response();
+        assert_eq!(response_body_string(resp).await, "again");
+    }
+
+    #[tokio::test]
+    async fn problem_json_body() {
+        let resp = problem_json()(not_found().combine(method_not_allowed()))
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::METHOD_NOT_ALLOWED);
+        assert_eq!(resp.headers()[CONTENT_TYPE], "application/problem+json");
+        assert_eq!(
+            response_body_string(resp).await,
+            r#"{"detail":"HTTP method not allowed","status":405,"title":"Method Not Allowed","type":"about:blank"}"#
+        );
+
+        let resp = problem_json()(custom(Left)).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::INTERNAL_SERVER_ERROR);
+        assert_eq!(
+            response_body_string(resp).await,
+            r#"{"status":500,"title":"Internal Server Error","type":"about:blank"}"#
+        );
+    }
+
     #[test]
     fn size_of_rejection() {
         assert_eq!(
@@ -8,7 +8,7 @@
 //! Besides them, you can return a type that implements [`Reply`](./trait.Reply.html). This
 //! could be any of the following:
 //!
-//! - [`http::Response<impl Into<hyper::Body>>`](https://docs.rs/http)
+//! - [`http::Response<impl http_body::Body>`](ResponseBody)
 //! - `String`
 //! - `&'static str`
 //! - `http::StatusCode`
@@ -33,12 +33,26 @@
 //!     .or(nextshell::post().and(custom));
 //! ```
 
+use std::any::Any;
 use std::borrow::Cow;
 use std::convert::TryFrom;
+use std::error::Error as StdError;
+use std::future::Future;
+use std::io;
+use std::pin::Pin;
+use std::task::{Context, Poll};
 
+use crate::filters::range::RangedBody;
 use crate::generic::{Either, One};
-use http::header::{HeaderName, HeaderValue, CONTENT_TYPE};
+use bytes::{Buf, Bytes, BytesMut};
+use futures_util::{ready, stream, Stream, StreamExt};
+use headers::HeaderMapExt;
+use http::header::{
+    HeaderMap, HeaderName, HeaderValue, CACHE_CONTROL, CONTENT_LANGUAGE, CONTENT_LENGTH,
+    CONTENT_TYPE, TRAILER,
+};
 use http::StatusCode;
+use hyper::body::HttpBody;
 use hyper::Body;
 use serde::Serialize;
 
@@ -128,6 +142,573 @@ impl Reply for Json {
     }
 }
 
+/// Convert the value into a `Reply` with the value encoded as JSON, tagged
+/// with a strong `etag` computed from the serialized body.
+///
+/// The reply also sets `cache-control: no-cache`, so clients keep it but
+/// revalidate it on every use. When replying to a `GET` or `HEAD` request
+/// whose `if-none-match` header matches the tag, the body is dropped and
+/// `304 Not Modified` is returned instead.
+///
+/// # Example
+///
+/// ```
+/// use nextshell::Filter;
+///
+/// let route = nextshell::path("ids")
+///     .map(|| nextshell::reply::json_with_etag(&vec![1, 3, 7, 13]));
+/// ```
+///
+/// # Note
+///
+/// Serialization errors are handled like with [`json`].
+pub fn json_with_etag<T>(val: &T) -> JsonWithETag
+where
+    T: Serialize,
+{
+    JsonWithETag { json: json(val) }
+}
+
+/// A JSON formatted reply, tagged with an `etag`.
+///
+/// Returned by `nextshell::reply::json_with_etag`.
+#[allow(missing_debug_implementations)]
+pub struct JsonWithETag {
+    json: Json,
+}
+
+impl Reply for JsonWithETag {
+    fn into_response(self) -> Response {
+        let etag = match self.json.inner {
+            Ok(ref body) => crate::filters::etag::strong_etag(body),
+            Err(()) => return self.json.into_response(),
+        };
+        let mut res = self.json.into_response();
+        res.headers_mut().typed_insert(etag);
+        res.headers_mut()
+            .insert(CACHE_CONTROL, HeaderValue::from_static("no-cache"));
+
+        // Outside of a request, such as when built in a test, there's no
+        // `if-none-match` to answer.
+        if !crate::route::is_set() {
+            return res;
+        }
+        let if_none_match = crate::route::with(|route| {
+            let method = route.method();
+            if method == http::Method::GET || method == http::Method::HEAD {
+                route.headers().typed_get::<headers::IfNoneMatch>()
+            } else {
+                None
+            }
+        });
+        crate::filters::etag::not_modified(res, if_none_match.as_ref())
+    }
+}
+
+/// Convert a stream of values into a `Reply` that serializes each item as
+/// JSON while it is being sent.
+///
+/// By default the items are written as the elements of a single JSON array,
+/// using a chunked `application/json` body. Call [`JsonStream::ndjson`] to
+/// instead write one JSON document per line (`application/x-ndjson`).
+///
+/// Unlike [`json`], the full payload is never buffered in memory, which makes
+/// this suitable for endpoints returning a very large number of rows.
+///
+/// # Example
+///
+/// ```
+/// use nextshell::Filter;
+/// use futures_util::stream;
+///
+/// // GET /ids returns a `200 OK` with a streamed JSON array of ids:
+/// // `[1,3,7,13]`
+/// let route = nextshell::path("ids")
+///     .map(|| {
+///         nextshell::reply::json_stream(stream::iter(vec![1, 3, 7, 13]))
+///     });
+/// ```
+///
+/// # Note
+///
+/// The status and headers are sent before the stream is polled, so if an
+/// item fails to be serialized, the error is logged at the `error` level and
+/// the response body is aborted.
+pub fn json_stream<S>(stream: S) -> JsonStream<S>
+where
+    S: Stream + Send + 'static,
+    S::Item: Serialize,
+{
+    JsonStream {
+        stream,
+        ndjson: false,
+    }
+}
+
+/// A streaming JSON reply.
+///
+/// Returned by `nextshell::reply::json_stream`.
+#[allow(missing_debug_implementations)]
+pub struct JsonStream<S> {
+    stream: S,
+    ndjson: bool,
+}
+
+impl<S> JsonStream<S> {
+    /// Write newline delimited JSON instead of a JSON array.
+    ///
+    /// Each item is serialized on its own line, and the `content-type` is
+    /// set to `application/x-ndjson`.
+    pub fn ndjson(mut self) -> Self {
+        self.ndjson = true;
+        self
+    }
+}
+
+impl<S> Reply for JsonStream<S>
+where
+    S: Stream + Send + 'static,
+    S::Item: Serialize,
+{
+    fn into_response(self) -> Response {
+        let ndjson = self.ndjson;
+        let mut first = true;
+        let items = self.stream.map(move |item| {
+            let mut buf = Vec::new();
+            if !ndjson && !first {
+                buf.push(b',');
+            }
+            first = false;
+            serde_json::to_writer(&mut buf, &item).map_err(|err| {
+                tracing::error!("reply::json_stream error: {}", err);
+                err
+            })?;
+            if ndjson {
+                buf.push(b'\n');
+            }
+            Ok::<_, serde_json::Error>(Bytes::from(buf))
+        });
+
+        let (body, content_type) = if ndjson {
+            (Body::wrap_stream(items), "application/x-ndjson")
+        } else {
+            let open = stream::once(async { Ok(Bytes::from_static(b"[")) });
+            let close = stream::once(async { Ok(Bytes::from_static(b"]")) });
+            (
+                Body::wrap_stream(open.chain(items).chain(close)),
+                "application/json",
+            )
+        };
+
+        let mut res = Response::new(body);
+        res.headers_mut()
+            .insert(CONTENT_TYPE, HeaderValue::from_static(content_type));
+        res
+    }
+}
+
+/// Reply with a body streamed from `stream`, chunk by chunk.
+///
+/// Each chunk is written to the connection as soon as the stream yields it,
+/// and the stream is only polled again once the client has accepted the
+/// previous chunks, so a slow client slows down the producer instead of
+/// chunks piling up in memory. This makes it suitable for forwarding a
+/// channel or a file to the client.
+///
+/// The `content-type` defaults to `application/octet-stream`. Unless
+/// [`content_length`](StreamReply::content_length) is set, the body is sent
+/// chunked over HTTP/1.1. If the stream yields an error, the response is
+/// aborted.
+///
+/// # Example
+///
+/// ```
+/// use bytes::Bytes;
+/// use nextshell::Filter;
+/// use tokio::sync::mpsc;
+/// use tokio_stream::wrappers::ReceiverStream;
+///
+/// let route = nextshell::path("export").map(|| {
+///     let (tx, rx) = mpsc::channel::<Result<Bytes, std::io::Error>>(4);
+///     tokio::spawn(async move {
+///         for line in ["id,name\n", "1,alice\n"] {
+///             if tx.send(Ok(Bytes::from(line))).await.is_err() {
+///                 break;
+///             }
+///         }
+///     });
+///     nextshell::reply::stream(ReceiverStream::new(rx)).content_type("text/csv")
+/// });
+/// ```
+pub fn stream<S, T, E>(stream: S) -> StreamReply<S>
+where
+    S: Stream<Item = Result<T, E>> + Send + 'static,
+    T: Into<Bytes> + 'static,
+    E: Into<Box<dyn StdError + Send + Sync>> + 'static,
+{
+    StreamReply {
+        stream,
+        content_type: HeaderValue::from_static("application/octet-stream"),
+        content_length: None,
+    }
+}
+
+/// A reply streaming its body.
+///
+/// Returned by `nextshell::reply::stream`.
+#[allow(missing_debug_implementations)]
+pub struct StreamReply<S> {
+    stream: S,
+    content_type: HeaderValue,
+    content_length: Option<u64>,
+}
+
+impl<S> StreamReply<S> {
+    /// Set the `content-type` of the reply.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `content_type` isn't a valid header value.
+    pub fn content_type(mut self, content_type: &str) -> Self {
+        self.content_type = HeaderValue::from_str(content_type)
+            .expect("reply::stream content-type must be a valid header value");
+        self
+    }
+
+    /// Set the `content-length` of the reply, when the size of the stream is
+    /// known in advance.
+    ///
+    /// The stream must yield exactly `len` bytes, or the connection is
+    /// closed.
+    pub fn content_length(mut self, len: u64) -> Self {
+        self.content_length = Some(len);
+        self
+    }
+}
+
+impl<S, T, E> Reply for StreamReply<S>
+where
+    S: Stream<Item = Result<T, E>> + Send + 'static,
+    T: Into<Bytes> + 'static,
+    E: Into<Box<dyn StdError + Send + Sync>> + 'static,
+{
+    fn into_response(self) -> Response {
+        let chunks = self.stream.map(|chunk| {
+            chunk.map_err(|err| {
+                let err = err.into();
+                tracing::debug!("reply::stream error: {}", err);
+                err
+            })
+        });
+        let mut res = Response::new(Body::wrap_stream(chunks));
+        res.headers_mut().insert(CONTENT_TYPE, self.content_type);
+        if let Some(len) = self.content_length {
+            res.headers_mut()
+                .insert(CONTENT_LENGTH, HeaderValue::from(len));
+        }
+        res
+    }
+}
+
+/// Reply with a body of `len` bytes, streamed by ranges.
+///
+/// `streams` is called with a `[start, end)` byte range, and must return a
+/// stream of exactly those bytes, for instance by seeking in a generated
+/// file or asking an object store for a range. Wrapped in
+/// [`range()`](crate::range()), `range` requests only open the streams of the
+/// requested ranges, one after the other, including in
+/// `multipart/byteranges` replies. Otherwise, the whole body is streamed.
+///
+/// The body's stream is only opened once the reply is sent.
+///
+/// # Example
+///
+/// ```
+/// use nextshell::Filter;
+/// use futures_util::stream;
+///
+/// const BLOB: &[u8] = b"some large generated blob";
+///
+/// let route = nextshell::path("blob")
+///     .map(|| {
+///         nextshell::reply::ranged(BLOB.len() as u64, |start, end| {
+///             let chunk = &BLOB[start as usize..end as usize];
+///             stream::once(async move { Ok(bytes::Bytes::from_static(chunk)) })
+///         })
+///     })
+///     .with(nextshell::range());
+/// ```
+pub fn ranged<F, S>(len: u64, streams: F) -> Ranged
+where
+    F: Fn(u64, u64) -> S + Send + Sync + 'static,
+    S: Stream<Item = Result<Bytes, io::Error>> + Send + 'static,
+{
+    Ranged {
+        body: RangedBody::new(len, streams),
+    }
+}
+
+/// A reply streamed by ranges.
+///
+/// Returned by `nextshell::reply::ranged`.
+#[allow(missing_debug_implementations)]
+pub struct Ranged {
+    body: RangedBody,
+}
+
+impl Reply for Ranged {
+    #[inline]
+    fn into_response(self) -> Response {
+        self.body.into_response()
+    }
+}
+
+/// Create a `multipart/mixed` reply, made of the parts added to it.
+///
+/// Parts can be added one by one, or as a stream for replies that never end,
+/// such as `multipart/x-mixed-replace` MJPEG streams. Each part has its own
+/// headers, and its body is streamed.
+///
+/// The reply has a `content-length` when the sizes of all the parts are
+/// known, and none is a stream.
+///
+/// # Example
+///
+/// ```
+/// use futures_util::stream::{self, StreamExt};
+/// use nextshell::Filter;
+/// use nextshell::reply::{self, Part};
+///
+/// let batch = nextshell::path("batch").map(|| {
+///     reply::multipart()
+///         .part(Part::new(r#"{"id":1}"#).header("content-type", "application/json"))
+///         .part(Part::new(r#"{"id":2}"#).header("content-type", "application/json"))
+/// });
+///
+/// let frames = nextshell::path("camera").map(|| {
+///     let frames = stream::repeat(&b"...jpeg..."[..])
+///         .map(|jpeg| Part::new(jpeg).header("content-type", "image/jpeg"));
+///     reply::multipart().subtype("x-mixed-replace").parts(frames)
+/// });
+/// ```
+pub fn multipart() -> Multipart {
+    Multipart {
+        subtype: "mixed".to_owned(),
+        boundary: None,
+        parts: Vec::new(),
+    }
+}
+
+/// A multipart reply.
+///
+/// Returned by `nextshell::reply::multipart`.
+#[allow(missing_debug_implementations)]
+pub struct Multipart {
+    subtype: String,
+    boundary: Option<String>,
+    parts: Vec<Parts>,
+}
+
+enum Parts {
+    One(Part),
+    Stream(Pin<Box<dyn Stream<Item = Part> + Send>>),
+}
+
+/// A part of a [`Multipart`] reply.
+#[allow(missing_debug_implementations)]
+pub struct Part {
+    headers: http::HeaderMap,
+    body: Body,
+    len: Option<u64>,
+}
+
+impl Multipart {
+    /// Set the subtype of the `multipart` content-type, instead of `mixed`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `subtype` isn't a valid token.
+    pub fn subtype(mut self, subtype: impl Into<String>) -> Self {
+        let subtype = subtype.into();
+        assert!(
+            !subtype.is_empty()
+                && subtype
+                    .bytes()
+                    .all(|b| b.is_ascii_alphanumeric() || b"!#$&-^_.+".contains(&b)),
+            "invalid multipart subtype: {:?}",
+            subtype
+        );
+        self.subtype = subtype;
+        self
+    }
+
+    /// Set the boundary between the parts, instead of a random one.
+    ///
+    /// A boundary with characters not allowed in a header token, such as
+    /// spaces or `:`, is quoted in the `content-type` header.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `boundary` isn't a valid multipart boundary, that is 1 to 70
+    /// letters, digits, spaces or `'()+_,-./:=?`, not ending with a space.
+    pub fn boundary(mut self, boundary: impl Into<String>) -> Self {
+        let boundary = boundary.into();
+        assert!(
+            (1..=70).contains(&boundary.len())
+                && !boundary.ends_with(' ')
+                && boundary
+                    .bytes()
+                    .all(|b| b.is_ascii_alphanumeric() || b" '()+_,-./:=?".contains(&b)),
+            "invalid multipart boundary: {:?}",
+            boundary
+        );
+        self.boundary = Some(boundary);
+        self
+    }
+
+    /// Add a part to the reply.
+    pub fn part(mut self, part: Part) -> Self {
+        self.parts.push(Parts::One(part));
+        self
+    }
+
+    /// Add a stream of parts to the reply, sent as they come.
+    pub fn parts<S>(mut self, parts: S) -> Self
+    where
+        S: Stream<Item = Part> + Send + 'static,
+    {
+        self.parts.push(Parts::Stream(Box::pin(parts)));
+        self
+    }
+}
+
+impl Reply for Multipart {
+    fn into_response(self) -> Response {
+        let boundary = self
+            .boundary
+            .unwrap_or_else(crate::filters::range::boundary);
+        let is_token = boundary
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() || b"'+_-.".contains(&b));
+        let content_type = if is_token {
+            format!("multipart/{}; boundary={}", self.subtype, boundary)
+        } else {
+            format!("multipart/{}; boundary=\"{}\"", self.subtype, boundary)
+        };
+        let tail = Bytes::from(format!("\r\n--{}--\r\n", boundary));
+
+        let mut content_length = Some(tail.len() as u64);
+        let mut streams = Vec::with_capacity(self.parts.len());
+        for parts in self.parts {
+            match parts {
+                Parts::One(part) => {
+                    let (head, body, len) = part.encode(&boundary);
+                    content_length =
+                        content_length.and_then(|total| Some(total + head.len() as u64 + len?));
+                    streams.push(part_stream(head, body).boxed());
+                }
+                Parts::Stream(parts) => {
+                    content_length = None;
+                    let boundary = boundary.clone();
+                    let parts = parts.flat_map(move |part| {
+                        let (head, body, _) = part.encode(&boundary);
+                        part_stream(head, body)
+                    });
+                    streams.push(parts.boxed());
+                }
+            }
+        }
+
+        let body = stream::iter(streams)
+            .flatten()
+            .chain(stream::once(async move { Ok(tail) }));
+        let mut res = Response::new(Body::wrap_stream(body));
+        res.headers_mut().insert(
+            CONTENT_TYPE,
+            HeaderValue::from_str(&content_type).expect("valid subtype and boundary"),
+        );
+        if let Some(len) = content_length {
+            res.headers_mut().typed_insert(headers::ContentLength(len));
+        }
+        res
+    }
+}
+
+fn part_stream(head: Bytes, body: Body) -> impl Stream<Item = Result<Bytes, hyper::Error>> + Send {
+    stream::once(async move { Ok(head) }).chain(body)
+}
+
+impl Part {
+    /// Create a part with this body.
+    pub fn new(body: impl Into<Body>) -> Self {
+        let body = body.into();
+        Part {
+            headers: http::HeaderMap::new(),
+            len: HttpBody::size_hint(&body).exact(),
+            body,
+        }
+    }
+
+    /// Create a part streaming this body.
+    pub fn stream<S, B, E>(stream: S) -> Self
+    where
+        S: Stream<Item = Result<B, E>> + Send + 'static,
+        B: Into<Bytes> + 'static,
+        E: Into<Box<dyn std::error::Error + Send + Sync>> + 'static,
+    {
+        Part::new(Body::wrap_stream(stream))
+    }
+
+    /// Add a header to this part.
+    ///
+    /// Like [`with_header`], an invalid name or value is logged and ignored.
+    pub fn header<K, V>(mut self, name: K, value: V) -> Self
+    where
+        HeaderName: TryFrom<K>,
+        <HeaderName as TryFrom<K>>::Error: Into<http::Error>,
+        HeaderValue: TryFrom<V>,
+        <HeaderValue as TryFrom<V>>::Error: Into<http::Error>,
+    {
+        match <HeaderName as TryFrom<K>>::try_from(name) {
+            Ok(name) => match <HeaderValue as TryFrom<V>>::try_from(value) {
+                Ok(value) => {
+                    self.headers.append(name, value);
+                }
+                Err(err) => {
+                    let err = err.into();
+                    tracing::error!("multipart part header value error: {}", err);
+                }
+            },
+            Err(err) => {
+                let err = err.into();
+                tracing::error!("multipart part header name error: {}", err);
+            }
+        }
+        self
+    }
+
+    /// The body of this part is `len` bytes long.
+    pub(crate) fn with_len(mut self, len: u64) -> Self {
+        self.len = Some(len);
+        self
+    }
+
+    fn encode(self, boundary: &str) -> (Bytes, Body, Option<u64>) {
+        let mut head = BytesMut::new();
+        head.extend_from_slice(b"\r\n--");
+        head.extend_from_slice(boundary.as_bytes());
+        head.extend_from_slice(b"\r\n");
+        for (name, value) in &self.headers {
+            head.extend_from_slice(name.as_str().as_bytes());
+            head.extend_from_slice(b": ");
+            head.extend_from_slice(value.as_bytes());
+            head.extend_from_slice(b"\r\n");
+        }
+        head.extend_from_slice(b"\r\n");
+        (head.freeze(), self.body, self.len)
+    }
+}
+
 /// Reply with a body and `content-type` set to `text/html; charset=utf-8`.
 ///
 /// # Example
@@ -181,6 +762,116 @@ where
     }
 }
 
+/// Reply with any [`http_body::Body`][http_body], including its trailers.
+///
+/// This lets bodies from other libraries, such as gRPC encoders or object
+/// store clients, be returned as they are, instead of being collected or
+/// adapted into a `hyper::Body` first. The body is streamed as it's polled,
+/// its exact size, if known, sets the `content-length`, and its trailers are
+/// sent on connections supporting them, such as HTTP/2.
+///
+/// Unless it's already a `hyper::Body`, the body is polled by a task
+/// spawned when the reply is converted, which must happen within a Tokio
+/// runtime, as it does when served.
+///
+/// # Example
+///
+/// ```
+/// use nextshell::Filter;
+///
+/// // Any type implementing `http_body::Body`, `String` being one.
+/// let route = nextshell::any()
+///     .map(|| nextshell::reply::body(String::from("from another library")));
+/// ```
+///
+/// [http_body]: https://docs.rs/http-body/0.4
+pub fn body<B>(body: B) -> BodyReply<B>
+where
+    B: HttpBody + Send + 'static,
+    B::Data: Send,
+    B::Error: Into<Box<dyn StdError + Send + Sync>>,
+{
+    BodyReply { body }
+}
+
+/// A reply with an `http_body::Body`.
+///
+/// Returned by `nextshell::reply::body`.
+#[allow(missing_debug_implementations)]
+pub struct BodyReply<B> {
+    body: B,
+}
+
+impl<B> Reply for BodyReply<B>
+where
+    B: HttpBody + Send + 'static,
+    B::Data: Send,
+    B::Error: Into<Box<dyn StdError + Send + Sync>>,
+{
+    fn into_response(self) -> Response {
+        let len = self.body.size_hint().exact();
+        let mut res = Response::new(into_body(self.body));
+        if let Some(len) = len {
+            res.headers_mut()
+                .insert(CONTENT_LENGTH, HeaderValue::from(len));
+        }
+        res
+    }
+}
+
+// Converts any `http_body::Body` into a `hyper::Body`, by feeding a channel
+// body from a spawned task, since only those carry trailers.
+pub(crate) fn into_body<B>(body: B) -> Body
+where
+    B: HttpBody + Send + 'static,
+    B::Data: Send,
+    B::Error: Into<Box<dyn StdError + Send + Sync>>,
+{
+    let mut body = Some(body);
+    if let Some(body) = (&mut body as &mut dyn Any).downcast_mut::<Option<Body>>() {
+        return body.take().expect("just set");
+    }
+    let body = body.expect("just set");
+
+    let (mut tx, rx) = Body::channel();
+    tokio::spawn(async move {
+        futures_util::pin_mut!(body);
+        loop {
+            // Converted before awaiting again, as the error may not be `Send`.
+            let data: Result<_, Box<dyn StdError + Send + Sync>> = match body.data().await {
+                Some(data) => data.map_err(Into::into),
+                None => break,
+            };
+            match data {
+                Ok(mut buf) => {
+                    let chunk = buf.copy_to_bytes(buf.remaining());
+                    if tx.send_data(chunk).await.is_err() {
+                        return;
+                    }
+                }
+                Err(err) => {
+                    tracing::debug!("reply::body error: {}", err);
+                    tx.abort();
+                    return;
+                }
+            }
+        }
+        let trailers: Result<_, Box<dyn StdError + Send + Sync>> =
+            body.trailers().await.map_err(Into::into);
+        match trailers {
+            Ok(Some(trailers)) => {
+                let _ = tx.send_trailers(trailers).await;
+            }
+            Ok(None) => {}
+            Err(err) => {
+                tracing::debug!("reply::body trailers error: {}", err);
+                tx.abort();
+            }
+        }
+    });
+    rx
+}
+
 /// Types that can be converted into a `Response`.
 ///
 /// This trait is implemented for the following:
@@ -378,13 +1069,273 @@ impl<T: Reply> Reply for WithHeader<T> {
     }
 }
 
-impl<T: Send> Reply for ::http::Response<T>
+/// Wrap an `impl Reply` to send trailers after its body.
+///
+/// `trailers` is awaited once the whole body has been sent, so it can
+/// resolve to values computed while streaming it, such as a checksum or a
+/// `grpc-status`. Trailers the body already has are kept, and extended.
+///
+/// Trailers are sent on HTTP/2 connections; HTTP/1.1 responses don't carry
+/// them.
+///
+/// # Example
+///
+/// ```
+/// use nextshell::Filter;
+/// use nextshell::http::HeaderMap;
+///
+/// let route = nextshell::any()
+///     .map(|| {
+///         let trailers = async {
+///             let mut trailers = HeaderMap::new();
+///             trailers.insert("grpc-status", "0".parse().unwrap());
+///             trailers
+///         };
+///         nextshell::reply::with_trailers("body", trailers).declare("grpc-status")
+///     });
+/// ```
+pub fn with_trailers<T, F>(reply: T, trailers: F) -> WithTrailers<T, F>
 where
-    Body: From<T>,
+    T: Reply,
+    F: Future<Output = HeaderMap> + Send + 'static,
 {
+    WithTrailers {
+        reply,
+        trailers,
+        declared: Vec::new(),
+    }
+}
+
+/// Wraps an `impl Reply` and sends trailers after its body.
+///
+/// Returned by `nextshell::reply::with_trailers`.
+#[allow(missing_debug_implementations)]
+pub struct WithTrailers<T, F> {
+    reply: T,
+    trailers: F,
+    declared: Vec<HeaderName>,
+}
+
+impl<T, F> WithTrailers<T, F> {
+    /// Declare a trailer in the `trailer` header, for clients to know it
+    /// will be sent.
+    ///
+    /// hyper strips this header from HTTP/2 responses, so it only reaches
+    /// HTTP/1.1 clients and intermediaries.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `name` isn't a valid header name.
+    pub fn declare<K>(mut self, name: K) -> Self
+    where
+        HeaderName: TryFrom<K>,
+    {
+        let name = HeaderName::try_from(name)
+            .map_err(|_| ())
+            .expect("invalid trailer name");
+        self.declared.push(name);
+        self
+    }
+}
+
+impl<T, F> Reply for WithTrailers<T, F>
+where
+    T: Reply,
+    F: Future<Output = HeaderMap> + Send + 'static,
+{
+    fn into_response(self) -> Response {
+        let (mut parts, body) = self.reply.into_response().into_parts();
+        if !self.declared.is_empty() {
+            let names = self
+                .declared
+                .iter()
+                .map(HeaderName::as_str)
+                .collect::<Vec<_>>()
+                .join(", ");
+            parts.headers.insert(
+                TRAILER,
+                HeaderValue::from_str(&names).expect("header names are valid values"),
+            );
+        }
+        let body = Trailing {
+            body,
+            own: None,
+            trailers: Some(Box::pin(self.trailers)),
+        };
+        Response::from_parts(parts, into_body(body))
+    }
+}
+
+// A body followed by extra trailers.
+struct Trailing<F> {
+    body: Body,
+    // The body's own trailers, once received.
+    own: Option<HeaderMap>,
+    trailers: Option<Pin<Box<F>>>,
+}
+
+impl<F> HttpBody for Trailing<F>
+where
+    F: Future<Output = HeaderMap>,
+{
+    type Data = Bytes;
+    type Error = hyper::Error;
+
+    fn poll_data(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Bytes, hyper::Error>>> {
+        Pin::new(&mut self.get_mut().body).poll_data(cx)
+    }
+
+    fn poll_trailers(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<Option<HeaderMap>, hyper::Error>> {
+        let this = self.get_mut();
+        let trailers = match this.trailers {
+            Some(ref mut trailers) => trailers,
+            None => return Poll::Ready(Ok(this.own.take())),
+        };
+        if this.own.is_none() {
+            this.own =
+                Some(ready!(Pin::new(&mut this.body).poll_trailers(cx))?.unwrap_or_default());
+        }
+        let extra = ready!(trailers.as_mut().poll(cx));
+        this.trailers = None;
+        let mut own = this.own.take().unwrap_or_default();
+        own.extend(extra);
+        Poll::Ready(Ok(Some(own)))
+    }
+
+    fn is_end_stream(&self) -> bool {
+        false
+    }
+}
+
+/// Reply with one of several translations of a reply, tagged with their
+/// language, such as `en` or `pt-BR`.
+///
+/// Once wrapped with [`with::content_language`], the variant that best
+/// matches the request's `accept-language` is sent, with the
+/// `content-language` and `vary` headers set accordingly. Otherwise, or
+/// when no variant is acceptable, the first one is sent.
+///
+/// # Panics
+///
+/// Panics if `lang` isn't a valid header value.
+///
+/// # Example
+///
+/// ```
+/// use nextshell::Filter;
+///
+/// let route = nextshell::path("hello")
+///     .map(|| {
+///         nextshell::reply::localized("en", "Hello")
+///             .variant("fr", "Bonjour")
+///             .variant("pt-BR", "Olá")
+///     })
+///     .with(nextshell::reply::with::content_language());
+/// ```
+pub fn localized<T: Reply>(lang: &str, reply: T) -> Localized {
+    Localized {
+        variants: Vec::new(),
+    }
+    .variant(lang, reply)
+}
+
+/// A reply available in several languages.
+///
+/// Returned by `nextshell::reply::localized`.
+#[allow(missing_debug_implementations)]
+pub struct Localized {
+    variants: Vec<(HeaderValue, Response)>,
+}
+
+impl Localized {
+    /// Add the variant of the reply in `lang`.
+    ///
+    /// Variants are preferred in the order they're added, when the client
+    /// accepts several of them equally.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `lang` isn't a valid header value.
+    pub fn variant<T: Reply>(mut self, lang: &str, reply: T) -> Self {
+        let lang = HeaderValue::from_str(lang).expect("invalid language tag");
+        self.variants.push((lang, reply.into_response()));
+        self
+    }
+}
+
+// The variants of a `Localized` reply other than the one it renders as,
+// for `with::content_language` to pick from.
+pub(crate) struct Variants(pub(crate) Vec<(HeaderValue, Response)>);
+
+impl Reply for Localized {
+    fn into_response(self) -> Response {
+        let mut variants = self.variants.into_iter();
+        let (lang, mut res) = variants.next().expect("a localized reply has a variant");
+        res.headers_mut().insert(CONTENT_LANGUAGE, lang);
+        let others = variants.collect::<Vec<_>>();
+        if !others.is_empty() {
+            res.extensions_mut().insert(Variants(others));
+        }
+        res
+    }
+}
+
+impl<T: ResponseBody> Reply for ::http::Response<T> {
     #[inline]
     fn into_response(self) -> Response {
-        self.map(Body::from)
+        self.map(ResponseBody::into_body)
+    }
+}
+
+/// A body of an `http::Response` used as a [`Reply`].
+///
+/// Implemented for every [`http_body::Body`][http_body], such as
+/// `hyper::Body`, `String`, the bodies of the `http-body` crate, or those of
+/// other libraries and applications, so they can be returned without
+/// conversion. Bodies other than `hyper::Body` and `String` are streamed
+/// through `Body::wrap_stream`, dropping their trailers, which
+/// [`reply::body`](body()) sends instead.
+///
+/// Other types a `hyper::Body` is created from, such as `&'static str` or
+/// `Vec<u8>`, don't implement `http_body::Body`, and need a `Body::from`.
+///
+/// [http_body]: https://docs.rs/http-body/0.4
+pub trait ResponseBody: Send + 'static {
+    /// Converts this into the body of a [`Response`].
+    fn into_body(self) -> Body;
+}
+
+impl<B> ResponseBody for B
+where
+    B: HttpBody + Send + 'static,
+    B::Data: Send,
+    B::Error: Into<Box<dyn StdError + Send + Sync>>,
+{
+    fn into_body(self) -> Body {
+        // Converted as is, keeping their length known to hyper.
+        let mut body = Some(self);
+        let any = &mut body as &mut dyn Any;
+        if let Some(body) = any.downcast_mut::<Option<Body>>() {
+            return body.take().expect("just set");
+        }
+        if let Some(body) = any.downcast_mut::<Option<String>>() {
+            return Body::from(body.take().expect("just set"));
+        }
+        let mut body = Box::pin(body.expect("just set"));
+        Body::wrap_stream(stream::poll_fn(move |cx| {
+            body.as_mut().poll_data(cx).map(|data| {
+                data.map(|data| {
+                    data.map(|mut buf| buf.copy_to_bytes(buf.remaining()))
+                        .map_err(Into::<Box<dyn StdError + Send + Sync>>::into)
+                })
+            })
+        }))
     }
 }
 
@@ -561,12 +1512,100 @@ mod tests {
     fn response_builder_error() {
         let res = ::http::Response::builder()
             .status(1337)
-            .body("woops")
+            .body(String::from("woops"))
             .into_response();
 
         assert_eq!(res.status(), 500);
     }
 
+    #[tokio::test]
+    async fn json_stream_array() {
+        let res = json_stream(stream::iter(vec![1, 2, 3])).into_response();
+        assert_eq!(res.headers()["content-type"], "application/json");
+        let body = hyper::body::to_bytes(res.into_body()).await.unwrap();
+        assert_eq!(body, "[1,2,3]");
+
+        let res = json_stream(stream::iter(Vec::<u8>::new())).into_response();
+        let body = hyper::body::to_bytes(res.into_body()).await.unwrap();
+        assert_eq!(body, "[]");
+    }
+
+    #[tokio::test]
+    async fn json_stream_ndjson() {
+        let res = json_stream(stream::iter(vec!["a", "b"]))
+            .ndjson()
+            .into_response();
+        assert_eq!(res.headers()["content-type"], "application/x-ndjson");
+        let body = hyper::body::to_bytes(res.into_body()).await.unwrap();
+        assert_eq!(body, "\"a\"\n\"b\"\n");
+    }
+
+    #[tokio::test]
+    async fn multipart_parts() {
+        let res = multipart()
+            .boundary("frontier")
+            .part(Part::new("one").header("content-type", "text/plain"))
+            .part(Part::new("two"))
+            .into_response();
+        assert_eq!(
+            res.headers()["content-type"],
+            "multipart/mixed; boundary=frontier"
+        );
+        let expected = "\r\n--frontier\r\ncontent-type: text/plain\r\n\r\none\
+                        \r\n--frontier\r\n\r\ntwo\
+                        \r\n--frontier--\r\n";
+        assert_eq!(
+            res.headers()["content-length"],
+            expected.len().to_string().as_str()
+        );
+        let body = hyper::body::to_bytes(res.into_body()).await.unwrap();
+        assert_eq!(body, expected);
+    }
+
+    #[tokio::test]
+    async fn multipart_stream() {
+        let frames = stream::iter(vec!["a", "b"])
+            .map(|frame| Part::stream(stream::once(async move { Ok::<_, io::Error>(frame) })));
+        let res = multipart()
+            .subtype("x-mixed-replace")
+            .boundary("frame")
+            .parts(frames)
+            .into_response();
+        assert_eq!(
+            res.headers()["content-type"],
+            "multipart/x-mixed-replace; boundary=frame"
+        );
+        assert!(!res.headers().contains_key("content-length"));
+        let body = hyper::body::to_bytes(res.into_body()).await.unwrap();
+        assert_eq!(
+            body,
+            "\r\n--frame\r\n\r\na\r\n--frame\r\n\r\nb\r\n--frame--\r\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn multipart_quoted_boundary() {
+        let res = multipart()
+            .boundary("gc0p4Jq0M:2Yt08j,34c0p")
+            .part(Part::new("one"))
+            .into_response();
+        assert_eq!(
+            res.headers()["content-type"],
+            "multipart/mixed; boundary=\"gc0p4Jq0M:2Yt08j,34c0p\""
+        );
+        let body = hyper::body::to_bytes(res.into_body()).await.unwrap();
+        assert_eq!(
+            body,
+            "\r\n--gc0p4Jq0M:2Yt08j,34c0p\r\n\r\none\r\n--gc0p4Jq0M:2Yt08j,34c0p--\r\n"
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "invalid multipart boundary")]
+    fn multipart_invalid_boundary() {
+        let _ = multipart().boundary("no\r\nnewlines");
+    }
+
     #[test]
     fn boxed_reply() {
         let r: Box<dyn Reply> = Box::new(reply());
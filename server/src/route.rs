@@ -62,10 +62,23 @@ impl Route {
         self.req.method()
     }
 
+    pub(crate) fn set_method(&mut self, method: http::Method) {
+        *self.req.method_mut() = method;
+    }
+
     pub(crate) fn headers(&self) -> &http::HeaderMap {
         self.req.headers()
     }
 
+    #[cfg(any(
+        feature = "compression-brotli",
+        feature = "compression-gzip",
+        feature = "compression-zstd"
+    ))]
+    pub(crate) fn headers_mut(&mut self) -> &mut http::HeaderMap {
+        self.req.headers_mut()
+    }
+
     pub(crate) fn version(&self) -> http::Version {
         self.req.version()
     }
@@ -74,7 +87,6 @@ impl Route {
         self.req.extensions()
     }
 
-    #[cfg(feature = "websocket")]
     pub(crate) fn extensions_mut(&mut self) -> &mut http::Extensions {
         self.req.extensions_mut()
     }
@@ -113,6 +125,11 @@ impl Route {
         self.segments_index
     }
 
+    pub(crate) fn skip_matched_path(&mut self, index: usize) {
+        debug_assert!(index <= self.req.uri().path().len());
+        self.segments_index = index;
+    }
+
     pub(crate) fn reset_matched_path_index(&mut self, index: usize) {
         debug_assert!(
             index <= self.segments_index,
@@ -137,4 +154,9 @@ impl Route {
             BodyState::Taken => None,
         }
     }
+
+    pub(crate) fn replace_body(&mut self, body: Body) {
+        *self.req.body_mut() = body;
+        self.body = BodyState::Ready;
+    }
 }
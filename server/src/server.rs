@@ -1,23 +1,36 @@
 #[cfg(feature = "tls")]
-use crate::tls::TlsConfigBuilder;
+use crate::tls::{TlsConfigBuilder, TlsVersion};
+use std::collections::HashMap;
 use std::convert::Infallible;
 use std::error::Error as StdError;
 use std::future::Future;
-use std::net::SocketAddr;
+use std::net::{IpAddr, SocketAddr};
 #[cfg(feature = "tls")]
 use std::path::Path;
+#[cfg(unix)]
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+#[cfg(unix)]
+use std::{fs, io};
 
+use futures_util::future::BoxFuture;
 use futures_util::{future, FutureExt, TryFuture, TryStream, TryStreamExt};
 use hyper::server::conn::AddrIncoming;
+use hyper::server::Builder as HyperBuilder;
 use hyper::service::{make_service_fn, service_fn};
 use hyper::Server as HyperServer;
 use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::sync::watch;
 use tracing::Instrument;
 
-use crate::filter::Filter;
+use crate::filter::{Filter, Recover};
+use crate::filters::state::States;
+use crate::generic::Func;
 use crate::reject::IsReject;
 use crate::reply::Reply;
-use crate::transport::Transport;
+use crate::transport::{HeadTimeoutAccept, Transport};
 
 /// Create a `Server` with the provided `Filter`.
 pub fn serve<F>(filter: F) -> Server<F>
@@ -28,6 +41,12 @@ where
 {
     Server {
         pipeline: false,
+        http2: Http2Config::default(),
+        tcp: TcpConfig::default(),
+        limits: ConnLimits::default(),
+        timeouts: Timeouts::default(),
+        hooks: Hooks::default(),
+        state: States::default(),
         filter,
     }
 }
@@ -36,9 +55,357 @@ where
 #[derive(Debug)]
 pub struct Server<F> {
     pipeline: bool,
+    http2: Http2Config,
+    tcp: TcpConfig,
+    limits: ConnLimits,
+    timeouts: Timeouts,
+    hooks: Hooks,
+    state: States,
     filter: F,
 }
 
+/// HTTP/2 settings, left to hyper's defaults when unset.
+#[derive(Clone, Copy, Debug, Default)]
+struct Http2Config {
+    max_concurrent_streams: Option<u32>,
+    initial_stream_window_size: Option<u32>,
+    initial_connection_window_size: Option<u32>,
+    adaptive_window: bool,
+    keep_alive_interval: Option<Duration>,
+    keep_alive_timeout: Option<Duration>,
+}
+
+impl Http2Config {
+    fn apply<I>(&self, mut builder: HyperBuilder<I>) -> HyperBuilder<I> {
+        if let Some(max) = self.max_concurrent_streams {
+            builder = builder.http2_max_concurrent_streams(max);
+        }
+        if let Some(size) = self.initial_stream_window_size {
+            builder = builder.http2_initial_stream_window_size(size);
+        }
+        if let Some(size) = self.initial_connection_window_size {
+            builder = builder.http2_initial_connection_window_size(size);
+        }
+        if self.adaptive_window {
+            builder = builder.http2_adaptive_window(true);
+        }
+        if let Some(interval) = self.keep_alive_interval {
+            builder = builder.http2_keep_alive_interval(interval);
+        }
+        if let Some(timeout) = self.keep_alive_timeout {
+            builder = builder.http2_keep_alive_timeout(timeout);
+        }
+        builder
+    }
+}
+
+/// TCP listener and accepted socket options.
+#[derive(Clone, Copy, Debug)]
+struct TcpConfig {
+    nodelay: bool,
+    keepalive: Option<Duration>,
+    keepalive_interval: Option<Duration>,
+    keepalive_retries: Option<u32>,
+    reuse_port: bool,
+    backlog: u32,
+    /// Set `IPV6_V6ONLY` on IPv6 listeners, see `v6_only`.
+    only_v6: bool,
+}
+
+impl Default for TcpConfig {
+    fn default() -> Self {
+        TcpConfig {
+            nodelay: true,
+            keepalive: None,
+            keepalive_interval: None,
+            keepalive_retries: None,
+            reuse_port: false,
+            backlog: 1024,
+            only_v6: false,
+        }
+    }
+}
+
+impl TcpConfig {
+    fn bind(&self, addr: &SocketAddr) -> Result<AddrIncoming, Box<dyn StdError + Send + Sync>> {
+        let mut incoming = AddrIncoming::from_listener(self.listen(addr)?)?;
+        incoming
+            .set_nodelay(self.nodelay)
+            .set_keepalive(self.keepalive)
+            .set_keepalive_interval(self.keepalive_interval)
+            .set_keepalive_retries(self.keepalive_retries);
+        Ok(incoming)
+    }
+
+    fn listen(&self, addr: &SocketAddr) -> std::io::Result<tokio::net::TcpListener> {
+        use socket2::{Domain, Protocol, Socket, Type};
+
+        let socket = Socket::new(
+            Domain::for_address(*addr),
+            Type::STREAM,
+            Some(Protocol::TCP),
+        )?;
+        // Same as `std::net::TcpListener::bind`, so restarts don't trip
+        // over connections in TIME_WAIT.
+        #[cfg(not(windows))]
+        socket.set_reuse_address(true)?;
+        #[cfg(all(unix, not(any(target_os = "solaris", target_os = "illumos"))))]
+        socket.set_reuse_port(self.reuse_port)?;
+        if addr.is_ipv6() && self.only_v6 {
+            socket.set_only_v6(true)?;
+        }
+        socket.set_nonblocking(true)?;
+        socket.bind(&(*addr).into())?;
+        socket.listen(self.backlog.min(i32::MAX as u32) as i32)?;
+        tokio::net::TcpListener::from_std(socket.into())
+    }
+}
+
+/// Whether `addrs` mix IPv4 and IPv6 addresses.
+///
+/// The IPv6 listeners then only accept IPv6 connections. Otherwise, on
+/// systems where IPv6 sockets also accept IPv4 connections by default, such
+/// as Linux, `[::]:port` takes `0.0.0.0:port` and binding the latter fails.
+fn v6_only(addrs: &[SocketAddr]) -> bool {
+    addrs.iter().any(SocketAddr::is_ipv4) && addrs.iter().any(SocketAddr::is_ipv6)
+}
+
+/// Limits on how slowly clients may send requests.
+#[derive(Clone, Copy, Debug, Default)]
+struct Timeouts {
+    header_read: Option<Duration>,
+    body_read: Option<Duration>,
+}
+
+/// Connection and request caps, shared by every listener of a `Server`.
+#[derive(Clone, Debug, Default)]
+struct ConnLimits {
+    max: Option<usize>,
+    per_ip: Option<usize>,
+    respond_503: bool,
+    headers: HeaderLimits,
+    stats: Arc<ConnStats>,
+}
+
+/// Caps on the request headers, checked before the filter runs.
+#[derive(Clone, Copy, Debug, Default)]
+struct HeaderLimits {
+    count: Option<usize>,
+    size: Option<usize>,
+    total: Option<usize>,
+}
+
+/// Room for the request line and the header framing in hyper's HTTP/1 read
+/// buffer, on top of `max_header_bytes`.
+const HEAD_SLACK: usize = 8 * 1024;
+
+impl HeaderLimits {
+    /// Caps hyper's HTTP/1 read buffer after `max_header_bytes`, so that
+    /// much larger heads are refused while being read, instead of buffered.
+    fn apply<I>(&self, builder: HyperBuilder<I>) -> HyperBuilder<I> {
+        match self.total {
+            // hyper requires at least 8KB.
+            Some(total) => builder.http1_max_buf_size(total.saturating_add(HEAD_SLACK)),
+            None => builder,
+        }
+    }
+
+    /// Whether `headers` fit within the caps. A header's size is the length
+    /// of its name and value.
+    fn allows(&self, headers: &http::HeaderMap) -> bool {
+        if self.count.is_some_and(|max| headers.len() > max) {
+            return false;
+        }
+        if self.size.is_none() && self.total.is_none() {
+            return true;
+        }
+        let mut total = 0;
+        for (name, value) in headers {
+            let size = name.as_str().len() + value.len();
+            if self.size.is_some_and(|max| size > max) {
+                return false;
+            }
+            total += size;
+        }
+        match self.total {
+            Some(max) => total <= max,
+            None => true,
+        }
+    }
+}
+
+/// Live counters of a `Server`, also read by `ServerHandle`.
+#[derive(Debug, Default)]
+struct ConnStats {
+    active: AtomicUsize,
+    accepted: AtomicU64,
+    requests: AtomicU64,
+    per_ip: Mutex<HashMap<IpAddr, usize>>,
+}
+
+impl ConnLimits {
+    /// Takes a connection slot, or returns `None` if a cap is reached.
+    fn acquire(&self, remote_addr: Option<SocketAddr>) -> Option<ConnGuard> {
+        let total = self.stats.active.fetch_add(1, Ordering::SeqCst) + 1;
+        let mut guard = ConnGuard {
+            stats: self.stats.clone(),
+            ip: None,
+        };
+        if self.max.is_some_and(|max| total > max) {
+            return None;
+        }
+        if let (Some(max), Some(addr)) = (self.per_ip, remote_addr) {
+            let mut per_ip = self.stats.per_ip.lock().unwrap();
+            let count = per_ip.entry(addr.ip()).or_insert(0);
+            if *count >= max {
+                return None;
+            }
+            *count += 1;
+            guard.ip = Some(addr.ip());
+        }
+        self.stats.accepted.fetch_add(1, Ordering::Relaxed);
+        Some(guard)
+    }
+}
+
+/// Releases a connection slot when the connection closes.
+struct ConnGuard {
+    stats: Arc<ConnStats>,
+    ip: Option<IpAddr>,
+}
+
+impl Drop for ConnGuard {
+    fn drop(&mut self) {
+        self.stats.active.fetch_sub(1, Ordering::SeqCst);
+        if let Some(ip) = self.ip {
+            let mut per_ip = self.stats.per_ip.lock().unwrap();
+            if let Some(count) = per_ip.get_mut(&ip) {
+                *count -= 1;
+                if *count == 0 {
+                    per_ip.remove(&ip);
+                }
+            }
+        }
+    }
+}
+
+#[derive(Debug)]
+struct ConnLimitExceeded;
+
+impl std::fmt::Display for ConnLimitExceeded {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("connection limit exceeded")
+    }
+}
+
+impl StdError for ConnLimitExceeded {}
+
+fn conn_limit_response(version: http::Version) -> crate::reply::Response {
+    let mut res = crate::reply::Response::new(hyper::Body::empty());
+    *res.status_mut() = http::StatusCode::SERVICE_UNAVAILABLE;
+    if version < http::Version::HTTP_2 {
+        res.headers_mut().insert(
+            http::header::CONNECTION,
+            http::HeaderValue::from_static("close"),
+        );
+    }
+    res
+}
+
+type ResponseHook = dyn Fn(RequestInfo, crate::reply::Response) -> BoxFuture<'static, crate::reply::Response>
+    + Send
+    + Sync;
+
+/// The hooks registered on a `Server`, run for every request.
+#[derive(Clone, Default)]
+struct Hooks {
+    on_response: Vec<Arc<ResponseHook>>,
+}
+
+impl Hooks {
+    /// Captures what the hooks need to know about `req`, if there are any.
+    fn request_info(
+        &self,
+        req: &crate::Request,
+        remote_addr: Option<SocketAddr>,
+    ) -> Option<RequestInfo> {
+        if self.on_response.is_empty() {
+            return None;
+        }
+        Some(RequestInfo {
+            method: req.method().clone(),
+            uri: req.uri().clone(),
+            version: req.version(),
+            headers: req.headers().clone(),
+            remote_addr,
+            started: tokio::time::Instant::now(),
+        })
+    }
+
+    async fn on_response(
+        &self,
+        info: RequestInfo,
+        mut res: crate::reply::Response,
+    ) -> crate::reply::Response {
+        for hook in &self.on_response {
+            res = hook(info.clone(), res).await;
+        }
+        res
+    }
+}
+
+impl std::fmt::Debug for Hooks {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Hooks")
+            .field("on_response", &self.on_response.len())
+            .finish()
+    }
+}
+
+/// What a [`Server::on_response`] hook is told about the request being
+/// answered.
+#[derive(Clone, Debug)]
+pub struct RequestInfo {
+    method: http::Method,
+    uri: http::Uri,
+    version: http::Version,
+    headers: http::HeaderMap,
+    remote_addr: Option<SocketAddr>,
+    started: tokio::time::Instant,
+}
+
+impl RequestInfo {
+    /// The request method.
+    pub fn method(&self) -> &http::Method {
+        &self.method
+    }
+
+    /// The request URI.
+    pub fn uri(&self) -> &http::Uri {
+        &self.uri
+    }
+
+    /// The HTTP version of the request.
+    pub fn version(&self) -> http::Version {
+        self.version
+    }
+
+    /// The request headers.
+    pub fn headers(&self) -> &http::HeaderMap {
+        &self.headers
+    }
+
+    /// The address of the client, if known.
+    pub fn remote_addr(&self) -> Option<SocketAddr> {
+        self.remote_addr
+    }
+
+    /// How long ago the request was received.
+    pub fn elapsed(&self) -> Duration {
+        self.started.elapsed()
+    }
+}
+
 /// A Nextshell Server ready to filter requests over TLS.
 ///
 /// *This type requires the `"tls"` feature.*
@@ -51,22 +418,81 @@ pub struct TlsServer<F> {
 // Getting all various generic bounds to make this a re-usable method is
 // very complicated, so instead this is just a macro.
 macro_rules! into_service {
-    ($into:expr) => {{
+    ($into:expr, $limits:expr, $timeouts:expr, $hooks:expr, $state:expr) => {{
         let inner = crate::service($into);
-        make_service_fn(move |transport| {
+        let limits = $limits.clone();
+        let body_read = $timeouts.body_read;
+        let hooks = $hooks.clone();
+        let state = $state.clone();
+        make_service_fn(move |transport: &crate::transport::HeadTimeout<_>| {
             let inner = inner.clone();
             let remote_addr = Transport::remote_addr(transport);
-            future::ok::<_, Infallible>(service_fn(move |req| {
-                inner.call_with_addr(req, remote_addr)
+            let requests = transport.requests();
+            #[cfg(feature = "tls")]
+            let tls = Transport::tls_info(transport);
+            // The guard lives as long as the connection's service does.
+            let guard = limits.acquire(remote_addr);
+            let over_limit = guard.is_none();
+            if over_limit && !limits.respond_503 {
+                tracing::debug!(?remote_addr, "connection limit reached, closing");
+                return future::err(ConnLimitExceeded);
+            }
+            let stats = limits.stats.clone();
+            let header_limits = limits.headers;
+            let hooks = hooks.clone();
+            let state = state.clone();
+            future::ok(service_fn(move |mut req: crate::Request| {
+                let _guard = &guard;
+                let in_flight = requests.start();
+                stats.requests.fetch_add(1, Ordering::Relaxed);
+                if over_limit {
+                    future::Either::Left(future::ok::<_, Infallible>(conn_limit_response(
+                        req.version(),
+                    )))
+                } else {
+                    if !header_limits.allows(req.headers()) {
+                        tracing::debug!("request headers over the limits, rejecting");
+                        let res = crate::reject::IsReject::into_response(
+                            &crate::reject::header_fields_too_large(),
+                        );
+                        drop(in_flight);
+                        return future::Either::Left(future::ok(res));
+                    }
+                    if !state.is_empty() {
+                        req.extensions_mut().insert(state.clone());
+                    }
+                    #[cfg(feature = "tls")]
+                    if let Some(info) = tls.as_ref().and_then(|tls| tls.get()) {
+                        req.extensions_mut().insert(info.clone());
+                    }
+                    let timed_out = body_read
+                        .and_then(|timeout| crate::filters::body::read_timeout(&mut req, timeout));
+                    let info = hooks.request_info(&req, remote_addr);
+                    let hooks = hooks.clone();
+                    let res = inner.call_with_addr(req, remote_addr);
+                    future::Either::Right(async move {
+                        let mut res = res.await?;
+                        if let Some(info) = info {
+                            res = hooks.on_response(info, res).await;
+                        }
+                        drop(in_flight);
+                        if timed_out.is_some_and(|flag| flag.load(Ordering::SeqCst)) {
+                            res.headers_mut().insert(
+                                http::header::CONNECTION,
+                                http::HeaderValue::from_static("close"),
+                            );
+                        }
+                        Ok(res)
+                    })
+                }
             }))
         })
     }};
 }
 
 macro_rules! addr_incoming {
-    ($addr:expr) => {{
-        let mut incoming = AddrIncoming::bind($addr)?;
-        incoming.set_nodelay(true);
+    ($tcp:expr, $addr:expr) => {{
+        let incoming = $tcp.bind($addr)?;
         let addr = incoming.local_addr();
         (addr, incoming)
     }};
@@ -74,20 +500,47 @@ macro_rules! addr_incoming {
 
 macro_rules! bind_inner {
     ($this:ident, $addr:expr) => {{
-        let service = into_service!($this.filter);
-        let (addr, incoming) = addr_incoming!($addr);
-        let srv = HyperServer::builder(incoming)
+        let header_limits = $this.limits.headers;
+        let service = into_service!(
+            $this.filter,
+            $this.limits,
+            $this.timeouts,
+            $this.hooks,
+            $this.state
+        );
+        let (addr, incoming) = addr_incoming!($this.tcp, $addr);
+        let srv = header_limits
+            .apply($this.http2.apply(HyperServer::builder(HeadTimeoutAccept::new(
+                incoming,
+                $this.timeouts.header_read,
+            ))))
             .http1_pipeline_flush($this.pipeline)
             .serve(service);
-        Ok::<_, hyper::Error>((addr, srv))
+        Ok::<_, Box<dyn std::error::Error + Send + Sync>>((addr, srv))
     }};
 
     (tls: $this:ident, $addr:expr) => {{
-        let service = into_service!($this.server.filter);
-        let (addr, incoming) = addr_incoming!($addr);
+        let server = $this.server;
         let tls = $this.tls.build()?;
-        let srv = HyperServer::builder(crate::tls::TlsAcceptor::new(tls, incoming))
-            .http1_pipeline_flush($this.server.pipeline)
+        bind_inner!(tls_config: server, tls, $addr)
+    }};
+
+    (tls_config: $server:ident, $tls:expr, $addr:expr) => {{
+        let header_limits = $server.limits.headers;
+        let service = into_service!(
+            $server.filter,
+            $server.limits,
+            $server.timeouts,
+            $server.hooks,
+            $server.state
+        );
+        let (addr, incoming) = addr_incoming!($server.tcp, $addr);
+        let srv = header_limits
+            .apply($server.http2.apply(HyperServer::builder(HeadTimeoutAccept::new(
+                crate::tls::TlsAcceptor::new($tls, incoming),
+                $server.timeouts.header_read,
+            ))))
+            .http1_pipeline_flush($server.pipeline)
             .serve(service);
         Ok::<_, Box<dyn std::error::Error + Send + Sync>>((addr, srv))
     }};
@@ -107,6 +560,13 @@ macro_rules! bind {
             panic!("error binding to {}: {}", addr, e);
         })
     }};
+
+    (tls_config: $server:ident, $tls:expr, $addr:expr) => {{
+        let addr = $addr.into();
+        (|addr| bind_inner!(tls_config: $server, $tls, addr))(&addr).unwrap_or_else(|e| {
+            panic!("error binding to {}: {}", addr, e);
+        })
+    }};
 }
 
 macro_rules! try_bind {
@@ -140,6 +600,47 @@ where
         fut.instrument(span).await;
     }
 
+    /// Run this `Server` forever on the current thread, listening on all
+    /// the provided addresses.
+    ///
+    /// Every listener serves the same filter, which is useful for dual-stack
+    /// or multi-port setups. When both IPv4 and IPv6 addresses are given,
+    /// the IPv6 listeners only accept IPv6 connections, so `0.0.0.0` and
+    /// `[::]` can share a port. To serve different filters, run several
+    /// servers together instead, for instance with `futures_util::future::join`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if we are unable to bind to any of the provided addresses.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use std::net::SocketAddr;
+    /// use nextshell::Filter;
+    ///
+    /// # async fn run() {
+    /// let v4: SocketAddr = ([0, 0, 0, 0], 3030).into();
+    /// let v6: SocketAddr = ([0, 0, 0, 0, 0, 0, 0, 0], 3030).into();
+    ///
+    /// nextshell::serve(nextshell::any().map(|| "hello"))
+    ///     .run_all([v4, v6])
+    ///     .await;
+    /// # }
+    /// ```
+    pub async fn run_all<A>(self, addrs: impl IntoIterator<Item = A>)
+    where
+        A: Into<SocketAddr>,
+    {
+        let (addrs, fut) = self.bind_all(addrs);
+        let span = tracing::info_span!("Server::run_all", ?addrs);
+        for addr in &addrs {
+            tracing::info!(parent: &span, "listening on http://{}", addr);
+        }
+
+        fut.instrument(span).await;
+    }
+
     /// Run this `Server` forever on the current thread with a specific stream
     /// of incoming connections.
     ///
@@ -224,6 +725,158 @@ where
         (addr, srv)
     }
 
+    /// Bind to several possibly ephemeral socket addresses, serving the same
+    /// filter on all of them.
+    ///
+    /// Returns the bound addresses, in the same order, and a `Future` that
+    /// can be executed on the current runtime.
+    ///
+    /// See [`run_all`](Server::run_all) for how IPv4 and IPv6 addresses are
+    /// bound together.
+    ///
+    /// # Panics
+    ///
+    /// Panics if we are unable to bind to any of the provided addresses.
+    pub fn bind_all<A>(
+        self,
+        addrs: impl IntoIterator<Item = A>,
+    ) -> (Vec<SocketAddr>, impl Future<Output = ()> + 'static)
+    where
+        A: Into<SocketAddr>,
+    {
+        let addrs = addrs.into_iter().map(Into::into).collect::<Vec<_>>();
+        let only_v6 = v6_only(&addrs);
+        let (addrs, servers): (Vec<_>, Vec<_>) = addrs
+            .into_iter()
+            .map(|addr| {
+                let mut server = self.duplicate();
+                server.tcp.only_v6 = only_v6;
+                server.bind_ephemeral(addr)
+            })
+            .unzip();
+
+        (addrs, future::join_all(servers).map(|_| ()))
+    }
+
+    /// Copies the filter and configuration, to bind them to another address.
+    fn duplicate(&self) -> Server<F> {
+        Server {
+            pipeline: self.pipeline,
+            http2: self.http2,
+            tcp: self.tcp,
+            limits: self.limits.clone(),
+            timeouts: self.timeouts,
+            hooks: self.hooks.clone(),
+            state: self.state.clone(),
+            filter: self.filter.clone(),
+        }
+    }
+
+    /// Run this `Server` forever on the current thread, listening on a Unix
+    /// domain socket.
+    ///
+    /// A stale socket file left behind by a previous process is removed
+    /// before binding, and the socket file is removed again once the server
+    /// future completes or is dropped.
+    ///
+    /// # Panics
+    ///
+    /// Panics if we are unable to bind to the provided socket.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use nextshell::Filter;
+    ///
+    /// # async fn run() {
+    /// let socket = nextshell::UnixSocket::new("/tmp/nextshell.sock").mode(0o660);
+    ///
+    /// nextshell::serve(nextshell::any().map(|| "hello"))
+    ///     .run_unix(socket)
+    ///     .await;
+    /// # }
+    /// ```
+    #[cfg(unix)]
+    pub async fn run_unix(self, socket: impl Into<UnixSocket>) {
+        let socket = socket.into();
+        let path = socket.path.clone();
+        let fut = self
+            .try_bind_unix(socket)
+            .unwrap_or_else(|err| panic!("error binding to {}: {}", path.display(), err));
+        let span = tracing::info_span!("Server::run_unix", path = %path.display());
+        tracing::info!(parent: &span, "listening on unix:{}", path.display());
+
+        fut.instrument(span).await;
+    }
+
+    /// Bind to a Unix domain socket, returning a `Future` that can be
+    /// executed on the current runtime.
+    ///
+    /// Returns a `Result` which fails in case we are unable to bind the
+    /// socket or apply its mode and ownership, or if another process is
+    /// still listening on it.
+    #[cfg(unix)]
+    pub fn try_bind_unix(
+        self,
+        socket: impl Into<UnixSocket>,
+    ) -> Result<impl Future<Output = ()> + 'static, crate::Error> {
+        let socket = socket.into();
+        let listener = socket.bind().map_err(crate::Error::new)?;
+        let guard = RemoveOnDrop(socket.path);
+        let incoming = futures_util::stream::poll_fn(move |cx| {
+            listener
+                .poll_accept(cx)
+                .map(|result| Some(result.map(|(stream, _)| crate::transport::LiftIo(stream))))
+        });
+
+        Ok(async move {
+            let _guard = guard;
+            self.serve_incoming2(incoming).await;
+        })
+    }
+
+    /// Run this `Server` forever on the current thread, listening on a
+    /// Windows named pipe such as `\\.\pipe\nextshell`.
+    ///
+    /// A new pipe instance is created for every accepted client.
+    ///
+    /// # Panics
+    ///
+    /// Panics if we are unable to create the pipe, for instance because
+    /// another process already owns it.
+    #[cfg(windows)]
+    pub async fn run_named_pipe(self, name: impl AsRef<std::ffi::OsStr>) {
+        use tokio::net::windows::named_pipe::ServerOptions;
+
+        let name = name.as_ref().to_owned();
+        let first = ServerOptions::new()
+            .first_pipe_instance(true)
+            .create(&name)
+            .unwrap_or_else(|err| panic!("error creating pipe {:?}: {}", name, err));
+        let span = tracing::info_span!("Server::run_named_pipe", ?name);
+        tracing::info!(parent: &span, "listening on pipe {:?}", name);
+
+        let incoming =
+            futures_util::stream::unfold((Some(first), name), |(pipe, name)| async move {
+                let pipe = match pipe {
+                    Some(pipe) => pipe,
+                    None => match ServerOptions::new().create(&name) {
+                        Ok(pipe) => pipe,
+                        Err(err) => return Some((Err(err), (None, name))),
+                    },
+                };
+                if let Err(err) = pipe.connect().await {
+                    return Some((Err(err), (None, name)));
+                }
+                // Have the next instance ready before handing this one out, so
+                // clients never find the pipe missing.
+                let next = ServerOptions::new().create(&name).ok();
+                Some((Ok(crate::transport::LiftIo(pipe)), (next, name)))
+            });
+
+        self.serve_incoming2(incoming).instrument(span).await;
+    }
+
     /// Tried to bind a possibly ephemeral socket address.
     ///
     /// Returns a `Result` which fails in case we are unable to bind with the
@@ -317,6 +970,88 @@ where
         Ok((addr, srv))
     }
 
+    /// Bind to the provided addresses and spawn this `Server` on the current
+    /// runtime, returning a [`ServerHandle`] to observe and stop it.
+    ///
+    /// See [`run_all`](Server::run_all) for how IPv4 and IPv6 addresses are
+    /// bound together.
+    ///
+    /// # Panics
+    ///
+    /// Panics if we are unable to bind to any of the provided addresses, or
+    /// if called outside of a Tokio runtime.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use nextshell::Filter;
+    ///
+    /// # async fn run() {
+    /// let handle = nextshell::serve(nextshell::any().map(|| "hello"))
+    ///     .start([([127, 0, 0, 1], 0)]);
+    /// handle.ready().await;
+    /// println!("listening on {:?}", handle.local_addr());
+    ///
+    /// // Later...
+    /// handle.shutdown();
+    /// handle.stopped().await;
+    /// # }
+    /// ```
+    pub fn start<A>(self, addrs: impl IntoIterator<Item = A>) -> ServerHandle
+    where
+        A: Into<SocketAddr>,
+    {
+        self.try_start(addrs)
+            .unwrap_or_else(|err| panic!("error binding server: {}", err))
+    }
+
+    /// Like [`start`](Server::start), but returns an error instead of
+    /// panicking if an address can't be bound.
+    pub fn try_start<A>(
+        self,
+        addrs: impl IntoIterator<Item = A>,
+    ) -> Result<ServerHandle, crate::Error>
+    where
+        A: Into<SocketAddr>,
+    {
+        let (shutdown_tx, shutdown_rx) = watch::channel(false);
+        let (ready_tx, ready_rx) = watch::channel(false);
+        let stats = self.limits.stats.clone();
+
+        let addrs = addrs.into_iter().map(Into::into).collect::<Vec<_>>();
+        let only_v6 = v6_only(&addrs);
+        let mut bound = Vec::new();
+        let mut servers = Vec::new();
+        for addr in addrs {
+            let mut server = self.duplicate();
+            server.tcp.only_v6 = only_v6;
+            let (addr, srv) =
+                server.try_bind_with_graceful_shutdown(addr, wait_true(shutdown_rx.clone()))?;
+            bound.push(addr);
+            servers.push(srv);
+        }
+
+        let span = tracing::info_span!("Server::start", addrs = ?bound);
+        for addr in &bound {
+            tracing::info!(parent: &span, "listening on http://{}", addr);
+        }
+        let task = tokio::spawn(
+            async move {
+                let _ = ready_tx.send(true);
+                future::join_all(servers).await;
+            }
+            .instrument(span),
+        );
+
+        Ok(ServerHandle {
+            addrs: bound,
+            stats,
+            ready: ready_rx,
+            shutdown: shutdown_tx,
+            task,
+        })
+    }
+
     /// Setup this `Server` with a specific stream of incoming connections.
     ///
     /// This can be used for Unix Domain Sockets, or TLS, etc.
@@ -353,16 +1088,28 @@ where
         I::Error: Into<Box<dyn StdError + Send + Sync>>,
     {
         let incoming = incoming.map_ok(crate::transport::LiftIo);
-        let service = into_service!(self.filter);
+        let header_limits = self.limits.headers;
+        let service = into_service!(
+            self.filter,
+            self.limits,
+            self.timeouts,
+            self.hooks,
+            self.state
+        );
         let pipeline = self.pipeline;
+        let http2 = self.http2;
+        let header_read = self.timeouts.header_read;
 
         async move {
-            let srv =
-                HyperServer::builder(hyper::server::accept::from_stream(incoming.into_stream()))
-                    .http1_pipeline_flush(pipeline)
-                    .serve(service)
-                    .with_graceful_shutdown(signal)
-                    .await;
+            let srv = header_limits
+                .apply(http2.apply(HyperServer::builder(HeadTimeoutAccept::new(
+                    hyper::server::accept::from_stream(incoming.into_stream()),
+                    header_read,
+                ))))
+                .http1_pipeline_flush(pipeline)
+                .serve(service)
+                .with_graceful_shutdown(signal)
+                .await;
 
             if let Err(err) = srv {
                 tracing::error!("server error: {}", err);
@@ -379,9 +1126,23 @@ where
         I::Ok: Transport + Send + 'static + Unpin,
         I::Error: Into<Box<dyn StdError + Send + Sync>>,
     {
-        let service = into_service!(self.filter);
+        let header_limits = self.limits.headers;
+        let service = into_service!(
+            self.filter,
+            self.limits,
+            self.timeouts,
+            self.hooks,
+            self.state
+        );
 
-        let srv = HyperServer::builder(hyper::server::accept::from_stream(incoming.into_stream()))
+        let srv = header_limits
+            .apply(
+                self.http2
+                    .apply(HyperServer::builder(HeadTimeoutAccept::new(
+                        hyper::server::accept::from_stream(incoming.into_stream()),
+                        self.timeouts.header_read,
+                    ))),
+            )
             .http1_pipeline_flush(self.pipeline)
             .serve(service)
             .await;
@@ -391,55 +1152,444 @@ where
         }
     }
 
-    // Generally shouldn't be used, as it can slow down non-pipelined responses.
-    //
-    // It's only real use is to make silly pipeline benchmarks look better.
-    #[doc(hidden)]
-    pub fn unstable_pipeline(mut self) -> Self {
-        self.pipeline = true;
+    /// Sets the maximum number of concurrent HTTP/2 streams per connection.
+    ///
+    /// Defaults to hyper's limit of 200.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use std::time::Duration;
+    /// use nextshell::Filter;
+    ///
+    /// # async fn run() {
+    /// nextshell::serve(nextshell::any().map(nextshell::reply))
+    ///     .http2_max_concurrent_streams(1_000)
+    ///     .http2_adaptive_window(true)
+    ///     .http2_keep_alive_interval(Duration::from_secs(30))
+    ///     .run(([127, 0, 0, 1], 3030))
+    ///     .await;
+    /// # }
+    /// ```
+    pub fn http2_max_concurrent_streams(mut self, max: u32) -> Self {
+        self.http2.max_concurrent_streams = Some(max);
         self
     }
 
-    /// Configure a server to use TLS.
+    /// Sets the initial HTTP/2 flow control window of each stream, in bytes.
     ///
-    /// *This function requires the `"tls"` feature.*
-    #[cfg(feature = "tls")]
-    pub fn tls(self) -> TlsServer<F> {
-        TlsServer {
-            server: self,
-            tls: TlsConfigBuilder::new(),
-        }
+    /// Ignored when [`http2_adaptive_window`](Server::http2_adaptive_window)
+    /// is enabled.
+    pub fn http2_initial_stream_window_size(mut self, size: u32) -> Self {
+        self.http2.initial_stream_window_size = Some(size);
+        self
     }
-}
-
-// // ===== impl TlsServer =====
 
-#[cfg(feature = "tls")]
-impl<F> TlsServer<F>
-where
-    F: Filter + Clone + Send + Sync + 'static,
-    <F::Future as TryFuture>::Ok: Reply,
-    <F::Future as TryFuture>::Error: IsReject,
-{
-    // TLS config methods
+    /// Sets the initial HTTP/2 flow control window of each connection, in
+    /// bytes.
+    ///
+    /// Ignored when [`http2_adaptive_window`](Server::http2_adaptive_window)
+    /// is enabled.
+    pub fn http2_initial_connection_window_size(mut self, size: u32) -> Self {
+        self.http2.initial_connection_window_size = Some(size);
+        self
+    }
 
-    /// Specify the file path to read the private key.
+    /// Sets whether HTTP/2 flow control windows adapt to the measured
+    /// bandwidth-delay product of each connection.
     ///
-    /// *This function requires the `"tls"` feature.*
-    pub fn key_path(self, path: impl AsRef<Path>) -> Self {
-        self.with_tls(|tls| tls.key_path(path))
+    /// Defaults to `false`.
+    pub fn http2_adaptive_window(mut self, enabled: bool) -> Self {
+        self.http2.adaptive_window = enabled;
+        self
     }
 
-    /// Specify the file path to read the certificate.
+    /// Sends HTTP/2 pings at this interval to keep idle connections alive.
     ///
-    /// *This function requires the `"tls"` feature.*
-    pub fn cert_path(self, path: impl AsRef<Path>) -> Self {
-        self.with_tls(|tls| tls.cert_path(path))
+    /// Disabled by default.
+    pub fn http2_keep_alive_interval(mut self, interval: Duration) -> Self {
+        self.http2.keep_alive_interval = Some(interval);
+        self
     }
 
-    /// Specify the file path to read the trust anchor for optional client authentication.
+    /// Closes HTTP/2 connections whose keep-alive ping isn't acknowledged
+    /// within this timeout.
     ///
-    /// Anonymous and authenticated clients will be accepted. If no trust anchor is provided by any
+    /// Defaults to 20 seconds. Does nothing unless
+    /// [`http2_keep_alive_interval`](Server::http2_keep_alive_interval) is
+    /// set.
+    pub fn http2_keep_alive_timeout(mut self, timeout: Duration) -> Self {
+        self.http2.keep_alive_timeout = Some(timeout);
+        self
+    }
+
+    /// Caps the number of concurrent connections, over all listeners.
+    ///
+    /// Connections beyond the cap are closed right away, or answered with
+    /// `503 Service Unavailable` if
+    /// [`connection_limit_503`](Server::connection_limit_503) is enabled.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use nextshell::Filter;
+    ///
+    /// # async fn run() {
+    /// nextshell::serve(nextshell::any().map(nextshell::reply))
+    ///     .max_connections(10_000)
+    ///     .max_connections_per_ip(64)
+    ///     .run(([0, 0, 0, 0], 3030))
+    ///     .await;
+    /// # }
+    /// ```
+    pub fn max_connections(mut self, max: usize) -> Self {
+        self.limits.max = Some(max);
+        self
+    }
+
+    /// Caps the number of concurrent connections from a single client IP.
+    ///
+    /// Connections without a remote address, such as Unix sockets, are only
+    /// subject to [`max_connections`](Server::max_connections).
+    pub fn max_connections_per_ip(mut self, max: usize) -> Self {
+        self.limits.per_ip = Some(max);
+        self
+    }
+
+    /// Answers every request on a connection over the caps with
+    /// `503 Service Unavailable`, instead of closing it immediately.
+    ///
+    /// Defaults to `false`.
+    pub fn connection_limit_503(mut self, enabled: bool) -> Self {
+        self.limits.respond_503 = enabled;
+        self
+    }
+
+    /// Caps the number of headers of a request.
+    ///
+    /// Requests over the cap are answered with
+    /// `431 Request Header Fields Too Large` before reaching the filter.
+    /// Disabled by default, though hyper itself refuses HTTP/1 requests with
+    /// more than 100 headers, so a cap above 100 only has an effect on
+    /// HTTP/2 requests.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use nextshell::Filter;
+    ///
+    /// # async fn run() {
+    /// nextshell::serve(nextshell::any().map(nextshell::reply))
+    ///     .max_header_count(32)
+    ///     .max_header_size(4 * 1024)
+    ///     .max_header_bytes(16 * 1024)
+    ///     .run(([0, 0, 0, 0], 3030))
+    ///     .await;
+    /// # }
+    /// ```
+    pub fn max_header_count(mut self, max: usize) -> Self {
+        self.limits.headers.count = Some(max);
+        self
+    }
+
+    /// Caps the size of each request header, counting the bytes of its name
+    /// and value.
+    ///
+    /// See [`max_header_count`](Server::max_header_count).
+    pub fn max_header_size(mut self, max: usize) -> Self {
+        self.limits.headers.size = Some(max);
+        self
+    }
+
+    /// Caps the total size of the request headers, counting the bytes of
+    /// their names and values.
+    ///
+    /// This also caps the buffer HTTP/1 request heads are read into to `max`
+    /// plus 8KB, left for the request line and the header framing, so that
+    /// much larger heads are refused while being read rather than buffered
+    /// whole first.
+    ///
+    /// See [`max_header_count`](Server::max_header_count).
+    pub fn max_header_bytes(mut self, max: usize) -> Self {
+        self.limits.headers.total = Some(max);
+        self
+    }
+
+    /// Closes HTTP/1 connections whose client takes longer than `timeout` to
+    /// send a request head, answering `408 Request Timeout` first if part of
+    /// one was received.
+    ///
+    /// The timer starts when the connection is accepted, TLS handshake
+    /// included, and again with the first bytes of each following request,
+    /// so clients can't hold connections by trickling headers in. It's
+    /// turned off once a connection turns out to speak HTTP/2, from its
+    /// preface or from the protocol negotiated with TLS.
+    ///
+    /// Disabled by default.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use std::time::Duration;
+    /// use nextshell::Filter;
+    ///
+    /// # async fn run() {
+    /// nextshell::serve(nextshell::any().map(nextshell::reply))
+    ///     .header_read_timeout(Duration::from_secs(10))
+    ///     .body_read_timeout(Duration::from_secs(30))
+    ///     .run(([0, 0, 0, 0], 3030))
+    ///     .await;
+    /// # }
+    /// ```
+    pub fn header_read_timeout(mut self, timeout: Duration) -> Self {
+        self.timeouts.header_read = Some(timeout);
+        self
+    }
+
+    /// Fails reading an HTTP/1 request body that stalls for longer than
+    /// `timeout` between two chunks.
+    ///
+    /// The body filters then reject with `408 Request Timeout`, and the
+    /// connection is closed after the response.
+    ///
+    /// Disabled by default.
+    pub fn body_read_timeout(mut self, timeout: Duration) -> Self {
+        self.timeouts.body_read = Some(timeout);
+        self
+    }
+
+    /// Shares `state` with every request, to be extracted with
+    /// [`nextshell::state()`](crate::state()).
+    ///
+    /// Can be called once per type; registering a second state of the same
+    /// type replaces the first.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use nextshell::Filter;
+    ///
+    /// #[derive(Clone)]
+    /// struct Pool;
+    ///
+    /// # async fn run() {
+    /// let route = nextshell::state::<Pool>().map(|_pool: Pool| "ok");
+    ///
+    /// nextshell::serve(route)
+    ///     .with_state(Pool)
+    ///     .run(([0, 0, 0, 0], 3030))
+    ///     .await;
+    /// # }
+    /// ```
+    pub fn with_state<T>(mut self, state: T) -> Self
+    where
+        T: Clone + Send + Sync + 'static,
+    {
+        self.state.insert(state);
+        self
+    }
+
+    /// Calls `hook` with every response produced by the filter, including
+    /// rejections, before it's written.
+    ///
+    /// The hook is given a [`RequestInfo`] describing the request, and
+    /// returns the response to send, so it can be used for audit logging,
+    /// signing responses, or enforcing headers on every route. Hooks run in
+    /// the order they are registered.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use nextshell::Filter;
+    ///
+    /// # async fn run() {
+    /// let routes = nextshell::path("hello").map(|| "world");
+    ///
+    /// nextshell::serve(routes)
+    ///     .on_response(|info: nextshell::RequestInfo, mut res: nextshell::reply::Response| async move {
+    ///         tracing::info!("{} {} -> {}", info.method(), info.uri(), res.status());
+    ///         res.headers_mut().insert("x-frame-options", "DENY".parse().unwrap());
+    ///         res
+    ///     })
+    ///     .run(([127, 0, 0, 1], 3030))
+    ///     .await;
+    /// # }
+    /// ```
+    pub fn on_response<H, Fut>(mut self, hook: H) -> Self
+    where
+        H: Fn(RequestInfo, crate::reply::Response) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = crate::reply::Response> + Send + 'static,
+    {
+        self.hooks
+            .on_response
+            .push(Arc::new(move |info, res| hook(info, res).boxed()));
+        self
+    }
+
+    /// Sets `TCP_NODELAY` on accepted connections.
+    ///
+    /// Defaults to `true`, disabling Nagle's algorithm.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use std::time::Duration;
+    /// use nextshell::Filter;
+    ///
+    /// # async fn run() {
+    /// nextshell::serve(nextshell::any().map(nextshell::reply))
+    ///     .tcp_keepalive(Some(Duration::from_secs(60)))
+    ///     .tcp_keepalive_interval(Some(Duration::from_secs(10)))
+    ///     .tcp_reuse_port(true)
+    ///     .backlog(4096)
+    ///     .run(([0, 0, 0, 0], 3030))
+    ///     .await;
+    /// # }
+    /// ```
+    pub fn tcp_nodelay(mut self, enabled: bool) -> Self {
+        self.tcp.nodelay = enabled;
+        self
+    }
+
+    /// Enables `SO_KEEPALIVE` on accepted connections, sending the first
+    /// probe after the connection has been idle for `time`.
+    ///
+    /// Disabled by default.
+    pub fn tcp_keepalive(mut self, time: Option<Duration>) -> Self {
+        self.tcp.keepalive = time;
+        self
+    }
+
+    /// Sets the time between TCP keepalive probes.
+    ///
+    /// Does nothing unless [`tcp_keepalive`](Server::tcp_keepalive) is set.
+    pub fn tcp_keepalive_interval(mut self, interval: Option<Duration>) -> Self {
+        self.tcp.keepalive_interval = interval;
+        self
+    }
+
+    /// Sets how many unanswered TCP keepalive probes close the connection.
+    ///
+    /// Does nothing unless [`tcp_keepalive`](Server::tcp_keepalive) is set.
+    pub fn tcp_keepalive_retries(mut self, retries: Option<u32>) -> Self {
+        self.tcp.keepalive_retries = retries;
+        self
+    }
+
+    /// Sets `SO_REUSEPORT` on the listening socket, letting several
+    /// processes bind the same address and share its connections.
+    ///
+    /// Defaults to `false`. Only available on Unix platforms.
+    #[cfg(all(unix, not(any(target_os = "solaris", target_os = "illumos"))))]
+    pub fn tcp_reuse_port(mut self, enabled: bool) -> Self {
+        self.tcp.reuse_port = enabled;
+        self
+    }
+
+    /// Sets the maximum length of the queue of pending connections.
+    ///
+    /// Defaults to 1024. The operating system may cap it further, e.g.
+    /// through `net.core.somaxconn` on Linux.
+    pub fn backlog(mut self, backlog: u32) -> Self {
+        self.tcp.backlog = backlog;
+        self
+    }
+
+    // Generally shouldn't be used, as it can slow down non-pipelined responses.
+    //
+    // It's only real use is to make silly pipeline benchmarks look better.
+    #[doc(hidden)]
+    pub fn unstable_pipeline(mut self) -> Self {
+        self.pipeline = true;
+        self
+    }
+
+    /// Configure a server to use TLS.
+    ///
+    /// *This function requires the `"tls"` feature.*
+    #[cfg(feature = "tls")]
+    pub fn tls(self) -> TlsServer<F> {
+        TlsServer {
+            server: self,
+            tls: TlsConfigBuilder::new(),
+        }
+    }
+}
+
+impl<F> Server<F>
+where
+    F: Filter<Error = crate::Rejection> + Clone + Send + Sync + 'static,
+    F::Extract: Reply,
+{
+    /// Handle any rejection escaping the filter tree with `handler`,
+    /// instead of the built-in plain text responses.
+    ///
+    /// This applies to unmatched routes as well as rejections a route
+    /// forgot to recover from, so every error response has the same shape.
+    /// It's the same as calling [`Filter::recover`] on the whole tree.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use nextshell::Filter;
+    ///
+    /// # async fn run() {
+    /// let routes = nextshell::path("hello").map(|| "world");
+    ///
+    /// nextshell::serve(routes)
+    ///     .recover(nextshell::reject::problem_json())
+    ///     .run(([127, 0, 0, 1], 3030))
+    ///     .await;
+    /// # }
+    /// ```
+    pub fn recover<H>(self, handler: H) -> Server<Recover<F, H>>
+    where
+        H: Func<crate::Rejection> + Clone + Send + Sync + 'static,
+        H::Output: TryFuture + Send,
+        <H::Output as TryFuture>::Ok: Reply,
+        <H::Output as TryFuture>::Error: IsReject,
+    {
+        Server {
+            pipeline: self.pipeline,
+            http2: self.http2,
+            tcp: self.tcp,
+            limits: self.limits,
+            timeouts: self.timeouts,
+            hooks: self.hooks,
+            state: self.state,
+            filter: self.filter.recover(handler),
+        }
+    }
+}
+
+// // ===== impl TlsServer =====
+
+#[cfg(feature = "tls")]
+impl<F> TlsServer<F>
+where
+    F: Filter + Clone + Send + Sync + 'static,
+    <F::Future as TryFuture>::Ok: Reply,
+    <F::Future as TryFuture>::Error: IsReject,
+{
+    // TLS config methods
+
+    /// Specify the file path to read the private key.
+    ///
+    /// *This function requires the `"tls"` feature.*
+    pub fn key_path(self, path: impl AsRef<Path>) -> Self {
+        self.with_tls(|tls| tls.key_path(path))
+    }
+
+    /// Specify the file path to read the certificate.
+    ///
+    /// *This function requires the `"tls"` feature.*
+    pub fn cert_path(self, path: impl AsRef<Path>) -> Self {
+        self.with_tls(|tls| tls.cert_path(path))
+    }
+
+    /// Specify the file path to read the trust anchor for optional client authentication.
+    ///
+    /// Anonymous and authenticated clients will be accepted. If no trust anchor is provided by any
     /// of the `client_auth_` methods, then client authentication is disabled by default.
     ///
     /// *This function requires the `"tls"` feature.*
@@ -498,6 +1648,122 @@ where
         self.with_tls(|tls| tls.ocsp_resp(resp.as_ref()))
     }
 
+    /// Specify the file path to read the DER-encoded OCSP response to staple.
+    ///
+    /// *This function requires the `"tls"` feature.*
+    pub fn ocsp_resp_path(self, path: impl AsRef<Path>) -> Self {
+        self.with_tls(|tls| tls.ocsp_resp_path(path))
+    }
+
+    /// Specify the lowest TLS version accepted from clients.
+    ///
+    /// Defaults to [`TlsVersion::Tls12`].
+    ///
+    /// *This function requires the `"tls"` feature.*
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use nextshell::{Filter, TlsVersion};
+    ///
+    /// # async fn run() {
+    /// nextshell::serve(nextshell::any().map(nextshell::reply))
+    ///     .tls()
+    ///     .cert_path("examples/tls/cert.pem")
+    ///     .key_path("examples/tls/key.rsa")
+    ///     .min_protocol_version(TlsVersion::Tls13)
+    ///     .run(([0, 0, 0, 0], 443))
+    ///     .await;
+    /// # }
+    /// ```
+    pub fn min_protocol_version(self, version: TlsVersion) -> Self {
+        self.with_tls(|tls| tls.min_protocol_version(version))
+    }
+
+    /// Specify the highest TLS version accepted from clients.
+    ///
+    /// Defaults to [`TlsVersion::Tls13`].
+    ///
+    /// *This function requires the `"tls"` feature.*
+    pub fn max_protocol_version(self, version: TlsVersion) -> Self {
+        self.with_tls(|tls| tls.max_protocol_version(version))
+    }
+
+    /// Restrict the cipher suites to the given ones, in order of preference.
+    ///
+    /// Suites are named as in the IANA registry, ignoring case, such as
+    /// `TLS13_AES_256_GCM_SHA384` or
+    /// `TLS_ECDHE_RSA_WITH_AES_256_GCM_SHA384`. Running the server fails if
+    /// a name is unknown, or if none of the suites is usable with the
+    /// allowed protocol versions.
+    ///
+    /// *This function requires the `"tls"` feature.*
+    pub fn cipher_suites<I>(self, names: I) -> Self
+    where
+        I: IntoIterator,
+        I::Item: AsRef<str>,
+    {
+        self.with_tls(|tls| tls.cipher_suites(names))
+    }
+
+    /// Specify the ALPN protocols offered to clients, in order of preference.
+    ///
+    /// Defaults to `h2` then `http/1.1`. Leaving out `h2` restricts clients
+    /// to HTTP/1.1.
+    ///
+    /// *This function requires the `"tls"` feature.*
+    pub fn alpn_protocols<I>(self, protocols: I) -> Self
+    where
+        I: IntoIterator,
+        I::Item: AsRef<[u8]>,
+    {
+        self.with_tls(|tls| tls.alpn_protocols(protocols))
+    }
+
+    /// Specify the file paths of a certificate and private key to serve to
+    /// clients asking for `hostname` with SNI.
+    ///
+    /// Can be called several times to serve multiple domains on a single
+    /// listener. `hostname` can be a wildcard such as `*.example.com`. The
+    /// certificate set with [`cert_path`](TlsServer::cert_path) and
+    /// [`key_path`](TlsServer::key_path) is optional once SNI certificates
+    /// are added, and used for clients matching none of them.
+    ///
+    /// *This function requires the `"tls"` feature.*
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use nextshell::Filter;
+    ///
+    /// # async fn run() {
+    /// nextshell::serve(nextshell::any().map(nextshell::reply))
+    ///     .tls()
+    ///     .sni_cert_path("foo.com", "foo.com.pem", "foo.com.key")
+    ///     .sni_cert_path("*.bar.com", "bar.com.pem", "bar.com.key")
+    ///     .run(([0, 0, 0, 0], 443))
+    ///     .await;
+    /// # }
+    /// ```
+    pub fn sni_cert_path(
+        self,
+        hostname: &str,
+        cert: impl AsRef<Path>,
+        key: impl AsRef<Path>,
+    ) -> Self {
+        self.with_tls(|tls| tls.sni_cert_path(hostname, cert, key))
+    }
+
+    /// Specify the in-memory contents of a certificate and private key to
+    /// serve to clients asking for `hostname` with SNI.
+    ///
+    /// See [`sni_cert_path`](TlsServer::sni_cert_path).
+    ///
+    /// *This function requires the `"tls"` feature.*
+    pub fn sni_cert(self, hostname: &str, cert: impl AsRef<[u8]>, key: impl AsRef<[u8]>) -> Self {
+        self.with_tls(|tls| tls.sni_cert(hostname, cert.as_ref(), key.as_ref()))
+    }
+
     fn with_tls<Func>(self, func: Func) -> Self
     where
         Func: FnOnce(TlsConfigBuilder) -> TlsConfigBuilder,
@@ -520,6 +1786,30 @@ where
         fut.instrument(span).await;
     }
 
+    /// Run this `TlsServer` forever on the current thread, listening on all
+    /// the provided addresses.
+    ///
+    /// See [`Server::run_all`].
+    ///
+    /// *This function requires the `"tls"` feature.*
+    ///
+    /// # Panics
+    ///
+    /// Panics if the TLS configuration is invalid, or if we are unable to
+    /// bind to any of the provided addresses.
+    pub async fn run_all<A>(self, addrs: impl IntoIterator<Item = A>)
+    where
+        A: Into<SocketAddr>,
+    {
+        let (addrs, fut) = self.bind_all(addrs);
+        let span = tracing::info_span!("TlsServer::run_all", ?addrs);
+        for addr in &addrs {
+            tracing::info!(parent: &span, "listening on https://{}", addr);
+        }
+
+        fut.instrument(span).await;
+    }
+
     /// Bind to a socket address, returning a `Future` that can be
     /// executed on a runtime.
     ///
@@ -557,6 +1847,53 @@ where
         (addr, srv)
     }
 
+    /// Bind to several possibly ephemeral socket addresses, serving the same
+    /// filter on all of them.
+    ///
+    /// The TLS configuration is built once, and shared by all listeners.
+    /// Returns the bound addresses, in the same order, and a `Future` that
+    /// can be executed on the current runtime.
+    ///
+    /// *This function requires the `"tls"` feature.*
+    ///
+    /// # Panics
+    ///
+    /// Panics if the TLS configuration is invalid, or if we are unable to
+    /// bind to any of the provided addresses.
+    pub fn bind_all<A>(
+        self,
+        addrs: impl IntoIterator<Item = A>,
+    ) -> (Vec<SocketAddr>, impl Future<Output = ()> + 'static)
+    where
+        A: Into<SocketAddr>,
+    {
+        let TlsServer { server, tls } = self;
+        let tls = tls
+            .build()
+            .unwrap_or_else(|e| panic!("error building TLS config: {}", e));
+        let addrs = addrs
+            .into_iter()
+            .map(Into::into)
+            .collect::<Vec<SocketAddr>>();
+        let only_v6 = v6_only(&addrs);
+        let (addrs, servers): (Vec<_>, Vec<_>) = addrs
+            .into_iter()
+            .map(|addr| {
+                let mut server = server.duplicate();
+                server.tcp.only_v6 = only_v6;
+                let (addr, srv) = bind!(tls_config: server, tls.clone(), addr);
+                let srv = srv.map(|result| {
+                    if let Err(err) = result {
+                        tracing::error!("server error: {}", err)
+                    }
+                });
+                (addr, srv)
+            })
+            .unzip();
+
+        (addrs, future::join_all(servers).map(|_| ()))
+    }
+
     /// Create a server with graceful shutdown signal.
     ///
     /// When the signal completes, the server will start the graceful shutdown
@@ -616,3 +1953,228 @@ where
             .finish()
     }
 }
+
+// ===== impl ServerHandle =====
+
+/// A handle to a running [`Server`], returned by [`Server::start`].
+///
+/// Dropping the handle leaves the server running.
+#[derive(Debug)]
+pub struct ServerHandle {
+    addrs: Vec<SocketAddr>,
+    stats: Arc<ConnStats>,
+    ready: watch::Receiver<bool>,
+    shutdown: watch::Sender<bool>,
+    task: tokio::task::JoinHandle<()>,
+}
+
+impl ServerHandle {
+    /// The bound addresses, in the order they were given to `start`.
+    pub fn addrs(&self) -> &[SocketAddr] {
+        &self.addrs
+    }
+
+    /// The first bound address, or `None` if the server was started without
+    /// any address.
+    pub fn local_addr(&self) -> Option<SocketAddr> {
+        self.addrs.first().copied()
+    }
+
+    /// Resolves once the server is accepting connections.
+    pub async fn ready(&self) {
+        wait_true(self.ready.clone()).await
+    }
+
+    /// The number of currently open connections.
+    pub fn active_connections(&self) -> usize {
+        self.stats.active.load(Ordering::SeqCst)
+    }
+
+    /// The number of connections accepted since the server started, not
+    /// counting those refused by connection caps.
+    pub fn total_connections(&self) -> u64 {
+        self.stats.accepted.load(Ordering::Relaxed)
+    }
+
+    /// The number of requests received since the server started.
+    pub fn total_requests(&self) -> u64 {
+        self.stats.requests.load(Ordering::Relaxed)
+    }
+
+    /// Starts a graceful shutdown: listeners stop accepting connections and
+    /// open connections are closed once their in-flight requests complete.
+    pub fn shutdown(&self) {
+        let _ = self.shutdown.send(true);
+    }
+
+    /// Resolves once the server has stopped, after [`shutdown`] was called.
+    ///
+    /// [`shutdown`]: ServerHandle::shutdown
+    pub async fn stopped(self) {
+        if let Err(err) = self.task.await {
+            tracing::error!("server task failed: {}", err);
+        }
+    }
+}
+
+/// Resolves when the watched flag turns `true`, and never if its sender is
+/// dropped before that.
+async fn wait_true(mut rx: watch::Receiver<bool>) {
+    while !*rx.borrow_and_update() {
+        if rx.changed().await.is_err() {
+            future::pending::<()>().await;
+        }
+    }
+}
+
+// ===== impl UnixSocket =====
+
+/// A Unix domain socket to serve on, see [`Server::run_unix`].
+#[cfg(unix)]
+#[derive(Clone, Debug)]
+pub struct UnixSocket {
+    path: PathBuf,
+    mode: Option<u32>,
+    uid: Option<u32>,
+    gid: Option<u32>,
+}
+
+#[cfg(unix)]
+impl UnixSocket {
+    /// Creates a socket bound at `path`, with the default mode and owner.
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        UnixSocket {
+            path: path.into(),
+            mode: None,
+            uid: None,
+            gid: None,
+        }
+    }
+
+    /// Sets the permission bits of the socket file, such as `0o660`.
+    ///
+    /// The mode and owner are applied before the socket appears at its
+    /// path, so clients can't connect with the default permissions in the
+    /// meantime.
+    pub fn mode(mut self, mode: u32) -> Self {
+        self.mode = Some(mode);
+        self
+    }
+
+    /// Sets the owning user and group of the socket file.
+    ///
+    /// `None` leaves the respective id unchanged. Changing ownership usually
+    /// requires elevated privileges.
+    pub fn owner(mut self, uid: Option<u32>, gid: Option<u32>) -> Self {
+        self.uid = uid;
+        self.gid = gid;
+        self
+    }
+
+    fn bind(&self) -> io::Result<tokio::net::UnixListener> {
+        self.remove_stale()?;
+        if self.mode.is_none() && self.uid.is_none() && self.gid.is_none() {
+            return tokio::net::UnixListener::bind(&self.path);
+        }
+
+        // Clients could connect between binding and changing the mode or
+        // owner, so the socket is set up in a private directory next to
+        // `path` and only moved into place once it's ready.
+        let file_name = self.path.file_name().ok_or_else(|| {
+            io::Error::new(io::ErrorKind::InvalidInput, "socket path has no file name")
+        })?;
+        let mut staging_name = std::ffi::OsString::from(".");
+        staging_name.push(file_name);
+        staging_name.push(format!(".{}", std::process::id()));
+        let staging = self.path.with_file_name(staging_name);
+        {
+            use std::os::unix::fs::DirBuilderExt;
+            fs::DirBuilder::new().mode(0o700).create(&staging)?;
+        }
+
+        let staged = staging.join("s");
+        let result = self.bind_staged(&staged);
+        let _ = fs::remove_file(&staged);
+        let _ = fs::remove_dir(&staging);
+        result
+    }
+
+    fn bind_staged(&self, staged: &std::path::Path) -> io::Result<tokio::net::UnixListener> {
+        let listener = tokio::net::UnixListener::bind(staged)?;
+        if let Some(mode) = self.mode {
+            use std::os::unix::fs::PermissionsExt;
+            fs::set_permissions(staged, fs::Permissions::from_mode(mode))?;
+        }
+        if self.uid.is_some() || self.gid.is_some() {
+            std::os::unix::fs::chown(staged, self.uid, self.gid)?;
+        }
+        fs::rename(staged, &self.path)?;
+        Ok(listener)
+    }
+
+    /// Removes a socket file nobody listens on anymore. Anything else at
+    /// the path is left untouched.
+    fn remove_stale(&self) -> io::Result<()> {
+        use std::os::unix::fs::FileTypeExt;
+
+        match fs::symlink_metadata(&self.path) {
+            Ok(meta) if meta.file_type().is_socket() => {
+                match std::os::unix::net::UnixStream::connect(&self.path) {
+                    Ok(_) => Err(io::Error::new(
+                        io::ErrorKind::AddrInUse,
+                        "socket is in use by another process",
+                    )),
+                    Err(err) if err.kind() == io::ErrorKind::ConnectionRefused => {
+                        tracing::debug!("removing stale socket {}", self.path.display());
+                        fs::remove_file(&self.path)
+                    }
+                    Err(err) => Err(err),
+                }
+            }
+            Ok(_) => Err(io::Error::new(
+                io::ErrorKind::AlreadyExists,
+                "path exists and is not a socket",
+            )),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(()),
+            Err(err) => Err(err),
+        }
+    }
+}
+
+#[cfg(unix)]
+impl From<&str> for UnixSocket {
+    fn from(path: &str) -> Self {
+        UnixSocket::new(path)
+    }
+}
+
+#[cfg(unix)]
+impl From<String> for UnixSocket {
+    fn from(path: String) -> Self {
+        UnixSocket::new(path)
+    }
+}
+
+#[cfg(unix)]
+impl From<&std::path::Path> for UnixSocket {
+    fn from(path: &std::path::Path) -> Self {
+        UnixSocket::new(path)
+    }
+}
+
+#[cfg(unix)]
+impl From<PathBuf> for UnixSocket {
+    fn from(path: PathBuf) -> Self {
+        UnixSocket::new(path)
+    }
+}
+
+#[cfg(unix)]
+struct RemoveOnDrop(PathBuf);
+
+#[cfg(unix)]
+impl Drop for RemoveOnDrop {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.0);
+    }
+}
@@ -1,3 +1,4 @@
 //! Convert `Filter`s into `Service`s
 
-pub use crate::filter::service::service;
+pub use crate::filter::layer::{InnerService, LayerError, WrapLayer};
+pub use crate::filter::service::{service, FilteredService};
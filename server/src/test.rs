@@ -81,33 +81,36 @@
 //!     assert_eq!(res.body(), "Sum is 3");
 //! }
 //! ```
+use std::cell::RefCell;
 use std::convert::TryFrom;
 use std::error::Error as StdError;
 use std::fmt;
 use std::future::Future;
 use std::net::SocketAddr;
-#[cfg(feature = "websocket")]
+use std::path::{Component, Path, PathBuf};
 use std::pin::Pin;
 #[cfg(feature = "websocket")]
-use std::task::Context;
-#[cfg(feature = "websocket")]
-use std::task::{self, Poll};
+use std::task;
+use std::task::{Context, Poll};
+use std::time::Duration;
 
 use bytes::Bytes;
 #[cfg(feature = "websocket")]
 use futures_channel::mpsc;
-#[cfg(feature = "websocket")]
-use futures_util::StreamExt;
-use futures_util::{future, FutureExt, TryFutureExt};
+use futures_util::{future, ready, FutureExt, StreamExt, TryFutureExt};
 use http::{
-    header::{HeaderName, HeaderValue},
+    header::{HeaderMap, HeaderName, HeaderValue},
     Response,
 };
+use hyper::body::HttpBody;
+use serde::de::DeserializeOwned;
 use serde::Serialize;
 #[cfg(feature = "websocket")]
 use tokio::sync::oneshot;
+use tokio::time::Sleep;
 
 use crate::filter::Filter;
+use crate::filters::state::States;
 #[cfg(feature = "websocket")]
 use crate::filters::ws::Message;
 use crate::reject::IsReject;
@@ -115,7 +118,8 @@ use crate::reply::Reply;
 use crate::route::{self, Route};
 use crate::Request;
 #[cfg(feature = "websocket")]
-use crate::{Sink, Stream};
+use crate::Sink;
+use crate::Stream;
 
 use self::inner::OneOrTuple;
 
@@ -124,9 +128,43 @@ pub fn request() -> RequestBuilder {
     RequestBuilder {
         remote_addr: None,
         req: Request::default(),
+        trailers: None,
+    }
+}
+
+/// Starts a snapshot of a response returned by
+/// [`RequestBuilder::reply`], to compare against a stored one.
+///
+/// See [`Snapshot`] for details.
+pub fn snapshot(res: &Response<Bytes>) -> Snapshot {
+    Snapshot {
+        status: res.status(),
+        headers: res.headers().clone(),
+        body: res.body().clone(),
+        ignored: vec![http::header::DATE],
+        dir: None,
     }
 }
 
+/// Asserts a response matches the snapshot stored under `name`.
+///
+/// Shorthand for `nextshell::test::snapshot(res).assert(name)`.
+///
+/// # Example
+///
+/// ```no_run
+/// # async {
+/// let route = nextshell::any().map(|| "hello");
+/// let res = nextshell::test::request().reply(&route).await;
+/// nextshell::test::assert_snapshot("hello", &res);
+/// # };
+/// # use nextshell::Filter;
+/// ```
+#[track_caller]
+pub fn assert_snapshot(name: &str, res: &Response<Bytes>) {
+    snapshot(res).assert(name)
+}
+
 /// Starts a new test `WsBuilder`.
 #[cfg(feature = "websocket")]
 pub fn ws() -> WsBuilder {
@@ -141,6 +179,7 @@ pub fn ws() -> WsBuilder {
 pub struct RequestBuilder {
     remote_addr: Option<SocketAddr>,
     req: Request,
+    trailers: Option<HeaderMap>,
 }
 
 /// A Websocket builder for testing filters.
@@ -166,6 +205,54 @@ pub struct WsError {
     cause: Box<dyn StdError + Send + Sync>,
 }
 
+/// A golden-file snapshot of a response.
+///
+/// A response is rendered as its status, its headers sorted by name, and
+/// its body, with JSON bodies pretty printed. The `date` header is left out,
+/// as are the ones passed to [`ignore_header`](Snapshot::ignore_header).
+///
+/// Snapshots are stored in `tests/snapshots/<name>.snap` of the crate being
+/// tested. Running the tests with `NEXTSHELL_UPDATE_SNAPSHOTS=1` writes the
+/// current responses as the new snapshots instead of comparing them.
+#[must_use = "Snapshot does nothing on its own"]
+#[derive(Debug)]
+pub struct Snapshot {
+    status: http::StatusCode,
+    headers: HeaderMap,
+    body: Bytes,
+    ignored: Vec<HeaderName>,
+    dir: Option<PathBuf>,
+}
+
+/// A test client for Server-Sent Events replies.
+///
+/// It is a `Stream` of the parsed events, yielding an error if no event
+/// arrives before the [`timeout`](SseClient::timeout).
+#[must_use = "streams do nothing unless polled"]
+pub struct SseClient {
+    body: hyper::Body,
+    buf: Vec<u8>,
+    pending: SseEvent,
+    last_event_id: Option<String>,
+    timeout: Duration,
+    sleep: Option<Pin<Box<Sleep>>>,
+}
+
+/// A Server-Sent Event, as received by an [`SseClient`].
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SseEvent {
+    data: Option<String>,
+    event: Option<String>,
+    id: Option<String>,
+    retry: Option<Duration>,
+}
+
+/// An error from Server-Sent Events filter tests.
+#[derive(Debug)]
+pub struct SseError {
+    cause: Box<dyn StdError + Send + Sync>,
+}
+
 impl RequestBuilder {
     /// Sets the method of this builder.
     ///
@@ -261,6 +348,25 @@ impl RequestBuilder {
         self
     }
 
+    /// Add a state, as [`Server::with_state`](crate::Server::with_state)
+    /// would, for [`state()`](crate::state()) to extract.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// let req = nextshell::test::request().state(42u64);
+    /// ```
+    pub fn state<T>(mut self, state: T) -> Self
+    where
+        T: Clone + Send + Sync + 'static,
+    {
+        let extensions = self.req.extensions_mut();
+        let mut states = extensions.remove::<States>().unwrap_or_default();
+        states.insert(state);
+        extensions.insert(states);
+        self
+    }
+
     /// Set the bytes of this request body.
     ///
     /// Default is an empty body.
@@ -294,6 +400,60 @@ impl RequestBuilder {
             .header("content-type", "application/json")
     }
 
+    /// Set a stream of chunks as this request body, sent with
+    /// `transfer-encoding: chunked` like an upload of unknown length.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use futures_util::stream;
+    ///
+    /// let chunks: Vec<Result<_, std::io::Error>> = vec![Ok("{\"n\":1}\n"), Ok("{\"n\":2}\n")];
+    /// let req = nextshell::test::request()
+    ///     .body_stream(stream::iter(chunks));
+    /// ```
+    pub fn body_stream<S, O, E>(mut self, stream: S) -> Self
+    where
+        S: futures_util::Stream<Item = Result<O, E>> + Send + 'static,
+        O: Into<Bytes> + 'static,
+        E: Into<Box<dyn StdError + Send + Sync>> + 'static,
+    {
+        *self.req.body_mut() = hyper::Body::wrap_stream(stream);
+        self.req.headers_mut().remove("content-length");
+        self.header("transfer-encoding", "chunked")
+    }
+
+    /// Set a trailer, sent after the end of this request body.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// let req = nextshell::test::request()
+    ///     .body("data")
+    ///     .trailer("x-checksum", "8d777f38");
+    /// ```
+    ///
+    /// # Panic
+    ///
+    /// This panics if the passed strings are not able to be parsed as a valid
+    /// `HeaderName` and `HeaderValue`.
+    pub fn trailer<K, V>(mut self, key: K, value: V) -> Self
+    where
+        HeaderName: TryFrom<K>,
+        HeaderValue: TryFrom<V>,
+    {
+        let name: HeaderName = TryFrom::try_from(key)
+            .map_err(|_| ())
+            .expect("invalid trailer name");
+        let value = TryFrom::try_from(value)
+            .map_err(|_| ())
+            .expect("invalid trailer value");
+        self.trailers
+            .get_or_insert_with(HeaderMap::new)
+            .insert(name, value);
+        self
+    }
+
     /// Tries to apply the `Filter` on this request.
     ///
     /// # Example
@@ -375,7 +535,7 @@ impl RequestBuilder {
         // TODO: de-duplicate this and apply_filter()
         assert!(!route::is_set(), "nested test filter calls");
 
-        let route = Route::new(self.req, self.remote_addr);
+        let route = self.into_route();
         let mut fut = Box::pin(
             route::set(&route, move || f.filter(crate::filter::Internal)).then(|result| {
                 let res = match result {
@@ -395,6 +555,69 @@ impl RequestBuilder {
         fut.await.expect("reply shouldn't fail")
     }
 
+    /// Applies the `Filter`, expecting a Server-Sent Events reply, and
+    /// returns a client to receive its events.
+    ///
+    /// This fails if the response isn't a `200 OK` with a
+    /// `text/event-stream` content type.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use std::convert::Infallible;
+    /// use futures_util::stream;
+    /// use nextshell::{sse::Event, Filter};
+    ///
+    /// async {
+    ///     let route = nextshell::any().map(|| {
+    ///         let events = vec![Ok::<_, Infallible>(Event::default().event("ping").data("1"))];
+    ///         nextshell::sse::reply(stream::iter(events))
+    ///     });
+    ///
+    ///     let mut client = nextshell::test::request().sse(&route).await.unwrap();
+    ///     let event = client.recv().await.unwrap();
+    ///     assert_eq!(event.event(), Some("ping"));
+    ///     assert_eq!(event.data(), "1");
+    ///     client.recv_closed().await.unwrap();
+    /// };
+    /// ```
+    pub async fn sse<F>(self, f: &F) -> Result<SseClient, SseError>
+    where
+        F: Filter,
+        F::Future: Send + 'static,
+        F::Extract: Reply + Send + 'static,
+        F::Error: IsReject + Send + 'static,
+    {
+        let res = match self.apply_filter(f).await {
+            Ok(rep) => rep.into_response(),
+            Err(rej) => {
+                tracing::debug!("rejected: {:?}", rej);
+                rej.into_response()
+            }
+        };
+
+        let content_type = res.headers().get(http::header::CONTENT_TYPE);
+        let is_event_stream = content_type
+            .and_then(|value| value.to_str().ok())
+            .is_some_and(|value| value.starts_with("text/event-stream"));
+        if res.status() != http::StatusCode::OK || !is_event_stream {
+            return Err(SseError::new(format!(
+                "expected an event stream, got {} with content-type {:?}",
+                res.status(),
+                content_type
+            )));
+        }
+
+        Ok(SseClient {
+            body: res.into_body(),
+            buf: Vec::new(),
+            pending: SseEvent::default(),
+            last_event_id: None,
+            timeout: Duration::from_secs(5),
+            sleep: None,
+        })
+    }
+
     fn apply_filter<F>(self, f: &F) -> impl Future<Output = Result<F::Extract, F::Error>>
     where
         F: Filter,
@@ -404,12 +627,38 @@ impl RequestBuilder {
     {
         assert!(!route::is_set(), "nested test filter calls");
 
-        let route = Route::new(self.req, self.remote_addr);
+        let route = self.into_route();
         let mut fut = Box::pin(route::set(&route, move || {
             f.filter(crate::filter::Internal)
         }));
         future::poll_fn(move |cx| route::set(&route, || fut.as_mut().poll(cx)))
     }
+
+    fn into_route(mut self) -> RefCell<Route> {
+        if let Some(trailers) = self.trailers {
+            // Only a channel body can carry trailers, so the body is fed
+            // into one.
+            let (mut tx, rx) = hyper::Body::channel();
+            let mut body = std::mem::replace(self.req.body_mut(), rx);
+            tokio::spawn(async move {
+                while let Some(chunk) = body.data().await {
+                    match chunk {
+                        Ok(chunk) => {
+                            if tx.send_data(chunk).await.is_err() {
+                                return;
+                            }
+                        }
+                        Err(_) => {
+                            tx.abort();
+                            return;
+                        }
+                    }
+                }
+                let _ = tx.send_trailers(trailers).await;
+            });
+        }
+        Route::new(self.req, self.remote_addr)
+    }
 }
 
 #[cfg(feature = "websocket")]
@@ -665,6 +914,316 @@ impl Stream for WsClient {
     }
 }
 
+// ===== impl Snapshot =====
+
+impl Snapshot {
+    /// Leaves a header out of the snapshot, such as one varying between
+    /// runs.
+    ///
+    /// # Panic
+    ///
+    /// This panics if the passed string is not a valid `HeaderName`.
+    pub fn ignore_header<K>(mut self, name: K) -> Self
+    where
+        HeaderName: TryFrom<K>,
+    {
+        let name = TryFrom::try_from(name)
+            .map_err(|_| ())
+            .expect("invalid header name");
+        self.ignored.push(name);
+        self
+    }
+
+    /// Stores the snapshots in `dir` instead of `tests/snapshots`.
+    pub fn dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.dir = Some(dir.into());
+        self
+    }
+
+    /// Renders the response as it is stored in a snapshot.
+    pub fn render(&self) -> String {
+        let mut out = format!("HTTP {}\n", self.status);
+
+        let mut names: Vec<&HeaderName> = self
+            .headers
+            .keys()
+            .filter(|name| !self.ignored.contains(name))
+            .collect();
+        names.sort_by_key(|name| name.as_str());
+        for name in names {
+            for value in self.headers.get_all(name) {
+                out.push_str(&format!(
+                    "{}: {}\n",
+                    name,
+                    String::from_utf8_lossy(value.as_bytes())
+                ));
+            }
+        }
+        out.push('\n');
+
+        let body = &self.body;
+        let is_json = self
+            .headers
+            .get(http::header::CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .is_some_and(|value| value.contains("json"));
+        let pretty = if is_json {
+            serde_json::from_slice::<serde_json::Value>(body)
+                .ok()
+                .and_then(|json| serde_json::to_string_pretty(&json).ok())
+        } else {
+            None
+        };
+        match (pretty, std::str::from_utf8(body)) {
+            (Some(json), _) => out.push_str(&json),
+            (None, Ok(text)) => out.push_str(text),
+            (None, Err(_)) => {
+                out.push_str(&format!("<{} bytes of binary data>\n", body.len()));
+                for line in body.chunks(32) {
+                    for byte in line {
+                        out.push_str(&format!("{:02x}", byte));
+                    }
+                    out.push('\n');
+                }
+            }
+        }
+        if !out.ends_with('\n') {
+            out.push('\n');
+        }
+        out
+    }
+
+    /// Asserts the response matches the snapshot stored under `name`, or
+    /// stores it when updating snapshots.
+    ///
+    /// # Panic
+    ///
+    /// This panics if the snapshot is missing or differs, or if `name` isn't
+    /// a relative path without `..` components.
+    #[track_caller]
+    pub fn assert(self, name: &str) {
+        let valid = !name.is_empty()
+            && Path::new(name)
+                .components()
+                .all(|c| matches!(c, Component::Normal(_)));
+        assert!(valid, "invalid snapshot name: {:?}", name);
+
+        let dir = self.dir.clone().unwrap_or_else(|| {
+            let root = std::env::var_os("CARGO_MANIFEST_DIR").unwrap_or_else(|| ".".into());
+            Path::new(&root).join("tests").join("snapshots")
+        });
+        let path = dir.join(format!("{}.snap", name));
+        let actual = self.render();
+
+        let update = std::env::var_os("NEXTSHELL_UPDATE_SNAPSHOTS").is_some_and(|v| v != "0");
+        if update {
+            if let Some(parent) = path.parent() {
+                std::fs::create_dir_all(parent).expect("create snapshot directory");
+            }
+            std::fs::write(&path, actual).expect("write snapshot");
+            return;
+        }
+
+        match std::fs::read_to_string(&path) {
+            Ok(expected) => assert!(
+                expected == actual,
+                "snapshot {:?} doesn't match, rerun with NEXTSHELL_UPDATE_SNAPSHOTS=1 to update it\n\
+                 --- expected ---\n{}--- actual ---\n{}",
+                name,
+                expected,
+                actual
+            ),
+            Err(err) => panic!(
+                "snapshot {:?} couldn't be read from {}: {}, rerun with \
+                 NEXTSHELL_UPDATE_SNAPSHOTS=1 to create it\n--- actual ---\n{}",
+                name,
+                path.display(),
+                err,
+                actual
+            ),
+        }
+    }
+}
+
+// ===== impl SseClient =====
+
+impl SseClient {
+    /// Sets how long to wait for each event before yielding an error.
+    ///
+    /// Defaults to 5 seconds.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Receive the next event from the server.
+    pub async fn recv(&mut self) -> Result<SseEvent, SseError> {
+        self.next()
+            .await
+            .unwrap_or_else(|| Err(SseError::new("closed")))
+    }
+
+    /// The last event `id` received, as a browser would send it back in the
+    /// `last-event-id` header when reconnecting.
+    ///
+    /// Events without data only update it, and aren't received.
+    pub fn last_event_id(&self) -> Option<&str> {
+        self.last_event_id.as_deref()
+    }
+
+    /// Assert the server has ended the event stream.
+    pub async fn recv_closed(&mut self) -> Result<(), SseError> {
+        match self.next().await {
+            Some(Ok(event)) => Err(SseError::new(format!("received event: {:?}", event))),
+            Some(Err(err)) => Err(err),
+            None => Ok(()),
+        }
+    }
+
+    // Parses the complete lines received so far, until an event is
+    // dispatched by a blank line.
+    fn parse_buffered(&mut self) -> Option<SseEvent> {
+        while let Some(pos) = self.buf.iter().position(|&b| b == b'\n') {
+            let line: Vec<u8> = self.buf.drain(..=pos).collect();
+            let line = String::from_utf8_lossy(&line);
+            let line = line.trim_end_matches('\n').trim_end_matches('\r');
+
+            if line.is_empty() {
+                let event = std::mem::take(&mut self.pending);
+                // Like browsers, only events with data are dispatched.
+                if event.data.is_some() {
+                    return Some(event);
+                }
+                continue;
+            }
+            if line.starts_with(':') {
+                // A comment, such as a keep-alive.
+                continue;
+            }
+
+            let (field, value) = match line.find(':') {
+                Some(idx) => {
+                    let value = &line[idx + 1..];
+                    (&line[..idx], value.strip_prefix(' ').unwrap_or(value))
+                }
+                None => (line, ""),
+            };
+            let event = &mut self.pending;
+            match field {
+                "data" => match event.data {
+                    Some(ref mut data) => {
+                        data.push('\n');
+                        data.push_str(value);
+                    }
+                    None => event.data = Some(value.to_owned()),
+                },
+                "event" => event.event = Some(value.to_owned()),
+                "id" if !value.contains('\0') => {
+                    event.id = Some(value.to_owned());
+                    self.last_event_id = event.id.clone();
+                }
+                "retry" => {
+                    if let Ok(millis) = value.parse() {
+                        event.retry = Some(Duration::from_millis(millis));
+                    }
+                }
+                _ => {}
+            }
+        }
+        None
+    }
+}
+
+impl Stream for SseClient {
+    type Item = Result<SseEvent, SseError>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = Pin::into_inner(self);
+        loop {
+            if let Some(event) = this.parse_buffered() {
+                this.sleep = None;
+                return Poll::Ready(Some(Ok(event)));
+            }
+
+            match Pin::new(&mut this.body).poll_data(cx) {
+                Poll::Ready(Some(Ok(chunk))) => this.buf.extend_from_slice(&chunk),
+                Poll::Ready(Some(Err(err))) => return Poll::Ready(Some(Err(SseError::new(err)))),
+                // An event not ended by a blank line is discarded.
+                Poll::Ready(None) => return Poll::Ready(None),
+                Poll::Pending => {
+                    let timeout = this.timeout;
+                    let sleep = this
+                        .sleep
+                        .get_or_insert_with(|| Box::pin(tokio::time::sleep(timeout)));
+                    ready!(sleep.as_mut().poll(cx));
+                    this.sleep = None;
+                    return Poll::Ready(Some(Err(SseError::new(format!(
+                        "no event received within {:?}",
+                        timeout
+                    )))));
+                }
+            }
+        }
+    }
+}
+
+impl fmt::Debug for SseClient {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SseClient")
+            .field("timeout", &self.timeout)
+            .finish()
+    }
+}
+
+// ===== impl SseEvent =====
+
+impl SseEvent {
+    /// The `data` of this event, with multiple lines joined by `\n`.
+    ///
+    /// Empty if the event has no data.
+    pub fn data(&self) -> &str {
+        self.data.as_deref().unwrap_or("")
+    }
+
+    /// The `event` name of this event, if any.
+    pub fn event(&self) -> Option<&str> {
+        self.event.as_deref()
+    }
+
+    /// The `id` of this event, if any.
+    pub fn id(&self) -> Option<&str> {
+        self.id.as_deref()
+    }
+
+    /// The `retry` delay of this event, if any.
+    pub fn retry(&self) -> Option<Duration> {
+        self.retry
+    }
+
+    /// Deserialize the `data` of this event from JSON.
+    pub fn json<T: DeserializeOwned>(&self) -> Result<T, serde_json::Error> {
+        serde_json::from_str(self.data())
+    }
+}
+
+// ===== impl SseError =====
+
+impl SseError {
+    fn new<E: Into<Box<dyn StdError + Send + Sync>>>(cause: E) -> Self {
+        SseError {
+            cause: cause.into(),
+        }
+    }
+}
+
+impl fmt::Display for SseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "server-sent events error: {}", self.cause)
+    }
+}
+
+impl StdError for SseError {}
+
 // ===== impl WsError =====
 
 #[cfg(feature = "websocket")]
@@ -761,3 +1320,24 @@ mod inner {
         T16
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use hyper::body::HttpBody;
+
+    #[tokio::test]
+    async fn trailers() {
+        let chunks: Vec<Result<_, std::io::Error>> = vec![Ok("a"), Ok("b")];
+        let mut body = super::request()
+            .body_stream(futures_util::stream::iter(chunks))
+            .trailer("x-checksum", "abc")
+            .filter(&crate::filters::body::body())
+            .await
+            .unwrap();
+
+        let bytes = hyper::body::to_bytes(&mut body).await.unwrap();
+        assert_eq!(bytes, "ab");
+        let trailers = body.trailers().await.unwrap().unwrap();
+        assert_eq!(trailers["x-checksum"], "abc");
+    }
+}
@@ -1,3 +1,12 @@
+//! TLS
+//!
+//! Details of the TLS session a request was received over, for servers
+//! configured with [`Server::tls`](crate::Server::tls).
+//!
+//! *This module requires the `"tls"` feature.*
+
+use std::collections::HashMap;
+use std::convert::Infallible;
 use std::fmt;
 use std::fs::File;
 use std::future::Future;
@@ -5,16 +14,23 @@ use std::io::{self, BufReader, Cursor, Read};
 use std::net::SocketAddr;
 use std::path::{Path, PathBuf};
 use std::pin::Pin;
-use std::sync::Arc;
+use std::sync::{Arc, OnceLock};
 use std::task::{Context, Poll};
 use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
 
-use futures_util::ready;
+use futures_util::{future, ready};
 use hyper::server::accept::Accept;
 use hyper::server::conn::{AddrIncoming, AddrStream};
-use tokio_rustls::rustls::server::WebPkiClientVerifier;
-use tokio_rustls::rustls::{Error as TlsError, RootCertStore, ServerConfig};
-
+use tokio_rustls::rustls::crypto::CryptoProvider;
+use tokio_rustls::rustls::pki_types::{CertificateDer, PrivateKeyDer};
+use tokio_rustls::rustls::server::{ClientHello, ResolvesServerCert, WebPkiClientVerifier};
+use tokio_rustls::rustls::sign::CertifiedKey;
+use tokio_rustls::rustls::version::{TLS12, TLS13};
+use tokio_rustls::rustls::{
+    Error as TlsError, ProtocolVersion, RootCertStore, ServerConfig, ServerConnection,
+};
+
+use crate::filter::{filter_fn_one, Filter};
 use crate::transport::Transport;
 
 /// Represents errors that can occur building the TlsConfig
@@ -33,6 +49,12 @@ pub(crate) enum TlsConfigError {
     EmptyKey,
     /// An error from an invalid key
     InvalidKey(TlsError),
+    /// A cipher suite name that isn't supported
+    UnknownCipherSuite(String),
+    /// The minimum protocol version is above the maximum
+    NoProtocolVersions,
+    /// An error from the protocol versions and cipher suites
+    InvalidConfig(TlsError),
 }
 
 impl fmt::Display for TlsConfigError {
@@ -48,6 +70,13 @@ impl fmt::Display for TlsConfigError {
             TlsConfigError::InvalidIdentityPem => write!(f, "identity PEM is invalid"),
             TlsConfigError::EmptyKey => write!(f, "key contains no private key"),
             TlsConfigError::InvalidKey(err) => write!(f, "key contains an invalid key, {}", err),
+            TlsConfigError::UnknownCipherSuite(name) => {
+                write!(f, "unknown cipher suite {:?}", name)
+            }
+            TlsConfigError::NoProtocolVersions => {
+                write!(f, "minimum protocol version is above the maximum")
+            }
+            TlsConfigError::InvalidConfig(err) => write!(f, "invalid TLS configuration, {}", err),
         }
     }
 }
@@ -64,12 +93,35 @@ pub(crate) enum TlsClientAuth {
     Required(Box<dyn Read + Send + Sync>),
 }
 
+/// A TLS protocol version.
+///
+/// *This type requires the `"tls"` feature.*
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum TlsVersion {
+    /// TLS 1.2
+    Tls12,
+    /// TLS 1.3
+    Tls13,
+}
+
+/// A certificate and key pair, served for an SNI hostname.
+struct SniCert {
+    hostname: String,
+    cert: Box<dyn Read + Send + Sync>,
+    key: Box<dyn Read + Send + Sync>,
+}
+
 /// Builder to set the configuration for the Tls server.
 pub(crate) struct TlsConfigBuilder {
     cert: Box<dyn Read + Send + Sync>,
     key: Box<dyn Read + Send + Sync>,
     client_auth: TlsClientAuth,
-    ocsp_resp: Vec<u8>,
+    ocsp_resp: Box<dyn Read + Send + Sync>,
+    sni: Vec<SniCert>,
+    min_version: TlsVersion,
+    max_version: TlsVersion,
+    cipher_suites: Option<Vec<String>>,
+    alpn_protocols: Vec<Vec<u8>>,
 }
 
 impl fmt::Debug for TlsConfigBuilder {
@@ -85,7 +137,12 @@ impl TlsConfigBuilder {
             key: Box::new(io::empty()),
             cert: Box::new(io::empty()),
             client_auth: TlsClientAuth::Off,
-            ocsp_resp: Vec::new(),
+            ocsp_resp: Box::new(io::empty()),
+            sni: Vec::new(),
+            min_version: TlsVersion::Tls12,
+            max_version: TlsVersion::Tls13,
+            cipher_suites: None,
+            alpn_protocols: vec![b"h2".to_vec(), b"http/1.1".to_vec()],
         }
     }
 
@@ -167,41 +224,131 @@ impl TlsConfigBuilder {
 
     /// sets the DER-encoded OCSP response
     pub(crate) fn ocsp_resp(mut self, ocsp_resp: &[u8]) -> Self {
-        self.ocsp_resp = Vec::from(ocsp_resp);
+        self.ocsp_resp = Box::new(Cursor::new(Vec::from(ocsp_resp)));
+        self
+    }
+
+    /// sets the DER-encoded OCSP response via File Path
+    pub(crate) fn ocsp_resp_path(mut self, path: impl AsRef<Path>) -> Self {
+        self.ocsp_resp = Box::new(LazyFile {
+            path: path.as_ref().into(),
+            file: None,
+        });
+        self
+    }
+
+    /// sets the lowest accepted protocol version
+    pub(crate) fn min_protocol_version(mut self, version: TlsVersion) -> Self {
+        self.min_version = version;
+        self
+    }
+
+    /// sets the highest accepted protocol version
+    pub(crate) fn max_protocol_version(mut self, version: TlsVersion) -> Self {
+        self.max_version = version;
+        self
+    }
+
+    /// restricts the cipher suites to the ones named, in order of preference
+    pub(crate) fn cipher_suites<I>(mut self, names: I) -> Self
+    where
+        I: IntoIterator,
+        I::Item: AsRef<str>,
+    {
+        self.cipher_suites = Some(
+            names
+                .into_iter()
+                .map(|name| name.as_ref().to_owned())
+                .collect(),
+        );
+        self
+    }
+
+    /// sets the ALPN protocols, in order of preference
+    pub(crate) fn alpn_protocols<I>(mut self, protocols: I) -> Self
+    where
+        I: IntoIterator,
+        I::Item: AsRef<[u8]>,
+    {
+        self.alpn_protocols = protocols
+            .into_iter()
+            .map(|protocol| protocol.as_ref().to_vec())
+            .collect();
+        self
+    }
+
+    fn crypto_provider(&self) -> Result<CryptoProvider, TlsConfigError> {
+        let mut provider = CryptoProvider::get_default()
+            .map(|provider| (**provider).clone())
+            .unwrap_or_else(tokio_rustls::rustls::crypto::ring::default_provider);
+        if let Some(ref names) = self.cipher_suites {
+            let suites = names
+                .iter()
+                .map(|name| {
+                    provider
+                        .cipher_suites
+                        .iter()
+                        .find(|suite| {
+                            let suite = format!("{:?}", suite.suite());
+                            suite.eq_ignore_ascii_case(name)
+                        })
+                        .copied()
+                        .ok_or_else(|| TlsConfigError::UnknownCipherSuite(name.clone()))
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+            provider.cipher_suites = suites;
+        }
+        Ok(provider)
+    }
+
+    /// Adds a certificate and key, via file paths, served to clients asking
+    /// for `hostname` with SNI.
+    pub(crate) fn sni_cert_path(
+        mut self,
+        hostname: &str,
+        cert: impl AsRef<Path>,
+        key: impl AsRef<Path>,
+    ) -> Self {
+        self.sni.push(SniCert {
+            hostname: hostname.to_ascii_lowercase(),
+            cert: Box::new(LazyFile {
+                path: cert.as_ref().into(),
+                file: None,
+            }),
+            key: Box::new(LazyFile {
+                path: key.as_ref().into(),
+                file: None,
+            }),
+        });
+        self
+    }
+
+    /// Adds a certificate and key, via bytes slices, served to clients
+    /// asking for `hostname` with SNI.
+    pub(crate) fn sni_cert(mut self, hostname: &str, cert: &[u8], key: &[u8]) -> Self {
+        self.sni.push(SniCert {
+            hostname: hostname.to_ascii_lowercase(),
+            cert: Box::new(Cursor::new(Vec::from(cert))),
+            key: Box::new(Cursor::new(Vec::from(key))),
+        });
         self
     }
 
     pub(crate) fn build(mut self) -> Result<ServerConfig, TlsConfigError> {
-        let mut cert_rdr = BufReader::new(self.cert);
-        let cert = rustls_pemfile::certs(&mut cert_rdr)
-            .collect::<Result<Vec<_>, _>>()
-            .map_err(|_e| TlsConfigError::CertParseError)?;
+        let provider = self.crypto_provider()?;
+        let (min_version, max_version) = (self.min_version, self.max_version);
+        let cert = read_certs(self.cert)?;
 
         let mut key_vec = Vec::new();
         self.key
             .read_to_end(&mut key_vec)
             .map_err(TlsConfigError::Io)?;
 
-        if key_vec.is_empty() {
-            return Err(TlsConfigError::EmptyKey);
-        }
-
-        let mut key_opt = None;
-        let mut key_cur = std::io::Cursor::new(key_vec);
-        for item in rustls_pemfile::read_all(&mut key_cur)
-            .collect::<Result<Vec<_>, _>>()
-            .map_err(|_e| TlsConfigError::InvalidIdentityPem)?
-        {
-            match item {
-                rustls_pemfile::Item::Pkcs1Key(k) => key_opt = Some(k.into()),
-                rustls_pemfile::Item::Pkcs8Key(k) => key_opt = Some(k.into()),
-                rustls_pemfile::Item::Sec1Key(k) => key_opt = Some(k.into()),
-                _ => return Err(TlsConfigError::UnknownPrivateKeyFormat),
-            }
-        }
-        let key = match key_opt {
-            Some(v) => v,
-            _ => return Err(TlsConfigError::MissingPrivateKey),
+        // With SNI certificates, the default one is optional.
+        let default = if key_vec.is_empty() && !self.sni.is_empty() {
+            None
+        } else {
+            Some((cert, parse_key(key_vec)?))
         };
 
         fn read_trust_anchor(
@@ -224,8 +371,18 @@ impl TlsConfigBuilder {
         }
 
         let config = {
-            let builder = ServerConfig::builder();
-            let mut config = match self.client_auth {
+            let versions = [(TlsVersion::Tls12, &TLS12), (TlsVersion::Tls13, &TLS13)]
+                .iter()
+                .filter(|(version, _)| (min_version..=max_version).contains(version))
+                .map(|&(_, version)| version)
+                .collect::<Vec<_>>();
+            if versions.is_empty() {
+                return Err(TlsConfigError::NoProtocolVersions);
+            }
+            let builder = ServerConfig::builder_with_provider(Arc::new(provider))
+                .with_protocol_versions(&versions)
+                .map_err(TlsConfigError::InvalidConfig)?;
+            let builder = match self.client_auth {
                 TlsClientAuth::Off => builder.with_no_client_auth(),
                 TlsClientAuth::Optional(trust_anchor) => {
                     let verifier =
@@ -242,10 +399,41 @@ impl TlsConfigBuilder {
                             .map_err(|_| TlsConfigError::CertParseError)?;
                     builder.with_client_cert_verifier(verifier)
                 }
-            }
-            .with_single_cert_with_ocsp(cert, key, self.ocsp_resp)
-            .map_err(TlsConfigError::InvalidKey)?;
-            config.alpn_protocols = vec!["h2".into(), "http/1.1".into()];
+            };
+            let mut ocsp_resp = Vec::new();
+            self.ocsp_resp
+                .read_to_end(&mut ocsp_resp)
+                .map_err(TlsConfigError::Io)?;
+            let sni = self.sni;
+            let mut config = match (default, sni.is_empty()) {
+                (Some((cert, key)), true) => builder
+                    .with_single_cert_with_ocsp(cert, key, ocsp_resp)
+                    .map_err(TlsConfigError::InvalidKey)?,
+                (default, _) => {
+                    let provider = builder.crypto_provider().clone();
+                    let default = default
+                        .map(|(cert, key)| {
+                            let mut certified = certified_key(cert, key, &provider)?;
+                            if !ocsp_resp.is_empty() {
+                                certified.ocsp = Some(ocsp_resp);
+                            }
+                            Ok(Arc::new(certified))
+                        })
+                        .transpose()?;
+                    let mut by_name = HashMap::new();
+                    for sni in sni {
+                        let cert = read_certs(sni.cert)?;
+                        let mut key_vec = Vec::new();
+                        let mut key = sni.key;
+                        key.read_to_end(&mut key_vec).map_err(TlsConfigError::Io)?;
+                        let key = parse_key(key_vec)?;
+                        let certified = certified_key(cert, key, &provider)?;
+                        by_name.insert(sni.hostname, Arc::new(certified));
+                    }
+                    builder.with_cert_resolver(Arc::new(SniResolver { by_name, default }))
+                }
+            };
+            config.alpn_protocols = self.alpn_protocols;
             config
         };
 
@@ -253,6 +441,76 @@ impl TlsConfigBuilder {
     }
 }
 
+fn read_certs(
+    cert: Box<dyn Read + Send + Sync>,
+) -> Result<Vec<CertificateDer<'static>>, TlsConfigError> {
+    let mut cert_rdr = BufReader::new(cert);
+    rustls_pemfile::certs(&mut cert_rdr)
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|_e| TlsConfigError::CertParseError)
+}
+
+fn parse_key(key_vec: Vec<u8>) -> Result<PrivateKeyDer<'static>, TlsConfigError> {
+    if key_vec.is_empty() {
+        return Err(TlsConfigError::EmptyKey);
+    }
+
+    let mut key_opt = None;
+    let mut key_cur = std::io::Cursor::new(key_vec);
+    for item in rustls_pemfile::read_all(&mut key_cur)
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|_e| TlsConfigError::InvalidIdentityPem)?
+    {
+        match item {
+            rustls_pemfile::Item::Pkcs1Key(k) => key_opt = Some(k.into()),
+            rustls_pemfile::Item::Pkcs8Key(k) => key_opt = Some(k.into()),
+            rustls_pemfile::Item::Sec1Key(k) => key_opt = Some(k.into()),
+            _ => return Err(TlsConfigError::UnknownPrivateKeyFormat),
+        }
+    }
+    key_opt.ok_or(TlsConfigError::MissingPrivateKey)
+}
+
+fn certified_key(
+    cert: Vec<CertificateDer<'static>>,
+    key: PrivateKeyDer<'static>,
+    provider: &CryptoProvider,
+) -> Result<CertifiedKey, TlsConfigError> {
+    CertifiedKey::from_der(cert, key, provider).map_err(TlsConfigError::InvalidKey)
+}
+
+/// Picks the certificate for the hostname a client asks for with SNI.
+///
+/// Names are matched exactly, then against a wildcard for their parent
+/// domain, such as `*.example.com`. Clients not using SNI, or asking for an
+/// unknown name, get the default certificate, if any.
+struct SniResolver {
+    by_name: HashMap<String, Arc<CertifiedKey>>,
+    default: Option<Arc<CertifiedKey>>,
+}
+
+impl ResolvesServerCert for SniResolver {
+    fn resolve(&self, client_hello: ClientHello<'_>) -> Option<Arc<CertifiedKey>> {
+        let found = client_hello.server_name().and_then(|name| {
+            let name = name.to_ascii_lowercase();
+            self.by_name.get(&name).or_else(|| {
+                let (_, parent) = name.split_once('.')?;
+                self.by_name.get(&format!("*.{}", parent))
+            })
+        });
+        found.or(self.default.as_ref()).cloned()
+    }
+}
+
+impl fmt::Debug for SniResolver {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SniResolver")
+            .field("names", &self.by_name.keys())
+            .field("default", &self.default.is_some())
+            .finish()
+    }
+}
+
 struct LazyFile {
     path: PathBuf,
     file: Option<File>,
@@ -284,6 +542,80 @@ impl Transport for TlsStream {
     fn remote_addr(&self) -> Option<SocketAddr> {
         Some(self.remote_addr)
     }
+
+    fn tls_info(&self) -> Option<Arc<OnceLock<TlsInfo>>> {
+        Some(self.info.clone())
+    }
+}
+
+/// Creates a `Filter` to get the details of the TLS session a request was
+/// received over.
+///
+/// Yields `None` for requests not received over TLS.
+///
+/// # Example
+///
+/// ```
+/// use nextshell::Filter;
+/// use nextshell::tls::TlsInfo;
+///
+/// let route = nextshell::tls::info()
+///     .map(|info: Option<TlsInfo>| match info {
+///         Some(info) => format!("{:?} with {}", info.version(), info.cipher_suite()),
+///         None => "plain text".to_owned(),
+///     });
+/// ```
+pub fn info() -> impl Filter<Extract = (Option<TlsInfo>,), Error = Infallible> + Copy {
+    filter_fn_one(|route| future::ok(route.extensions().get::<TlsInfo>().cloned()))
+}
+
+/// The negotiated parameters of a TLS session, extracted by [`info()`].
+#[derive(Clone, Debug)]
+pub struct TlsInfo {
+    version: TlsVersion,
+    cipher_suite: String,
+    server_name: Option<String>,
+    alpn_protocol: Option<Vec<u8>>,
+}
+
+impl TlsInfo {
+    fn new(conn: &ServerConnection) -> Option<TlsInfo> {
+        let version = match conn.protocol_version()? {
+            ProtocolVersion::TLSv1_2 => TlsVersion::Tls12,
+            ProtocolVersion::TLSv1_3 => TlsVersion::Tls13,
+            _ => return None,
+        };
+        let suite = conn.negotiated_cipher_suite()?.suite();
+        Some(TlsInfo {
+            version,
+            cipher_suite: suite
+                .as_str()
+                .map_or_else(|| format!("{:?}", suite), str::to_owned),
+            server_name: conn.server_name().map(str::to_owned),
+            alpn_protocol: conn.alpn_protocol().map(<[u8]>::to_vec),
+        })
+    }
+
+    /// The protocol version.
+    pub fn version(&self) -> TlsVersion {
+        self.version
+    }
+
+    /// The IANA name of the cipher suite, such as
+    /// `TLS13_AES_128_GCM_SHA256`.
+    pub fn cipher_suite(&self) -> &str {
+        &self.cipher_suite
+    }
+
+    /// The hostname the client asked for with SNI, if any.
+    pub fn server_name(&self) -> Option<&str> {
+        self.server_name.as_deref()
+    }
+
+    /// The protocol agreed on with ALPN, such as `b"h2"`, if any.
+    pub fn alpn_protocol(&self) -> Option<&[u8]> {
+        self.alpn_protocol.as_deref()
+    }
 }
 
 enum State {
@@ -297,6 +629,7 @@ enum State {
 pub(crate) struct TlsStream {
     state: State,
     remote_addr: SocketAddr,
+    info: Arc<OnceLock<TlsInfo>>,
 }
 
 impl TlsStream {
@@ -306,8 +639,16 @@ impl TlsStream {
         TlsStream {
             state: State::Handshaking(accept),
             remote_addr,
+            info: Arc::default(),
         }
     }
+
+    fn handshaken(&mut self, stream: tokio_rustls::server::TlsStream<AddrStream>) {
+        if let Some(info) = TlsInfo::new(stream.get_ref().1) {
+            let _ = self.info.set(info);
+        }
+        self.state = State::Streaming(stream);
+    }
 }
 
 impl AsyncRead for TlsStream {
@@ -321,7 +662,7 @@ impl AsyncRead for TlsStream {
             State::Handshaking(ref mut accept) => match ready!(Pin::new(accept).poll(cx)) {
                 Ok(mut stream) => {
                     let result = Pin::new(&mut stream).poll_read(cx, buf);
-                    pin.state = State::Streaming(stream);
+                    pin.handshaken(stream);
                     result
                 }
                 Err(err) => Poll::Ready(Err(err)),
@@ -342,7 +683,7 @@ impl AsyncWrite for TlsStream {
             State::Handshaking(ref mut accept) => match ready!(Pin::new(accept).poll(cx)) {
                 Ok(mut stream) => {
                     let result = Pin::new(&mut stream).poll_write(cx, buf);
-                    pin.state = State::Streaming(stream);
+                    pin.handshaken(stream);
                     result
                 }
                 Err(err) => Poll::Ready(Err(err)),
@@ -442,4 +783,90 @@ mod tests {
             .build()
             .unwrap();
     }
+
+    #[test]
+    fn sni_cert_key() {
+        let key = include_str!("../examples/tls/key.ecc");
+        let cert = include_str!("../examples/tls/cert.ecc.pem");
+
+        // without a default certificate
+        let config = TlsConfigBuilder::new()
+            .sni_cert("foo.com", cert.as_bytes(), key.as_bytes())
+            .sni_cert_path("*.bar.com", "examples/tls/cert.pem", "examples/tls/key.rsa")
+            .build()
+            .unwrap();
+        assert!(format!("{:?}", config.cert_resolver).contains("SniResolver"));
+
+        TlsConfigBuilder::new()
+            .key_path("examples/tls/key.rsa")
+            .cert_path("examples/tls/cert.pem")
+            .sni_cert("Foo.com", cert.as_bytes(), key.as_bytes())
+            .build()
+            .unwrap();
+
+        // mismatched certificate and key
+        TlsConfigBuilder::new()
+            .sni_cert(
+                "foo.com",
+                cert.as_bytes(),
+                include_bytes!("../examples/tls/key.rsa"),
+            )
+            .build()
+            .unwrap_err();
+    }
+
+    #[test]
+    fn protocol_and_cipher_suites() {
+        let config = TlsConfigBuilder::new()
+            .key_path("examples/tls/key.rsa")
+            .cert_path("examples/tls/cert.pem")
+            .min_protocol_version(TlsVersion::Tls13)
+            .cipher_suites(["tls13_aes_256_gcm_sha384"])
+            .alpn_protocols(["http/1.1"])
+            .build()
+            .unwrap();
+        assert_eq!(config.alpn_protocols, vec![b"http/1.1".to_vec()]);
+        let suites = &config.crypto_provider().cipher_suites;
+        assert_eq!(suites.len(), 1);
+        assert_eq!(
+            format!("{:?}", suites[0].suite()),
+            "TLS13_AES_256_GCM_SHA384"
+        );
+
+        let err = TlsConfigBuilder::new()
+            .key_path("examples/tls/key.rsa")
+            .cert_path("examples/tls/cert.pem")
+            .cipher_suites(["TLS_NOPE"])
+            .build()
+            .unwrap_err();
+        assert!(matches!(err, TlsConfigError::UnknownCipherSuite(_)));
+
+        // only TLS 1.2 suites for TLS 1.3
+        TlsConfigBuilder::new()
+            .key_path("examples/tls/key.rsa")
+            .cert_path("examples/tls/cert.pem")
+            .min_protocol_version(TlsVersion::Tls13)
+            .cipher_suites(["TLS_ECDHE_RSA_WITH_AES_256_GCM_SHA384"])
+            .build()
+            .unwrap_err();
+
+        let err = TlsConfigBuilder::new()
+            .key_path("examples/tls/key.rsa")
+            .cert_path("examples/tls/cert.pem")
+            .min_protocol_version(TlsVersion::Tls13)
+            .max_protocol_version(TlsVersion::Tls12)
+            .build()
+            .unwrap_err();
+        assert!(matches!(err, TlsConfigError::NoProtocolVersions));
+    }
+
+    #[test]
+    fn ocsp_resp_path() {
+        TlsConfigBuilder::new()
+            .key_path("examples/tls/key.rsa")
+            .cert_path("examples/tls/cert.pem")
+            .ocsp_resp_path("examples/tls/missing.ocsp")
+            .build()
+            .unwrap_err();
+    }
 }
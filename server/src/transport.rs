@@ -1,13 +1,32 @@
+use std::future::Future;
 use std::io;
 use std::net::SocketAddr;
 use std::pin::Pin;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+#[cfg(feature = "tls")]
+use std::sync::OnceLock;
 use std::task::{Context, Poll};
+use std::time::Duration;
 
+use futures_util::ready;
+use hyper::server::accept::Accept;
 use hyper::server::conn::AddrStream;
+use pin_project::pin_project;
 use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tokio::time::{Instant, Sleep};
+
+#[cfg(feature = "tls")]
+use crate::tls::TlsInfo;
 
 pub trait Transport: AsyncRead + AsyncWrite {
     fn remote_addr(&self) -> Option<SocketAddr>;
+
+    /// The TLS session details, known once the handshake is done.
+    #[cfg(feature = "tls")]
+    fn tls_info(&self) -> Option<Arc<OnceLock<TlsInfo>>> {
+        None
+    }
 }
 
 impl Transport for AddrStream {
@@ -51,3 +70,261 @@ impl<T: AsyncRead + AsyncWrite + Unpin> Transport for LiftIo<T> {
         None
     }
 }
+
+// Written as is when a request head times out, there's no request to
+// answer through hyper yet.
+const REQUEST_TIMEOUT: &[u8] =
+    b"HTTP/1.1 408 Request Timeout\r\ncontent-length: 0\r\nconnection: close\r\n\r\n";
+
+// How HTTP/2 connections start, see RFC 9113 section 3.4.
+const H2_PREFACE: &[u8] = b"PRI * HTTP/2.0\r\n\r\nSM\r\n\r\n";
+
+/// A connection whose client must send each request head within a timeout.
+///
+/// The timer starts with the connection, and with the first bytes of each
+/// following request, and stops once the request is handed to the service.
+/// Once over, `408 Request Timeout` is written if the client started an
+/// HTTP/1 request, and reading fails, so hyper closes the connection.
+/// HTTP/2 connections are left alone.
+pub(crate) struct HeadTimeout<T> {
+    io: T,
+    timeout: Option<Duration>,
+    requests: Requests,
+    timer: Option<Pin<Box<Sleep>>>,
+    // How much of the HTTP/2 preface was read, until the connection is
+    // known to speak HTTP/1.
+    preface: Option<usize>,
+    // How much of `REQUEST_TIMEOUT` was written, once timed out.
+    expired: Option<usize>,
+}
+
+impl<T> HeadTimeout<T> {
+    fn new(io: T, timeout: Option<Duration>) -> Self {
+        HeadTimeout {
+            io,
+            timeout,
+            requests: Requests::default(),
+            timer: timeout.map(|timeout| Box::pin(tokio::time::sleep(timeout))),
+            preface: Some(0),
+            expired: None,
+        }
+    }
+
+    /// The requests in flight on this connection, which stop the timer.
+    pub(crate) fn requests(&self) -> Requests {
+        self.requests.clone()
+    }
+
+    fn disable(&mut self) {
+        self.timeout = None;
+        self.timer = None;
+    }
+
+    // Checks the bytes just read against the HTTP/2 preface, returning
+    // whether the connection is now known to speak HTTP/2.
+    fn sniff(&mut self, read: &[u8]) -> bool {
+        if let Some(matched) = self.preface {
+            let n = read.len().min(H2_PREFACE.len() - matched);
+            if read[..n] != H2_PREFACE[matched..matched + n] {
+                self.preface = None;
+            } else if matched + n == H2_PREFACE.len() {
+                return true;
+            } else {
+                self.preface = Some(matched + n);
+            }
+        }
+        false
+    }
+}
+
+impl<T: Transport + Unpin> HeadTimeout<T> {
+    // Whether HTTP/2 was negotiated during the TLS handshake.
+    fn alpn_h2(&self) -> bool {
+        #[cfg(feature = "tls")]
+        {
+            if let Some(info) = self.io.tls_info() {
+                return info.get().and_then(TlsInfo::alpn_protocol) == Some(b"h2");
+            }
+        }
+        false
+    }
+
+    fn poll_expired(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let written = self.expired.get_or_insert(0);
+        while *written < REQUEST_TIMEOUT.len() {
+            match ready!(Pin::new(&mut self.io).poll_write(cx, &REQUEST_TIMEOUT[*written..])) {
+                Ok(0) | Err(_) => return Poll::Ready(Err(io::ErrorKind::TimedOut.into())),
+                Ok(n) => *written += n,
+            }
+        }
+        // The connection is closed either way.
+        let _ = ready!(Pin::new(&mut self.io).poll_flush(cx));
+        Poll::Ready(Err(io::ErrorKind::TimedOut.into()))
+    }
+}
+
+impl<T: Transport + Unpin> AsyncRead for HeadTimeout<T> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        if this.expired.is_some() {
+            return this.poll_expired(cx);
+        }
+        let timeout = match this.timeout {
+            Some(timeout) => timeout,
+            None => return Pin::new(&mut this.io).poll_read(cx, buf),
+        };
+        if this.preface.is_some() && this.alpn_h2() {
+            this.disable();
+            return Pin::new(&mut this.io).poll_read(cx, buf);
+        }
+        let idle = this.requests.is_idle();
+        if !idle {
+            this.timer = None;
+        }
+
+        let filled = buf.filled().len();
+        let result = Pin::new(&mut this.io).poll_read(cx, buf);
+        if let Poll::Ready(Ok(())) = result {
+            let read = &buf.filled()[filled..];
+            if this.sniff(read) {
+                this.disable();
+                return result;
+            }
+            if !read.is_empty() && idle && this.timer.is_none() {
+                this.timer = Some(Box::pin(tokio::time::sleep(timeout)));
+            }
+        }
+
+        if let Some(timer) = this.timer.as_mut() {
+            if timer.as_mut().poll(cx).is_ready() {
+                tracing::debug!("request head timed out, closing");
+                this.disable();
+                // Only HTTP/1 clients can make sense of the answer.
+                if this.preface.is_some() {
+                    return Poll::Ready(Err(io::ErrorKind::TimedOut.into()));
+                }
+                return this.poll_expired(cx);
+            }
+        }
+        result
+    }
+}
+
+impl<T: AsyncWrite + Unpin> AsyncWrite for HeadTimeout<T> {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        let result = Pin::new(&mut this.io).poll_write(cx, buf);
+        this.progress(&result);
+        result
+    }
+
+    fn poll_write_vectored(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        bufs: &[io::IoSlice<'_>],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        let result = Pin::new(&mut this.io).poll_write_vectored(cx, bufs);
+        this.progress(&result);
+        result
+    }
+
+    fn is_write_vectored(&self) -> bool {
+        self.io.is_write_vectored()
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), io::Error>> {
+        Pin::new(&mut self.get_mut().io).poll_flush(cx)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), io::Error>> {
+        Pin::new(&mut self.get_mut().io).poll_shutdown(cx)
+    }
+}
+
+impl<T> HeadTimeout<T> {
+    // A response still being written to the client, the next request isn't
+    // being waited on yet.
+    fn progress(&mut self, result: &Poll<io::Result<usize>>) {
+        if let (Some(timer), Some(timeout), Poll::Ready(Ok(n))) =
+            (self.timer.as_mut(), self.timeout, result)
+        {
+            if *n > 0 {
+                timer.as_mut().reset(Instant::now() + timeout);
+            }
+        }
+    }
+}
+
+impl<T: Transport + Unpin> Transport for HeadTimeout<T> {
+    fn remote_addr(&self) -> Option<SocketAddr> {
+        self.io.remote_addr()
+    }
+
+    #[cfg(feature = "tls")]
+    fn tls_info(&self) -> Option<Arc<OnceLock<TlsInfo>>> {
+        self.io.tls_info()
+    }
+}
+
+/// Counts the requests in flight on a connection.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct Requests(Arc<AtomicUsize>);
+
+impl Requests {
+    /// Marks a request as in flight, until the returned guard is dropped.
+    pub(crate) fn start(&self) -> InFlight {
+        self.0.fetch_add(1, Ordering::SeqCst);
+        InFlight(self.0.clone())
+    }
+
+    fn is_idle(&self) -> bool {
+        self.0.load(Ordering::SeqCst) == 0
+    }
+}
+
+pub(crate) struct InFlight(Arc<AtomicUsize>);
+
+impl Drop for InFlight {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// Wraps each accepted connection in a [`HeadTimeout`].
+#[pin_project]
+pub(crate) struct HeadTimeoutAccept<A> {
+    #[pin]
+    accept: A,
+    timeout: Option<Duration>,
+}
+
+impl<A> HeadTimeoutAccept<A> {
+    pub(crate) fn new(accept: A, timeout: Option<Duration>) -> Self {
+        HeadTimeoutAccept { accept, timeout }
+    }
+}
+
+impl<A: Accept> Accept for HeadTimeoutAccept<A> {
+    type Conn = HeadTimeout<A::Conn>;
+    type Error = A::Error;
+
+    fn poll_accept(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Self::Conn, Self::Error>>> {
+        let this = self.project();
+        let timeout = *this.timeout;
+        this.accept
+            .poll_accept(cx)
+            .map(|conn| conn.map(|conn| conn.map(|io| HeadTimeout::new(io, timeout))))
+    }
+}
@@ -203,3 +203,61 @@ async fn stream() {
     assert_eq!(bufs.len(), 1);
     assert_eq!(bufs[0].chunk(), b"foo=bar");
 }
+
+#[tokio::test]
+async fn body_stream() {
+    let _ = pretty_env_logger::try_init();
+
+    let chunks: Vec<Result<_, std::io::Error>> = vec![Ok("{\"n\":1}\n"), Ok("{\"n\":2}\n")];
+    let req = nextshell::test::request()
+        .body_stream(futures_util::stream::iter(chunks))
+        .trailer("x-checksum", "abc");
+
+    let body = req
+        .filter(&nextshell::body::stream())
+        .await
+        .expect("filter() stream");
+
+    let bufs: Result<Vec<_>, nextshell::Error> = body.try_collect().await;
+    let bufs = bufs.unwrap();
+
+    assert_eq!(bufs.len(), 2);
+    assert_eq!(bufs[0].chunk(), b"{\"n\":1}\n");
+    assert_eq!(bufs[1].chunk(), b"{\"n\":2}\n");
+}
+
+#[tokio::test]
+async fn body_stream_requires_no_length() {
+    let chunks: Vec<Result<_, std::io::Error>> = vec![Ok("foo")];
+    let route = nextshell::body::content_length_limit(16).and(nextshell::body::bytes());
+
+    let res = nextshell::test::request()
+        .body("longer than the stream")
+        .body_stream(futures_util::stream::iter(chunks))
+        .reply(&route.map(|_| ""))
+        .await;
+
+    assert_eq!(res.status(), 411);
+}
+
+#[tokio::test]
+async fn stream_with_trailers() {
+    let _ = pretty_env_logger::try_init();
+
+    let chunks: Vec<Result<_, std::io::Error>> = vec![Ok("a"), Ok("b")];
+    let mut body = nextshell::test::request()
+        .body_stream(futures_util::stream::iter(chunks))
+        .trailer("x-checksum", "abc")
+        .filter(&nextshell::body::stream_with_trailers())
+        .await
+        .expect("filter() stream_with_trailers");
+
+    let mut data = Vec::new();
+    while let Some(chunk) = body.try_next().await.unwrap() {
+        data.extend_from_slice(&chunk);
+    }
+    assert_eq!(data, b"ab");
+
+    let trailers = body.trailers().await.unwrap().unwrap();
+    assert_eq!(trailers["x-checksum"], "abc");
+}
@@ -0,0 +1,179 @@
+#![deny(warnings)]
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use nextshell::Filter;
+
+fn counted(
+    calls: &Arc<AtomicUsize>,
+) -> impl Filter<Extract = (String,), Error = nextshell::Rejection> + Clone {
+    let calls = calls.clone();
+    nextshell::path!("report" / String).map(move |name| {
+        let n = calls.fetch_add(1, Ordering::SeqCst) + 1;
+        format!("{} #{}", name, n)
+    })
+}
+
+async fn get<F>(route: &F, path: &str) -> String
+where
+    F: Filter + 'static,
+    F::Extract: nextshell::Reply + Send,
+{
+    let res = nextshell::test::request().path(path).reply(route).await;
+    String::from_utf8(res.body().to_vec()).unwrap()
+}
+
+#[tokio::test]
+async fn caches_responses() {
+    let _ = pretty_env_logger::try_init();
+    let calls = Arc::new(AtomicUsize::new(0));
+    let cache = nextshell::cache(Duration::from_secs(60)).vary("accept-language");
+    let route = counted(&calls).with(cache.clone());
+
+    assert_eq!(get(&route, "/report/a").await, "a #1");
+    let res = nextshell::test::request()
+        .path("/report/a")
+        .reply(&route)
+        .await;
+    assert_eq!(res.body(), "a #1");
+    assert_eq!(res.headers()["age"], "0");
+
+    // Other paths, queries and keyed headers are cached apart.
+    assert_eq!(get(&route, "/report/b").await, "b #2");
+    assert_eq!(get(&route, "/report/a?x=1").await, "a #3");
+    let res = nextshell::test::request()
+        .path("/report/a")
+        .header("accept-language", "fr")
+        .reply(&route)
+        .await;
+    assert_eq!(res.body(), "a #4");
+
+    // Other methods aren't cached.
+    let res = nextshell::test::request()
+        .method("POST")
+        .path("/report/a")
+        .reply(&route)
+        .await;
+    assert_eq!(res.body(), "a #5");
+
+    cache.invalidate("/report/a");
+    assert_eq!(get(&route, "/report/a").await, "a #6");
+    assert_eq!(get(&route, "/report/b").await, "b #2");
+
+    cache.clear();
+    assert_eq!(get(&route, "/report/b").await, "b #7");
+}
+
+#[tokio::test]
+async fn uncacheable_responses() {
+    let _ = pretty_env_logger::try_init();
+    let calls = Arc::new(AtomicUsize::new(0));
+    let route = counted(&calls)
+        .map(|body| nextshell::reply::with_header(body, "cache-control", "no-store"))
+        .with(nextshell::cache(Duration::from_secs(60)));
+
+    assert_eq!(get(&route, "/report/a").await, "a #1");
+    assert_eq!(get(&route, "/report/a").await, "a #2");
+
+    let calls = Arc::new(AtomicUsize::new(0));
+    let route = counted(&calls).with(nextshell::cache(Duration::from_secs(60)).max_entry_bytes(3));
+    assert_eq!(get(&route, "/report/a").await, "a #1");
+    assert_eq!(get(&route, "/report/a").await, "a #2");
+
+    // Varying on headers that aren't keyed.
+    for vary in ["accept-encoding", "*", "accept-language, origin"] {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let route = counted(&calls)
+            .map(move |body| nextshell::reply::with_header(body, "vary", vary))
+            .with(nextshell::cache(Duration::from_secs(60)).vary("accept-language"));
+        assert_eq!(get(&route, "/report/a").await, "a #1");
+        assert_eq!(get(&route, "/report/a").await, "a #2", "{}", vary);
+    }
+    let calls = Arc::new(AtomicUsize::new(0));
+    let route = counted(&calls)
+        .map(|body| nextshell::reply::with_header(body, "vary", "Accept-Language"))
+        .with(nextshell::cache(Duration::from_secs(60)).vary("accept-language"));
+    assert_eq!(get(&route, "/report/a").await, "a #1");
+    assert_eq!(get(&route, "/report/a").await, "a #1");
+}
+
+#[tokio::test]
+async fn credentialed_requests() {
+    let _ = pretty_env_logger::try_init();
+    let calls = Arc::new(AtomicUsize::new(0));
+    let route = counted(&calls).with(nextshell::cache(Duration::from_secs(60)));
+
+    for header in ["authorization", "cookie"] {
+        for _ in 0..2 {
+            nextshell::test::request()
+                .path("/report/a")
+                .header(header, "secret")
+                .reply(&route)
+                .await;
+        }
+    }
+    assert_eq!(calls.load(Ordering::SeqCst), 4);
+
+    // Unless they're keyed.
+    let calls = Arc::new(AtomicUsize::new(0));
+    let route = counted(&calls).with(nextshell::cache(Duration::from_secs(60)).vary("cookie"));
+    for _ in 0..2 {
+        let res = nextshell::test::request()
+            .path("/report/a")
+            .header("cookie", "session=1")
+            .reply(&route)
+            .await;
+        assert_eq!(res.body(), "a #1");
+    }
+}
+
+#[tokio::test]
+async fn expires_and_evicts() {
+    let _ = pretty_env_logger::try_init();
+    let calls = Arc::new(AtomicUsize::new(0));
+    let route = counted(&calls).with(nextshell::cache(Duration::from_millis(50)).max_entries(1));
+
+    assert_eq!(get(&route, "/report/a").await, "a #1");
+    assert_eq!(get(&route, "/report/a").await, "a #1");
+    tokio::time::sleep(Duration::from_millis(60)).await;
+    assert_eq!(get(&route, "/report/a").await, "a #2");
+
+    // `a` is evicted to keep a single entry.
+    assert_eq!(get(&route, "/report/b").await, "b #3");
+    assert_eq!(get(&route, "/report/a").await, "a #4");
+}
+
+#[tokio::test]
+async fn evicts_least_recently_used() {
+    let _ = pretty_env_logger::try_init();
+    let calls = Arc::new(AtomicUsize::new(0));
+    let route = counted(&calls).with(nextshell::cache(Duration::from_secs(60)).max_entries(2));
+
+    assert_eq!(get(&route, "/report/a").await, "a #1");
+    assert_eq!(get(&route, "/report/b").await, "b #2");
+    // Using `a` makes `b` the least recently used.
+    assert_eq!(get(&route, "/report/a").await, "a #1");
+    assert_eq!(get(&route, "/report/c").await, "c #3");
+    assert_eq!(get(&route, "/report/a").await, "a #1");
+    assert_eq!(get(&route, "/report/b").await, "b #4");
+}
+
+#[tokio::test]
+async fn stale_while_revalidate() {
+    let _ = pretty_env_logger::try_init();
+    let calls = Arc::new(AtomicUsize::new(0));
+    let route = nextshell::path("api").and(counted(&calls).with(
+        nextshell::cache(Duration::from_millis(50)).stale_while_revalidate(Duration::from_secs(60)),
+    ));
+
+    assert_eq!(get(&route, "/api/report/a").await, "a #1");
+    tokio::time::sleep(Duration::from_millis(60)).await;
+
+    // Served stale, while refreshed in the background.
+    assert_eq!(get(&route, "/api/report/a").await, "a #1");
+    assert_eq!(get(&route, "/api/report/a").await, "a #1");
+    tokio::time::sleep(Duration::from_millis(20)).await;
+    assert_eq!(calls.load(Ordering::SeqCst), 2);
+    assert_eq!(get(&route, "/api/report/a").await, "a #2");
+}
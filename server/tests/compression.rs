@@ -0,0 +1,267 @@
+#![deny(warnings)]
+use nextshell::Filter;
+
+#[tokio::test]
+async fn auto_prefers_highest_quality() {
+    let _ = pretty_env_logger::try_init();
+
+    let route = nextshell::any()
+        .map(|| "hello world")
+        .with(nextshell::compression::auto());
+
+    let res = nextshell::test::request()
+        .header("accept-encoding", "deflate;q=0.5, gzip;q=0.8")
+        .reply(&route)
+        .await;
+    assert_eq!(res.headers()["content-encoding"], "gzip");
+    assert_eq!(res.headers()["vary"], "accept-encoding");
+    assert!(!res.headers().contains_key("content-length"));
+
+    let res = nextshell::test::request()
+        .header("accept-encoding", "gzip;q=0.5, deflate")
+        .reply(&route)
+        .await;
+    assert_eq!(res.headers()["content-encoding"], "deflate");
+}
+
+#[tokio::test]
+async fn auto_wildcard_uses_server_preference() {
+    let route = nextshell::any()
+        .map(|| "hello world")
+        .with(nextshell::compression::auto());
+
+    let res = nextshell::test::request()
+        .header("accept-encoding", "*")
+        .reply(&route)
+        .await;
+    assert_eq!(res.headers()["content-encoding"], "br");
+
+    let res = nextshell::test::request()
+        .header("accept-encoding", "br;q=0, *;q=0.5")
+        .reply(&route)
+        .await;
+    assert_eq!(res.headers()["content-encoding"], "gzip");
+}
+
+#[tokio::test]
+async fn auto_identity() {
+    let route = nextshell::any()
+        .map(|| "hello world")
+        .with(nextshell::compression::auto());
+
+    // no accept-encoding
+    let res = nextshell::test::request().reply(&route).await;
+    assert!(!res.headers().contains_key("content-encoding"));
+    assert_eq!(res.headers()["vary"], "accept-encoding");
+    assert_eq!(res.body(), "hello world");
+
+    // nothing supported
+    let res = nextshell::test::request()
+        .header("accept-encoding", "compress, gzip;q=0")
+        .reply(&route)
+        .await;
+    assert!(!res.headers().contains_key("content-encoding"));
+    assert_eq!(res.body(), "hello world");
+
+    // identity explicitly preferred
+    let res = nextshell::test::request()
+        .header("accept-encoding", "gzip;q=0.5, identity")
+        .reply(&route)
+        .await;
+    assert!(!res.headers().contains_key("content-encoding"));
+    assert_eq!(res.body(), "hello world");
+}
+
+#[tokio::test]
+async fn auto_skips_already_encoded() {
+    let route = nextshell::any()
+        .map(|| nextshell::reply::with_header("hello", "content-encoding", "zstd"))
+        .with(nextshell::compression::auto());
+
+    let res = nextshell::test::request()
+        .header("accept-encoding", "gzip")
+        .reply(&route)
+        .await;
+    assert_eq!(res.headers()["content-encoding"], "zstd");
+    assert_eq!(res.body(), "hello");
+}
+
+#[tokio::test]
+async fn min_size() {
+    let route = nextshell::path("small")
+        .map(|| "tiny")
+        .or(nextshell::path("large").map(|| "a".repeat(2048)))
+        .with(nextshell::compression::gzip().min_size(1024));
+
+    let res = nextshell::test::request()
+        .path("/small")
+        .reply(&route)
+        .await;
+    assert!(!res.headers().contains_key("content-encoding"));
+    assert_eq!(res.body(), "tiny");
+
+    let res = nextshell::test::request()
+        .path("/large")
+        .reply(&route)
+        .await;
+    assert_eq!(res.headers()["content-encoding"], "gzip");
+
+    // Responses skipped for their size still went through negotiation.
+    let route = nextshell::any()
+        .map(|| "tiny")
+        .with(nextshell::compression::auto().min_size(1024));
+
+    let res = nextshell::test::request()
+        .header("accept-encoding", "gzip")
+        .reply(&route)
+        .await;
+    assert!(!res.headers().contains_key("content-encoding"));
+    assert_eq!(res.headers()["vary"], "accept-encoding");
+}
+
+#[tokio::test]
+async fn content_types() {
+    let png = || {
+        nextshell::http::Response::builder()
+            .header("content-type", "image/png")
+            .body(String::from("png"))
+    };
+
+    // Compression filters can be cloned to be reused for several routes.
+    let compression = nextshell::compression::gzip().deny_content_type("image/*");
+    let route = nextshell::path("png")
+        .map(png)
+        .with(compression.clone())
+        .or(nextshell::path("text").map(|| "text").with(compression));
+
+    let res = nextshell::test::request().path("/png").reply(&route).await;
+    assert!(!res.headers().contains_key("content-encoding"));
+
+    let res = nextshell::test::request().path("/text").reply(&route).await;
+    assert_eq!(res.headers()["content-encoding"], "gzip");
+
+    let route = nextshell::path("png")
+        .map(png)
+        .or(nextshell::path("text").map(|| "text"))
+        .with(nextshell::compression::gzip().allow_content_types(["text/*", "application/json"]));
+
+    let res = nextshell::test::request().path("/png").reply(&route).await;
+    assert!(!res.headers().contains_key("content-encoding"));
+
+    let res = nextshell::test::request().path("/text").reply(&route).await;
+    assert_eq!(res.headers()["content-encoding"], "gzip");
+
+    // There's no limit on the length of the lists.
+    let denied = [
+        "application/zip",
+        "application/gzip",
+        "application/x-7z-compressed",
+        "application/x-bzip2",
+        "application/x-rar-compressed",
+        "application/x-xz",
+        "application/zstd",
+        "application/pdf",
+        "application/wasm",
+        "audio/*",
+        "font/woff",
+        "font/woff2",
+        "video/*",
+        "image/gif",
+        "image/jpeg",
+        "image/webp",
+        "image/avif",
+        "image/png",
+    ];
+    let route = nextshell::path("png")
+        .map(png)
+        .or(nextshell::path("text").map(|| "text"))
+        .with(nextshell::compression::gzip().deny_content_types(denied));
+
+    let res = nextshell::test::request().path("/png").reply(&route).await;
+    assert!(!res.headers().contains_key("content-encoding"));
+
+    let res = nextshell::test::request().path("/text").reply(&route).await;
+    assert_eq!(res.headers()["content-encoding"], "gzip");
+}
+
+async fn compressed(encoding: &str, body: &[u8]) -> Vec<u8> {
+    use async_compression::tokio::bufread::{BrotliEncoder, GzipEncoder, ZlibEncoder, ZstdEncoder};
+    use tokio::io::AsyncReadExt;
+
+    let mut out = Vec::new();
+    match encoding {
+        "br" => BrotliEncoder::new(body).read_to_end(&mut out).await,
+        // HTTP's `deflate` is the zlib format.
+        "deflate" => ZlibEncoder::new(body).read_to_end(&mut out).await,
+        "gzip" => GzipEncoder::new(body).read_to_end(&mut out).await,
+        "zstd" => ZstdEncoder::new(body).read_to_end(&mut out).await,
+        _ => unreachable!(),
+    }
+    .unwrap();
+    out
+}
+
+#[tokio::test]
+async fn decompress_request_body() {
+    let _ = pretty_env_logger::try_init();
+
+    let route = nextshell::body::decompress()
+        .and(nextshell::header::optional::<String>("content-encoding"))
+        .and(nextshell::body::json())
+        .map(|encoding: Option<String>, body: serde_json::Value| {
+            assert_eq!(encoding, None);
+            nextshell::reply::json(&body)
+        });
+
+    for encoding in &["br", "deflate", "gzip", "zstd"] {
+        let body = compressed(encoding, br#"{"hello":"nextshell"}"#).await;
+        let res = nextshell::test::request()
+            .header("content-encoding", *encoding)
+            .header("content-length", body.len().to_string())
+            .body(body)
+            .reply(&route)
+            .await;
+        assert_eq!(res.status(), 200, "{}", encoding);
+        assert_eq!(res.body(), r#"{"hello":"nextshell"}"#);
+    }
+
+    let res = nextshell::test::request()
+        .body(r#"{"hello":"nextshell"}"#)
+        .reply(&route)
+        .await;
+    assert_eq!(res.status(), 200);
+
+    let res = nextshell::test::request()
+        .header("content-encoding", "compress")
+        .body("?")
+        .reply(&route)
+        .await;
+    assert_eq!(res.status(), 415);
+}
+
+#[tokio::test]
+async fn decompress_limit() {
+    let _ = pretty_env_logger::try_init();
+
+    let route = nextshell::body::decompress_limit(1024)
+        .and(nextshell::body::bytes())
+        .map(|body: bytes::Bytes| body.len().to_string());
+
+    let body = compressed("gzip", &[b'a'; 1024]).await;
+    let res = nextshell::test::request()
+        .header("content-encoding", "gzip")
+        .body(body)
+        .reply(&route)
+        .await;
+    assert_eq!(res.body(), "1024");
+
+    // A small body, expanding over the limit.
+    let body = compressed("gzip", &[b'a'; 1025]).await;
+    assert!(body.len() < 100);
+    let res = nextshell::test::request()
+        .header("content-encoding", "gzip")
+        .body(body)
+        .reply(&route)
+        .await;
+    assert_eq!(res.status(), 413);
+}
@@ -42,6 +42,8 @@ async fn origin_not_allowed() {
         .await;
 
     assert_eq!(res.status(), 403);
+    // The rejection depends on the origin, caches mustn't reuse it.
+    assert_eq!(res.headers()["vary"], "origin");
 
     let res = nextshell::test::request()
         .header("origin", "https://nextshell.rs")
@@ -182,3 +184,189 @@ async fn with_log() {
 
     assert_eq!(res.status(), 200);
 }
+
+#[tokio::test]
+async fn origin_patterns() {
+    let cors = nextshell::cors()
+        .allow_methods(&[Method::GET])
+        .allow_origin("https://example.com")
+        .allow_origin_pattern("https://*.example.com");
+
+    let route = nextshell::any().map(nextshell::reply).with(cors);
+
+    for (origin, status) in [
+        ("https://example.com", 200),
+        ("https://a.example.com", 200),
+        ("https://a.b.EXAMPLE.com", 200),
+        ("https://a-1.example.com", 200),
+        ("http://a.example.com", 403),
+        ("https://.example.com", 403),
+        ("https://a..example.com", 403),
+        ("https://a_b.example.com", 403),
+        ("https://a.example.com.evil", 403),
+        ("https://evilexample.com", 403),
+    ] {
+        let res = nextshell::test::request()
+            .header("origin", origin)
+            .reply(&route)
+            .await;
+        assert_eq!(res.status(), status, "{}", origin);
+    }
+}
+
+#[cfg(feature = "cors-regex")]
+#[tokio::test]
+async fn origin_regex() {
+    let cors = nextshell::cors()
+        .allow_methods(&[Method::GET])
+        .allow_origin_regex(r"http://localhost:\d+");
+
+    let route = nextshell::any().map(nextshell::reply).with(cors);
+
+    for (origin, status) in [
+        ("http://localhost:8080", 200),
+        ("http://localhost:8080.evil", 403),
+        ("http://localhost", 403),
+    ] {
+        let res = nextshell::test::request()
+            .header("origin", origin)
+            .reply(&route)
+            .await;
+        assert_eq!(res.status(), status, "{}", origin);
+    }
+}
+
+#[tokio::test]
+async fn origin_fn() {
+    let cors = nextshell::cors()
+        .allow_methods(&[Method::GET])
+        .allow_origin_fn(|origin| async move { origin == "https://tenant.example" });
+
+    let route = nextshell::any().map(nextshell::reply).with(cors);
+
+    let res = nextshell::test::request()
+        .method("OPTIONS")
+        .header("origin", "https://tenant.example")
+        .header("access-control-request-method", "GET")
+        .reply(&route)
+        .await;
+    assert_eq!(res.status(), 200);
+    assert_eq!(
+        res.headers()["access-control-allow-origin"],
+        "https://tenant.example"
+    );
+
+    // Still checks the method once the origin is allowed.
+    let res = nextshell::test::request()
+        .method("OPTIONS")
+        .header("origin", "https://tenant.example")
+        .header("access-control-request-method", "DELETE")
+        .reply(&route)
+        .await;
+    assert_eq!(res.status(), 403);
+
+    let res = nextshell::test::request()
+        .header("origin", "https://tenant.example")
+        .reply(&route)
+        .await;
+    assert_eq!(res.status(), 200);
+    assert_eq!(
+        res.headers()["access-control-allow-origin"],
+        "https://tenant.example"
+    );
+
+    let res = nextshell::test::request()
+        .header("origin", "https://other.example")
+        .reply(&route)
+        .await;
+    assert_eq!(res.status(), 403);
+}
+
+#[tokio::test]
+async fn vary() {
+    let cors = nextshell::cors()
+        .allow_origin("https://hyper.rs")
+        .allow_methods(&[Method::GET]);
+
+    let route = nextshell::any().map(nextshell::reply).with(cors);
+
+    let res = nextshell::test::request()
+        .method("OPTIONS")
+        .header("origin", "https://hyper.rs")
+        .header("access-control-request-method", "GET")
+        .reply(&route)
+        .await;
+    assert_eq!(
+        res.headers()["vary"],
+        "origin, access-control-request-method, access-control-request-headers"
+    );
+
+    let res = nextshell::test::request()
+        .header("origin", "https://hyper.rs")
+        .reply(&route)
+        .await;
+    assert_eq!(res.headers()["vary"], "origin");
+
+    let res = nextshell::test::request().reply(&route).await;
+    assert_eq!(res.status(), 200);
+    assert_eq!(res.headers()["vary"], "origin");
+    assert!(!res.headers().contains_key("access-control-allow-origin"));
+}
+
+#[tokio::test]
+async fn private_network() {
+    let preflight = || {
+        nextshell::test::request()
+            .method("OPTIONS")
+            .header("origin", "https://hyper.rs")
+            .header("access-control-request-method", "GET")
+            .header("access-control-request-private-network", "true")
+    };
+
+    let cors = nextshell::cors().allow_methods(&[Method::GET]);
+    let route = nextshell::any().map(nextshell::reply).with(cors);
+    let res = preflight().reply(&route).await;
+    assert!(!res
+        .headers()
+        .contains_key("access-control-allow-private-network"));
+
+    let cors = nextshell::cors()
+        .allow_methods(&[Method::GET])
+        .allow_private_network(true);
+    let route = nextshell::any().map(nextshell::reply).with(cors);
+    let res = preflight().reply(&route).await;
+    assert_eq!(res.status(), 200);
+    assert_eq!(
+        res.headers()["access-control-allow-private-network"],
+        "true"
+    );
+    assert!(res.headers()["vary"]
+        .to_str()
+        .unwrap()
+        .ends_with("access-control-request-private-network"));
+}
+
+#[tokio::test]
+async fn allow_any_header() {
+    let cors = nextshell::cors()
+        .allow_methods(&[Method::GET])
+        .allow_any_header();
+
+    let route = nextshell::any().map(nextshell::reply).with(cors);
+
+    let res = nextshell::test::request()
+        .method("OPTIONS")
+        .header("origin", "https://hyper.rs")
+        .header("access-control-request-method", "GET")
+        .header(
+            "access-control-request-headers",
+            "x-sdk-version, x-trace-42",
+        )
+        .reply(&route)
+        .await;
+    assert_eq!(res.status(), 200);
+    assert_eq!(
+        res.headers()["access-control-allow-headers"],
+        "x-sdk-version, x-trace-42"
+    );
+}
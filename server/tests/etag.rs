@@ -0,0 +1,118 @@
+#![deny(warnings)]
+use nextshell::Filter;
+
+#[tokio::test]
+async fn sets_etag_and_not_modified() {
+    let _ = pretty_env_logger::try_init();
+
+    let route = nextshell::any().map(|| "hello").with(nextshell::etag());
+
+    let res = nextshell::test::request().reply(&route).await;
+    assert_eq!(res.status(), 200);
+    assert_eq!(res.body(), "hello");
+    // The tag only depends on the bytes, so restarts and replicas agree.
+    let etag = res.headers()["etag"].clone();
+    assert_eq!(etag, "\"5-aaf4c61ddcc5e8a2dabede0f3b482cd9aea9434d\"");
+
+    // same body, same tag
+    let res = nextshell::test::request().reply(&route).await;
+    assert_eq!(res.headers()["etag"], etag);
+
+    let res = nextshell::test::request()
+        .header("if-none-match", etag.clone())
+        .reply(&route)
+        .await;
+    assert_eq!(res.status(), 304);
+    assert_eq!(res.headers()["etag"], etag);
+    assert_eq!(res.body(), "");
+
+    let res = nextshell::test::request()
+        .header("if-none-match", "\"other\"")
+        .reply(&route)
+        .await;
+    assert_eq!(res.status(), 200);
+    assert_eq!(res.body(), "hello");
+}
+
+#[tokio::test]
+async fn handler_provided_etag() {
+    let route = nextshell::any()
+        .map(|| nextshell::reply::with_header("hello", "etag", "\"v1\""))
+        .with(nextshell::etag());
+
+    let res = nextshell::test::request().reply(&route).await;
+    assert_eq!(res.headers()["etag"], "\"v1\"");
+
+    let res = nextshell::test::request()
+        .header("if-none-match", "W/\"v0\", \"v1\"")
+        .reply(&route)
+        .await;
+    assert_eq!(res.status(), 304);
+}
+
+#[tokio::test]
+async fn ignores_unsafe_methods_and_errors() {
+    let route = nextshell::post()
+        .map(|| "created")
+        .or(nextshell::get().map(|| nextshell::http::StatusCode::NOT_FOUND))
+        .with(nextshell::etag());
+
+    let res = nextshell::test::request()
+        .method("POST")
+        .reply(&route)
+        .await;
+    assert_eq!(res.status(), 200);
+    assert!(!res.headers().contains_key("etag"));
+
+    let res = nextshell::test::request().reply(&route).await;
+    assert_eq!(res.status(), 404);
+    assert!(!res.headers().contains_key("etag"));
+}
+
+#[tokio::test]
+async fn json_with_etag() {
+    let route = nextshell::any().map(|| nextshell::reply::json_with_etag(&vec![1, 3, 7]));
+
+    let res = nextshell::test::request().reply(&route).await;
+    assert_eq!(res.status(), 200);
+    assert_eq!(res.body(), "[1,3,7]");
+    assert_eq!(res.headers()["content-type"], "application/json");
+    assert_eq!(res.headers()["cache-control"], "no-cache");
+    let etag = res.headers()["etag"].clone();
+
+    let res = nextshell::test::request()
+        .header("if-none-match", etag.clone())
+        .reply(&route)
+        .await;
+    assert_eq!(res.status(), 304);
+    assert_eq!(res.headers()["etag"], etag);
+    assert_eq!(res.body(), "");
+
+    let res = nextshell::test::request()
+        .method("POST")
+        .header("if-none-match", etag)
+        .reply(&route)
+        .await;
+    assert_eq!(res.status(), 200);
+}
+
+#[tokio::test]
+async fn json_with_etag_served() {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    let route = nextshell::any().map(|| nextshell::reply::json_with_etag(&"hi"));
+    let etag = nextshell::test::request().reply(&route).await.headers()["etag"].clone();
+
+    let (addr, server) = nextshell::serve(route).bind_ephemeral(([127, 0, 0, 1], 0));
+    tokio::spawn(server);
+
+    let mut stream = tokio::net::TcpStream::connect(addr).await.unwrap();
+    let req = format!(
+        "GET / HTTP/1.1\r\nhost: localhost\r\nif-none-match: {}\r\nconnection: close\r\n\r\n",
+        etag.to_str().unwrap()
+    );
+    stream.write_all(req.as_bytes()).await.unwrap();
+    let mut res = String::new();
+    stream.read_to_string(&mut res).await.unwrap();
+    assert!(res.starts_with("HTTP/1.1 304 Not Modified\r\n"), "{}", res);
+}
@@ -79,6 +79,33 @@ async fn map() {
     assert_eq!(resp.status(), 200);
 }
 
+#[tokio::test]
+async fn map_response() {
+    let _ = pretty_env_logger::try_init();
+
+    let route = nextshell::path("hello").map(|| "hello").map_response(
+        |res: nextshell::reply::Response| async move {
+            let (mut parts, body) = res.into_parts();
+            let body = nextshell::hyper::body::to_bytes(body).await.unwrap();
+            parts.headers.remove("content-length");
+            parts.headers.insert("x-signed", "1".parse().unwrap());
+            let signed = format!("{} (signed)", String::from_utf8_lossy(&body));
+            nextshell::reply::Response::from_parts(parts, signed.into())
+        },
+    );
+
+    let res = nextshell::test::request()
+        .path("/hello")
+        .reply(&route)
+        .await;
+    assert_eq!(res.status(), 200);
+    assert_eq!(res.headers()["x-signed"], "1");
+    assert_eq!(res.body(), "hello (signed)");
+
+    let res = nextshell::test::request().path("/nope").reply(&route).await;
+    assert_eq!(res.status(), 404);
+}
+
 #[tokio::test]
 async fn or() {
     let _ = pretty_env_logger::try_init();
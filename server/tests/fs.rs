@@ -1,4 +1,5 @@
 #![deny(warnings)]
+use nextshell::Filter;
 use std::fs;
 
 #[tokio::test]
@@ -269,3 +270,495 @@ async fn byte_ranges_with_excluded_file_size() {
     );
     assert_eq!(res.body(), &contents[100..=contents.len() - 1]);
 }
+
+#[tokio::test]
+async fn suffix_byte_range() {
+    let _ = pretty_env_logger::try_init();
+
+    let contents = fs::read("examples/todos.rs").expect("fs::read");
+    let len = contents.len();
+    let file = nextshell::fs::file("examples/todos.rs");
+
+    let res = nextshell::test::request()
+        .header("range", "bytes=-10")
+        .reply(&file)
+        .await;
+    assert_eq!(res.status(), 206);
+    assert_eq!(
+        res.headers()["content-range"],
+        format!("bytes {}-{}/{}", len - 10, len - 1, len)
+    );
+    assert_eq!(res.headers()["content-length"], "10");
+    assert_eq!(res.body(), &contents[len - 10..]);
+
+    // an empty suffix can't be satisfied
+    let res = nextshell::test::request()
+        .header("range", "bytes=-0")
+        .reply(&file)
+        .await;
+    assert_eq!(res.status(), 416);
+    assert_eq!(res.headers()["content-range"], format!("bytes */{}", len));
+}
+
+#[tokio::test]
+async fn multiple_byte_ranges() {
+    let _ = pretty_env_logger::try_init();
+
+    let contents = fs::read("examples/todos.rs").expect("fs::read");
+    let len = contents.len();
+    let file = nextshell::fs::file("examples/todos.rs");
+
+    let res = nextshell::test::request()
+        .header("range", "bytes=0-4,10-14")
+        .reply(&file)
+        .await;
+    assert_eq!(res.status(), 206);
+    assert_eq!(res.headers().get("content-range"), None);
+    let content_type = res.headers()["content-type"].to_str().unwrap();
+    assert!(
+        content_type.starts_with("multipart/byteranges; boundary="),
+        "{}",
+        content_type
+    );
+    assert_eq!(
+        res.headers()["content-length"],
+        res.body().len().to_string()
+    );
+
+    let body = String::from_utf8_lossy(res.body());
+    let first = format!(
+        "content-range: bytes 0-4/{}\r\n\r\n{}",
+        len,
+        String::from_utf8_lossy(&contents[0..5])
+    );
+    let second = format!(
+        "content-range: bytes 10-14/{}\r\n\r\n{}",
+        len,
+        String::from_utf8_lossy(&contents[10..15])
+    );
+    assert!(body.contains(&first), "{}", body);
+    assert!(body.contains(&second), "{}", body);
+    assert!(body.find(&first) < body.find(&second));
+
+    // unsatisfiable ranges are dropped when others remain
+    let res = nextshell::test::request()
+        .header("range", format!("bytes=0-4,{}-", len + 10))
+        .reply(&file)
+        .await;
+    assert_eq!(res.status(), 206);
+    assert_eq!(res.headers()["content-range"], format!("bytes 0-4/{}", len));
+    assert_eq!(res.body(), &contents[0..5]);
+}
+
+#[tokio::test]
+async fn etag_not_modified() {
+    let _ = pretty_env_logger::try_init();
+
+    let file = nextshell::fs::file("examples/todos.rs");
+
+    let res1 = nextshell::test::request().reply(&file).await;
+    assert_eq!(res1.status(), 200);
+    let etag = res1.headers()["etag"].clone();
+    assert!(res1.headers().contains_key("last-modified"));
+
+    let res = nextshell::test::request()
+        .header("if-none-match", etag.clone())
+        .reply(&file)
+        .await;
+    assert_eq!(res.status(), 304);
+    assert_eq!(res.headers()["etag"], etag);
+    assert_eq!(res.body(), "");
+
+    // if-none-match takes precedence over if-modified-since
+    let res = nextshell::test::request()
+        .header("if-none-match", "\"other\"")
+        .header("if-modified-since", &res1.headers()["last-modified"])
+        .reply(&file)
+        .await;
+    assert_eq!(res.status(), 200);
+}
+
+#[tokio::test]
+async fn etag_precondition() {
+    let _ = pretty_env_logger::try_init();
+
+    let file = nextshell::fs::file("examples/todos.rs");
+
+    let res1 = nextshell::test::request().reply(&file).await;
+    let etag = res1.headers()["etag"].clone();
+
+    let res = nextshell::test::request()
+        .header("if-match", etag.clone())
+        .reply(&file)
+        .await;
+    assert_eq!(res.status(), 200);
+
+    let res = nextshell::test::request()
+        .header("if-match", "\"other\"")
+        .reply(&file)
+        .await;
+    assert_eq!(res.status(), 412);
+
+    // if-range with a matching etag allows the range
+    let res = nextshell::test::request()
+        .header("range", "bytes=0-9")
+        .header("if-range", etag)
+        .reply(&file)
+        .await;
+    assert_eq!(res.status(), 206);
+    assert_eq!(res.headers()["content-length"], "10");
+}
+
+#[tokio::test]
+async fn dir_listing() {
+    let _ = pretty_env_logger::try_init();
+
+    let tmp = std::env::temp_dir().join("nextshell-dir-listing");
+    let _ = fs::remove_dir_all(&tmp);
+    fs::create_dir_all(tmp.join("sub dir")).unwrap();
+    fs::write(tmp.join("a<b>.txt"), "hello").unwrap();
+    fs::write(tmp.join(".hidden"), "secret").unwrap();
+    fs::create_dir_all(tmp.join("q\"onmouseover=x")).unwrap();
+    fs::write(tmp.join("q\"onmouseover=x").join("c.txt"), "hello").unwrap();
+
+    let route = nextshell::path("static").and(nextshell::fs::dir(tmp.clone()).listing());
+
+    let res = nextshell::test::request()
+        .path("/static")
+        .reply(&route)
+        .await;
+    assert_eq!(res.status(), 200);
+    assert_eq!(res.headers()["content-type"], "text/html; charset=utf-8");
+    let body = std::str::from_utf8(res.body()).unwrap();
+    assert!(body.contains(r#"<a href="/static/sub%20dir/">sub dir/</a>"#));
+    assert!(body.contains(r#"<a href="/static/a%3Cb%3E.txt">a&lt;b&gt;.txt</a>"#));
+    assert!(!body.contains(".hidden"));
+
+    // The request path can't break out of the link attributes.
+    let res = nextshell::test::request()
+        .path("/static/q\"onmouseover=x/")
+        .reply(&route)
+        .await;
+    assert_eq!(res.status(), 200);
+    let body = std::str::from_utf8(res.body()).unwrap();
+    assert!(body.contains(r#"<a href="/static/q%22onmouseover=x/c.txt">c.txt</a>"#));
+    assert!(body.contains("<h1>Index of /static/q&quot;onmouseover=x/</h1>"));
+
+    let res = nextshell::test::request()
+        .path("/static/")
+        .header("accept", "application/json")
+        .reply(&route)
+        .await;
+    assert_eq!(res.headers()["content-type"], "application/json");
+    let body = std::str::from_utf8(res.body()).unwrap();
+    assert!(body.starts_with(r#"[{"is_dir":true"#), "{}", body);
+    assert!(body.contains(r#""name":"a<b>.txt""#));
+
+    let route = nextshell::fs::dir(tmp.clone())
+        .listing()
+        .listing_hidden(true);
+    let res = nextshell::test::request().path("/").reply(&route).await;
+    let body = std::str::from_utf8(res.body()).unwrap();
+    assert!(body.contains(".hidden"));
+
+    // still a 404 without listings
+    let route = nextshell::fs::dir(tmp.clone());
+    let res = nextshell::test::request().path("/").reply(&route).await;
+    assert_eq!(res.status(), 404);
+
+    // index.html is served if present
+    let route = nextshell::fs::dir("examples").listing();
+    let res = nextshell::test::request().path("/dir").reply(&route).await;
+    let contents = fs::read("examples/dir/index.html").expect("fs::read");
+    assert_eq!(res.body(), &*contents);
+}
+
+#[tokio::test]
+async fn dir_fallback() {
+    let _ = pretty_env_logger::try_init();
+
+    let route = nextshell::fs::dir("examples").fallback("dir/index.html");
+    let index = fs::read("examples/dir/index.html").expect("fs::read");
+
+    let res = nextshell::test::request()
+        .path("/some/client/route")
+        .reply(&route)
+        .await;
+    assert_eq!(res.status(), 200);
+    assert_eq!(res.headers()["content-type"], "text/html");
+    assert_eq!(res.body(), &*index);
+
+    // existing files are still served
+    let res = nextshell::test::request()
+        .path("/todos.rs")
+        .reply(&route)
+        .await;
+    let contents = fs::read("examples/todos.rs").expect("fs::read");
+    assert_eq!(res.status(), 200);
+    assert_eq!(res.body(), &*contents);
+
+    // traversal is still rejected
+    let res = nextshell::test::request()
+        .path("/../README.md")
+        .reply(&route)
+        .await;
+    assert_eq!(res.status(), 404);
+}
+
+#[tokio::test]
+async fn dir_dotfiles_and_extensions() {
+    let _ = pretty_env_logger::try_init();
+
+    let tmp = std::env::temp_dir().join("nextshell-dir-policy");
+    let _ = fs::remove_dir_all(&tmp);
+    fs::create_dir_all(tmp.join(".git")).unwrap();
+    fs::write(tmp.join(".git/config"), "secret").unwrap();
+    fs::write(tmp.join("app.JS"), "app").unwrap();
+    fs::write(tmp.join("notes.txt"), "notes").unwrap();
+
+    let route = nextshell::fs::dir(tmp.clone())
+        .dotfiles(false)
+        .allow_extensions([".js", "html"]);
+
+    let res = nextshell::test::request()
+        .path("/.git/config")
+        .reply(&route)
+        .await;
+    assert_eq!(res.status(), 403);
+
+    let res = nextshell::test::request()
+        .path("/notes.txt")
+        .reply(&route)
+        .await;
+    assert_eq!(res.status(), 403);
+
+    let res = nextshell::test::request()
+        .path("/app.JS")
+        .reply(&route)
+        .await;
+    assert_eq!(res.status(), 200);
+    assert_eq!(res.body(), "app");
+
+    let rejection = nextshell::test::request()
+        .path("/%2Egit/config")
+        .filter(&route)
+        .await
+        .unwrap_err();
+    assert!(rejection
+        .find::<nextshell::fs::DotfileForbidden>()
+        .is_some());
+
+    let rejection = nextshell::test::request()
+        .path("/notes.txt")
+        .filter(&route)
+        .await
+        .unwrap_err();
+    assert!(rejection
+        .find::<nextshell::fs::ExtensionForbidden>()
+        .is_some());
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn dir_symlinks() {
+    use nextshell::fs::Symlinks;
+
+    let _ = pretty_env_logger::try_init();
+
+    let tmp = std::env::temp_dir().join("nextshell-dir-symlinks");
+    let _ = fs::remove_dir_all(&tmp);
+    fs::create_dir_all(tmp.join("root")).unwrap();
+    fs::write(tmp.join("outside.txt"), "outside").unwrap();
+    fs::write(tmp.join("root/inside.txt"), "inside").unwrap();
+    std::os::unix::fs::symlink(tmp.join("outside.txt"), tmp.join("root/out.txt")).unwrap();
+    std::os::unix::fs::symlink(tmp.join("root/inside.txt"), tmp.join("root/in.txt")).unwrap();
+
+    let root = tmp.join("root");
+    let status = |symlinks, path: &'static str| {
+        let route = nextshell::fs::dir(root.clone()).symlinks(symlinks);
+        async move {
+            nextshell::test::request()
+                .path(path)
+                .reply(&route)
+                .await
+                .status()
+        }
+    };
+
+    assert_eq!(status(Symlinks::Always, "/out.txt").await, 200);
+    assert_eq!(status(Symlinks::WithinRoot, "/out.txt").await, 403);
+    assert_eq!(status(Symlinks::WithinRoot, "/in.txt").await, 200);
+    assert_eq!(status(Symlinks::Never, "/in.txt").await, 403);
+    assert_eq!(status(Symlinks::Never, "/inside.txt").await, 200);
+
+    let rejection = nextshell::test::request()
+        .path("/out.txt")
+        .filter(&nextshell::fs::dir(root.clone()).symlinks(Symlinks::Never))
+        .await
+        .unwrap_err();
+    assert!(rejection
+        .find::<nextshell::fs::SymlinkForbidden>()
+        .is_some());
+}
+
+#[tokio::test]
+async fn dir_cache() {
+    use nextshell::fs::FileCache;
+    use std::time::Duration;
+
+    let _ = pretty_env_logger::try_init();
+
+    let tmp = std::env::temp_dir().join("nextshell-dir-cache");
+    let _ = fs::remove_dir_all(&tmp);
+    fs::create_dir_all(&tmp).unwrap();
+    fs::write(tmp.join("a.txt"), "first").unwrap();
+
+    // cached files are served until the ttl expires
+    let route =
+        nextshell::fs::dir(tmp.clone()).cache(FileCache::new(1024).ttl(Duration::from_secs(60)));
+    let res = nextshell::test::request()
+        .path("/a.txt")
+        .reply(&route)
+        .await;
+    assert_eq!(res.body(), "first");
+
+    fs::write(tmp.join("a.txt"), "second!").unwrap();
+    let res = nextshell::test::request()
+        .path("/a.txt")
+        .reply(&route)
+        .await;
+    assert_eq!(res.body(), "first");
+
+    // ranges are served from memory too
+    let res = nextshell::test::request()
+        .path("/a.txt")
+        .header("range", "bytes=1-2")
+        .reply(&route)
+        .await;
+    assert_eq!(res.status(), 206);
+    assert_eq!(res.body(), "ir");
+
+    // an expired entry is checked again
+    let route = nextshell::fs::dir(tmp.clone()).cache(FileCache::new(1024).ttl(Duration::ZERO));
+    let res = nextshell::test::request()
+        .path("/a.txt")
+        .reply(&route)
+        .await;
+    assert_eq!(res.body(), "second!");
+    fs::write(tmp.join("a.txt"), "third").unwrap();
+    let res = nextshell::test::request()
+        .path("/a.txt")
+        .reply(&route)
+        .await;
+    assert_eq!(res.body(), "third");
+
+    // missing files are still rejected
+    let res = nextshell::test::request()
+        .path("/missing.txt")
+        .reply(&route)
+        .await;
+    assert_eq!(res.status(), 404);
+}
+
+#[cfg(feature = "embed")]
+#[tokio::test]
+async fn embedded() {
+    let _ = pretty_env_logger::try_init();
+
+    let route = nextshell::fs::embed!("examples", ["todos.rs", "dir/index.html"]);
+    let contents = fs::read("examples/todos.rs").expect("fs::read");
+
+    let res = nextshell::test::request()
+        .path("/todos.rs")
+        .reply(&route)
+        .await;
+    assert_eq!(res.status(), 200);
+    assert_eq!(res.headers()["content-type"], "text/x-rust");
+    assert_eq!(res.headers()["accept-ranges"], "bytes");
+    assert_eq!(res.body(), &*contents);
+    let etag = res.headers()["etag"].clone();
+
+    let res = nextshell::test::request()
+        .path("/todos.rs")
+        .header("if-none-match", etag)
+        .reply(&route)
+        .await;
+    assert_eq!(res.status(), 304);
+
+    let res = nextshell::test::request()
+        .path("/todos.rs")
+        .header("range", "bytes=0-9")
+        .reply(&route)
+        .await;
+    assert_eq!(res.status(), 206);
+    assert_eq!(res.body(), &contents[..10]);
+
+    let res = nextshell::test::request().path("/dir/").reply(&route).await;
+    let index = fs::read("examples/dir/index.html").expect("fs::read");
+    assert_eq!(res.status(), 200);
+    assert_eq!(res.body(), &*index);
+
+    let res = nextshell::test::request()
+        .path("/missing.rs")
+        .reply(&route)
+        .await;
+    assert_eq!(res.status(), 404);
+
+    let res = nextshell::test::request()
+        .method("POST")
+        .path("/todos.rs")
+        .reply(&route)
+        .await;
+    assert_eq!(res.status(), 405);
+}
+
+#[cfg(feature = "embed")]
+#[tokio::test]
+async fn embedded_dir() {
+    let _ = pretty_env_logger::try_init();
+
+    let route = nextshell::fs::embed!("$CARGO_MANIFEST_DIR/examples");
+
+    let res = nextshell::test::request()
+        .path("/todos.rs")
+        .reply(&route)
+        .await;
+    let contents = fs::read("examples/todos.rs").expect("fs::read");
+    assert_eq!(res.status(), 200);
+    assert_eq!(res.body(), &*contents);
+
+    let res = nextshell::test::request().path("/dir/").reply(&route).await;
+    let index = fs::read("examples/dir/index.html").expect("fs::read");
+    assert_eq!(res.status(), 200);
+    assert_eq!(res.headers()["content-type"], "text/html");
+    assert_eq!(res.body(), &*index);
+
+    let res = nextshell::test::request()
+        .path("/missing.rs")
+        .reply(&route)
+        .await;
+    assert_eq!(res.status(), 404);
+}
+
+#[tokio::test]
+async fn dir_read_buffer_size() {
+    let _ = pretty_env_logger::try_init();
+
+    let route = nextshell::fs::dir("examples").read_buffer_size(16);
+    let contents = fs::read("examples/todos.rs").expect("fs::read");
+
+    let res = nextshell::test::request()
+        .path("/todos.rs")
+        .reply(&route)
+        .await;
+    assert_eq!(res.status(), 200);
+    assert_eq!(res.body(), &*contents);
+
+    let res = nextshell::test::request()
+        .path("/todos.rs")
+        .header("range", "bytes=5-100")
+        .reply(&route)
+        .await;
+    assert_eq!(res.status(), 206);
+    assert_eq!(res.body(), &contents[5..=100]);
+}
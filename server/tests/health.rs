@@ -0,0 +1,104 @@
+#![deny(warnings)]
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use serde_json::Value;
+
+fn json(body: &[u8]) -> Value {
+    serde_json::from_slice(body).unwrap()
+}
+
+#[tokio::test]
+async fn livez() {
+    let health = nextshell::health().check("never", || async { Err::<(), _>("down") });
+
+    let res = nextshell::test::request()
+        .path("/livez")
+        .reply(&health.endpoints())
+        .await;
+    assert_eq!(res.status(), 200);
+    assert_eq!(res.headers()["content-type"], "application/json");
+    assert_eq!(json(res.body())["status"], "ok");
+}
+
+#[tokio::test]
+async fn readyz() {
+    let ready = Arc::new(AtomicBool::new(false));
+    let flag = ready.clone();
+    let health = nextshell::health()
+        .check("db", || async { Ok::<_, String>(()) })
+        .check("cache", move || {
+            let ready = flag.load(Ordering::SeqCst);
+            async move {
+                if ready {
+                    Ok(())
+                } else {
+                    Err("warming up")
+                }
+            }
+        });
+    let route = health.readyz();
+
+    let res = nextshell::test::request()
+        .path("/readyz")
+        .reply(&route)
+        .await;
+    assert_eq!(res.status(), 503);
+    let body = json(res.body());
+    assert_eq!(body["status"], "fail");
+    assert_eq!(body["checks"]["db"]["status"], "ok");
+    assert_eq!(body["checks"]["cache"]["status"], "fail");
+    assert_eq!(body["checks"]["cache"]["error"], "warming up");
+
+    ready.store(true, Ordering::SeqCst);
+    let res = nextshell::test::request()
+        .path("/readyz")
+        .reply(&route)
+        .await;
+    assert_eq!(res.status(), 200);
+    assert_eq!(json(res.body())["status"], "ok");
+
+    // Checks registered once the filter is built are run too.
+    let _ = health.check("late", || async { Err::<(), _>("nope") });
+    let res = nextshell::test::request()
+        .path("/readyz")
+        .reply(&route)
+        .await;
+    assert_eq!(res.status(), 503);
+    assert_eq!(json(res.body())["checks"]["late"]["error"], "nope");
+}
+
+#[tokio::test]
+async fn readyz_timeout() {
+    let health = nextshell::health()
+        .timeout(Duration::from_millis(50))
+        .check("slow", || async {
+            tokio::time::sleep(Duration::from_secs(1)).await;
+            Ok::<_, String>(())
+        })
+        .check_with_timeout("patient", Duration::from_secs(5), || async {
+            tokio::time::sleep(Duration::from_millis(100)).await;
+            Ok::<_, String>(())
+        });
+
+    let res = nextshell::test::request()
+        .path("/readyz")
+        .reply(&health.endpoints())
+        .await;
+    assert_eq!(res.status(), 503);
+    let body = json(res.body());
+    assert_eq!(body["checks"]["slow"]["error"], "timed out");
+    assert_eq!(body["checks"]["patient"]["status"], "ok");
+}
+
+#[tokio::test]
+async fn only_get() {
+    let health = nextshell::health();
+    let res = nextshell::test::request()
+        .method("POST")
+        .path("/readyz")
+        .reply(&health.endpoints())
+        .await;
+    assert_eq!(res.status(), 405);
+}
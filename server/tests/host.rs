@@ -85,3 +85,31 @@ async fn optional() {
     let req = nextshell::test::request();
     assert_eq!(req.filter(&filter).await.unwrap(), None);
 }
+
+#[tokio::test]
+async fn scheme() {
+    use nextshell::host::Scheme;
+
+    let filter = nextshell::host::scheme();
+
+    let req = nextshell::test::request();
+    assert_eq!(req.filter(&filter).await.unwrap(), Scheme::HTTP);
+
+    let req = nextshell::test::request().path("https://known.com/about-us");
+    assert_eq!(req.filter(&filter).await.unwrap(), Scheme::HTTPS);
+
+    let req = nextshell::test::request().header("x-forwarded-proto", "https, http");
+    assert_eq!(req.filter(&filter).await.unwrap(), Scheme::HTTPS);
+
+    let req = nextshell::test::request().header(
+        "forwarded",
+        "for=192.0.2.60;proto=\"https\";by=203.0.113.43, proto=http",
+    );
+    assert_eq!(req.filter(&filter).await.unwrap(), Scheme::HTTPS);
+
+    // x-forwarded-proto wins
+    let req = nextshell::test::request()
+        .header("x-forwarded-proto", "http")
+        .header("forwarded", "proto=https");
+    assert_eq!(req.filter(&filter).await.unwrap(), Scheme::HTTP);
+}
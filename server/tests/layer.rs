@@ -0,0 +1,142 @@
+#![deny(warnings)]
+use std::convert::Infallible;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::task::{Context, Poll};
+
+use nextshell::http::{HeaderValue, Request};
+use nextshell::hyper::service::Service;
+use nextshell::hyper::Body;
+use nextshell::reply::Response;
+use nextshell::service::InnerService;
+use nextshell::Filter;
+
+// A middleware counting requests and tagging responses with their status.
+#[derive(Clone)]
+struct Tagged<S> {
+    inner: S,
+    seen: Arc<AtomicUsize>,
+}
+
+impl<S> Service<Request<Body>> for Tagged<S>
+where
+    S: Service<Request<Body>, Response = Response, Error = Infallible>,
+    S::Future: Send + 'static,
+{
+    type Response = Response;
+    type Error = Infallible;
+    type Future = Pin<Box<dyn Future<Output = Result<Response, Infallible>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: Request<Body>) -> Self::Future {
+        self.seen.fetch_add(1, Ordering::SeqCst);
+        let fut = self.inner.call(req);
+        Box::pin(async move {
+            let mut res = fut.await?;
+            let status = HeaderValue::from(res.status().as_u16());
+            res.headers_mut().insert("x-seen-status", status);
+            Ok(res)
+        })
+    }
+}
+
+// A middleware that is never ready.
+#[derive(Clone)]
+struct Overloaded;
+
+impl Service<Request<Body>> for Overloaded {
+    type Response = Response;
+    type Error = &'static str;
+    type Future = std::future::Ready<Result<Response, &'static str>>;
+
+    fn poll_ready(&mut self, _: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Err("overloaded"))
+    }
+
+    fn call(&mut self, _: Request<Body>) -> Self::Future {
+        unreachable!("not ready")
+    }
+}
+
+#[tokio::test]
+async fn wraps_filter() {
+    let seen = Arc::new(AtomicUsize::new(0));
+    let layer_seen = seen.clone();
+
+    let users = nextshell::path!("users" / u32)
+        .map(|id| format!("user {}", id))
+        .with(nextshell::wrap_layer(move |svc: InnerService| Tagged {
+            inner: svc,
+            seen: layer_seen.clone(),
+        }));
+    let route = nextshell::path("api").and(users);
+
+    let res = nextshell::test::request()
+        .path("/api/users/7")
+        .reply(&route)
+        .await;
+    assert_eq!(res.status(), 200);
+    assert_eq!(res.headers()["x-seen-status"], "200");
+    assert_eq!(res.body(), "user 7");
+    assert_eq!(seen.load(Ordering::SeqCst), 1);
+}
+
+#[tokio::test]
+async fn rejections_pass_through() {
+    let seen = Arc::new(AtomicUsize::new(0));
+    let layer_seen = seen.clone();
+
+    let a =
+        nextshell::path("a")
+            .map(|| "a")
+            .with(nextshell::wrap_layer(move |svc: InnerService| Tagged {
+                inner: svc,
+                seen: layer_seen.clone(),
+            }));
+    let b = nextshell::path("b")
+        .and(nextshell::ext::get::<&'static str>())
+        .and(nextshell::body::bytes())
+        .map(|ext: &'static str, body: bytes::Bytes| format!("{} {:?}", ext, body));
+    let route = a.or(b);
+
+    let res = nextshell::test::request()
+        .path("/b")
+        .extension("ext")
+        .body("body")
+        .reply(&route)
+        .await;
+    assert_eq!(res.status(), 200);
+    assert!(res.headers().get("x-seen-status").is_none());
+    assert_eq!(res.body(), "ext b\"body\"");
+    assert_eq!(seen.load(Ordering::SeqCst), 1);
+}
+
+#[tokio::test]
+async fn middleware_error() {
+    let route = nextshell::any()
+        .map(|| "unreachable")
+        .with(nextshell::wrap_layer(|_: InnerService| Overloaded));
+
+    let res = nextshell::test::request().reply(&route).await;
+    assert_eq!(res.status(), 503);
+}
+
+#[tokio::test]
+async fn into_service() {
+    let mut svc = nextshell::path!("hello" / String)
+        .map(|name| format!("Hello, {}!", name))
+        .into_service();
+
+    let req = Request::get("/hello/tower").body(Body::empty()).unwrap();
+    let res = svc.call(req).await.unwrap();
+    assert_eq!(res.status(), 200);
+    let body = nextshell::hyper::body::to_bytes(res.into_body())
+        .await
+        .unwrap();
+    assert_eq!(body, "Hello, tower!");
+}
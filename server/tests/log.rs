@@ -0,0 +1,156 @@
+#![deny(warnings)]
+use std::io::{self, Write};
+use std::sync::{Arc, Mutex};
+
+use nextshell::log::Format;
+use nextshell::Filter;
+
+#[derive(Clone, Default)]
+struct Buf(Arc<Mutex<Vec<u8>>>);
+
+impl Write for Buf {
+    fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+        self.0.lock().unwrap().extend_from_slice(data);
+        Ok(data.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Buf {
+    fn take(&self) -> String {
+        String::from_utf8(std::mem::take(&mut *self.0.lock().unwrap())).unwrap()
+    }
+}
+
+#[tokio::test]
+async fn common_and_combined() {
+    let _ = pretty_env_logger::try_init();
+
+    let buf = Buf::default();
+    let route = nextshell::path("hello")
+        .map(|| "hello")
+        .with(nextshell::log::writer(Format::common(), buf.clone()));
+
+    nextshell::test::request()
+        .path("/hello?x=1")
+        .remote_addr("10.0.0.1:4000".parse().unwrap())
+        .reply(&route)
+        .await;
+    let line = buf.take();
+    assert!(line.starts_with("10.0.0.1 - - ["), "{}", line);
+    assert!(
+        line.ends_with("] \"GET /hello?x=1 HTTP/1.1\" 200 5\n"),
+        "{}",
+        line
+    );
+
+    let route = nextshell::path("hello")
+        .map(|| "hello")
+        .with(nextshell::log::writer(Format::combined(), buf.clone()));
+    nextshell::test::request()
+        .path("/nope")
+        .header("referer", "http://example.com/")
+        .header("user-agent", "curl")
+        .reply(&route)
+        .await;
+    let line = buf.take();
+    assert!(line.starts_with("- - - ["), "{}", line);
+    assert!(
+        line.ends_with("] \"GET /nope HTTP/1.1\" 404 - \"http://example.com/\" \"curl\"\n"),
+        "{}",
+        line
+    );
+}
+
+#[tokio::test]
+async fn custom_format() {
+    let buf = Buf::default();
+    let format = Format::custom(|info| {
+        format!(
+            "{} {} {:?}",
+            info.uri(),
+            info.status().as_u16(),
+            info.response_headers().map(|h| h.len()),
+        )
+    });
+    let route = nextshell::any()
+        .map(|| "hi")
+        .with(nextshell::log::writer(format, buf.clone()));
+
+    nextshell::test::request().path("/a?b").reply(&route).await;
+    // Just `content-type`.
+    assert_eq!(buf.take(), "/a?b 200 Some(1)\n");
+}
+
+#[tokio::test]
+async fn file() {
+    let path = std::env::temp_dir().join(format!("nextshell-log-{}.log", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+
+    let route = nextshell::any()
+        .map(nextshell::reply)
+        .with(nextshell::log::file(Format::common(), &path).unwrap());
+    nextshell::test::request().reply(&route).await;
+    nextshell::test::request().reply(&route).await;
+
+    let lines = std::fs::read_to_string(&path).unwrap();
+    assert_eq!(lines.lines().count(), 2);
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[tokio::test]
+async fn structured() {
+    let buf = Buf::default();
+    let route = nextshell::path!("users" / u32)
+        .and(nextshell::metrics::route("/users/{id}"))
+        .map(|_| "user")
+        .with(nextshell::log::structured().writer(buf.clone()));
+
+    nextshell::test::request()
+        .path("/users/7")
+        .header("x-request-id", "abc")
+        .header("content-length", "0")
+        .remote_addr("10.0.0.1:4000".parse().unwrap())
+        .reply(&route)
+        .await;
+    let line = buf.take();
+    let obj: serde_json::Value = serde_json::from_str(line.trim_end()).unwrap();
+    assert_eq!(obj["method"], "GET");
+    assert_eq!(obj["path"], "/users/7");
+    assert_eq!(obj["route"], "/users/{id}");
+    assert_eq!(obj["status"], 200);
+    assert_eq!(obj["request_bytes"], 0);
+    assert_eq!(obj["response_bytes"], 4);
+    assert_eq!(obj["request_id"], "abc");
+    assert_eq!(obj["remote_addr"], "10.0.0.1:4000");
+    assert!(obj["latency_ms"].is_number());
+
+    nextshell::test::request().path("/nope").reply(&route).await;
+    let obj: serde_json::Value = serde_json::from_str(buf.take().trim_end()).unwrap();
+    assert_eq!(obj["status"], 404);
+    assert_eq!(obj["route"], serde_json::Value::Null);
+    assert_eq!(obj["request_id"], serde_json::Value::Null);
+}
+
+#[tokio::test]
+async fn structured_sampling() {
+    let buf = Buf::default();
+    let route = nextshell::path("ok").map(nextshell::reply).with(
+        nextshell::log::structured()
+            .sample(2, 0.25)
+            .sample(4, 0.0)
+            .writer(buf.clone()),
+    );
+
+    for _ in 0..8 {
+        nextshell::test::request().path("/ok").reply(&route).await;
+        nextshell::test::request()
+            .path("/missing")
+            .reply(&route)
+            .await;
+    }
+    assert_eq!(buf.take().lines().count(), 2);
+}
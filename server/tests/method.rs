@@ -52,3 +52,208 @@ async fn bad_request_trumps_method_not_allowed() {
     // assume POST was the appropriate method.
     assert_eq!(resp.status(), 400);
 }
+
+#[tokio::test]
+async fn priority_overrides_precedence() {
+    let _ = pretty_env_logger::try_init();
+    let get = nextshell::get().and(nextshell::path("hello").map(nextshell::reply));
+    let post = nextshell::post().and(nextshell::path("bye").map(nextshell::reply));
+    let prefer_not_found = nextshell::reject::priority(|status| match status {
+        nextshell::http::StatusCode::NOT_FOUND => 1,
+        _ => 0,
+    });
+
+    let routes = get.or(post).with(prefer_not_found);
+
+    let req = nextshell::test::request().method("GET").path("/bye");
+    let resp = req.reply(&routes).await;
+    assert_eq!(resp.status(), 404);
+
+    let req = nextshell::test::request().method("POST").path("/hello");
+    let resp = req.reply(&routes).await;
+    assert_eq!(resp.status(), 404);
+
+    let req = nextshell::test::request().method("POST").path("/bye");
+    let resp = req.reply(&routes).await;
+    assert_eq!(resp.status(), 200);
+
+    // Ties fall back to the built-in precedence.
+    let get = nextshell::get()
+        .and(nextshell::path("hello"))
+        .and(nextshell::header::exact("foo", "bar"))
+        .map(nextshell::reply);
+    let post = nextshell::post()
+        .and(nextshell::path("bye"))
+        .map(nextshell::reply);
+    let routes = get.or(post).with(nextshell::reject::priority(|_| 0));
+
+    let req = nextshell::test::request().method("GET").path("/hello");
+    let resp = req.reply(&routes).await;
+    assert_eq!(resp.status(), 400);
+}
+
+#[tokio::test]
+async fn auto_head() {
+    let _ = pretty_env_logger::try_init();
+    let hello = nextshell::get()
+        .and(nextshell::path("hello"))
+        .map(|| nextshell::reply::with_header("hello", "x-hello", "1"));
+    let head = nextshell::head()
+        .and(nextshell::path("custom"))
+        .map(|| nextshell::reply::with_header("", "x-custom", "1"));
+    let routes = hello.or(head).with(nextshell::auto_head());
+
+    let res = nextshell::test::request()
+        .method("HEAD")
+        .path("/hello")
+        .reply(&routes)
+        .await;
+    assert_eq!(res.status(), 200);
+    assert_eq!(res.headers()["x-hello"], "1");
+    assert_eq!(res.headers()["content-length"], "5");
+    assert_eq!(res.body(), "");
+
+    // Explicit `HEAD` routes still win.
+    let res = nextshell::test::request()
+        .method("HEAD")
+        .path("/custom")
+        .reply(&routes)
+        .await;
+    assert_eq!(res.status(), 200);
+    assert_eq!(res.headers()["x-custom"], "1");
+
+    let res = nextshell::test::request()
+        .method("HEAD")
+        .path("/nope")
+        .reply(&routes)
+        .await;
+    assert_eq!(res.status(), 405);
+
+    let res = nextshell::test::request()
+        .path("/hello")
+        .reply(&routes)
+        .await;
+    assert_eq!(res.body(), "hello");
+}
+
+#[tokio::test]
+async fn auto_head_server() {
+    let route = nextshell::path("hello")
+        .and(nextshell::get())
+        .map(|| "hello")
+        .with(nextshell::auto_head());
+    let (addr, server) = nextshell::serve(route).bind_ephemeral(([127, 0, 0, 1], 0));
+    tokio::spawn(server);
+
+    let req = hyper::Request::head(format!("http://{}/hello", addr))
+        .body(hyper::Body::empty())
+        .unwrap();
+    let res = hyper::Client::new().request(req).await.unwrap();
+    assert_eq!(res.status(), 200);
+    assert_eq!(res.headers()["content-length"], "5");
+}
+
+#[tokio::test]
+async fn auto_options() {
+    let _ = pretty_env_logger::try_init();
+    let list = nextshell::path!("items").and(nextshell::get()).map(|| "[]");
+    let create = nextshell::path!("items")
+        .and(nextshell::post())
+        .map(|| "created");
+    let remove = nextshell::path!("items" / u32)
+        .and(nextshell::delete())
+        .map(|_| "deleted");
+    let custom = nextshell::path!("custom")
+        .and(nextshell::options())
+        .map(|| "custom");
+    let routes = list
+        .or(create)
+        .or(remove)
+        .or(custom)
+        .with(nextshell::auto_options());
+
+    let res = nextshell::test::request()
+        .method("OPTIONS")
+        .path("/items")
+        .reply(&routes)
+        .await;
+    assert_eq!(res.status(), 204);
+    // `GET` routes only answer `HEAD` with `auto_head`.
+    assert_eq!(res.headers()["allow"], "GET, POST, OPTIONS");
+
+    let res = nextshell::test::request()
+        .method("OPTIONS")
+        .path("/items/3")
+        .reply(&routes)
+        .await;
+    assert_eq!(res.status(), 204);
+    assert_eq!(res.headers()["allow"], "DELETE, OPTIONS");
+
+    // Explicit `OPTIONS` routes still win.
+    let res = nextshell::test::request()
+        .method("OPTIONS")
+        .path("/custom")
+        .reply(&routes)
+        .await;
+    assert_eq!(res.status(), 200);
+    assert_eq!(res.body(), "custom");
+
+    // CORS preflights are left alone.
+    let res = nextshell::test::request()
+        .method("OPTIONS")
+        .path("/items")
+        .header("origin", "https://example.com")
+        .header("access-control-request-method", "POST")
+        .reply(&routes)
+        .await;
+    assert_eq!(res.status(), 405);
+
+    let res = nextshell::test::request()
+        .method("OPTIONS")
+        .path("/nope")
+        .reply(&routes)
+        .await;
+    assert_eq!(res.status(), 404);
+}
+
+#[tokio::test]
+async fn auto_options_with_head() {
+    let list = || nextshell::path!("items").and(nextshell::get()).map(|| "[]");
+    let create = || {
+        nextshell::path!("items")
+            .and(nextshell::post())
+            .map(|| "created")
+    };
+    let probe = |path: &'static str| nextshell::test::request().method("OPTIONS").path(path);
+
+    let outer = list()
+        .or(create())
+        .with(nextshell::auto_options())
+        .with(nextshell::auto_head());
+    let res = probe("/items").reply(&outer).await;
+    assert_eq!(res.headers()["allow"], "GET, HEAD, POST, OPTIONS");
+
+    let inner = list()
+        .with(nextshell::auto_head())
+        .or(create())
+        .with(nextshell::auto_options());
+    let res = probe("/items").reply(&inner).await;
+    assert_eq!(res.headers()["allow"], "GET, HEAD, POST, OPTIONS");
+
+    // Only the wrapped `GET` routes answer `HEAD`.
+    let partial = create()
+        .with(nextshell::auto_head())
+        .or(list())
+        .with(nextshell::auto_options());
+    let res = probe("/items").reply(&partial).await;
+    assert_eq!(res.headers()["allow"], "POST, GET, OPTIONS");
+
+    // Explicit `HEAD` routes are listed as is.
+    let head = nextshell::path!("items")
+        .and(nextshell::head())
+        .map(nextshell::reply)
+        .or(list())
+        .with(nextshell::auto_options());
+    let res = probe("/items").reply(&head).await;
+    assert_eq!(res.headers()["allow"], "HEAD, GET, OPTIONS");
+}
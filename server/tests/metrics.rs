@@ -0,0 +1,136 @@
+#![deny(warnings)]
+use nextshell::Filter;
+
+#[tokio::test]
+async fn records_and_exposes() {
+    let _ = pretty_env_logger::try_init();
+
+    let metrics = nextshell::metrics().buckets(vec![0.1, 1.0]);
+    let hello = nextshell::path!("hello" / String)
+        .and(nextshell::metrics::route("/hello/{name}"))
+        .map(|name| format!("Hello, {}!", name));
+    let routes = metrics.endpoint().or(hello).with(metrics.clone());
+
+    let res = nextshell::test::request()
+        .path("/hello/alice")
+        .reply(&routes)
+        .await;
+    assert_eq!(res.status(), 200);
+    nextshell::test::request()
+        .path("/hello/bob")
+        .reply(&routes)
+        .await;
+    nextshell::test::request()
+        .path("/nope")
+        .reply(&routes)
+        .await;
+
+    let res = nextshell::test::request()
+        .path("/metrics")
+        .reply(&routes)
+        .await;
+    assert_eq!(res.status(), 200);
+    assert_eq!(res.headers()["content-type"], "text/plain; version=0.0.4");
+    let body = std::str::from_utf8(res.body()).unwrap();
+
+    let labels = "method=\"GET\",route=\"/hello/{name}\",status=\"200\"";
+    assert!(body.contains(&format!("nextshell_http_requests_total{{{}}} 2\n", labels)));
+    assert!(body.contains(
+        "nextshell_http_requests_total{method=\"GET\",route=\"unmatched\",status=\"404\"} 1\n"
+    ));
+    assert!(body.contains(&format!(
+        "nextshell_http_request_duration_seconds_bucket{{{},le=\"0.1\"}} 2\n",
+        labels
+    )));
+    assert!(body.contains(&format!(
+        "nextshell_http_request_duration_seconds_bucket{{{},le=\"+Inf\"}} 2\n",
+        labels
+    )));
+    assert!(body.contains(&format!(
+        "nextshell_http_request_duration_seconds_count{{{}}} 2\n",
+        labels
+    )));
+    // The scrape itself is in flight while rendering.
+    assert!(body.contains("nextshell_http_requests_in_flight 1\n"));
+}
+
+#[tokio::test]
+async fn extension_methods() {
+    let metrics = nextshell::metrics();
+    let routes = metrics
+        .endpoint()
+        .or(nextshell::any().map(nextshell::reply))
+        .with(metrics.clone());
+
+    for method in ["PURGE", "FOO", "BAR"] {
+        nextshell::test::request()
+            .method(method)
+            .path("/nope")
+            .reply(&routes)
+            .await;
+    }
+
+    let res = nextshell::test::request()
+        .path("/metrics")
+        .reply(&routes)
+        .await;
+    let body = std::str::from_utf8(res.body()).unwrap();
+    assert!(body.contains(
+        "nextshell_http_requests_total{method=\"other\",route=\"unmatched\",status=\"200\"} 3\n"
+    ));
+    assert!(!body.contains("PURGE"));
+}
+
+#[tokio::test]
+async fn instrumented_filters() {
+    let metrics = nextshell::metrics().buckets(vec![0.1, 1.0]);
+    let slow = nextshell::path("slow")
+        .and_then(|| async {
+            tokio::time::sleep(std::time::Duration::from_millis(150)).await;
+            Ok::<_, nextshell::Rejection>(())
+        })
+        .untuple_one()
+        .instrument("slow")
+        .map(nextshell::reply);
+    let id = nextshell::header::<u32>("x-id")
+        .instrument("id")
+        .map(|id: u32| id.to_string());
+    let route = slow.or(id).with(metrics.clone());
+
+    nextshell::test::request().path("/slow").reply(&route).await;
+    nextshell::test::request()
+        .path("/other")
+        .header("x-id", "7")
+        .reply(&route)
+        .await;
+    nextshell::test::request()
+        .path("/other")
+        .reply(&route)
+        .await;
+
+    let body = metrics.render();
+    let slow = "filter=\"slow\",outcome=\"ok\"";
+    assert!(body.contains(&format!(
+        "nextshell_filter_duration_seconds_bucket{{{},le=\"0.1\"}} 0\n",
+        slow
+    )));
+    assert!(body.contains(&format!(
+        "nextshell_filter_duration_seconds_bucket{{{},le=\"1\"}} 1\n",
+        slow
+    )));
+    assert!(body.contains(
+        "nextshell_filter_duration_seconds_count{filter=\"slow\",outcome=\"rejected\"} 2\n"
+    ));
+    assert!(
+        body.contains("nextshell_filter_duration_seconds_count{filter=\"id\",outcome=\"ok\"} 1\n")
+    );
+    assert!(body.contains(
+        "nextshell_filter_duration_seconds_count{filter=\"id\",outcome=\"rejected\"} 1\n"
+    ));
+}
+
+#[test]
+#[should_panic(expected = "strictly increasing")]
+fn unsorted_buckets() {
+    let _ = nextshell::metrics().buckets(vec![1.0, 0.5]);
+}
@@ -0,0 +1,104 @@
+#![deny(warnings)]
+use std::time::{Duration, SystemTime};
+
+use nextshell::precondition::Preconditions;
+use nextshell::Filter;
+
+fn update(
+    etag: &'static str,
+    modified: SystemTime,
+) -> impl Filter<Extract = (impl nextshell::Reply,), Error = nextshell::Rejection> + Clone {
+    nextshell::precondition().and_then(move |pre: Preconditions| async move {
+        pre.check(Some(etag), Some(modified))?;
+        Ok::<_, nextshell::Rejection>("updated")
+    })
+}
+
+#[tokio::test]
+async fn if_match() {
+    let route = update("\"v2\"", SystemTime::UNIX_EPOCH);
+
+    let res = nextshell::test::request()
+        .method("PUT")
+        .header("if-match", "\"v1\", \"v2\"")
+        .reply(&route)
+        .await;
+    assert_eq!(res.status(), 200);
+
+    let res = nextshell::test::request()
+        .method("PUT")
+        .header("if-match", "\"v1\"")
+        .reply(&route)
+        .await;
+    assert_eq!(res.status(), 412);
+
+    // Strong comparison never matches weak tags.
+    let res = nextshell::test::request()
+        .method("PUT")
+        .header("if-match", "W/\"v2\"")
+        .reply(&route)
+        .await;
+    assert_eq!(res.status(), 412);
+
+    let res = nextshell::test::request()
+        .method("PUT")
+        .header("if-match", "*")
+        .reply(&route)
+        .await;
+    assert_eq!(res.status(), 200);
+}
+
+#[tokio::test]
+async fn if_unmodified_since() {
+    let modified = SystemTime::UNIX_EPOCH + Duration::from_secs(784_111_777);
+    let route = update("\"v1\"", modified);
+
+    let res = nextshell::test::request()
+        .method("PUT")
+        .header("if-unmodified-since", "Sun, 06 Nov 1994 08:49:37 GMT")
+        .reply(&route)
+        .await;
+    assert_eq!(res.status(), 200);
+
+    let res = nextshell::test::request()
+        .method("PUT")
+        .header("if-unmodified-since", "Sun, 06 Nov 1994 08:49:36 GMT")
+        .reply(&route)
+        .await;
+    assert_eq!(res.status(), 412);
+
+    // `if-match` takes precedence.
+    let res = nextshell::test::request()
+        .method("PUT")
+        .header("if-match", "\"v1\"")
+        .header("if-unmodified-since", "Sun, 06 Nov 1994 08:49:36 GMT")
+        .reply(&route)
+        .await;
+    assert_eq!(res.status(), 200);
+}
+
+#[tokio::test]
+async fn missing_resource() {
+    let route = nextshell::precondition().map(|pre: Preconditions| {
+        assert!(!pre.is_empty());
+        assert!(pre.if_match().unwrap().is_any());
+        pre.check(None, None).is_ok().to_string()
+    });
+
+    let res = nextshell::test::request()
+        .header("if-match", "*")
+        .reply(&route)
+        .await;
+    assert_eq!(res.body(), "false");
+}
+
+#[tokio::test]
+async fn no_preconditions() {
+    let route = nextshell::precondition().map(|pre: Preconditions| {
+        assert!(pre.is_empty());
+        pre.check(None, None).is_ok().to_string()
+    });
+
+    let res = nextshell::test::request().reply(&route).await;
+    assert_eq!(res.body(), "true");
+}
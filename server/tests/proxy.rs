@@ -0,0 +1,189 @@
+#![deny(warnings)]
+use std::net::SocketAddr;
+
+use nextshell::Filter;
+
+fn backend() -> SocketAddr {
+    let echo = nextshell::method()
+        .and(nextshell::path::full())
+        .and(
+            nextshell::query::raw()
+                .or(nextshell::any().map(String::new))
+                .unify(),
+        )
+        .and(nextshell::header::headers_cloned())
+        .and(nextshell::body::bytes())
+        .map(
+            |method: http::Method,
+             path: nextshell::path::FullPath,
+             query: String,
+             headers: http::HeaderMap,
+             body: bytes::Bytes| {
+                let header = |name| {
+                    headers
+                        .get(name)
+                        .map_or("-", |v: &http::HeaderValue| v.to_str().unwrap())
+                        .to_owned()
+                };
+                let res = format!(
+                    "{} {}?{} host={} secret={} xff={} xfh={} body={}",
+                    method,
+                    path.as_str(),
+                    query,
+                    header("host"),
+                    header("x-secret"),
+                    header("x-forwarded-for"),
+                    header("x-forwarded-host"),
+                    String::from_utf8_lossy(&body),
+                );
+                nextshell::reply::with_header(res, "keep-alive", "timeout=5")
+            },
+        );
+    let (addr, server) = nextshell::serve(echo).bind_ephemeral(([127, 0, 0, 1], 0));
+    tokio::spawn(server);
+    addr
+}
+
+#[tokio::test]
+async fn forwards_requests() {
+    let _ = pretty_env_logger::try_init();
+    let addr = backend();
+    let route = nextshell::path("api").and(nextshell::proxy::to(&format!("http://{}/v1/", addr)));
+
+    let res = nextshell::test::request()
+        .method("POST")
+        .path("/api/users/7?page=2")
+        .header("host", "example.com")
+        .header("connection", "x-secret")
+        .header("x-secret", "1")
+        .header("x-forwarded-for", "10.0.0.1")
+        .remote_addr(([192, 168, 0, 2], 4242).into())
+        .body("hello")
+        .reply(&route)
+        .await;
+    assert_eq!(res.status(), 200);
+    assert_eq!(
+        res.body(),
+        &format!(
+            "POST /v1/users/7?page=2 host={} secret=- xff=10.0.0.1, 192.168.0.2 \
+             xfh=example.com body=hello",
+            addr
+        )
+    );
+    assert!(!res.headers().contains_key("keep-alive"));
+}
+
+#[tokio::test]
+async fn bad_gateway() {
+    let _ = pretty_env_logger::try_init();
+    let route = nextshell::proxy::to(&format!("http://{}", closed()));
+
+    let res = nextshell::test::request().path("/").reply(&route).await;
+    assert_eq!(res.status(), 502);
+}
+
+fn named(name: &'static str) -> SocketAddr {
+    let route = nextshell::path("slow")
+        .and_then(move || async move {
+            tokio::time::sleep(std::time::Duration::from_millis(500)).await;
+            Ok::<_, std::convert::Infallible>(name)
+        })
+        .or(nextshell::any().map(move || name));
+    let (addr, server) = nextshell::serve(route).bind_ephemeral(([127, 0, 0, 1], 0));
+    tokio::spawn(server);
+    addr
+}
+
+fn closed() -> SocketAddr {
+    std::net::TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+}
+
+async fn bodies<F>(route: &F, n: usize) -> Vec<String>
+where
+    F: Filter + 'static,
+    F::Extract: nextshell::Reply + Send,
+{
+    let mut bodies = Vec::new();
+    for _ in 0..n {
+        let res = nextshell::test::request().reply(route).await;
+        bodies.push(String::from_utf8(res.body().to_vec()).unwrap());
+    }
+    bodies
+}
+
+#[tokio::test]
+async fn pool_round_robin() {
+    let _ = pretty_env_logger::try_init();
+    let route = nextshell::proxy::pool()
+        .upstream(format!("http://{}", named("a")))
+        .upstream(format!("http://{}", named("b")))
+        .forward();
+
+    assert_eq!(bodies(&route, 4).await, ["a", "b", "a", "b"]);
+}
+
+#[tokio::test]
+async fn pool_weighted() {
+    let _ = pretty_env_logger::try_init();
+    let route = nextshell::proxy::pool()
+        .upstream(nextshell::proxy::backend(&format!("http://{}", named("a"))).weight(2))
+        .upstream(format!("http://{}", named("b")))
+        .strategy(nextshell::proxy::Strategy::Weighted)
+        .forward();
+
+    assert_eq!(bodies(&route, 6).await, ["a", "a", "b", "a", "a", "b"]);
+}
+
+#[tokio::test]
+async fn pool_least_connections() {
+    let _ = pretty_env_logger::try_init();
+    let route = nextshell::proxy::pool()
+        .upstream(format!("http://{}", named("a")))
+        .upstream(format!("http://{}", named("b")))
+        .strategy(nextshell::proxy::Strategy::LeastConnections)
+        .forward();
+
+    // `a` is busy with the slow request.
+    let slow = nextshell::test::request().path("/slow").reply(&route);
+    let fast = async {
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+        bodies(&route, 2).await
+    };
+    let (slow, fast) = tokio::join!(slow, fast);
+    assert_eq!(slow.body(), "a");
+    assert_eq!(fast, ["b", "b"]);
+}
+
+#[tokio::test]
+async fn pool_passive_health() {
+    let _ = pretty_env_logger::try_init();
+    let route = nextshell::proxy::pool()
+        .upstream(format!("http://{}", closed()))
+        .upstream(format!("http://{}", named("b")))
+        .max_failures(1)
+        .forward();
+
+    let res = nextshell::test::request().reply(&route).await;
+    assert_eq!(res.status(), 502);
+    assert_eq!(bodies(&route, 3).await, ["b", "b", "b"]);
+}
+
+#[tokio::test]
+async fn pool_timeout() {
+    let _ = pretty_env_logger::try_init();
+    let route = nextshell::proxy::pool()
+        .upstream(
+            nextshell::proxy::backend(&format!("http://{}", named("a")))
+                .timeout(std::time::Duration::from_millis(100)),
+        )
+        .forward();
+
+    let res = nextshell::test::request().path("/slow").reply(&route).await;
+    assert_eq!(res.status(), 504);
+
+    let res = nextshell::test::request().reply(&route).await;
+    assert_eq!(res.body(), "a");
+}
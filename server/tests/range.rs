@@ -0,0 +1,161 @@
+#![deny(warnings)]
+use nextshell::Filter;
+
+#[tokio::test]
+async fn full_body() {
+    let _ = pretty_env_logger::try_init();
+
+    let route = nextshell::any()
+        .map(|| "hello range")
+        .with(nextshell::range());
+
+    let res = nextshell::test::request().reply(&route).await;
+    assert_eq!(res.status(), 200);
+    assert_eq!(res.headers()["accept-ranges"], "bytes");
+    assert_eq!(res.body(), "hello range");
+}
+
+#[tokio::test]
+async fn single_range() {
+    let _ = pretty_env_logger::try_init();
+
+    let route = nextshell::any()
+        .map(|| "hello range")
+        .with(nextshell::range());
+
+    let res = nextshell::test::request()
+        .header("range", "bytes=6-")
+        .reply(&route)
+        .await;
+    assert_eq!(res.status(), 206);
+    assert_eq!(res.headers()["content-range"], "bytes 6-10/11");
+    assert_eq!(res.headers()["content-length"], "5");
+    assert_eq!(res.headers()["content-type"], "text/plain; charset=utf-8");
+    assert_eq!(res.body(), "range");
+
+    let res = nextshell::test::request()
+        .header("range", "bytes=20-30")
+        .reply(&route)
+        .await;
+    assert_eq!(res.status(), 416);
+    assert_eq!(res.headers()["content-range"], "bytes */11");
+}
+
+#[tokio::test]
+async fn multiple_ranges() {
+    let _ = pretty_env_logger::try_init();
+
+    let route = nextshell::any()
+        .map(|| "hello range")
+        .with(nextshell::range());
+
+    let res = nextshell::test::request()
+        .header("range", "bytes=0-4,-5")
+        .reply(&route)
+        .await;
+    assert_eq!(res.status(), 206);
+    let content_type = res.headers()["content-type"].to_str().unwrap();
+    let boundary = content_type
+        .strip_prefix("multipart/byteranges; boundary=")
+        .expect("multipart content-type");
+    let expected = format!(
+        "\r\n--{b}\r\ncontent-type: text/plain; charset=utf-8\r\ncontent-range: bytes 0-4/11\r\n\r\nhello\
+         \r\n--{b}\r\ncontent-type: text/plain; charset=utf-8\r\ncontent-range: bytes 6-10/11\r\n\r\nrange\
+         \r\n--{b}--\r\n",
+        b = boundary
+    );
+    assert_eq!(res.body(), &expected);
+    assert_eq!(res.headers()["content-length"], expected.len().to_string());
+}
+
+#[tokio::test]
+async fn if_range() {
+    let _ = pretty_env_logger::try_init();
+
+    let route = nextshell::any()
+        .map(|| nextshell::reply::with_header("hello range", "etag", "\"v1\""))
+        .with(nextshell::range());
+
+    let res = nextshell::test::request()
+        .header("range", "bytes=0-4")
+        .header("if-range", "\"v1\"")
+        .reply(&route)
+        .await;
+    assert_eq!(res.status(), 206);
+    assert_eq!(res.headers()["etag"], "\"v1\"");
+    assert_eq!(res.body(), "hello");
+
+    let res = nextshell::test::request()
+        .header("range", "bytes=0-4")
+        .header("if-range", "\"v2\"")
+        .reply(&route)
+        .await;
+    assert_eq!(res.status(), 200);
+    assert_eq!(res.body(), "hello range");
+}
+
+#[tokio::test]
+async fn ranged_reply() {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    let _ = pretty_env_logger::try_init();
+
+    const BODY: &[u8] = b"hello range";
+    let opened = Arc::new(AtomicUsize::new(0));
+    let counter = opened.clone();
+    let route = nextshell::any()
+        .map(move || {
+            let counter = counter.clone();
+            let ranged = nextshell::reply::ranged(BODY.len() as u64, move |start, end| {
+                counter.fetch_add(1, Ordering::SeqCst);
+                let chunk = bytes::Bytes::from_static(&BODY[start as usize..end as usize]);
+                futures_util::stream::once(async move { Ok(chunk) })
+            });
+            nextshell::reply::with_header(ranged, "content-type", "text/plain")
+        })
+        .with(nextshell::range());
+
+    let res = nextshell::test::request().reply(&route).await;
+    assert_eq!(res.status(), 200);
+    assert_eq!(res.headers()["accept-ranges"], "bytes");
+    assert_eq!(res.headers()["content-length"], "11");
+    assert_eq!(res.body(), "hello range");
+    assert_eq!(opened.swap(0, Ordering::SeqCst), 1);
+
+    let res = nextshell::test::request()
+        .header("range", "bytes=6-")
+        .reply(&route)
+        .await;
+    assert_eq!(res.status(), 206);
+    assert_eq!(res.headers()["content-range"], "bytes 6-10/11");
+    assert_eq!(res.headers()["content-length"], "5");
+    assert_eq!(res.headers()["content-type"], "text/plain");
+    assert_eq!(res.body(), "range");
+    assert_eq!(opened.swap(0, Ordering::SeqCst), 1);
+
+    let res = nextshell::test::request()
+        .header("range", "bytes=0-4,-5")
+        .reply(&route)
+        .await;
+    assert_eq!(res.status(), 206);
+    let content_type = res.headers()["content-type"].to_str().unwrap();
+    let boundary = content_type
+        .strip_prefix("multipart/byteranges; boundary=")
+        .expect("multipart content-type");
+    let expected = format!(
+        "\r\n--{b}\r\ncontent-type: text/plain\r\ncontent-range: bytes 0-4/11\r\n\r\nhello\
+         \r\n--{b}\r\ncontent-type: text/plain\r\ncontent-range: bytes 6-10/11\r\n\r\nrange\
+         \r\n--{b}--\r\n",
+        b = boundary
+    );
+    assert_eq!(res.body(), &expected);
+    assert_eq!(opened.swap(0, Ordering::SeqCst), 2);
+
+    let res = nextshell::test::request()
+        .header("range", "bytes=20-30")
+        .reply(&route)
+        .await;
+    assert_eq!(res.status(), 416);
+    assert_eq!(opened.load(Ordering::SeqCst), 0);
+}
@@ -59,3 +59,88 @@ async fn redirect_permanent_uri() {
     assert_eq!(resp.status(), 308);
     assert_eq!(resp.headers()["location"], "/over-there");
 }
+
+#[tokio::test]
+async fn redirect_to_https() {
+    let route = nextshell::redirect::to_https();
+
+    let resp = nextshell::test::request()
+        .path("/foo/bar?baz=1")
+        .header("host", "example.com:8080")
+        .reply(&route)
+        .await;
+    assert_eq!(resp.status(), 301);
+    assert_eq!(
+        resp.headers()["location"],
+        "https://example.com/foo/bar?baz=1"
+    );
+
+    // already https, left to other routes
+    let app = route.or(nextshell::any().map(|| "app"));
+    let resp = nextshell::test::request()
+        .path("/foo")
+        .header("host", "example.com")
+        .header("x-forwarded-proto", "https")
+        .reply(&app)
+        .await;
+    assert_eq!(resp.status(), 200);
+    assert_eq!(resp.body(), "app");
+
+    let resp = nextshell::test::request()
+        .path("/foo")
+        .reply(&nextshell::redirect::to_https())
+        .await;
+    assert_eq!(resp.status(), 400);
+}
+
+#[tokio::test]
+async fn redirect_to_relative() {
+    let route = nextshell::redirect::to("new");
+    let resp = nextshell::test::request()
+        .path("/docs/old")
+        .reply(&route)
+        .await;
+    assert_eq!(resp.status(), 301);
+    assert_eq!(resp.headers()["location"], "/docs/new");
+
+    let route = nextshell::redirect::to("../v2/./users/");
+    let resp = nextshell::test::request()
+        .path("/api/v1/users")
+        .reply(&route)
+        .await;
+    assert_eq!(resp.headers()["location"], "/api/v2/users/");
+
+    let route = nextshell::redirect::to("https://example.com/new")
+        .status(nextshell::http::StatusCode::TEMPORARY_REDIRECT);
+    let resp = nextshell::test::request().path("/old").reply(&route).await;
+    assert_eq!(resp.status(), 307);
+    assert_eq!(resp.headers()["location"], "https://example.com/new");
+}
+
+#[tokio::test]
+async fn redirect_to_preserve_query() {
+    let route = nextshell::redirect::to("/new").preserve_query();
+    let resp = nextshell::test::request()
+        .path("/old?page=2&sort=asc")
+        .reply(&route)
+        .await;
+    assert_eq!(resp.headers()["location"], "/new?page=2&sort=asc");
+
+    let route = nextshell::redirect::to("/new?lang=en#top").preserve_query();
+    let resp = nextshell::test::request()
+        .path("/old?page=2")
+        .reply(&route)
+        .await;
+    assert_eq!(resp.headers()["location"], "/new?lang=en&page=2#top");
+
+    let resp = nextshell::test::request().path("/old").reply(&route).await;
+    assert_eq!(resp.headers()["location"], "/new?lang=en#top");
+
+    // the query is dropped by default
+    let route = nextshell::redirect::to("/new");
+    let resp = nextshell::test::request()
+        .path("/old?page=2")
+        .reply(&route)
+        .await;
+    assert_eq!(resp.headers()["location"], "/new");
+}
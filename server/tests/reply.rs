@@ -0,0 +1,257 @@
+#![deny(warnings)]
+use std::convert::Infallible;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use bytes::Bytes;
+use futures_util::StreamExt;
+use nextshell::http::HeaderMap;
+use nextshell::hyper::body::HttpBody;
+use nextshell::Filter;
+
+// A body from "another library", with a checksum trailer.
+struct Checksummed {
+    chunks: Vec<&'static str>,
+}
+
+impl HttpBody for Checksummed {
+    type Data = Bytes;
+    type Error = Infallible;
+
+    fn poll_data(
+        mut self: Pin<&mut Self>,
+        _: &mut Context<'_>,
+    ) -> Poll<Option<Result<Self::Data, Self::Error>>> {
+        if self.chunks.is_empty() {
+            return Poll::Ready(None);
+        }
+        Poll::Ready(Some(Ok(Bytes::from_static(
+            self.chunks.remove(0).as_bytes(),
+        ))))
+    }
+
+    fn poll_trailers(
+        self: Pin<&mut Self>,
+        _: &mut Context<'_>,
+    ) -> Poll<Result<Option<HeaderMap>, Self::Error>> {
+        let mut trailers = HeaderMap::new();
+        trailers.insert("x-checksum", "8d777f38".parse().unwrap());
+        Poll::Ready(Ok(Some(trailers)))
+    }
+}
+
+#[tokio::test]
+async fn body_with_known_size() {
+    let route = nextshell::any().map(|| nextshell::reply::body(String::from("sized")));
+
+    let res = nextshell::test::request().reply(&route).await;
+    assert_eq!(res.status(), 200);
+    assert_eq!(res.headers()["content-length"], "5");
+    assert_eq!(res.body(), "sized");
+}
+
+#[tokio::test]
+async fn body_with_trailers() {
+    let _ = pretty_env_logger::try_init();
+
+    let route = nextshell::any().map(|| {
+        nextshell::reply::body(Checksummed {
+            chunks: vec!["hello ", "trailers"],
+        })
+    });
+    let (addr, server) = nextshell::serve(route).bind_ephemeral(([127, 0, 0, 1], 0));
+    tokio::spawn(server);
+
+    let client = hyper::Client::builder()
+        .http2_only(true)
+        .build_http::<hyper::Body>();
+    let res = client
+        .get(format!("http://{}/", addr).parse().unwrap())
+        .await
+        .unwrap();
+    assert_eq!(res.status(), 200);
+    assert!(res.headers().get("content-length").is_none());
+
+    let mut body = res.into_body();
+    let data = hyper::body::to_bytes(&mut body).await.unwrap();
+    assert_eq!(data, "hello trailers");
+    let trailers = body.trailers().await.unwrap().unwrap();
+    assert_eq!(trailers["x-checksum"], "8d777f38");
+}
+
+#[tokio::test]
+async fn response_with_http_body() {
+    let route = nextshell::any().map(|| {
+        nextshell::http::Response::builder()
+            .header("content-type", "text/plain")
+            .body(http_body::Full::new(Bytes::from_static(b"full")))
+            .unwrap()
+    });
+
+    let res = nextshell::test::request().reply(&route).await;
+    assert_eq!(res.status(), 200);
+    assert_eq!(res.headers()["content-type"], "text/plain");
+    assert_eq!(res.body(), "full");
+}
+
+#[tokio::test]
+async fn response_with_boxed_body() {
+    let route = nextshell::any().map(|| {
+        let body = Checksummed {
+            chunks: vec!["boxed ", "body"],
+        };
+        nextshell::http::Response::new(body.boxed_unsync())
+    });
+
+    let res = nextshell::test::request().reply(&route).await;
+    assert_eq!(res.status(), 200);
+    assert_eq!(res.body(), "boxed body");
+}
+
+#[tokio::test]
+async fn trailers_over_h2() {
+    let _ = pretty_env_logger::try_init();
+
+    // Echoes the request's checksum trailer, as a trailer of the reply.
+    let route =
+        nextshell::body::stream_with_trailers().map(|mut body: nextshell::body::TrailingStream| {
+            let trailers = async move {
+                while body.next().await.is_some() {}
+                let mut trailers = HeaderMap::new();
+                if let Ok(Some(received)) = body.trailers().await {
+                    if let Some(checksum) = received.get("x-checksum") {
+                        trailers.insert("x-checksum", checksum.clone());
+                    }
+                }
+                trailers.insert("grpc-status", "0".parse().unwrap());
+                trailers
+            };
+            nextshell::reply::with_trailers("echoed", trailers)
+        });
+    let (addr, server) = nextshell::serve(route).bind_ephemeral(([127, 0, 0, 1], 0));
+    tokio::spawn(server);
+
+    let (mut tx, req_body) = hyper::Body::channel();
+    tokio::spawn(async move {
+        tx.send_data(Bytes::from_static(b"upload")).await.unwrap();
+        let mut trailers = HeaderMap::new();
+        trailers.insert("x-checksum", "8d777f38".parse().unwrap());
+        tx.send_trailers(trailers).await.unwrap();
+    });
+    let req = hyper::Request::post(format!("http://{}/", addr))
+        .body(req_body)
+        .unwrap();
+
+    let client = hyper::Client::builder()
+        .http2_only(true)
+        .build_http::<hyper::Body>();
+    let res = client.request(req).await.unwrap();
+    assert_eq!(res.status(), 200);
+
+    let mut body = res.into_body();
+    let data = hyper::body::to_bytes(&mut body).await.unwrap();
+    assert_eq!(data, "echoed");
+    let trailers = body.trailers().await.unwrap().unwrap();
+    assert_eq!(trailers["x-checksum"], "8d777f38");
+    assert_eq!(trailers["grpc-status"], "0");
+}
+
+#[tokio::test]
+async fn declared_trailers() {
+    let route = nextshell::any().map(|| {
+        nextshell::reply::with_trailers("body", async { HeaderMap::new() })
+            .declare("x-checksum")
+            .declare("grpc-status")
+    });
+
+    let res = nextshell::test::request().reply(&route).await;
+    assert_eq!(res.headers()["trailer"], "x-checksum, grpc-status");
+    assert_eq!(res.body(), "body");
+}
+
+#[tokio::test]
+async fn stream() {
+    let route = nextshell::path("csv")
+        .map(|| {
+            let chunks =
+                futures_util::stream::iter(vec![Ok::<_, Infallible>("id,name\n"), Ok("1,alice\n")]);
+            nextshell::reply::stream(chunks)
+                .content_type("text/csv")
+                .content_length(16)
+        })
+        .or(nextshell::path("broken").map(|| {
+            let chunks = futures_util::stream::iter(vec![
+                Ok(Bytes::from_static(b"partial")),
+                Err(std::io::Error::new(std::io::ErrorKind::Other, "oops")),
+            ]);
+            nextshell::reply::stream(chunks)
+        }));
+    let (addr, server) = nextshell::serve(route).bind_ephemeral(([127, 0, 0, 1], 0));
+    tokio::spawn(server);
+
+    let client = hyper::Client::new();
+    let res = client
+        .get(format!("http://{}/csv", addr).parse().unwrap())
+        .await
+        .unwrap();
+    assert_eq!(res.headers()["content-type"], "text/csv");
+    assert_eq!(res.headers()["content-length"], "16");
+    let body = hyper::body::to_bytes(res.into_body()).await.unwrap();
+    assert_eq!(body, "id,name\n1,alice\n");
+
+    // The error may abort the response before its head is even sent.
+    let res = client
+        .get(format!("http://{}/broken", addr).parse().unwrap())
+        .await;
+    if let Ok(res) = res {
+        assert_eq!(res.headers()["content-type"], "application/octet-stream");
+        assert!(hyper::body::to_bytes(res.into_body()).await.is_err());
+    }
+}
+
+#[tokio::test]
+async fn stream_backpressure() {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    let produced = Arc::new(AtomicUsize::new(0));
+    let counter = produced.clone();
+    let route = nextshell::any().map(move || {
+        let counter = counter.clone();
+        let chunks = futures_util::stream::repeat_with(move || {
+            counter.fetch_add(1, Ordering::SeqCst);
+            Ok::<_, Infallible>(Bytes::from(vec![b'x'; 64 * 1024]))
+        });
+        nextshell::reply::stream(chunks)
+    });
+    let (addr, server) = nextshell::serve(route).bind_ephemeral(([127, 0, 0, 1], 0));
+    tokio::spawn(server);
+
+    // Read the response head, then stop reading.
+    let res = hyper::Client::new()
+        .get(format!("http://{}/", addr).parse().unwrap())
+        .await
+        .unwrap();
+    tokio::time::sleep(std::time::Duration::from_millis(200)).await;
+
+    // Only as much as fits in the socket buffers was produced.
+    let before = produced.load(Ordering::SeqCst);
+    assert!(before < 1024, "produced {} chunks", before);
+    tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+    assert_eq!(produced.load(Ordering::SeqCst), before);
+    drop(res);
+}
+
+#[tokio::test]
+async fn response_with_custom_body() {
+    let route = nextshell::any().map(|| {
+        let body = Checksummed {
+            chunks: vec!["custom ", "body"],
+        };
+        nextshell::http::Response::new(body)
+    });
+
+    let res = nextshell::test::request().reply(&route).await;
+    assert_eq!(res.status(), 200);
+    assert_eq!(res.body(), "custom body");
+}
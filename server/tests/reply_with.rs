@@ -71,3 +71,194 @@ async fn default_header() {
 
     assert_eq!(resp.headers()["foo"], "sean", "doesn't replace header");
 }
+
+#[tokio::test]
+async fn cache_control() {
+    use std::time::Duration;
+
+    let cache = nextshell::reply::with::cache_control()
+        .public()
+        .max_age(Duration::from_secs(3600))
+        .s_maxage(Duration::from_secs(60))
+        .immutable();
+    let route = nextshell::any().map(nextshell::reply).with(cache);
+
+    let resp = nextshell::test::request().reply(&route).await;
+    assert_eq!(
+        resp.headers()["cache-control"],
+        "public, max-age=3600, s-maxage=60, immutable"
+    );
+
+    let no_store = nextshell::any()
+        .map(nextshell::reply)
+        .with(nextshell::reply::with::cache_control().private().no_store());
+
+    let resp = nextshell::test::request().reply(&no_store).await;
+    assert_eq!(resp.headers()["cache-control"], "private, no-store");
+}
+
+#[tokio::test]
+async fn expires() {
+    use std::time::Duration;
+
+    let route = nextshell::any()
+        .map(nextshell::reply)
+        .with(nextshell::reply::with::expires(Duration::from_secs(3600)));
+
+    let resp = nextshell::test::request().reply(&route).await;
+    let expires = resp.headers()["expires"].to_str().unwrap();
+    assert!(expires.ends_with(" GMT"), "http date: {}", expires);
+}
+
+#[tokio::test]
+async fn security_headers() {
+    use std::time::Duration;
+
+    use nextshell::reply::with::{ContentSecurityPolicy, FrameOptions};
+
+    let route = nextshell::any()
+        .map(nextshell::reply)
+        .with(nextshell::reply::with::security_headers());
+
+    let resp = nextshell::test::request().reply(&route).await;
+    assert_eq!(
+        resp.headers()["strict-transport-security"],
+        "max-age=31536000; includeSubDomains"
+    );
+    assert_eq!(resp.headers()["x-content-type-options"], "nosniff");
+    assert_eq!(resp.headers()["x-frame-options"], "DENY");
+    assert_eq!(
+        resp.headers()["referrer-policy"],
+        "strict-origin-when-cross-origin"
+    );
+    assert!(!resp.headers().contains_key("content-security-policy"));
+
+    let security = nextshell::reply::with::security_headers()
+        .hsts(Some(Duration::from_secs(60)))
+        .hsts_include_subdomains(false)
+        .hsts_preload(true)
+        .content_type_options(false)
+        .frame_options(Some(FrameOptions::SameOrigin))
+        .referrer_policy(None)
+        .content_security_policy(
+            ContentSecurityPolicy::new()
+                .default_src(["'self'"])
+                .img_src(["'self'", "data:"])
+                .upgrade_insecure_requests(),
+        );
+    let route = nextshell::any()
+        .map(|| nextshell::reply::with_header("", "x-frame-options", "ALLOWALL"))
+        .with(security);
+
+    let resp = nextshell::test::request().reply(&route).await;
+    assert_eq!(
+        resp.headers()["strict-transport-security"],
+        "max-age=60; preload"
+    );
+    assert!(!resp.headers().contains_key("x-content-type-options"));
+    assert!(!resp.headers().contains_key("referrer-policy"));
+    assert_eq!(
+        resp.headers()["x-frame-options"],
+        "ALLOWALL",
+        "doesn't replace header"
+    );
+    assert_eq!(
+        resp.headers()["content-security-policy"],
+        "default-src 'self'; img-src 'self' data:; upgrade-insecure-requests"
+    );
+}
+
+#[tokio::test]
+async fn deprecation() {
+    use std::time::{Duration, SystemTime};
+
+    let since = SystemTime::UNIX_EPOCH + Duration::from_secs(1_688_169_599);
+    let deprecation = nextshell::reply::with::deprecation(since, "https://example.com/migrate")
+        .sunset(SystemTime::UNIX_EPOCH + Duration::from_secs(1_735_689_600));
+
+    let route = nextshell::any()
+        .map(|| {
+            nextshell::reply::with_header("ok", "link", "<https://example.com/docs>; rel=\"help\"")
+        })
+        .with(&deprecation);
+
+    let resp = nextshell::test::request().reply(&route).await;
+    assert_eq!(resp.headers()["deprecation"], "@1688169599");
+    assert_eq!(resp.headers()["sunset"], "Wed, 01 Jan 2025 00:00:00 GMT");
+    let links = resp
+        .headers()
+        .get_all("link")
+        .iter()
+        .map(|link| link.to_str().unwrap())
+        .collect::<Vec<_>>();
+    assert_eq!(
+        links,
+        [
+            "<https://example.com/docs>; rel=\"help\"",
+            "<https://example.com/migrate>; rel=\"deprecation\"; type=\"text/html\"",
+        ]
+    );
+
+    nextshell::test::request().reply(&route).await;
+    assert_eq!(deprecation.uses(), 2);
+}
+
+#[tokio::test]
+async fn content_language() {
+    let route = nextshell::any()
+        .map(|| {
+            nextshell::reply::localized("en", "Hello")
+                .variant("fr", "Bonjour")
+                .variant("pt-BR", "Olá")
+        })
+        .with(nextshell::reply::with::content_language());
+
+    let negotiate = |accept_language: Option<&'static str>| {
+        let route = route.clone();
+        async move {
+            let mut req = nextshell::test::request();
+            if let Some(accept_language) = accept_language {
+                req = req.header("accept-language", accept_language);
+            }
+            let resp = req.reply(&route).await;
+            assert_eq!(resp.headers()["vary"], "accept-language");
+            (
+                resp.headers()["content-language"]
+                    .to_str()
+                    .unwrap()
+                    .to_owned(),
+                String::from_utf8(resp.body().to_vec()).unwrap(),
+            )
+        }
+    };
+
+    assert_eq!(negotiate(None).await, ("en".into(), "Hello".into()));
+    assert_eq!(
+        negotiate(Some("fr-CH, fr;q=0.9, en;q=0.8")).await,
+        ("fr".into(), "Bonjour".into())
+    );
+    assert_eq!(
+        negotiate(Some("de, pt;q=0.5, en;q=0.4")).await,
+        ("pt-BR".into(), "Olá".into())
+    );
+    assert_eq!(
+        negotiate(Some("*;q=0.5, en;q=0.1")).await,
+        ("fr".into(), "Bonjour".into())
+    );
+    // Nothing acceptable falls back to the first variant.
+    assert_eq!(
+        negotiate(Some("de, ja")).await,
+        ("en".into(), "Hello".into())
+    );
+
+    // Other replies are left alone.
+    let plain = nextshell::any()
+        .map(nextshell::reply)
+        .with(nextshell::reply::with::content_language());
+    let resp = nextshell::test::request()
+        .header("accept-language", "fr")
+        .reply(&plain)
+        .await;
+    assert!(!resp.headers().contains_key("vary"));
+    assert!(!resp.headers().contains_key("content-language"));
+}
@@ -0,0 +1,545 @@
+#![deny(warnings)]
+use std::time::Duration;
+
+use nextshell::Filter;
+
+#[tokio::test]
+async fn http2_settings() {
+    let _ = pretty_env_logger::try_init();
+
+    let route = nextshell::any().map(|| "hello h2");
+    let (addr, server) = nextshell::serve(route)
+        .http2_max_concurrent_streams(10)
+        .http2_initial_stream_window_size(1024 * 1024)
+        .http2_initial_connection_window_size(4 * 1024 * 1024)
+        .http2_keep_alive_interval(Duration::from_secs(10))
+        .http2_keep_alive_timeout(Duration::from_secs(5))
+        .bind_ephemeral(([127, 0, 0, 1], 0));
+    tokio::spawn(server);
+
+    let client = hyper::Client::builder()
+        .http2_only(true)
+        .build_http::<hyper::Body>();
+    let res = client
+        .get(format!("http://{}/", addr).parse().unwrap())
+        .await
+        .unwrap();
+    assert_eq!(res.version(), nextshell::http::Version::HTTP_2);
+    let body = hyper::body::to_bytes(res.into_body()).await.unwrap();
+    assert_eq!(body, "hello h2");
+}
+
+#[tokio::test]
+async fn bind_all() {
+    let _ = pretty_env_logger::try_init();
+
+    let route = nextshell::any().map(|| "hello");
+    let (addrs, server) =
+        nextshell::serve(route).bind_all([([127, 0, 0, 1], 0), ([127, 0, 0, 1], 0)]);
+    tokio::spawn(server);
+
+    assert_eq!(addrs.len(), 2);
+    assert_ne!(addrs[0], addrs[1]);
+
+    let client = hyper::Client::new();
+    for addr in addrs {
+        let res = client
+            .get(format!("http://{}/", addr).parse().unwrap())
+            .await
+            .unwrap();
+        let body = hyper::body::to_bytes(res.into_body()).await.unwrap();
+        assert_eq!(body, "hello");
+    }
+}
+
+#[tokio::test]
+async fn bind_all_dual_stack() {
+    let _ = pretty_env_logger::try_init();
+
+    let port = std::net::TcpListener::bind("0.0.0.0:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .port();
+    let route = nextshell::any().map(|| "hello");
+    let (addrs, server) = nextshell::serve(route).bind_all([
+        std::net::SocketAddr::from(([0, 0, 0, 0], port)),
+        std::net::SocketAddr::from(([0, 0, 0, 0, 0, 0, 0, 0], port)),
+    ]);
+    tokio::spawn(server);
+
+    assert_eq!(addrs[0].port(), port);
+    assert_eq!(addrs[1].port(), port);
+
+    let client = hyper::Client::new();
+    let res = client
+        .get(format!("http://127.0.0.1:{}/", port).parse().unwrap())
+        .await
+        .unwrap();
+    let body = hyper::body::to_bytes(res.into_body()).await.unwrap();
+    assert_eq!(body, "hello");
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn run_unix() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let _ = pretty_env_logger::try_init();
+
+    let dir = std::env::temp_dir().join(format!("nextshell-unix-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("server.sock");
+
+    // A socket file left behind by a dead listener is stale.
+    drop(std::os::unix::net::UnixListener::bind(&path).unwrap());
+    assert!(path.exists());
+
+    let route = nextshell::any().map(|| "hello unix");
+    let server = nextshell::serve(route)
+        .try_bind_unix(nextshell::UnixSocket::new(&path).mode(0o600))
+        .unwrap();
+    let (tx, rx) = tokio::sync::oneshot::channel::<()>();
+    let handle = tokio::spawn(async move {
+        tokio::select! {
+            _ = server => {}
+            _ = rx => {}
+        }
+    });
+
+    let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+    assert_eq!(mode & 0o777, 0o600);
+    assert_eq!(
+        std::fs::read_dir(&dir).unwrap().count(),
+        1,
+        "staging directory removed"
+    );
+
+    // A live socket is never replaced.
+    let err = nextshell::serve(nextshell::any().map(nextshell::reply))
+        .try_bind_unix(&*path.to_string_lossy())
+        .map(|_| ())
+        .unwrap_err();
+    assert!(err.to_string().contains("in use"), "{}", err);
+
+    let stream = tokio::net::UnixStream::connect(&path).await.unwrap();
+    let (mut sender, conn) = hyper::client::conn::handshake(stream).await.unwrap();
+    tokio::spawn(conn);
+    let req = hyper::Request::get("/").body(hyper::Body::empty()).unwrap();
+    let res = sender.send_request(req).await.unwrap();
+    let body = hyper::body::to_bytes(res.into_body()).await.unwrap();
+    assert_eq!(body, "hello unix");
+
+    tx.send(()).unwrap();
+    handle.await.unwrap();
+    assert!(!path.exists(), "socket removed on shutdown");
+
+    // Regular files are never removed.
+    std::fs::write(&path, "data").unwrap();
+    assert!(nextshell::serve(nextshell::any().map(nextshell::reply))
+        .try_bind_unix(path.clone())
+        .is_err());
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[cfg(target_os = "linux")]
+#[tokio::test]
+async fn tcp_options() {
+    let _ = pretty_env_logger::try_init();
+
+    let server = || {
+        nextshell::serve(nextshell::any().map(|| "tuned"))
+            .tcp_nodelay(false)
+            .tcp_keepalive(Some(Duration::from_secs(60)))
+            .tcp_keepalive_interval(Some(Duration::from_secs(5)))
+            .tcp_keepalive_retries(Some(3))
+            .tcp_reuse_port(true)
+            .backlog(64)
+    };
+    let (addr, first) = server().bind_ephemeral(([127, 0, 0, 1], 0));
+    tokio::spawn(first);
+    // SO_REUSEPORT lets a second listener share the port.
+    let (second_addr, second) = server().try_bind_ephemeral(addr).unwrap();
+    assert_eq!(addr, second_addr);
+    tokio::spawn(second);
+
+    // Without it, the port stays exclusive.
+    assert!(nextshell::serve(nextshell::any().map(nextshell::reply))
+        .try_bind_ephemeral(addr)
+        .is_err());
+
+    let res = hyper::Client::new()
+        .get(format!("http://{}/", addr).parse().unwrap())
+        .await
+        .unwrap();
+    let body = hyper::body::to_bytes(res.into_body()).await.unwrap();
+    assert_eq!(body, "tuned");
+}
+
+async fn connect(addr: std::net::SocketAddr) -> hyper::client::conn::SendRequest<hyper::Body> {
+    let stream = tokio::net::TcpStream::connect(addr).await.unwrap();
+    let (sender, conn) = hyper::client::conn::handshake(stream).await.unwrap();
+    tokio::spawn(async move {
+        let _ = conn.await;
+    });
+    sender
+}
+
+async fn get(
+    sender: &mut hyper::client::conn::SendRequest<hyper::Body>,
+) -> Result<nextshell::http::StatusCode, hyper::Error> {
+    let req = hyper::Request::get("/").body(hyper::Body::empty()).unwrap();
+    sender.send_request(req).await.map(|res| res.status())
+}
+
+#[tokio::test]
+async fn max_connections() {
+    let _ = pretty_env_logger::try_init();
+
+    let (addr, server) = nextshell::serve(nextshell::any().map(nextshell::reply))
+        .max_connections(1)
+        .connection_limit_503(true)
+        .bind_ephemeral(([127, 0, 0, 1], 0));
+    tokio::spawn(server);
+
+    let mut first = connect(addr).await;
+    assert_eq!(get(&mut first).await.unwrap(), 200);
+
+    let mut second = connect(addr).await;
+    assert_eq!(get(&mut second).await.unwrap(), 503);
+
+    // Closing the first connection frees its slot.
+    drop(first);
+    let mut status = None;
+    for _ in 0..50 {
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        if let Ok(s) = get(&mut connect(addr).await).await {
+            status = Some(s);
+            if s == 200 {
+                break;
+            }
+        }
+    }
+    assert_eq!(status, Some(nextshell::http::StatusCode::OK));
+}
+
+#[tokio::test]
+async fn max_connections_per_ip() {
+    let _ = pretty_env_logger::try_init();
+
+    let (addr, server) = nextshell::serve(nextshell::any().map(nextshell::reply))
+        .max_connections_per_ip(1)
+        .bind_ephemeral(([127, 0, 0, 1], 0));
+    tokio::spawn(server);
+
+    let mut first = connect(addr).await;
+    assert_eq!(get(&mut first).await.unwrap(), 200);
+
+    // Excess connections are closed without a response.
+    let mut second = connect(addr).await;
+    assert!(get(&mut second).await.is_err());
+
+    assert_eq!(get(&mut first).await.unwrap(), 200);
+}
+
+#[tokio::test]
+async fn start_handle() {
+    let _ = pretty_env_logger::try_init();
+
+    let handle = nextshell::serve(nextshell::any().map(|| "handled"))
+        .start([([127, 0, 0, 1], 0), ([127, 0, 0, 1], 0)]);
+    handle.ready().await;
+    assert_eq!(handle.addrs().len(), 2);
+    assert_eq!(handle.local_addr(), Some(handle.addrs()[0]));
+
+    let mut sender = connect(handle.addrs()[1]).await;
+    assert_eq!(get(&mut sender).await.unwrap(), 200);
+    assert_eq!(get(&mut sender).await.unwrap(), 200);
+    assert_eq!(handle.active_connections(), 1);
+    assert_eq!(handle.total_connections(), 1);
+    assert_eq!(handle.total_requests(), 2);
+
+    handle.shutdown();
+    tokio::time::timeout(Duration::from_secs(5), handle.stopped())
+        .await
+        .expect("server stops after shutdown");
+}
+
+#[tokio::test]
+async fn start_without_addrs() {
+    let handle = nextshell::serve(nextshell::any().map(nextshell::reply))
+        .start(Vec::<std::net::SocketAddr>::new());
+    handle.ready().await;
+    assert!(handle.addrs().is_empty());
+    assert_eq!(handle.local_addr(), None);
+
+    handle.shutdown();
+    handle.stopped().await;
+}
+
+#[tokio::test]
+async fn start_dual_stack() {
+    let port = std::net::TcpListener::bind("0.0.0.0:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .port();
+    let handle = nextshell::serve(nextshell::any().map(|| "hello")).start([
+        std::net::SocketAddr::from(([0, 0, 0, 0], port)),
+        std::net::SocketAddr::from(([0, 0, 0, 0, 0, 0, 0, 0], port)),
+    ]);
+    handle.ready().await;
+    assert_eq!(handle.addrs().len(), 2);
+
+    let client = hyper::Client::new();
+    let res = client
+        .get(format!("http://127.0.0.1:{}/", port).parse().unwrap())
+        .await
+        .unwrap();
+    let body = hyper::body::to_bytes(res.into_body()).await.unwrap();
+    assert_eq!(body, "hello");
+
+    handle.shutdown();
+    handle.stopped().await;
+}
+
+#[tokio::test]
+async fn default_recover() {
+    let _ = pretty_env_logger::try_init();
+
+    let route = nextshell::path("hello").map(|| "world");
+    let (addr, server) = nextshell::serve(route)
+        .recover(nextshell::reject::problem_json())
+        .bind_ephemeral(([127, 0, 0, 1], 0));
+    tokio::spawn(server);
+
+    let mut sender = connect(addr).await;
+    let req = hyper::Request::get("/nope")
+        .body(hyper::Body::empty())
+        .unwrap();
+    let res = sender.send_request(req).await.unwrap();
+    assert_eq!(res.status(), 404);
+    assert_eq!(res.headers()["content-type"], "application/problem+json");
+
+    let req = hyper::Request::get("/hello")
+        .body(hyper::Body::empty())
+        .unwrap();
+    let res = sender.send_request(req).await.unwrap();
+    assert_eq!(res.status(), 200);
+}
+
+// Writes `head` on a new connection, then reads until the server closes it.
+async fn raw(addr: std::net::SocketAddr, head: &[u8]) -> String {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    let mut stream = tokio::net::TcpStream::connect(addr).await.unwrap();
+    stream.write_all(head).await.unwrap();
+    let mut buf = Vec::new();
+    tokio::time::timeout(Duration::from_secs(5), stream.read_to_end(&mut buf))
+        .await
+        .expect("connection closed")
+        .unwrap();
+    String::from_utf8(buf).unwrap()
+}
+
+#[tokio::test]
+async fn header_read_timeout() {
+    let _ = pretty_env_logger::try_init();
+
+    let (addr, server) = nextshell::serve(nextshell::any().map(|| "fast"))
+        .header_read_timeout(Duration::from_millis(100))
+        .bind_ephemeral(([127, 0, 0, 1], 0));
+    tokio::spawn(server);
+
+    let res = raw(addr, b"GET / HTTP/1.1\r\nhost: localhost\r\n").await;
+    assert!(
+        res.starts_with("HTTP/1.1 408 Request Timeout\r\n"),
+        "{}",
+        res
+    );
+
+    // Quick clients are served as usual, on the same connection.
+    let mut sender = connect(addr).await;
+    assert_eq!(get(&mut sender).await.unwrap(), 200);
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    assert_eq!(get(&mut sender).await.unwrap(), 200);
+}
+
+#[tokio::test]
+async fn header_read_timeout_idle_h2() {
+    let _ = pretty_env_logger::try_init();
+
+    let (addr, server) = nextshell::serve(nextshell::any().map(|| "fast"))
+        .header_read_timeout(Duration::from_millis(100))
+        .bind_ephemeral(([127, 0, 0, 1], 0));
+    tokio::spawn(server);
+
+    let stream = tokio::net::TcpStream::connect(addr).await.unwrap();
+    // Nothing to read yet when the server first polls the connection.
+    tokio::time::sleep(Duration::from_millis(20)).await;
+    let (mut sender, conn) = hyper::client::conn::Builder::new()
+        .http2_only(true)
+        .handshake(stream)
+        .await
+        .unwrap();
+    let conn = tokio::spawn(conn);
+
+    // Frames keep coming in while no request is open, settings
+    // acknowledgements at least, the connection outlives the timeout all
+    // the same.
+    assert_eq!(get(&mut sender).await.unwrap(), 200);
+    tokio::time::sleep(Duration::from_millis(300)).await;
+    assert!(!conn.is_finished());
+    assert_eq!(get(&mut sender).await.unwrap(), 200);
+}
+
+#[tokio::test]
+async fn body_read_timeout() {
+    let _ = pretty_env_logger::try_init();
+
+    let route = nextshell::body::bytes().map(|body: bytes::Bytes| body.len().to_string());
+    let (addr, server) = nextshell::serve(route)
+        .body_read_timeout(Duration::from_millis(100))
+        .bind_ephemeral(([127, 0, 0, 1], 0));
+    tokio::spawn(server);
+
+    let res = raw(
+        addr,
+        b"POST / HTTP/1.1\r\nhost: localhost\r\ncontent-length: 10\r\n\r\nabc",
+    )
+    .await;
+    assert!(
+        res.starts_with("HTTP/1.1 408 Request Timeout\r\n"),
+        "{}",
+        res
+    );
+    assert!(res.contains("connection: close\r\n"), "{}", res);
+
+    let res = raw(
+        addr,
+        b"POST / HTTP/1.1\r\nhost: localhost\r\ncontent-length: 3\r\nconnection: close\r\n\r\nabc",
+    )
+    .await;
+    assert!(res.starts_with("HTTP/1.1 200 OK\r\n"), "{}", res);
+    assert!(res.ends_with("\r\n\r\n3"), "{}", res);
+}
+
+#[tokio::test]
+async fn on_response() {
+    use std::sync::{Arc, Mutex};
+
+    let _ = pretty_env_logger::try_init();
+
+    let seen = Arc::new(Mutex::new(Vec::new()));
+    let log = seen.clone();
+    let route = nextshell::path("hello").map(|| "world");
+    let (addr, server) = nextshell::serve(route)
+        .on_response(
+            move |info: nextshell::RequestInfo, res: nextshell::reply::Response| {
+                log.lock().unwrap().push(format!(
+                    "{} {} {} {}",
+                    info.method(),
+                    info.uri(),
+                    info.remote_addr().unwrap().ip(),
+                    res.status().as_u16()
+                ));
+                async move { res }
+            },
+        )
+        .on_response(|_, mut res: nextshell::reply::Response| async move {
+            res.headers_mut()
+                .insert("x-frame-options", "DENY".parse().unwrap());
+            res
+        })
+        .bind_ephemeral(([127, 0, 0, 1], 0));
+    tokio::spawn(server);
+
+    let mut sender = connect(addr).await;
+    let req = hyper::Request::get("/hello")
+        .body(hyper::Body::empty())
+        .unwrap();
+    let res = sender.send_request(req).await.unwrap();
+    assert_eq!(res.status(), 200);
+    assert_eq!(res.headers()["x-frame-options"], "DENY");
+
+    let req = hyper::Request::post("/nope")
+        .body(hyper::Body::empty())
+        .unwrap();
+    let res = sender.send_request(req).await.unwrap();
+    assert_eq!(res.status(), 404);
+    assert_eq!(res.headers()["x-frame-options"], "DENY");
+
+    assert_eq!(
+        *seen.lock().unwrap(),
+        ["GET /hello 127.0.0.1 200", "POST /nope 127.0.0.1 404"]
+    );
+}
+
+#[tokio::test]
+async fn header_limits() {
+    let _ = pretty_env_logger::try_init();
+
+    let (addr, server) = nextshell::serve(nextshell::any().map(nextshell::reply))
+        .max_header_count(4)
+        .max_header_size(64)
+        .max_header_bytes(128)
+        .bind_ephemeral(([127, 0, 0, 1], 0));
+    tokio::spawn(server);
+
+    let send = |headers: Vec<(&'static str, String)>| async move {
+        let mut sender = connect(addr).await;
+        let mut req = hyper::Request::get("/");
+        for (name, value) in headers {
+            req = req.header(name, value);
+        }
+        let res = sender
+            .send_request(req.body(hyper::Body::empty()).unwrap())
+            .await
+            .unwrap();
+        res.status()
+    };
+
+    assert_eq!(send(vec![("a", "1".into())]).await, 200);
+    let many = ["a", "b", "c", "d", "e"]
+        .iter()
+        .map(|name| (*name, "1".to_owned()))
+        .collect();
+    assert_eq!(send(many).await, 431);
+    assert_eq!(send(vec![("a", "x".repeat(64))]).await, 431);
+    let large = vec![
+        ("a", "x".repeat(50)),
+        ("b", "x".repeat(50)),
+        ("c", "x".repeat(50)),
+    ];
+    assert_eq!(send(large).await, 431);
+}
+
+#[tokio::test]
+async fn header_bytes_cap_read_buffer() {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    let _ = pretty_env_logger::try_init();
+
+    let (addr, server) = nextshell::serve(nextshell::any().map(nextshell::reply))
+        .max_header_bytes(128)
+        .bind_ephemeral(([127, 0, 0, 1], 0));
+    tokio::spawn(server);
+
+    // Way over the cap, but far below hyper's own limit.
+    let mut stream = tokio::net::TcpStream::connect(addr).await.unwrap();
+    let head = format!(
+        "GET / HTTP/1.1\r\nhost: x\r\na: {}\r\n\r\n",
+        "x".repeat(32 * 1024)
+    );
+    stream.write_all(head.as_bytes()).await.unwrap();
+
+    // Refused by hyper before the head is complete, without a body.
+    let mut res = Vec::new();
+    tokio::time::timeout(Duration::from_secs(5), stream.read_to_end(&mut res))
+        .await
+        .expect("connection closed")
+        .unwrap();
+    let res = String::from_utf8_lossy(&res);
+    assert!(res.starts_with("HTTP/1.1 431 "), "{}", res);
+    assert!(!res.contains("too large"), "{}", res);
+}
@@ -0,0 +1,64 @@
+#![deny(warnings)]
+use nextshell::Filter;
+
+fn route() -> impl Filter<Extract = (impl nextshell::Reply,), Error = nextshell::Rejection> + Clone
+{
+    nextshell::path!("todos" / u32).map(|id| {
+        nextshell::reply::with_header(
+            nextshell::reply::json(&serde_json::json!({ "id": id, "done": false })),
+            "x-request-id",
+            "varies",
+        )
+    })
+}
+
+#[tokio::test]
+async fn stored() {
+    let res = nextshell::test::request()
+        .path("/todos/7")
+        .reply(&route())
+        .await;
+
+    nextshell::test::snapshot(&res)
+        .ignore_header("x-request-id")
+        .assert("todo");
+}
+
+#[tokio::test]
+async fn render() {
+    let res = nextshell::test::request()
+        .path("/todos/7")
+        .reply(&route())
+        .await;
+
+    assert_eq!(
+        nextshell::test::snapshot(&res).render(),
+        "HTTP 200 OK\n\
+         content-type: application/json\n\
+         x-request-id: varies\n\
+         \n\
+         {\n  \"done\": false,\n  \"id\": 7\n}\n"
+    );
+}
+
+#[tokio::test]
+#[should_panic(expected = "doesn't match")]
+async fn mismatch() {
+    let dir = std::env::temp_dir().join(format!("nextshell-snapshots-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(dir.join("todo.snap"), "HTTP 404 Not Found\n\n").unwrap();
+
+    let res = nextshell::test::request()
+        .path("/todos/7")
+        .reply(&route())
+        .await;
+
+    nextshell::test::snapshot(&res).dir(&dir).assert("todo");
+}
+
+#[tokio::test]
+#[should_panic(expected = "invalid snapshot name")]
+async fn rejects_parent_dirs() {
+    let res = nextshell::test::request().reply(&route()).await;
+    nextshell::test::assert_snapshot("../escape", &res);
+}
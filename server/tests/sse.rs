@@ -0,0 +1,200 @@
+#![deny(warnings)]
+use futures_util::{StreamExt, TryStreamExt};
+use nextshell::sse::{Broadcaster, Event};
+use nextshell::Filter;
+
+fn data(event: Event) -> String {
+    event.to_string()
+}
+
+#[tokio::test]
+async fn broadcaster_fans_out() {
+    let _ = pretty_env_logger::try_init();
+
+    let broadcaster = Broadcaster::new(8);
+    assert_eq!(broadcaster.send(Event::default().data("nobody")), 0);
+
+    let mut a = Box::pin(broadcaster.subscribe());
+    let mut b = Box::pin(broadcaster.subscribe());
+    assert_eq!(broadcaster.subscribers(), 2);
+
+    assert_eq!(broadcaster.send(Event::default().data("hello")), 2);
+    assert_eq!(data(a.next().await.unwrap().unwrap()), "data:hello\n\n");
+    assert_eq!(data(b.next().await.unwrap().unwrap()), "data:hello\n\n");
+
+    drop(broadcaster);
+    assert!(a.next().await.is_none());
+}
+
+#[tokio::test]
+async fn broadcaster_lagging() {
+    let _ = pretty_env_logger::try_init();
+
+    let broadcaster = Broadcaster::new(2);
+    let mut skipping = Box::pin(broadcaster.subscribe());
+    let mut disconnected = Box::pin(broadcaster.clone().disconnect_lagging(true).subscribe());
+
+    for i in 0..4 {
+        broadcaster.send(Event::default().data(i.to_string()));
+    }
+
+    // Only the last `capacity` events are kept for a lagging client.
+    assert_eq!(data(skipping.next().await.unwrap().unwrap()), "data:2\n\n");
+    assert_eq!(data(skipping.next().await.unwrap().unwrap()), "data:3\n\n");
+
+    assert!(disconnected.next().await.is_none());
+}
+
+#[tokio::test]
+async fn broadcaster_reply() {
+    let _ = pretty_env_logger::try_init();
+
+    let broadcaster = Broadcaster::new(8);
+    let sse = broadcaster.clone();
+    let route = nextshell::any().map(move || nextshell::sse::reply(sse.subscribe()));
+
+    let (addr, server) = nextshell::serve(route).bind_ephemeral(([127, 0, 0, 1], 0));
+    tokio::spawn(server);
+
+    let res = tokio::spawn(hyper::Client::new().get(format!("http://{}/", addr).parse().unwrap()));
+    while broadcaster.subscribers() == 0 {
+        tokio::task::yield_now().await;
+    }
+    broadcaster.send(Event::default().event("tick").data("1"));
+
+    let res = res.await.unwrap().unwrap();
+    assert_eq!(res.headers()["content-type"], "text/event-stream");
+    let chunk = res.into_body().next().await.unwrap().unwrap();
+    assert_eq!(chunk, "event:tick\ndata:1\n\n");
+}
+
+#[tokio::test]
+async fn json_stream() {
+    use serde_derive::Serialize;
+    use std::convert::Infallible;
+
+    #[derive(Serialize)]
+    enum Status {
+        #[serde(rename = "ready")]
+        Ready,
+        #[serde(rename = "progress")]
+        Progress(u8, u8),
+    }
+
+    #[derive(Serialize)]
+    struct Point {
+        x: i32,
+    }
+
+    let events = nextshell::sse::json_stream(futures_util::stream::iter(vec![
+        Ok::<_, Infallible>(Status::Ready),
+        Ok(Status::Progress(1, 3)),
+    ]));
+    let out: Vec<String> = events
+        .map_ok(|event| event.to_string())
+        .try_collect()
+        .await
+        .unwrap();
+    assert_eq!(
+        out,
+        [
+            "event:ready\ndata:null\n\n",
+            "event:progress\ndata:[1,3]\n\n"
+        ]
+    );
+
+    // Anything but an enum is sent as-is, without an event name.
+    let points =
+        nextshell::sse::json_stream(futures_util::stream::iter(vec![Ok::<_, Infallible>(
+            Point { x: 1 },
+        )]));
+    let out: Vec<String> = points
+        .map_ok(|event| event.to_string())
+        .try_collect()
+        .await
+        .unwrap();
+    assert_eq!(out, ["data:{\"x\":1}\n\n"]);
+}
+
+#[tokio::test]
+async fn test_client_parses_events() {
+    let _ = pretty_env_logger::try_init();
+
+    let route = nextshell::any().map(|| {
+        let events = vec![
+            Ok::<_, std::convert::Infallible>(Event::default().data("first")),
+            Ok(Event::default()
+                .id("2")
+                .event("chat")
+                .data("multi\nline")
+                .retry(std::time::Duration::from_millis(1500))),
+            Ok(Event::default().comment("ignored")),
+            Ok(Event::default().json_data([1, 2, 3]).unwrap()),
+        ];
+        nextshell::sse::reply(futures_util::stream::iter(events))
+    });
+
+    let mut client = nextshell::test::request().sse(&route).await.unwrap();
+
+    assert_eq!(client.recv().await.unwrap().data(), "first");
+
+    let event = client.recv().await.unwrap();
+    assert_eq!(event.id(), Some("2"));
+    assert_eq!(event.event(), Some("chat"));
+    assert_eq!(event.data(), "multi\nline");
+    assert_eq!(event.retry(), Some(std::time::Duration::from_millis(1500)));
+
+    let event = client.recv().await.unwrap();
+    assert_eq!(event.json::<Vec<u32>>().unwrap(), vec![1, 2, 3]);
+
+    client.recv_closed().await.unwrap();
+}
+
+#[tokio::test]
+async fn test_client_skips_events_without_data() {
+    let route = nextshell::any().map(|| {
+        let events = vec![
+            Ok::<_, std::convert::Infallible>(Event::default().id("7")),
+            Ok(Event::default().retry(std::time::Duration::from_secs(1))),
+            Ok(Event::default().data("after")),
+        ];
+        nextshell::sse::reply(futures_util::stream::iter(events))
+    });
+
+    let mut client = nextshell::test::request().sse(&route).await.unwrap();
+    assert_eq!(client.last_event_id(), None);
+
+    let event = client.recv().await.unwrap();
+    assert_eq!(event.data(), "after");
+    assert_eq!(event.id(), None);
+    assert_eq!(client.last_event_id(), Some("7"));
+
+    client.recv_closed().await.unwrap();
+}
+
+#[tokio::test]
+async fn test_client_timeout() {
+    let route = nextshell::any().map(|| {
+        nextshell::sse::reply(futures_util::stream::pending::<
+            Result<Event, std::convert::Infallible>,
+        >())
+    });
+
+    let client = nextshell::test::request().sse(&route).await.unwrap();
+    let mut client = client.timeout(std::time::Duration::from_millis(10));
+
+    let err = client.next().await.unwrap().unwrap_err();
+    assert!(err.to_string().contains("no event received"), "{}", err);
+}
+
+#[tokio::test]
+async fn test_client_requires_event_stream() {
+    let route = nextshell::any().map(|| "not events");
+
+    let err = nextshell::test::request().sse(&route).await.unwrap_err();
+    assert!(
+        err.to_string().contains("expected an event stream"),
+        "{}",
+        err
+    );
+}
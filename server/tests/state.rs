@@ -0,0 +1,53 @@
+#![deny(warnings)]
+use nextshell::Filter;
+
+#[derive(Clone, Debug, PartialEq)]
+struct Config(&'static str);
+
+#[derive(Clone, Debug, PartialEq)]
+struct Count(u64);
+
+#[tokio::test]
+async fn extract() {
+    let state = nextshell::state::<Config>().and(nextshell::state::<Count>());
+
+    let extracted = nextshell::test::request()
+        .state(Config("db"))
+        .state(Count(3))
+        .filter(&state)
+        .await
+        .unwrap();
+
+    assert_eq!(extracted, (Config("db"), Count(3)));
+}
+
+#[tokio::test]
+async fn missing() {
+    let route = nextshell::state().map(|c: Config| c.0);
+
+    let res = nextshell::test::request()
+        .state(Count(3))
+        .reply(&route)
+        .await;
+
+    assert_eq!(res.status(), 500);
+    assert_eq!(res.body(), "Missing server state");
+}
+
+#[tokio::test]
+async fn server_with_state() {
+    let route = nextshell::state().map(|c: Config| c.0);
+    let (addr, server) = nextshell::serve(route)
+        .with_state(Config("from server"))
+        .bind_ephemeral(([127, 0, 0, 1], 0));
+    tokio::spawn(server);
+
+    let client = hyper::Client::new();
+    let res = client
+        .get(format!("http://{}/", addr).parse().unwrap())
+        .await
+        .unwrap();
+    assert_eq!(res.status(), 200);
+    let body = hyper::body::to_bytes(res.into_body()).await.unwrap();
+    assert_eq!(body, "from server");
+}
@@ -0,0 +1,135 @@
+#![deny(warnings)]
+#![cfg(feature = "tls")]
+use std::convert::TryFrom;
+use std::sync::Arc;
+
+use nextshell::tls::TlsInfo;
+use nextshell::{Filter, TlsVersion};
+use tokio_rustls::rustls::client::danger::{
+    HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier,
+};
+use tokio_rustls::rustls::crypto::ring;
+use tokio_rustls::rustls::pki_types::{CertificateDer, ServerName, UnixTime};
+use tokio_rustls::rustls::{ClientConfig, DigitallySignedStruct, Error, SignatureScheme};
+
+// The example certificate isn't signed by a known root.
+#[derive(Debug)]
+struct AcceptAny;
+
+impl ServerCertVerifier for AcceptAny {
+    fn verify_server_cert(
+        &self,
+        _: &CertificateDer<'_>,
+        _: &[CertificateDer<'_>],
+        _: &ServerName<'_>,
+        _: &[u8],
+        _: UnixTime,
+    ) -> Result<ServerCertVerified, Error> {
+        Ok(ServerCertVerified::assertion())
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        _: &[u8],
+        _: &CertificateDer<'_>,
+        _: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, Error> {
+        Ok(HandshakeSignatureValid::assertion())
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        _: &[u8],
+        _: &CertificateDer<'_>,
+        _: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, Error> {
+        Ok(HandshakeSignatureValid::assertion())
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        ring::default_provider()
+            .signature_verification_algorithms
+            .supported_schemes()
+    }
+}
+
+async fn get(addr: std::net::SocketAddr, alpn: &[u8]) -> String {
+    let mut config = ClientConfig::builder()
+        .dangerous()
+        .with_custom_certificate_verifier(Arc::new(AcceptAny))
+        .with_no_client_auth();
+    config.alpn_protocols = vec![alpn.to_vec()];
+    let connector = tokio_rustls::TlsConnector::from(Arc::new(config));
+
+    let tcp = tokio::net::TcpStream::connect(addr).await.unwrap();
+    let stream = connector
+        .connect(ServerName::try_from("localhost").unwrap(), tcp)
+        .await
+        .unwrap();
+    let (mut sender, conn) = hyper::client::conn::Builder::new()
+        .http2_only(alpn == b"h2")
+        .handshake(stream)
+        .await
+        .unwrap();
+    tokio::spawn(async move {
+        let _ = conn.await;
+    });
+
+    let req = hyper::Request::get("https://localhost/")
+        .body(hyper::Body::empty())
+        .unwrap();
+    let res = sender.send_request(req).await.unwrap();
+    let body = hyper::body::to_bytes(res.into_body()).await.unwrap();
+    String::from_utf8(body.to_vec()).unwrap()
+}
+
+#[tokio::test]
+async fn info() {
+    let _ = pretty_env_logger::try_init();
+
+    let route = nextshell::tls::info().map(|info: Option<TlsInfo>| {
+        let info = info.expect("served over TLS");
+        assert_eq!(info.version(), TlsVersion::Tls13);
+        assert!(info.cipher_suite().starts_with("TLS13_"));
+        format!(
+            "{} {}",
+            info.server_name().unwrap_or("-"),
+            String::from_utf8_lossy(info.alpn_protocol().unwrap_or(b"-")),
+        )
+    });
+    let (addr, server) = nextshell::serve(route)
+        .tls()
+        .cert_path("examples/tls/cert.pem")
+        .key_path("examples/tls/key.rsa")
+        .bind_ephemeral(([127, 0, 0, 1], 0));
+    tokio::spawn(server);
+
+    assert_eq!(get(addr, b"http/1.1").await, "localhost http/1.1");
+    assert_eq!(get(addr, b"h2").await, "localhost h2");
+}
+
+#[tokio::test]
+async fn info_without_tls() {
+    let route = nextshell::tls::info().map(|info: Option<TlsInfo>| info.is_some().to_string());
+    let res = nextshell::test::request().reply(&route).await;
+    assert_eq!(res.body(), "false");
+}
+
+#[tokio::test]
+async fn bind_all() {
+    let _ = pretty_env_logger::try_init();
+
+    let route = nextshell::any().map(|| "hello");
+    let (addrs, server) = nextshell::serve(route)
+        .tls()
+        .cert_path("examples/tls/cert.pem")
+        .key_path("examples/tls/key.rsa")
+        .bind_all([([127, 0, 0, 1], 0), ([127, 0, 0, 1], 0)]);
+    tokio::spawn(server);
+
+    assert_eq!(addrs.len(), 2);
+    assert_ne!(addrs[0], addrs[1]);
+    for addr in addrs {
+        assert_eq!(get(addr, b"http/1.1").await, "hello");
+    }
+}
@@ -46,3 +46,60 @@ async fn uses_tracing() {
     let resp = req.reply(&ok);
     assert_eq!(resp.await.status(), 200);
 }
+
+#[tokio::test]
+async fn server_timing() {
+    use std::time::Duration;
+
+    use nextshell::trace::ServerTimings;
+
+    let route = nextshell::path("report")
+        .and(nextshell::trace::timings())
+        .map(|timings: ServerTimings| {
+            drop(timings.start("db"));
+            timings.record_with_description(
+                "cache miss",
+                "missed \"hot\" key",
+                Duration::from_millis(2),
+            );
+            "report"
+        })
+        .or(nextshell::header::<u32>("x-id")
+            .instrument("id")
+            .map(|id: u32| id.to_string()))
+        .with(nextshell::trace::server_timing());
+
+    let res = nextshell::test::request()
+        .path("/report")
+        .reply(&route)
+        .await;
+    let header = res.headers()["server-timing"].to_str().unwrap();
+    let metrics = header.split(", ").collect::<Vec<_>>();
+    assert_eq!(metrics.len(), 3, "{}", header);
+    assert!(metrics[0].starts_with("db;dur="), "{}", header);
+    assert_eq!(
+        metrics[1],
+        "cache_miss;desc=\"missed \\\"hot\\\" key\";dur=2.0"
+    );
+    assert!(metrics[2].starts_with("total;dur="), "{}", header);
+
+    let res = nextshell::test::request()
+        .path("/other")
+        .header("x-id", "7")
+        .reply(&route)
+        .await;
+    let header = res.headers()["server-timing"].to_str().unwrap();
+    let names = header
+        .split(", ")
+        .map(|metric| metric.split(';').next().unwrap())
+        .collect::<Vec<_>>();
+    assert_eq!(names, ["id", "total"]);
+
+    // Rejections are left alone.
+    let res = nextshell::test::request()
+        .path("/other")
+        .reply(&route)
+        .await;
+    assert_eq!(res.status(), 400);
+    assert!(!res.headers().contains_key("server-timing"));
+}
@@ -1,5 +1,7 @@
 #![deny(warnings)]
 
+use std::time::Duration;
+
 use futures_util::{FutureExt, SinkExt, StreamExt};
 use nextshell::ws::Message;
 use nextshell::Filter;
@@ -267,6 +269,169 @@ async fn limit_frame_size() {
     assert!(client.recv().await.is_err());
 }
 
+#[tokio::test]
+async fn ping_interval() {
+    let _ = pretty_env_logger::try_init();
+
+    let route = nextshell::ws().map(|ws: nextshell::ws::Ws| {
+        ws.ping_interval(Duration::from_millis(20))
+            .on_upgrade(|mut websocket| async move {
+                // Reading drives the keepalive; the client answers each ping.
+                let msg = websocket.next().await.expect("item").expect("ok");
+                assert!(msg.is_pong());
+            })
+    });
+
+    let mut client = nextshell::test::ws()
+        .handshake(route)
+        .await
+        .expect("handshake");
+
+    let msg = client.recv().await.expect("recv");
+    assert!(msg.is_ping());
+}
+
+#[tokio::test]
+async fn idle_timeout() {
+    let _ = pretty_env_logger::try_init();
+
+    let route = nextshell::ws().map(|ws: nextshell::ws::Ws| {
+        ws.idle_timeout(Duration::from_millis(50))
+            .on_upgrade(|mut websocket| async move {
+                let err = websocket.next().await.expect("item").unwrap_err();
+                assert_eq!(err.to_string(), "websocket idle timeout");
+                assert!(websocket.next().await.is_none());
+            })
+    });
+
+    let mut client = nextshell::test::ws()
+        .handshake(route)
+        .await
+        .expect("handshake");
+
+    client.recv_closed().await.expect("closed");
+}
+
+#[tokio::test]
+async fn limit_closes_with_code() {
+    use tokio_tungstenite::tungstenite::protocol::{frame::coding::CloseCode, Message as Raw};
+
+    let _ = pretty_env_logger::try_init();
+
+    let route = nextshell::ws().map(|ws: nextshell::ws::Ws| {
+        ws.max_message_size(16)
+            .on_upgrade(|mut websocket| async move {
+                assert!(websocket.next().await.expect("item").is_err());
+                assert!(websocket.next().await.is_none());
+            })
+    });
+    let (addr, server) = nextshell::serve(route).bind_ephemeral(([127, 0, 0, 1], 0));
+    tokio::spawn(server);
+
+    let (mut client, _) = tokio_tungstenite::connect_async(format!("ws://{}/", addr))
+        .await
+        .expect("connect");
+    client.send(Raw::binary(vec![0; 17])).await.unwrap();
+
+    match client.next().await.expect("item").expect("ok") {
+        Raw::Close(Some(frame)) => assert_eq!(frame.code, CloseCode::Size),
+        other => panic!("expected close frame, got {:?}", other),
+    }
+}
+
+#[tokio::test]
+async fn close_handshake() {
+    use tokio_tungstenite::tungstenite::protocol::{frame::coding::CloseCode, Message as Raw};
+
+    let _ = pretty_env_logger::try_init();
+
+    let (tx, rx) = tokio::sync::oneshot::channel();
+    let tx = std::sync::Arc::new(std::sync::Mutex::new(Some(tx)));
+    let route = nextshell::ws().map(move |ws: nextshell::ws::Ws| {
+        let tx = tx.lock().unwrap().take().unwrap();
+        ws.on_upgrade(|websocket| async move {
+            let peer = websocket.close_with(4000u16, "done").await.unwrap();
+            tx.send(peer).unwrap();
+        })
+    });
+    let (addr, server) = nextshell::serve(route).bind_ephemeral(([127, 0, 0, 1], 0));
+    tokio::spawn(server);
+
+    let (mut client, _) = tokio_tungstenite::connect_async(format!("ws://{}/", addr))
+        .await
+        .expect("connect");
+    match client.next().await.expect("item").expect("ok") {
+        Raw::Close(Some(frame)) => {
+            assert_eq!(frame.code, CloseCode::from(4000));
+            assert_eq!(frame.reason, "done");
+        }
+        other => panic!("expected close frame, got {:?}", other),
+    }
+    // Draining the client flushes its acknowledgment.
+    assert!(client.next().await.is_none());
+
+    assert_eq!(rx.await.unwrap(), Some((4000, "done".to_owned())));
+}
+
+#[tokio::test]
+async fn close_handshake_timeout() {
+    let _ = pretty_env_logger::try_init();
+
+    let (tx, rx) = tokio::sync::oneshot::channel();
+    let tx = std::sync::Arc::new(std::sync::Mutex::new(Some(tx)));
+    let route = nextshell::ws().map(move |ws: nextshell::ws::Ws| {
+        let tx = tx.lock().unwrap().take().unwrap();
+        ws.close_timeout(Duration::from_millis(50))
+            .on_upgrade(|websocket| async move {
+                let peer = websocket.close_with(1000u16, "bye").await.unwrap();
+                tx.send(peer).unwrap();
+            })
+    });
+    let (addr, server) = nextshell::serve(route).bind_ephemeral(([127, 0, 0, 1], 0));
+    tokio::spawn(server);
+
+    // The client never reads, so never acknowledges the close.
+    let (_client, _) = tokio_tungstenite::connect_async(format!("ws://{}/", addr))
+        .await
+        .expect("connect");
+
+    let peer = tokio::time::timeout(Duration::from_secs(5), rx)
+        .await
+        .expect("close_with gives up")
+        .unwrap();
+    assert_eq!(peer, None);
+}
+
+#[tokio::test]
+async fn peer_close_frame() {
+    let _ = pretty_env_logger::try_init();
+
+    let (tx, rx) = tokio::sync::oneshot::channel();
+    let tx = std::sync::Arc::new(std::sync::Mutex::new(Some(tx)));
+    let route = nextshell::ws().map(move |ws: nextshell::ws::Ws| {
+        let tx = tx.lock().unwrap().take().unwrap();
+        ws.on_upgrade(|mut websocket| async move {
+            assert_eq!(websocket.peer_close_frame(), None);
+            while let Some(msg) = websocket.next().await {
+                msg.unwrap();
+            }
+            let peer = websocket
+                .peer_close_frame()
+                .map(|(code, reason)| (code, reason.to_owned()));
+            tx.send(peer).unwrap();
+        })
+    });
+
+    let mut client = nextshell::test::ws()
+        .handshake(route)
+        .await
+        .expect("handshake");
+    client.send(Message::close_with(4001u16, "bye")).await;
+    client.recv_closed().await.expect("closed");
+
+    assert_eq!(rx.await.unwrap(), Some((4001, "bye".to_owned())));
+}
+
 #[derive(Deserialize)]
 struct MyQuery {
     hello: String,
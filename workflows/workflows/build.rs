@@ -7,9 +7,9 @@ use std::io::Write;
 use std::process::Command;
 use walkdir::WalkDir;
 
-/// Generates Workflows as rust files from the yaml stored in /specs. Each Workflow is stored within
-/// its own mod within the `generated_workflows` module. Additionally, a function called `workflows`
-/// is generated that returns a vector of all the `Workflow`s that were created.
+/// Generates Workflows as rust files from the yaml (or toml) stored in /specs. Each Workflow is
+/// stored within its own mod within the `generated_workflows` module. Additionally, a function
+/// called `workflows` is generated that returns a vector of all the `Workflow`s that were created.
 fn main() -> Result<()> {
     println!("cargo:rerun-if-changed=../specs");
 
@@ -20,20 +20,23 @@ fn main() -> Result<()> {
 
     for entry in WalkDir::new("../specs") {
         let entry = entry?;
-        let valid_yaml_file = entry
-            .path()
-            .extension()
-            .and_then(OsStr::to_str)
-            .is_some_and(|extension| extension == "yaml" || extension == "yml");
+        let format = match entry.path().extension().and_then(OsStr::to_str) {
+            Some("yaml" | "yml") => Some(Format::Yaml),
+            Some("toml") => Some(Format::Toml),
+            _ => None,
+        };
 
-        if valid_yaml_file {
+        if let Some(format) = format {
             let file = File::open(entry.path())?;
 
             let mmap = unsafe { memmap::Mmap::map(&file) }?;
-            let yaml_content = std::str::from_utf8(&mmap)?;
+            let content = std::str::from_utf8(&mmap)?;
 
             println!("attempting to generate workflow at {:?}", entry.path());
-            let workflow: Workflow = serde_yaml::from_str(yaml_content)?;
+            let workflow: Workflow = match format {
+                Format::Yaml => serde_yaml::from_str(content)?,
+                Format::Toml => toml::from_str(content)?,
+            };
             println!("generated workflow is {workflow:?}");
 
             let file_name = entry
@@ -42,6 +45,7 @@ fn main() -> Result<()> {
                 .expect("OsStr should convert to str")
                 .replace(".yaml", "")
                 .replace(".yml", "")
+                .replace(".toml", "")
                 .to_case(Case::Snake);
             println!("file name is {file_name:?}");
 
@@ -64,6 +68,12 @@ fn main() -> Result<()> {
     Ok(())
 }
 
+/// The formats a Workflow spec may be written in, by file extension.
+enum Format {
+    Yaml,
+    Toml,
+}
+
 /// Writes a `workflows` function into the module at path `parent_modules`. The generated function
 /// will look approximately like:
 /// ```ignore